@@ -0,0 +1,195 @@
+//! Runs commands without the graphical interface, for
+//! `my_cli_tool -c "commands" [name [arguments ...]]` and
+//! `my_cli_tool script [arguments ...]`.
+//!
+//! The commands read the standard input of the process, their output is
+//! written to its standard output and standard error, and the process exits
+//! with the status of the last command.
+
+use std::io::Write;
+use crate::shell::core::cancellation::CancellationToken;
+use crate::shell::core::command_executor::{self, EXIT_CANNOT_EXECUTE, EXIT_NOT_FOUND};
+use crate::shell::core::output::{OutputChunk, OutputSink, Stream};
+use crate::shell::core::stage_io::{Source, StageIo, Target};
+use crate::shell::core::ShellCore;
+
+/// What the command-line arguments ask the shell to run.
+#[derive(Debug, PartialEq)]
+pub enum Invocation {
+    /// `-c commands [name [arguments ...]]`: runs `commands`, with `name`
+    /// as `$0` and the arguments as the positional parameters.
+    Command { commands: String, name: Option<String>, args: Vec<String> },
+    /// `script [arguments ...]`: runs the commands of the file `script`.
+    Script { path: String, args: Vec<String> },
+}
+
+/// Parses the arguments the program was started with.
+///
+/// # Arguments
+///
+/// * `args` - The arguments, not including the program name.
+///
+/// # Returns
+///
+/// The commands to run, `None` if there are no arguments and the GUI
+/// should start, or an error message for an invalid option.
+pub fn parse_args(args: &[String]) -> Result<Option<Invocation>, String> {
+    match args {
+        [] => Ok(None),
+        [option] if option == "-c" => Err("-c: option requires an argument".to_string()),
+        [option, commands, rest @ ..] if option == "-c" => {
+            let (name, args) = match rest.split_first() {
+                Some((name, args)) => (Some(name.clone()), args.to_vec()),
+                None => (None, Vec::new()),
+            };
+            Ok(Some(Invocation::Command { commands: commands.clone(), name, args }))
+        }
+        [option, ..] if option.starts_with('-') => Err(format!("{}: invalid option", option)),
+        [path, args @ ..] => Ok(Some(Invocation::Script { path: path.clone(), args: args.to_vec() })),
+    }
+}
+
+/// Runs the commands of an invocation in a new shell.
+///
+/// # Returns
+///
+/// The exit status of the last command, `127` if the script does not exist
+/// or `126` if it cannot be read.
+pub async fn run(invocation: Invocation) -> i32 {
+    let mut shell_core = ShellCore::new();
+    let script = match invocation {
+        Invocation::Command { commands, name, args } => {
+            if let Some(name) = name {
+                shell_core.shell_name = name;
+            }
+            shell_core.positional_params = args;
+            commands
+        }
+        Invocation::Script { path, args } => {
+            let script = match std::fs::read_to_string(shell_core.current_dir.join(&path)) {
+                Ok(script) => script,
+                Err(e) => {
+                    eprintln!("{}: {}: {}", env!("CARGO_PKG_NAME"), path, e);
+                    return if e.kind() == std::io::ErrorKind::NotFound { EXIT_NOT_FOUND } else { EXIT_CANNOT_EXECUTE };
+                }
+            };
+            shell_core.shell_name = path;
+            shell_core.positional_params = args;
+            script
+        }
+    };
+    execute(&mut shell_core, &script).await
+}
+
+/// Runs a script with the standard descriptors of the process.
+///
+/// Commands write to the process's standard output and standard error
+/// directly, so their output is passed on byte for byte. Only the shell's
+/// own diagnostics go through the output sink, prefixed with the program
+/// name.
+async fn execute(shell_core: &mut ShellCore, script: &str) -> i32 {
+    let io = StageIo {
+        stdin: Source::process_stdin().unwrap_or(Source::Null),
+        stdout: Target::process_stdout().unwrap_or(Target::Sink(Stream::Stdout)),
+        stderr: Target::process_stderr().unwrap_or(Target::Sink(Stream::Stderr)),
+    };
+    execute_with(shell_core, script, io).await
+}
+
+/// Runs a script with the given descriptors, printing what reaches the
+/// output sink as it arrives.
+async fn execute_with(shell_core: &mut ShellCore, script: &str, io: StageIo) -> i32 {
+    let (output, mut receiver) = OutputSink::with_error_prefix(concat!(env!("CARGO_PKG_NAME"), ": "));
+    let cancellation = CancellationToken::new();
+
+    let run = command_executor::execute_script(shell_core, script, &io, &output, &cancellation);
+    tokio::pin!(run);
+    let status = loop {
+        tokio::select! {
+            status = &mut run => break status,
+            Some(chunk) = receiver.recv() => print_chunk(&chunk),
+        }
+    };
+    // Every command has finished, so the rest of the output is queued.
+    output.end_pipeline();
+    while let Ok(chunk) = receiver.try_recv() {
+        print_chunk(&chunk);
+    }
+    status
+}
+
+fn print_chunk(chunk: &OutputChunk) {
+    // A closed stream, as after `my_cli_tool script | head`, is not an error.
+    let _ = match chunk.stream {
+        Stream::Stdout => write_flushed(&mut std::io::stdout().lock(), &chunk.text),
+        Stream::Stderr => write_flushed(&mut std::io::stderr().lock(), &chunk.text),
+    };
+}
+
+fn write_flushed(stream: &mut impl Write, text: &str) -> std::io::Result<()> {
+    stream.write_all(text.as_bytes())?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shell::core::stage_io;
+    use std::io::Read;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(parse_args(&[]), Ok(None));
+        assert_eq!(
+            parse_args(&strings(&["-c", "echo $1", "name", "a"])),
+            Ok(Some(Invocation::Command {
+                commands: "echo $1".to_string(),
+                name: Some("name".to_string()),
+                args: strings(&["a"]),
+            }))
+        );
+        assert_eq!(
+            parse_args(&strings(&["build.sh", "-v", "x"])),
+            Ok(Some(Invocation::Script { path: "build.sh".to_string(), args: strings(&["-v", "x"]) }))
+        );
+        assert_eq!(parse_args(&strings(&["-c"])), Err("-c: option requires an argument".to_string()));
+        assert_eq!(parse_args(&strings(&["-x"])), Err("-x: invalid option".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_exit_status_of_last_command() {
+        let invocation = Invocation::Command { commands: "true; false".to_string(), name: None, args: Vec::new() };
+        assert_eq!(run(invocation).await, 1);
+        let commands = "f() { return $#; }; f \"$@\"".to_string();
+        let invocation = Invocation::Command { commands, name: None, args: strings(&["a", "b"]) };
+        assert_eq!(run(invocation).await, 2);
+        let invocation = Invocation::Script { path: "no_such_script_123.sh".to_string(), args: Vec::new() };
+        assert_eq!(run(invocation).await, EXIT_NOT_FOUND);
+    }
+
+    /// Runs commands with their output going to pipes, and returns what
+    /// was written to standard output and standard error.
+    async fn run_piped(commands: &str) -> (String, String) {
+        let (mut stdout, stdout_writer) = stage_io::pipe().unwrap();
+        let (mut stderr, stderr_writer) = stage_io::pipe().unwrap();
+        let io = StageIo { stdin: Source::Null, stdout: Target::File(stdout_writer), stderr: Target::File(stderr_writer) };
+        execute_with(&mut ShellCore::new(), commands, io).await;
+        let (mut out, mut err) = (String::new(), String::new());
+        stdout.read_to_string(&mut out).unwrap();
+        stderr.read_to_string(&mut err).unwrap();
+        (out, err)
+    }
+
+    #[tokio::test]
+    async fn test_output_is_written_unchanged() {
+        assert_eq!(run_piped("printf a; printf b").await, ("ab".to_string(), String::new()));
+        assert_eq!(run_piped("printf x").await, ("x".to_string(), String::new()));
+        assert_eq!(run_piped("echo out; printf err >&2").await, ("out\n".to_string(), "err".to_string()));
+        let expected = format!("{}: cd: '/no_such_dir_123': No such file or directory\n", env!("CARGO_PKG_NAME"));
+        assert_eq!(run_piped("cd /no_such_dir_123").await.1, expected);
+    }
+}
@@ -0,0 +1,431 @@
+//! Rendering of text that contains ANSI escape sequences.
+//!
+//! Programs such as `cargo`, `git` or `ls --color` style their output with
+//! SGR (Select Graphic Rendition) sequences and redraw progress bars with
+//! carriage returns. A `Parser` interprets them into lines of styled spans,
+//! and `append_spans` adds the spans to an `egui` layout for the output view.
+//! Other escape sequences are removed.
+
+use eframe::egui::{self, text::LayoutJob, Color32, FontId, TextFormat};
+use std::borrow::Cow;
+
+/// The standard and bright colors of the 16-color palette.
+const ANSI_COLORS: [Color32; 16] = [
+    Color32::from_rgb(0, 0, 0),
+    Color32::from_rgb(205, 49, 49),
+    Color32::from_rgb(13, 188, 121),
+    Color32::from_rgb(229, 229, 16),
+    Color32::from_rgb(36, 114, 200),
+    Color32::from_rgb(188, 63, 188),
+    Color32::from_rgb(17, 168, 205),
+    Color32::from_rgb(229, 229, 229),
+    Color32::from_rgb(102, 102, 102),
+    Color32::from_rgb(241, 76, 76),
+    Color32::from_rgb(35, 209, 139),
+    Color32::from_rgb(245, 245, 67),
+    Color32::from_rgb(59, 142, 234),
+    Color32::from_rgb(214, 112, 214),
+    Color32::from_rgb(41, 184, 219),
+    Color32::from_rgb(255, 255, 255),
+];
+
+/// Returns a color of the xterm 256-color palette.
+///
+/// Indices 0-15 are the ANSI colors, 16-231 a 6x6x6 color cube and 232-255
+/// a grayscale ramp.
+pub fn indexed_color(index: u8) -> Color32 {
+    match index {
+        0..=15 => ANSI_COLORS[index as usize],
+        16..=231 => {
+            let level = |value: u8| if value == 0 { 0 } else { 55 + value * 40 };
+            let index = index - 16;
+            Color32::from_rgb(level(index / 36), level(index / 6 % 6), level(index % 6))
+        }
+        _ => {
+            let gray = 8 + (index - 232) * 10;
+            Color32::from_rgb(gray, gray, gray)
+        }
+    }
+}
+
+/// A color set by an SGR sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    /// An entry of the 256-color palette; 0-15 are the ANSI colors.
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    fn to_color32(self) -> Color32 {
+        match self {
+            Color::Indexed(index) => indexed_color(index),
+            Color::Rgb(r, g, b) => Color32::from_rgb(r, g, b),
+        }
+    }
+}
+
+/// The graphic rendition of a piece of text. `None` colors are the defaults.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Style {
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub inverse: bool,
+}
+
+/// A run of text with a single style.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub text: String,
+    pub style: Style,
+}
+
+/// Escape sequences longer than this are dropped rather than waited for
+/// when the output ends in the middle of one.
+const MAX_ESCAPE_LENGTH: usize = 4096;
+
+/// The furthest column a cursor movement (`ESC [ C`, `ESC [ G`) can reach
+/// past the end of the line, so a huge count cannot make the line take
+/// gigabytes of blank cells.
+const MAX_CURSOR_COLUMN: usize = 1024;
+
+/// Interprets the escape sequences and control characters in output that
+/// arrives in pieces, one line at a time.
+///
+/// SGR sequences change the style of the text that follows, including on
+/// the following lines. A carriage return moves back to the start of the
+/// line, so text written after it overwrites the line, as progress bars
+/// expect; backspace and the erase line sequence (`ESC [ K`) are handled
+/// likewise. An escape sequence may be split between two pieces.
+#[derive(Default)]
+pub struct Parser {
+    line: Vec<(char, Style)>,
+    column: usize,
+    style: Style,
+    /// The start of the escape sequence the last piece ended in.
+    pending: String,
+}
+
+impl Parser {
+    /// Interprets the next piece of output.
+    ///
+    /// # Returns
+    ///
+    /// The lines ended by a newline in `text`, as styled spans without the
+    /// newline.
+    pub fn feed(&mut self, text: &str) -> Vec<Vec<Span>> {
+        let pending = std::mem::take(&mut self.pending);
+        let text = if pending.is_empty() { Cow::Borrowed(text) } else { Cow::Owned(pending + text) };
+        let mut lines = Vec::new();
+        let mut position = 0;
+        while let Some(c) = text[position..].chars().next() {
+            position += c.len_utf8();
+            match c {
+                '\x1b' => match read_escape(&text[position..]) {
+                    Some((length, sequence)) => {
+                        if let Some((params, command)) = sequence {
+                            self.control_sequence(params, command);
+                        }
+                        position += length;
+                    }
+                    None => {
+                        if text.len() - position < MAX_ESCAPE_LENGTH {
+                            self.pending = text[position - 1..].to_string();
+                        }
+                        break;
+                    }
+                },
+                '\r' => self.column = 0,
+                '\n' => lines.push(self.end_line()),
+                '\x08' => self.column = self.column.saturating_sub(1),
+                '\t' => {
+                    let next_stop = (self.column / 8 + 1) * 8;
+                    while self.column < next_stop {
+                        self.put(' ');
+                    }
+                }
+                c if c.is_control() => {}
+                c => self.put(c),
+            }
+        }
+        lines
+    }
+
+    /// Returns the line being written, which no newline has ended yet.
+    pub fn open_line(&self) -> Vec<Span> {
+        spans(&self.line)
+    }
+
+    /// Ends the line being written, as a newline would.
+    pub fn end_line(&mut self) -> Vec<Span> {
+        self.column = 0;
+        spans(&std::mem::take(&mut self.line))
+    }
+
+    fn put(&mut self, c: char) {
+        if self.column < self.line.len() {
+            self.line[self.column] = (c, self.style);
+        } else {
+            self.line.resize(self.column, (' ', Style::default()));
+            self.line.push((c, self.style));
+        }
+        self.column += 1;
+    }
+
+    fn control_sequence(&mut self, params: &str, command: char) {
+        let count = || params.parse::<usize>().unwrap_or(1).max(1);
+        match command {
+            'm' => self.select_graphic_rendition(params),
+            'K' => match params {
+                "" | "0" => self.line.truncate(self.column),
+                "1" => self.line.iter_mut().take(self.column + 1).for_each(|cell| *cell = (' ', Style::default())),
+                "2" => self.line.clear(),
+                _ => {}
+            },
+            'C' => self.move_to(self.column.saturating_add(count())),
+            'D' => self.column = self.column.saturating_sub(count()),
+            // Columns count from 1, and `count` is at least 1.
+            'G' => self.move_to(count() - 1),
+            _ => {}
+        }
+    }
+
+    /// Moves the cursor to `column`, at most `MAX_CURSOR_COLUMN` or the end
+    /// of the line, whichever is further.
+    fn move_to(&mut self, column: usize) {
+        self.column = column.min(self.line.len().max(MAX_CURSOR_COLUMN));
+    }
+
+    fn select_graphic_rendition(&mut self, params: &str) {
+        // Parameters are separated by `;`, or by `:` in the ITU form of
+        // extended colors.
+        // An empty parameter counts as 0, so `ESC [ m` resets the style.
+        let mut params = params.split([';', ':']).map(|p| p.parse::<u16>().unwrap_or(0));
+        let style = &mut self.style;
+        let mut next = params.next();
+        while let Some(param) = next {
+            match param {
+                0 => *style = Style::default(),
+                1 => style.bold = true,
+                3 => style.italic = true,
+                4 => style.underline = true,
+                7 => style.inverse = true,
+                22 => style.bold = false,
+                23 => style.italic = false,
+                24 => style.underline = false,
+                27 => style.inverse = false,
+                30..=37 => style.foreground = Some(Color::Indexed(param as u8 - 30)),
+                38 => style.foreground = extended_color(&mut params),
+                39 => style.foreground = None,
+                40..=47 => style.background = Some(Color::Indexed(param as u8 - 40)),
+                48 => style.background = extended_color(&mut params),
+                49 => style.background = None,
+                90..=97 => style.foreground = Some(Color::Indexed(param as u8 - 90 + 8)),
+                100..=107 => style.background = Some(Color::Indexed(param as u8 - 100 + 8)),
+                _ => {}
+            }
+            next = params.next();
+        }
+    }
+}
+
+/// Reads the escape sequence that follows an ESC at the start of `text`.
+///
+/// # Returns
+///
+/// The length of the sequence and, for a control sequence, its parameters
+/// and command. `None` if `text` ends before the sequence does.
+fn read_escape(text: &str) -> Option<(usize, Option<(&str, char)>)> {
+    let mut chars = text.char_indices();
+    match chars.next()? {
+        (_, '[') => {
+            let (end, command) = chars.find(|(_, c)| ('\x40'..='\x7e').contains(c))?;
+            Some((end + command.len_utf8(), Some((&text[1..end], command))))
+        }
+        (_, ']') => {
+            // Operating system commands, such as setting the window title,
+            // end with BEL or `ESC \`.
+            let mut previous = None;
+            for (index, c) in chars {
+                if c == '\x07' || (c == '\\' && previous == Some('\x1b')) {
+                    return Some((index + 1, None));
+                }
+                previous = Some(c);
+            }
+            None
+        }
+        (_, c) => Some((c.len_utf8(), None)),
+    }
+}
+
+/// Merges cells of the same style into spans.
+fn spans(cells: &[(char, Style)]) -> Vec<Span> {
+    let mut spans: Vec<Span> = Vec::new();
+    for &(c, style) in cells {
+        match spans.last_mut() {
+            Some(last) if last.style == style => last.text.push(c),
+            _ => spans.push(Span { text: c.to_string(), style }),
+        }
+    }
+    spans
+}
+
+/// Reads the color of a `38;5;n` or `38;2;r;g;b` sequence.
+fn extended_color(params: &mut impl Iterator<Item = u16>) -> Option<Color> {
+    let mut component = || params.next().map(|value| value.min(255) as u8);
+    match component()? {
+        5 => Some(Color::Indexed(component()?)),
+        2 => Some(Color::Rgb(component()?, component()?, component()?)),
+        _ => None,
+    }
+}
+
+/// Appends the spans to a layout in a monospace font.
+///
+/// # Arguments
+///
+/// * `spans` - Styled text returned by `parse`.
+/// * `font_id` - The font to draw the text with.
+/// * `foreground`, `background` - The colors used for the default style.
+pub fn append_spans(job: &mut LayoutJob, spans: &[Span], font_id: FontId, foreground: Color32, background: Color32) {
+    for span in spans {
+        let style = &span.style;
+        let mut color = match style.foreground {
+            // Bold text in one of the eight standard colors is drawn in the
+            // bright variant, as terminals do.
+            Some(Color::Indexed(index @ 0..=7)) if style.bold => indexed_color(index + 8),
+            Some(color) => color.to_color32(),
+            None => foreground,
+        };
+        let mut fill = style.background.map_or(Color32::TRANSPARENT, Color::to_color32);
+        if style.inverse {
+            let inverted = if fill == Color32::TRANSPARENT { background } else { fill };
+            fill = color;
+            color = inverted;
+        }
+        let format = TextFormat {
+            font_id: font_id.clone(),
+            color,
+            background: fill,
+            italics: style.italic,
+            underline: if style.underline { egui::Stroke::new(1.0, color) } else { egui::Stroke::NONE },
+            ..Default::default()
+        };
+        job.append(&span.text, 0.0, format);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Interprets all of `text` at once, with `\n` spans between the lines.
+    fn parse(text: &str) -> Vec<Span> {
+        let mut parser = Parser::default();
+        let mut lines = parser.feed(text);
+        lines.push(parser.open_line());
+        let newline = Span { text: "\n".to_string(), style: Style::default() };
+        let mut spans: Vec<Span> = Vec::new();
+        for (index, line) in lines.into_iter().enumerate() {
+            let separator = (index > 0).then(|| newline.clone());
+            for span in separator.into_iter().chain(line) {
+                match spans.last_mut() {
+                    Some(last) if last.style == span.style => last.text.push_str(&span.text),
+                    _ => spans.push(span),
+                }
+            }
+        }
+        spans
+    }
+
+    fn text(spans: &[Span]) -> String {
+        spans.iter().map(|s| s.text.as_str()).collect()
+    }
+
+    #[test]
+    fn test_indexed_colors() {
+        assert_eq!(indexed_color(1), Color32::from_rgb(205, 49, 49));
+        assert_eq!(indexed_color(16), Color32::from_rgb(0, 0, 0));
+        assert_eq!(indexed_color(196), Color32::from_rgb(255, 0, 0));
+        assert_eq!(indexed_color(232), Color32::from_rgb(8, 8, 8));
+        assert_eq!(indexed_color(255), Color32::from_rgb(238, 238, 238));
+    }
+
+    #[test]
+    fn test_plain_text_is_one_span() {
+        let spans = parse("hello\nworld");
+        assert_eq!(spans, vec![Span { text: "hello\nworld".to_string(), style: Style::default() }]);
+    }
+
+    #[test]
+    fn test_sgr_colors_and_attributes() {
+        let spans = parse("\x1b[1;31merror\x1b[0m: \x1b[38;5;208mx\x1b[48;2;1;2;3my\x1b[4;7mz\x1b[m.");
+        assert_eq!(text(&spans), "error: xyz.");
+        assert_eq!(spans[0].style, Style { foreground: Some(Color::Indexed(1)), bold: true, ..Default::default() });
+        assert_eq!(spans[1].style, Style::default());
+        assert_eq!(spans[2].style.foreground, Some(Color::Indexed(208)));
+        assert_eq!(spans[3].style.background, Some(Color::Rgb(1, 2, 3)));
+        assert!(spans[4].style.underline && spans[4].style.inverse);
+        assert_eq!(spans[5].style, Style::default());
+    }
+
+    #[test]
+    fn test_bright_colors_and_resets() {
+        let spans = parse("\x1b[92;101ma\x1b[39mb\x1b[49mc");
+        assert_eq!(spans[0].style, Style { foreground: Some(Color::Indexed(10)), background: Some(Color::Indexed(9)), ..Default::default() });
+        assert_eq!(spans[1].style, Style { background: Some(Color::Indexed(9)), ..Default::default() });
+        assert_eq!(spans[2].style, Style::default());
+    }
+
+    #[test]
+    fn test_carriage_return_overwrites_line() {
+        assert_eq!(text(&parse("progress 10%\rprogress 100%\ndone")), "progress 100%\ndone");
+        assert_eq!(text(&parse("abcdef\rxy")), "xycdef");
+        assert_eq!(text(&parse("abcdef\rxy\x1b[K")), "xy");
+        assert_eq!(text(&parse("windows\r\nline")), "windows\nline");
+        assert_eq!(text(&parse("ab\x08c")), "ac");
+    }
+
+    #[test]
+    fn test_cursor_movements_are_clamped() {
+        assert_eq!(text(&parse("a\x1b[3Cb\x1b[2Gc")), "ac  b");
+        let line = text(&parse("a\x1b[999999999Cb"));
+        assert_eq!(line.len(), MAX_CURSOR_COLUMN + 1);
+        assert!(line.starts_with('a') && line.ends_with('b'));
+        assert_eq!(text(&parse("\x1b[4000000000Gx")).len(), MAX_CURSOR_COLUMN + 1);
+        assert_eq!(text(&parse("\x1b[99999999999999999999999Cx")), " x");
+    }
+
+    #[test]
+    fn test_other_sequences_are_removed() {
+        assert_eq!(text(&parse("\x1b]0;title\x07a\x1b[2Jb\x1b]8;;http://x\x1b\\c")), "abc");
+    }
+
+    #[test]
+    fn test_output_is_interpreted_as_it_arrives() {
+        let red = Style { foreground: Some(Color::Indexed(1)), ..Default::default() };
+        let mut parser = Parser::default();
+        assert!(parser.feed("\x1b[3").is_empty());
+        assert_eq!(parser.feed("1mred\nstill"), vec![vec![Span { text: "red".to_string(), style: red }]]);
+        assert_eq!(parser.open_line(), vec![Span { text: "still".to_string(), style: red }]);
+        assert_eq!(parser.feed(" red\x1b[0m\r").len(), 0);
+        assert_eq!(text(&parser.end_line()), "still red");
+        assert!(parser.open_line().is_empty());
+        assert_eq!(parser.feed("\x1b]0;ti"), Vec::<Vec<Span>>::new());
+        assert_eq!(parser.feed("tle\x07plain\n"), vec![vec![Span { text: "plain".to_string(), style: Style::default() }]]);
+    }
+
+    #[test]
+    fn test_append_spans_applies_styles() {
+        let spans = parse("\x1b[1;34mdir\x1b[0m \x1b[7mrev");
+        let mut job = LayoutJob::default();
+        append_spans(&mut job, &spans, FontId::monospace(14.0), Color32::WHITE, Color32::BLACK);
+        assert_eq!(job.text, "dir rev");
+        assert_eq!(job.sections[0].format.color, indexed_color(12));
+        assert_eq!(job.sections[1].format.background, Color32::TRANSPARENT);
+        assert_eq!((job.sections[2].format.color, job.sections[2].format.background), (Color32::BLACK, Color32::WHITE));
+    }
+}
@@ -3,6 +3,7 @@
 use eframe::egui;
 use tokio::sync::oneshot;
 
+use crate::shell::history::HistoryStore;
 use super::tab::ShellTab;
 use super::tab_bar;
 
@@ -11,15 +12,29 @@ use super::tab_bar;
 pub struct GuiApp {
     pub(super) tabs: Vec<ShellTab>,
     pub(super) selected_tab: usize,
+    /// The command history shared by all tabs.
+    pub(super) history: HistoryStore,
     shutdown_sender: Option<oneshot::Sender<()>>,
 }
 
 impl GuiApp {
     /// Creates a new `GuiApp` instance.
-    pub fn new(shutdown_sender: oneshot::Sender<()>) -> Self {
+    ///
+    /// The command history is loaded from the data directory. If it cannot
+    /// be read, the error is reported and the history of this session is
+    /// kept in memory only.
+    pub fn new(ctx: &egui::Context, shutdown_sender: oneshot::Sender<()>) -> Self {
+        let history = match HistoryStore::default_path() {
+            Some(path) => HistoryStore::open(&path).unwrap_or_else(|e| {
+                eprintln!("{}: {}: {}", env!("CARGO_PKG_NAME"), path.display(), e);
+                HistoryStore::new()
+            }),
+            None => HistoryStore::new(),
+        };
         Self {
-            tabs: vec![ShellTab::new("Tab 1".to_string())],
+            tabs: vec![ShellTab::new("Tab 1".to_string(), ctx, history.clone())],
             selected_tab: 0,
+            history,
             shutdown_sender: Some(shutdown_sender),
         }
     }
@@ -0,0 +1,240 @@
+//! One entry of a tab's history: a command together with its output.
+//!
+//! Each command line the user runs becomes a `CommandBlock` that records
+//! where and when it ran, how it ended and what it printed. Blocks are drawn
+//! with a header showing a status badge and the duration, can be collapsed,
+//! and offer buttons to copy them or run the command again. Their output is
+//! drawn a line at a time, laying out only the lines that are in view.
+
+use chrono::{DateTime, Local};
+use eframe::egui::{self, Color32, RichText};
+
+use crate::shell::core::output::{OutputChunk, Stream};
+use crate::shell::history::format_duration;
+use super::ansi;
+use super::scrollback::{self, Line, Scrollback};
+
+/// The fewest rows of output shown before a block's output scrolls.
+const MIN_OUTPUT_ROWS: f32 = 5.0;
+
+/// What the user asked for by clicking a block's buttons.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockAction {
+    /// Run this command line again.
+    Rerun(String),
+}
+
+/// A command that was run in a tab and its output.
+pub struct CommandBlock {
+    /// Identifies the block while its command runs, even if blocks before it
+    /// are removed.
+    pub id: u64,
+    pub command: String,
+    /// The working directory the command started in.
+    pub cwd: String,
+    /// The git branch and status indicator at the start, e.g. `(main*)`.
+    pub git_info: String,
+    pub started_at: DateTime<Local>,
+    pub finished_at: Option<DateTime<Local>>,
+    /// `None` while the command is still running.
+    pub exit_status: Option<i32>,
+    /// Output in the order it was produced, with the stream it came from.
+    pub output: Scrollback,
+    pub collapsed: bool,
+}
+
+impl CommandBlock {
+    /// Creates the block of a command that starts now.
+    pub fn new(id: u64, command: String, cwd: String, git_info: String) -> Self {
+        Self {
+            id,
+            command,
+            cwd,
+            git_info,
+            started_at: Local::now(),
+            finished_at: None,
+            exit_status: None,
+            output: Scrollback::new(scrollback::DEFAULT_CAPACITY),
+            collapsed: false,
+        }
+    }
+
+    /// Appends output.
+    pub fn push_chunk(&mut self, chunk: OutputChunk) {
+        self.output.push(&chunk);
+    }
+
+    /// Records that the command finished with `exit_status`.
+    pub fn finish(&mut self, exit_status: i32) {
+        self.finished_at = Some(Local::now());
+        self.exit_status = Some(exit_status);
+    }
+
+    /// Returns how long the command ran, or has been running so far.
+    pub fn duration(&self) -> chrono::Duration {
+        self.finished_at.unwrap_or_else(Local::now) - self.started_at
+    }
+
+    /// Returns the output kept, standard output and standard error
+    /// interleaved, without escape sequences.
+    pub fn text(&self) -> String {
+        self.output.text(None)
+    }
+
+    pub fn stdout(&self) -> String {
+        self.output.text(Some(Stream::Stdout))
+    }
+
+    pub fn stderr(&self) -> String {
+        self.output.text(Some(Stream::Stderr))
+    }
+
+    /// Draws the output, laying out only the lines in view.
+    ///
+    /// Lines are not wrapped, so that all rows have the same height, and
+    /// long lines scroll sideways. Output taller than the tab's view scrolls
+    /// on its own, following new lines while the view is at the bottom.
+    fn output_ui(&self, ui: &mut egui::Ui) {
+        let style = ui.style().clone();
+        let font_id = egui::TextStyle::Monospace.resolve(&style);
+        let row_height = ui.fonts(|fonts| fonts.row_height(&font_id));
+        let max_height = ui.clip_rect().height().max(row_height * MIN_OUTPUT_ROWS);
+        ui.scope(|ui| {
+            ui.spacing_mut().item_spacing.y = 0.0;
+            egui::ScrollArea::both()
+                .id_source("output")
+                .auto_shrink([false, true])
+                .max_height(max_height)
+                .stick_to_bottom(true)
+                .show_rows(ui, row_height, self.output.len(), |ui, rows| {
+                    for index in rows {
+                        if let Some(line) = self.output.line(index) {
+                            // egui caches the layout of lines it has seen, so
+                            // scrolling back and forth does not lay them out again.
+                            ui.add(egui::Label::new(line_layout(&line, &style)).wrap(false));
+                        }
+                    }
+                });
+        });
+    }
+
+    /// Draws the block.
+    ///
+    /// # Returns
+    ///
+    /// The action requested with the block's buttons, if any.
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> Option<BlockAction> {
+        let mut action = None;
+        ui.horizontal(|ui| {
+            let toggle = if self.collapsed { "▶" } else { "▼" };
+            if ui.small_button(toggle).on_hover_text("Show or hide the output").clicked() {
+                self.collapsed = !self.collapsed;
+            }
+            match self.exit_status {
+                None => ui.label(RichText::new("…").weak()),
+                Some(0) => ui.label(RichText::new("✔").color(Color32::from_rgb(13, 188, 121))),
+                Some(status) => ui.label(RichText::new(format!("❌ {}", status)).color(Color32::from_rgb(205, 49, 49))),
+            };
+            ui.label(RichText::new(format!("$ {}", self.command)).monospace().strong());
+            ui.label(RichText::new(format!("{} {}", self.cwd, self.git_info)).weak());
+
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.small_button("Rerun").clicked() {
+                    action = Some(BlockAction::Rerun(self.command.clone()));
+                }
+                ui.menu_button("Copy", |ui| {
+                    let copied = if ui.button("Command and output").clicked() {
+                        Some(format!("$ {}\n{}", self.command, self.text()))
+                    } else if ui.button("Command").clicked() {
+                        Some(self.command.clone())
+                    } else if ui.button("Output").clicked() {
+                        Some(self.text())
+                    } else if ui.button("Standard output").clicked() {
+                        Some(self.stdout())
+                    } else if ui.button("Standard error").clicked() {
+                        Some(self.stderr())
+                    } else {
+                        None
+                    };
+                    if let Some(text) = copied {
+                        ui.output_mut(|o| o.copied_text = text);
+                        ui.close_menu();
+                    }
+                });
+                let timestamp = self.started_at.format("%Y-%m-%d %H:%M:%S");
+                ui.label(RichText::new(format!("{} · {}", timestamp, format_duration(self.duration()))).weak());
+            });
+        });
+
+        if !self.collapsed && !self.output.is_empty() {
+            if self.output.dropped() > 0 {
+                ui.label(RichText::new(format!("({} earlier lines dropped)", self.output.dropped())).weak());
+            }
+            self.output_ui(ui);
+        }
+        ui.separator();
+        action
+    }
+}
+
+/// Lays out a line of output, drawing standard error in the error color so
+/// it stands out from standard output.
+fn line_layout(line: &Line, style: &egui::Style) -> egui::text::LayoutJob {
+    let font_id = egui::TextStyle::Monospace.resolve(style);
+    let visuals = &style.visuals;
+    let foreground = match line.stream {
+        Stream::Stdout => visuals.text_color(),
+        Stream::Stderr => visuals.error_fg_color,
+    };
+    let mut job = egui::text::LayoutJob::default();
+    // An empty line still takes up a row.
+    job.append("", 0.0, egui::TextFormat::simple(font_id.clone(), foreground));
+    ansi::append_spans(&mut job, &line.spans, font_id, foreground, visuals.extreme_bg_color);
+    job
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(stream: Stream, text: &str) -> OutputChunk {
+        OutputChunk { stream, text: text.to_string() }
+    }
+
+    #[test]
+    fn test_streams_are_kept_apart_and_interleaved() {
+        let mut block = CommandBlock::new(1, "build".to_string(), "/tmp".to_string(), String::new());
+        block.push_chunk(chunk(Stream::Stdout, "compiling "));
+        block.push_chunk(chunk(Stream::Stdout, "a\n"));
+        block.push_chunk(chunk(Stream::Stderr, "warning\n"));
+        block.push_chunk(chunk(Stream::Stdout, "done\n"));
+
+        assert_eq!(block.output.len(), 3);
+        assert_eq!(block.text(), "compiling a\nwarning\ndone\n");
+        assert_eq!(block.stdout(), "compiling a\ndone\n");
+        assert_eq!(block.stderr(), "warning\n");
+    }
+
+    #[test]
+    fn test_finish_records_status_and_end() {
+        let mut block = CommandBlock::new(1, "false".to_string(), "/tmp".to_string(), String::new());
+        assert_eq!(block.exit_status, None);
+        block.finish(1);
+        assert_eq!(block.exit_status, Some(1));
+        assert!(block.finished_at.unwrap() >= block.started_at);
+        assert_eq!(block.duration(), block.finished_at.unwrap() - block.started_at);
+    }
+
+    #[test]
+    fn test_stderr_is_drawn_in_error_color() {
+        let mut block = CommandBlock::new(1, "build".to_string(), "/tmp".to_string(), String::new());
+        block.push_chunk(chunk(Stream::Stdout, "ok\n"));
+        block.push_chunk(chunk(Stream::Stderr, "failed\n"));
+
+        let style = egui::Style::default();
+        let jobs: Vec<_> = (0..2).map(|index| line_layout(&block.output.line(index).unwrap(), &style)).collect();
+        assert_eq!((jobs[0].text.as_str(), jobs[1].text.as_str()), ("ok", "failed"));
+        assert_eq!(jobs[0].sections[1].format.color, style.visuals.text_color());
+        assert_eq!(jobs[1].sections[1].format.color, style.visuals.error_fg_color);
+    }
+}
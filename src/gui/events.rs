@@ -0,0 +1,52 @@
+//! Messages from the tasks running a tab's commands to the tab.
+//!
+//! A tab owns its state and changes it only while drawing a frame, by
+//! draining the events that arrived since the previous one. Sending an event
+//! asks for a repaint, so an idle tab is not redrawn at all.
+
+use eframe::egui;
+use tokio::sync::mpsc;
+
+use crate::shell::core::output::OutputChunk;
+use super::block::CommandBlock;
+
+/// Something that happened in a tab's shell.
+pub enum TabEvent {
+    /// The current directory of the shell changed.
+    DirectoryChanged(String),
+    /// The git branch or status of the current directory changed, as shown
+    /// next to it, e.g. `(main*)`.
+    GitChanged(String),
+    /// A command wrote output to its block.
+    Output { block_id: u64, chunk: OutputChunk },
+    /// A command line finished.
+    Finished { block_id: u64, status: i32 },
+    /// A background job finished and can be reported.
+    JobFinished,
+    /// The startup files printed something, shown in a block of its own.
+    StartupOutput(Box<CommandBlock>),
+}
+
+/// Sends events to a tab. Clones send to the same tab.
+#[derive(Clone)]
+pub struct EventSender {
+    sender: mpsc::UnboundedSender<TabEvent>,
+    ctx: egui::Context,
+}
+
+impl EventSender {
+    /// Sends an event and asks for the frame that will handle it.
+    ///
+    /// Events for a tab that has been closed are dropped.
+    pub fn send(&self, event: TabEvent) {
+        if self.sender.send(event).is_ok() {
+            self.ctx.request_repaint();
+        }
+    }
+}
+
+/// Creates the channel of a tab drawn in `ctx`.
+pub fn channel(ctx: &egui::Context) -> (EventSender, mpsc::UnboundedReceiver<TabEvent>) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    (EventSender { sender, ctx: ctx.clone() }, receiver)
+}
@@ -0,0 +1,233 @@
+//! The reverse history search of a tab, opened with Ctrl+R.
+//!
+//! An overlay lists the commands of the history that fuzzily match what is
+//! typed, best first, with the matched characters highlighted and the
+//! details of the selected command below the list.
+
+use eframe::egui::{self, RichText};
+use std::ops::Range;
+
+use crate::shell::history::{format_duration, search, HistoryEntry, HistoryStore, SearchResult};
+
+/// The number of results shown without scrolling.
+const VISIBLE_RESULTS: usize = 12;
+
+/// What the user chose in the search.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchAction {
+    /// Put the command in the input field to edit it (Tab).
+    Insert(String),
+    /// Run the command (Enter, or a double click).
+    Execute(String),
+    /// Close the search without choosing a command (Escape).
+    Close,
+}
+
+/// The state of an open search.
+pub struct HistorySearch {
+    query: String,
+    results: Vec<SearchResult>,
+    /// The index of the selected result.
+    selected: usize,
+    /// The query, history revision and directory `results` were found for,
+    /// so the history is only searched again when one of them changes.
+    searched: Option<(String, u64, String)>,
+    /// Whether the search field still has to take the focus.
+    focus_query: bool,
+}
+
+impl HistorySearch {
+    /// Opens a search for `query`, usually what has been typed so far.
+    pub fn new(query: String) -> Self {
+        Self { query, results: Vec::new(), selected: 0, searched: None, focus_query: true }
+    }
+
+    /// Draws the search over the tab and handles its keys.
+    ///
+    /// Up and Down, or Ctrl+R for the next result, move the selection.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context the tab is drawn in.
+    /// * `history` - The history to search.
+    /// * `cwd` - The tab's current directory, whose commands rank higher.
+    ///
+    /// # Returns
+    ///
+    /// What the user chose, if anything.
+    pub fn ui(&mut self, ctx: &egui::Context, history: &HistoryStore, cwd: &str) -> Option<SearchAction> {
+        let (up, down, execute, insert, close) = ctx.input_mut(|i| {
+            (
+                i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp),
+                i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown) | i.consume_key(egui::Modifiers::CTRL, egui::Key::R),
+                i.consume_key(egui::Modifiers::NONE, egui::Key::Enter),
+                i.consume_key(egui::Modifiers::NONE, egui::Key::Tab),
+                i.consume_key(egui::Modifiers::NONE, egui::Key::Escape),
+            )
+        });
+        if close {
+            return Some(SearchAction::Close);
+        }
+        self.refresh(history, cwd);
+        if up {
+            self.selected = self.selected.saturating_sub(1);
+        }
+        if down && self.selected + 1 < self.results.len() {
+            self.selected += 1;
+        }
+        let selected_command = || self.results.get(self.selected).and_then(|result| history.get(result.index)).map(|entry| entry.command);
+        if execute {
+            return selected_command().map(SearchAction::Execute);
+        }
+        if insert {
+            return selected_command().map(SearchAction::Insert);
+        }
+
+        let mut action = None;
+        egui::Window::new("History search")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_BOTTOM, [0.0, -60.0])
+            .default_width(640.0)
+            .show(ctx, |ui| {
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut self.query)
+                        .hint_text("Search history")
+                        .desired_width(f32::INFINITY),
+                );
+                if std::mem::take(&mut self.focus_query) {
+                    response.request_focus();
+                }
+                ui.separator();
+                action = self.results_ui(ui, history);
+                ui.separator();
+                self.preview_ui(ui, history);
+                ui.label(RichText::new("Enter: run · Tab: edit · ↑↓ or Ctrl+R: select · Esc: close").weak().small());
+            });
+        action
+    }
+
+    /// Searches the history again if the query, the history or the
+    /// directory changed since the last search.
+    fn refresh(&mut self, history: &HistoryStore, cwd: &str) {
+        let key = (self.query.clone(), history.revision(), cwd.to_string());
+        if self.searched.as_ref() == Some(&key) {
+            return;
+        }
+        if self.searched.as_ref().is_none_or(|(query, _, _)| *query != key.0) {
+            self.selected = 0;
+        }
+        self.results = history.read(|entries| search(entries, &self.query, cwd));
+        self.selected = self.selected.min(self.results.len().saturating_sub(1));
+        self.searched = Some(key);
+    }
+
+    /// Lists the results, best first, scrolled to keep the selected one in
+    /// view.
+    fn results_ui(&mut self, ui: &mut egui::Ui, history: &HistoryStore) -> Option<SearchAction> {
+        if self.results.is_empty() {
+            ui.label(RichText::new("No matching commands").weak());
+            return None;
+        }
+        let mut action = None;
+        let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+        let row_height = ui.spacing().interact_size.y.max(ui.fonts(|fonts| fonts.row_height(&font_id)));
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, true])
+            .max_height(row_height * VISIBLE_RESULTS as f32)
+            .show_rows(ui, row_height, self.results.len(), |ui, rows| {
+                for row in rows {
+                    let result = &self.results[row];
+                    let Some(entry) = history.get(result.index) else { continue };
+                    let layout = highlighted_layout(&entry.command, &result.positions, ui.style());
+                    let response = ui.add(egui::SelectableLabel::new(row == self.selected, layout));
+                    if row == self.selected {
+                        response.scroll_to_me(None);
+                    }
+                    if response.double_clicked() {
+                        action = Some(SearchAction::Execute(entry.command));
+                    } else if response.clicked() {
+                        self.selected = row;
+                    }
+                }
+            });
+        action
+    }
+
+    /// Shows where, when and how the selected command last ran.
+    fn preview_ui(&self, ui: &mut egui::Ui, history: &HistoryStore) {
+        let Some(result) = self.results.get(self.selected) else { return };
+        let Some(entry) = history.get(result.index) else { return };
+        let runs = if result.runs == 1 { "run once".to_string() } else { format!("run {} times", result.runs) };
+        ui.label(RichText::new(format!("#{} · {} · last {}", result.index + 1, runs, last_run(&entry))).weak());
+        let place = [entry.cwd.clone(), entry.git_branch.map(|branch| format!("({})", branch)), entry.tab]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" · ");
+        if !place.is_empty() {
+            ui.label(RichText::new(place).weak());
+        }
+    }
+}
+
+/// Describes when and how an entry's command ran, e.g.
+/// `2024-05-02 10:30:00 · exit 101 · 3m 05s`.
+fn last_run(entry: &HistoryEntry) -> String {
+    let mut parts = vec![entry.started_at.map_or("at an unknown time".to_string(), |started_at| started_at.format("%Y-%m-%d %H:%M:%S").to_string())];
+    if let Some(status) = entry.exit_status {
+        parts.push(format!("exit {}", status));
+    }
+    if let Some(duration_ms) = entry.duration_ms {
+        parts.push(format_duration(chrono::Duration::milliseconds(duration_ms as i64)));
+    }
+    parts.join(" · ")
+}
+
+/// Lays out a command on one line, with its matched characters in the
+/// highlight color.
+fn highlighted_layout(command: &str, positions: &[usize], style: &egui::Style) -> egui::text::LayoutJob {
+    let font_id = egui::TextStyle::Monospace.resolve(style);
+    let normal = egui::TextFormat::simple(font_id.clone(), style.visuals.text_color());
+    let highlighted = egui::TextFormat { underline: egui::Stroke::new(1.0, style.visuals.warn_fg_color), ..egui::TextFormat::simple(font_id, style.visuals.warn_fg_color) };
+    // A multi-line command is shown on one line; a newline and a space are
+    // both one byte, so the positions still apply.
+    let command = command.replace('\n', " ");
+    let mut job = egui::text::LayoutJob::default();
+    for (range, matched) in match_runs(&command, positions) {
+        job.append(&command[range], 0.0, if matched { highlighted.clone() } else { normal.clone() });
+    }
+    job
+}
+
+/// Splits a text into runs of matched and unmatched characters.
+///
+/// # Arguments
+///
+/// * `text` - The text that was matched.
+/// * `positions` - The byte offsets of the matched characters, in order.
+fn match_runs(text: &str, positions: &[usize]) -> Vec<(Range<usize>, bool)> {
+    let mut runs: Vec<(Range<usize>, bool)> = Vec::new();
+    let mut positions = positions.iter().peekable();
+    for (offset, c) in text.char_indices() {
+        let matched = positions.next_if_eq(&&offset).is_some();
+        let end = offset + c.len_utf8();
+        match runs.last_mut() {
+            Some((range, run_matched)) if *run_matched == matched => range.end = end,
+            _ => runs.push((offset..end, matched)),
+        }
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_match_runs() {
+        assert_eq!(match_runs("git commit", &[0, 4, 5]), vec![(0..1, true), (1..4, false), (4..6, true), (6..10, false)]);
+        assert_eq!(match_runs("été", &[2]), vec![(0..2, false), (2..3, true), (3..5, false)]);
+        assert_eq!(match_runs("ls", &[]), vec![(0..2, false)]);
+    }
+}
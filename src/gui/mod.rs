@@ -1,8 +1,14 @@
 //! This module defines the graphical user interface (GUI) for the `my_cli_tool` application.
 //! It uses the `eframe` and `egui` crates to create an interactive terminal-like experience.
 
+mod ansi;
 mod app;
+mod block;
+mod events;
+mod history_search;
+mod scrollback;
 mod tab;
 mod tab_bar;
+mod terminal_view;
 
 pub use app::GuiApp;
@@ -0,0 +1,174 @@
+//! The output of a command, kept as lines ready to be drawn.
+//!
+//! Output is split into lines as it arrives and each line is interpreted
+//! once, so drawing a block only lays out the lines in view, however long
+//! the output is. Only the most recent lines are kept: once a buffer holds
+//! its capacity, every new line drops the oldest one.
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+
+use crate::shell::core::output::{OutputChunk, Stream};
+use super::ansi::{self, Span};
+
+/// The number of lines a block keeps unless `SCROLLBACK` says otherwise.
+pub const DEFAULT_CAPACITY: usize = 100_000;
+
+/// A line of output and the stream it was written to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub stream: Stream,
+    pub spans: Vec<Span>,
+}
+
+impl Line {
+    /// Returns the text of the line without its styles.
+    pub fn text(&self) -> String {
+        self.spans.iter().map(|span| span.text.as_str()).collect()
+    }
+}
+
+/// The lines of output of a command, oldest first.
+pub struct Scrollback {
+    lines: VecDeque<Line>,
+    stdout: ansi::Parser,
+    stderr: ansi::Parser,
+    /// The stream whose last line has been started but not ended yet.
+    open: Option<Stream>,
+    capacity: usize,
+    /// How many lines have been dropped to stay within the capacity.
+    dropped: usize,
+}
+
+impl Scrollback {
+    /// Creates an empty buffer that keeps at most `capacity` lines.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            lines: VecDeque::new(),
+            stdout: ansi::Parser::default(),
+            stderr: ansi::Parser::default(),
+            open: None,
+            capacity: capacity.max(1),
+            dropped: 0,
+        }
+    }
+
+    /// Appends output.
+    ///
+    /// Output of one stream ends the unfinished line of the other, so that
+    /// lines are kept in the order they were written.
+    pub fn push(&mut self, chunk: &OutputChunk) {
+        if let Some(open) = self.open.filter(|open| *open != chunk.stream) {
+            let spans = self.parser(open).end_line();
+            self.push_line(Line { stream: open, spans });
+        }
+        for spans in self.parser(chunk.stream).feed(&chunk.text) {
+            self.push_line(Line { stream: chunk.stream, spans });
+        }
+        let has_open_line = !self.parser(chunk.stream).open_line().is_empty();
+        self.open = has_open_line.then_some(chunk.stream);
+    }
+
+    /// Returns the number of lines, including an unfinished last line.
+    pub fn len(&self) -> usize {
+        self.lines.len() + usize::from(self.open.is_some())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns how many of the oldest lines have been dropped.
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Returns the line at `index`, counting from the oldest line kept.
+    pub fn line(&self, index: usize) -> Option<Cow<'_, Line>> {
+        match self.lines.get(index) {
+            Some(line) => Some(Cow::Borrowed(line)),
+            None if index == self.lines.len() => {
+                let stream = self.open?;
+                let spans = match stream {
+                    Stream::Stdout => self.stdout.open_line(),
+                    Stream::Stderr => self.stderr.open_line(),
+                };
+                Some(Cow::Owned(Line { stream, spans }))
+            }
+            None => None,
+        }
+    }
+
+    /// Returns the text of the lines kept, without styles.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - Only lines of this stream are included, or all lines if
+    ///   `None`.
+    pub fn text(&self, stream: Option<Stream>) -> String {
+        let mut text = String::new();
+        for index in 0..self.len() {
+            let line = self.line(index).expect("index is within the buffer");
+            if stream.is_none_or(|stream| stream == line.stream) {
+                text.push_str(&line.text());
+                if index < self.lines.len() {
+                    text.push('\n');
+                }
+            }
+        }
+        text
+    }
+
+    fn parser(&mut self, stream: Stream) -> &mut ansi::Parser {
+        match stream {
+            Stream::Stdout => &mut self.stdout,
+            Stream::Stderr => &mut self.stderr,
+        }
+    }
+
+    fn push_line(&mut self, line: Line) {
+        if self.lines.len() == self.capacity {
+            self.lines.pop_front();
+            self.dropped += 1;
+        }
+        self.lines.push_back(line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(stream: Stream, text: &str) -> OutputChunk {
+        OutputChunk { stream, text: text.to_string() }
+    }
+
+    #[test]
+    fn test_lines_are_split_as_output_arrives() {
+        let mut output = Scrollback::new(DEFAULT_CAPACITY);
+        output.push(&chunk(Stream::Stdout, "one\ntw"));
+        assert_eq!(output.len(), 2);
+        assert_eq!(output.line(1).unwrap().text(), "tw");
+        output.push(&chunk(Stream::Stdout, "o\nthree\n"));
+        assert_eq!(output.len(), 3);
+        assert_eq!(output.text(None), "one\ntwo\nthree\n");
+
+        // Another stream ends the unfinished line.
+        output.push(&chunk(Stream::Stdout, "progress"));
+        output.push(&chunk(Stream::Stderr, "warning\nerr"));
+        assert_eq!(output.text(Some(Stream::Stdout)), "one\ntwo\nthree\nprogress\n");
+        assert_eq!(output.text(Some(Stream::Stderr)), "warning\nerr");
+        assert_eq!(output.line(5).unwrap().stream, Stream::Stderr);
+        assert!(output.line(6).is_none());
+    }
+
+    #[test]
+    fn test_oldest_lines_are_dropped_beyond_capacity() {
+        let mut output = Scrollback::new(3);
+        let text: String = (1..=10).map(|n| format!("line {}\n", n)).collect();
+        output.push(&chunk(Stream::Stdout, &text));
+        assert_eq!(output.len(), 3);
+        assert_eq!(output.dropped(), 7);
+        assert_eq!(output.text(None), "line 8\nline 9\nline 10\n");
+    }
+}
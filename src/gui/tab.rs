@@ -1,68 +1,217 @@
 //! This module defines the state and UI for a single shell tab in the `my_cli_tool` application.
 
 use eframe::egui;
+use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use tokio::sync::{mpsc, oneshot, Mutex};
 use tokio::task;
-use chrono::Local;
 
-use crate::shell::history::CommandHistory;
+use crate::shell::history::{expand_history, CommandHistory, EntryId, HistoryEntry, HistoryStore};
 use crate::shell::core::ShellCore;
+use crate::shell::core::cancellation::CancellationToken;
+use crate::shell::core::command_executor::{EXIT_CANNOT_EXECUTE, EXIT_FAILURE};
+use crate::shell::core::jobs::JobTable;
+use crate::shell::core::output::{OutputChunk, OutputSink, Stream};
+use crate::shell::core::parser;
+use crate::shell::core::pty::{self, PtySession};
+use crate::shell::core::shared::SharedShell;
+use crate::shell::core::startup;
 use crate::shell::features::autocompletion::Autocompleter;
+use super::block::{BlockAction, CommandBlock};
+use super::events::{self, EventSender, TabEvent};
+use super::history_search::{HistorySearch, SearchAction};
+use super::scrollback::Scrollback;
+use super::terminal_view;
 
 /// `ShellTab` holds the state for a single tab, including input, output, and shell core.
 pub struct ShellTab {
     pub title: String,
     input: String,
-    output: Arc<Mutex<String>>,
-    shell_core: Arc<Mutex<ShellCore>>,
+    /// The commands run in this tab with their output, oldest first.
+    blocks: Vec<CommandBlock>,
+    next_block_id: u64,
+    /// What the tasks running the tab's commands report, drained every frame.
+    events: mpsc::UnboundedReceiver<TabEvent>,
+    event_sender: EventSender,
+    /// The tab's shell. Commands run against a snapshot of it, so the UI
+    /// can read it at any time.
+    shell_core: SharedShell,
+    /// The background jobs of the shell, which can be read while it is busy.
+    jobs: JobTable,
     command_history: CommandHistory,
-    current_dir_display: Arc<Mutex<String>>,
-    git_info_display: Arc<Mutex<String>>,
+    /// The history entries of the commands still running, by block id.
+    history_entries: HashMap<u64, EntryId>,
+    /// The reverse history search, while it is open.
+    history_search: Option<HistorySearch>,
+    /// The current directory and git state of the shell, as shown in the tab.
+    current_dir: String,
+    git_info: String,
     autocompleter: Autocompleter,
     ghost_text: Arc<Mutex<String>>,
+    /// Shared by the commands started since the last Ctrl+C; cancelling it
+    /// interrupts them.
+    cancellation: CancellationToken,
+    /// The interactive program running under a pseudo-terminal, if any,
+    /// with the id of its block. While it runs, the tab shows its screen and
+    /// forwards keys to it.
+    pty: Option<(u64, PtySession)>,
+    /// The terminal size last computed from the tab's area, as rows and columns.
+    terminal_size: (u16, u16),
 }
 
 impl ShellTab {
     /// Creates a new `ShellTab` instance.
-    pub fn new(title: String) -> Self {
-        let shell_core = Arc::new(Mutex::new(ShellCore::new()));
-        let command_history = CommandHistory::new();
-        let autocompleter = Autocompleter::new(command_history.clone());
-        let current_dir = "Loading...".to_string();
+    ///
+    /// # Arguments
+    ///
+    /// * `title` - The name shown in the tab bar.
+    /// * `ctx` - The context the tab is drawn in, repainted when its shell
+    ///   reports something.
+    /// * `history` - The command history shared by all tabs.
+    pub fn new(title: String, ctx: &egui::Context, history: HistoryStore) -> Self {
+        let (event_sender, events) = events::channel(ctx);
+        let mut shell_core = ShellCore::new();
+        shell_core.history = history.clone();
+        let (current_dir, git_info) = prompt_info(&shell_core);
+        let jobs = shell_core.jobs.clone();
+        jobs.on_finish({
+            let event_sender = event_sender.clone();
+            move |_| event_sender.send(TabEvent::JobFinished)
+        });
+        let shell_core = SharedShell::new(shell_core);
+        let autocompleter = Autocompleter::new(history.clone());
+        let command_history = CommandHistory::with_store(history);
 
-        Self {
+        let mut tab = Self {
             title,
             input: String::new(),
-            output: Arc::new(Mutex::new(String::new())),
+            blocks: Vec::new(),
+            next_block_id: 0,
+            events,
+            event_sender,
             shell_core,
+            jobs,
             command_history,
-            current_dir_display: Arc::new(Mutex::new(current_dir)),
-            git_info_display: Arc::new(Mutex::new(String::new())),
+            history_entries: HashMap::new(),
+            history_search: None,
+            current_dir,
+            git_info,
             autocompleter,
             ghost_text: Arc::new(Mutex::new(String::new())),
+            cancellation: CancellationToken::new(),
+            pty: None,
+            terminal_size: (24, 80),
+        };
+        tab.run_startup_files();
+        tab
+    }
+
+    /// Runs the startup files in the tab's shell before any command.
+    ///
+    /// If they print anything, such as an error, it is shown in a block of
+    /// its own.
+    fn run_startup_files(&mut self) {
+        // Nothing else has the new shell yet, so commands typed meanwhile
+        // wait for the files to finish.
+        let Some(mut shell_core) = self.shell_core.try_begin() else {
+            return;
+        };
+        let block_id = self.next_block_id;
+        self.next_block_id += 1;
+        let events = self.event_sender.clone();
+        let cancellation = self.cancellation.clone();
+
+        task::spawn(async move {
+            let (sink, mut receiver) = OutputSink::new();
+            let before = prompt_info(&shell_core);
+            let files = startup::run_startup_files(&mut shell_core, &sink, &cancellation).await;
+            let after = prompt_info(&shell_core);
+            let status = shell_core.last_exit_status;
+            shell_core.commit();
+            drop(sink);
+            let current_dir = after.0.clone();
+            send_prompt_changes(&events, before, after);
+            let mut chunks = Vec::new();
+            while let Some(chunk) = receiver.recv().await {
+                chunks.push(chunk);
+            }
+            if chunks.is_empty() {
+                return;
+            }
+
+            let command = files.iter().map(|file| format!("source {}", file.display())).collect::<Vec<_>>().join("; ");
+            let mut block = CommandBlock::new(block_id, command, current_dir, String::new());
+            for chunk in chunks {
+                block.push_chunk(chunk);
+            }
+            block.finish(status);
+            events.send(TabEvent::StartupOutput(Box::new(block)));
+        });
+    }
+
+    /// Applies the events sent since the last frame.
+    fn handle_events(&mut self) {
+        while let Ok(event) = self.events.try_recv() {
+            match event {
+                TabEvent::DirectoryChanged(current_dir) => self.current_dir = current_dir,
+                TabEvent::GitChanged(git_info) => self.git_info = git_info,
+                TabEvent::Output { block_id, chunk } => self.update_block(block_id, |block| block.push_chunk(chunk)),
+                TabEvent::Finished { block_id, status } => self.finish_block(block_id, status),
+                TabEvent::JobFinished => self.report_finished_jobs(),
+                // Startup files run before any command, so their block comes first.
+                TabEvent::StartupOutput(block) => self.blocks.insert(0, *block),
+            }
         }
     }
 
     /// Renders the UI for this tab.
     pub fn ui(&mut self, ui: &mut egui::Ui) {
-        // Asynchronously update current_dir_display and git_info_display
-        let shell_core_arc_clone = self.shell_core.clone();
-        let current_dir_display_arc_clone_for_spawn = self.current_dir_display.clone();
-        let git_info_display_arc_clone_for_spawn = self.git_info_display.clone();
-        task::spawn(async move {
-            let shell_core = shell_core_arc_clone.lock().await;
-            let new_dir = shell_core.get_current_dir().to_string_lossy().into_owned();
-            *current_dir_display_arc_clone_for_spawn.lock().await = new_dir;
-
-            let git_info_str = if let Some(info) = &shell_core.git_info {
-                let changes_indicator = if info.has_changes { "*" } else { "" };
-                format!("({}{})", info.branch_name, changes_indicator)
-            } else {
-                String::new()
-            };
-            *git_info_display_arc_clone_for_spawn.lock().await = git_info_str;
+        self.handle_events();
+
+        if self.pty.is_some() {
+            self.terminal_ui(ui);
+            return;
+        }
+
+        let input_id = ui.make_persistent_id("command_input");
+
+        // Ctrl+C interrupts the running commands. Most platforms report it as
+        // a copy event rather than a key press, so both are checked; while
+        // some text is selected, the event only copies it.
+        let interrupt = ui.input(|i| {
+            i.modifiers.ctrl
+                && (i.key_pressed(egui::Key::C) || i.events.iter().any(|e| matches!(e, egui::Event::Copy)))
         });
+        if interrupt && !has_text_selection(ui.ctx(), input_id) {
+            self.cancellation.cancel();
+            self.cancellation = CancellationToken::new();
+        }
+
+        // Ctrl+R opens the reverse history search, which takes the keys it
+        // uses before the input field sees them.
+        if let Some(search) = &mut self.history_search {
+            match search.ui(ui.ctx(), self.command_history.store(), &self.current_dir) {
+                Some(SearchAction::Insert(command)) => {
+                    self.input = command;
+                    self.history_search = None;
+                    ui.memory_mut(|mem| mem.request_focus(input_id));
+                }
+                Some(SearchAction::Execute(command)) => {
+                    // The command is already expanded, so it runs as it is.
+                    self.history_search = None;
+                    self.record_and_run(ui.ctx(), command);
+                    self.clear_input();
+                    ui.memory_mut(|mem| mem.request_focus(input_id));
+                }
+                Some(SearchAction::Close) => {
+                    self.history_search = None;
+                    ui.memory_mut(|mem| mem.request_focus(input_id));
+                }
+                None => {}
+            }
+        } else if ui.input_mut(|i| i.consume_key(egui::Modifiers::CTRL, egui::Key::R)) {
+            self.history_search = Some(HistorySearch::new(self.input.clone()));
+        }
 
         // Handle Tab key press for autocompletion BEFORE the main UI panel
         if ui.input(|i| i.key_pressed(egui::Key::Tab)) {
@@ -75,8 +224,6 @@ impl ShellTab {
             }
         }
 
-        let mut input_id = None;
-
         // Bottom panel for command input
         egui::TopBottomPanel::bottom("input_panel").show(ui.ctx(), |ui| {
             ui.horizontal(|ui| {
@@ -112,12 +259,20 @@ impl ShellTab {
                     ui.fonts(|f| f.layout_job(layout_job))
                 };
 
+                // Enter runs a complete command line. For an unfinished one,
+                // such as a `for` loop without its `done`, or with Shift held,
+                // it starts a new line instead.
+                let submit = ui.memory(|mem| mem.has_focus(input_id))
+                    && !parser::needs_more_input(&self.input)
+                    && ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Enter));
+
                 let response = ui.add(
-                    egui::TextEdit::singleline(&mut self.input)
+                    egui::TextEdit::multiline(&mut self.input)
+                        .id(input_id)
+                        .desired_rows(1)
                         .desired_width(f32::INFINITY)
                         .layouter(&mut layouter)
                 );
-                input_id = Some(response.id);
 
                 if response.changed() {
                     let input_clone = self.input.clone();
@@ -126,10 +281,10 @@ impl ShellTab {
                     let ghost_text_clone = self.ghost_text.clone();
 
                     task::spawn(async move {
-                        let shell_core = shell_core_clone.lock().await;
-                        let suggestions = autocompleter_clone.get_suggestions(&input_clone, &shell_core.get_current_dir()).await;
+                        let current_dir = shell_core_clone.read(|shell_core| shell_core.get_current_dir());
+                        let suggestions = autocompleter_clone.get_suggestions(&input_clone, &current_dir).await;
                         let mut ghost_text = ghost_text_clone.lock().await;
-                        if let Some(first_suggestion) = suggestions.get(0) {
+                        if let Some(first_suggestion) = suggestions.first() {
                             *ghost_text = first_suggestion.clone();
                         } else {
                             ghost_text.clear();
@@ -137,110 +292,331 @@ impl ShellTab {
                     });
                 }
 
-                if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
-                    self.execute_command();
+                if submit {
+                    self.execute_command(ui.ctx());
                     response.request_focus();
                 }
 
                 if ui.button("Run").clicked() {
-                    self.execute_command();
+                    self.execute_command(ui.ctx());
                 }
 
                 if ui.button("Clear").clicked() {
-                    let output_arc = self.output.clone();
-                    tokio::task::spawn(async move {
-                        output_arc.lock().await.clear();
-                    });
+                    self.blocks.clear();
                 }
             });
         });
 
         // Central panel for output
         egui::CentralPanel::default().show(ui.ctx(), |ui| {
-            let dir_str = self.current_dir_display.try_lock().map(|s| s.clone()).unwrap_or_else(|_|"(Loading...)".to_string());
-            let git_str = self.git_info_display.try_lock().map(|s| s.clone()).unwrap_or_default();
-            ui.label(format!("Current Directory: {} {}", dir_str, git_str));
+            let running: Vec<String> = self.jobs.running().iter().map(|job| format!("[{}] {}", job.id, job.command)).collect();
+            let jobs_str = if running.is_empty() { String::new() } else { format!("  Jobs: {}", running.join(", ")) };
+            ui.label(format!("Current Directory: {} {}{}", self.current_dir, self.git_info, jobs_str));
             ui.separator();
 
+            let mut actions = Vec::new();
             egui::ScrollArea::vertical().stick_to_bottom(true).show(ui, |ui_scroll| {
-                let output_str = self.output.try_lock().map(|s| s.clone()).unwrap_or_else(|_|"(Output busy...)".to_string());
                 ui_scroll.set_width(ui_scroll.available_width());
-                ui_scroll.add(egui::Label::new(egui::RichText::new(&output_str).monospace()).wrap(true));
+                for block in self.blocks.iter_mut() {
+                    actions.extend(ui_scroll.push_id(block.id, |ui| block.ui(ui)).inner);
+                }
+                // Keep the durations of running commands ticking.
+                if self.blocks.iter().any(|block| block.exit_status.is_none()) {
+                    ui_scroll.ctx().request_repaint_after(std::time::Duration::from_millis(100));
+                }
             });
+            for action in actions {
+                match action {
+                    BlockAction::Rerun(command) => self.run_command(ui.ctx(), command, None),
+                }
+            }
         });
 
-        if let Some(id) = input_id {
-            if ui.memory(|mem| mem.has_focus(id)) {
-                ui.input(|i| {
-                    if i.key_pressed(egui::Key::ArrowUp) {
-                        if let Some(cmd) = self.command_history.navigate_up() {
-                            self.input = cmd.to_owned();
-                        }
-                    } else if i.key_pressed(egui::Key::ArrowDown) {
-                        if let Some(cmd) = self.command_history.navigate_down() {
-                            self.input = cmd.to_owned();
-                        }
+        // Up and Down move between the lines of a multi-line command instead.
+        if !self.input.contains('\n') && ui.memory(|mem| mem.has_focus(input_id)) {
+            ui.input(|i| {
+                if i.key_pressed(egui::Key::ArrowUp) {
+                    if let Some(cmd) = self.command_history.navigate_up() {
+                        self.input = cmd;
                     }
-                });
-            }
+                } else if i.key_pressed(egui::Key::ArrowDown) {
+                    if let Some(cmd) = self.command_history.navigate_down() {
+                        self.input = cmd;
+                    }
+                }
+            });
         }
     }
 
     /// Executes the command currently in the input field.
-    fn execute_command(&mut self) {
+    ///
+    /// History references such as `!!` are expanded first, and the
+    /// expanded line is what is recorded and run.
+    fn execute_command(&mut self, ctx: &egui::Context) {
         let input_command = self.input.trim().to_string();
         if input_command.is_empty() {
             return;
         }
 
-        self.command_history.add(input_command.clone());
+        match self.command_history.store().read(|entries| expand_history(&input_command, entries)) {
+            Ok(command) => self.record_and_run(ctx, command),
+            Err(error) => {
+                let block_id = self.next_block_id;
+                self.next_block_id += 1;
+                self.start_block(block_id, input_command);
+                self.update_block(block_id, |block| {
+                    block.push_chunk(OutputChunk { stream: Stream::Stderr, text: format!("Error: {}\n", error) });
+                    block.finish(EXIT_FAILURE);
+                });
+            }
+        }
+        self.clear_input();
+    }
 
-        let output_arc = self.output.clone();
-        let shell_core_arc = self.shell_core.clone();
-        let current_dir_display_arc = self.current_dir_display.clone();
-        let git_info_display_arc = self.git_info_display.clone();
+    /// Records a command line in the history, with the directory, git
+    /// branch and tab it runs in, and runs it.
+    fn record_and_run(&mut self, ctx: &egui::Context, command: String) {
+        let git_branch = self.shell_core.read(|shell_core| shell_core.git_info.as_ref().map(|info| info.branch_name.clone()));
+        let entry = HistoryEntry {
+            cwd: Some(self.current_dir.clone()),
+            git_branch,
+            tab: Some(self.title.clone()),
+            ..HistoryEntry::new(command.clone())
+        };
+        let history_entry = self.command_history.add(entry);
+        self.run_command(ctx, command, history_entry);
+    }
 
+    /// Empties the input field and its autocompletion suggestion.
+    fn clear_input(&mut self) {
+        self.input.clear();
+        let ghost_text_clone = self.ghost_text.clone();
         task::spawn(async move {
-            {
-                let mut output = output_arc.lock().await;
-                let current_dir = current_dir_display_arc.lock().await;
-                let git_info = git_info_display_arc.lock().await;
-                let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
-                output.push_str(&format!("\n[{}] {} {} $ {}\n", timestamp, *current_dir, *git_info, &input_command));
-            }
+            ghost_text_clone.lock().await.clear();
+        });
+    }
 
-            let command_output = {
-                let mut shell_core = shell_core_arc.lock().await;
-                shell_core.execute_shell_command(&input_command).await
-            };
+    /// Runs a command line in a new block.
+    ///
+    /// The output, the end of the line and any change to the current
+    /// directory or git state are sent to the tab as events.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context the tab is drawn in.
+    /// * `input_command` - The command line to run.
+    /// * `history_entry` - The id of the line's history entry, which is
+    ///   given its exit status once it finishes, or `None` for a line that
+    ///   is not recorded, such as a rerun.
+    fn run_command(&mut self, ctx: &egui::Context, input_command: String, history_entry: Option<EntryId>) {
+        let block_id = self.next_block_id;
+        self.next_block_id += 1;
+        if let Some(id) = history_entry {
+            self.history_entries.insert(block_id, id);
+        }
 
-            {
-                let mut output = output_arc.lock().await;
-                output.push_str(&command_output);
-                output.push('\n');
-            }
+        // A busy shell is left to run the line normally once it is free.
+        let interactive = (!self.shell_core.is_busy()).then(|| self.shell_core.read(|shell_core| {
+            let argv = pty::interactive_command(shell_core, &input_command)?;
+            let ctx = ctx.clone();
+            let (rows, cols) = self.terminal_size;
+            Some(PtySession::spawn(shell_core, &argv, rows, cols, move || ctx.request_repaint()))
+        })).flatten();
+        if let Some(session) = interactive {
+            self.start_terminal(block_id, input_command, session);
+            return;
+        }
+
+        self.start_block(block_id, input_command.clone());
+        let shell_core_arc = self.shell_core.clone();
+        let events = self.event_sender.clone();
+        let cancellation = self.cancellation.clone();
 
-            {
-                let shell_core = shell_core_arc.lock().await;
+        task::spawn(async move {
+            let (sink, mut receiver) = OutputSink::new();
+            let (line_done, mut done) = oneshot::channel::<()>();
+            let events_for_chunks = events.clone();
+            // Forwards the output until the line has finished and what it
+            // wrote has been sent, then hands the receiver back.
+            let forwarder = task::spawn(async move {
+                loop {
+                    tokio::select! {
+                        chunk = receiver.recv() => match chunk {
+                            Some(chunk) => events_for_chunks.send(TabEvent::Output { block_id, chunk }),
+                            None => break,
+                        },
+                        _ = &mut done => {
+                            while let Ok(chunk) = receiver.try_recv() {
+                                events_for_chunks.send(TabEvent::Output { block_id, chunk });
+                            }
+                            break;
+                        }
+                    }
+                }
+                receiver
+            });
+
+            // Lines run one after the other; each one's changes to the shell
+            // are published when it finishes.
+            let (status, before, after) = {
+                let mut shell_core = shell_core_arc.begin().await;
+                let before = prompt_info(&shell_core);
+                shell_core.execute_shell_command_streaming(&input_command, &sink, &cancellation).await;
                 // The git info is already updated inside execute_shell_command
-                let new_dir = shell_core.get_current_dir().to_string_lossy().into_owned();
-                *current_dir_display_arc.lock().await = new_dir;
-                
-                let git_info_str = if let Some(info) = &shell_core.git_info {
-                    let changes_indicator = if info.has_changes { "*" } else { "" };
-                    format!("({}{})", info.branch_name, changes_indicator)
-                } else {
-                    String::new()
-                };
-                *git_info_display_arc.lock().await = git_info_str;
+                let after = prompt_info(&shell_core);
+                let status = shell_core.last_exit_status;
+                shell_core.commit();
+                (status, before, after)
+            };
+            drop(sink);
+            let _ = line_done.send(());
+            let receiver = forwarder.await.ok();
+            events.send(TabEvent::Finished { block_id, status });
+            send_prompt_changes(&events, before, after);
+            // Background jobs started by the line keep writing to its block,
+            // so the block is finished without waiting for their output.
+            if let Some(mut receiver) = receiver {
+                while let Some(chunk) = receiver.recv().await {
+                    events.send(TabEvent::Output { block_id, chunk });
+                }
             }
         });
+    }
 
-        self.input.clear();
-        // Clear ghost text after command execution
-        let ghost_text_clone = self.ghost_text.clone();
-        task::spawn(async move {
-            ghost_text_clone.lock().await.clear();
+    /// Adds a block reporting each background job that has finished.
+    fn report_finished_jobs(&mut self) {
+        for job in self.jobs.take_finished() {
+            let block_id = self.next_block_id;
+            self.next_block_id += 1;
+            let mut block = CommandBlock::new(block_id, format!("{} &", job.command), self.current_dir.clone(), String::new());
+            block.started_at = job.started_at;
+            block.push_chunk(OutputChunk { stream: Stream::Stdout, text: format!("{}\n", job.describe('+')) });
+            block.finish(job.status.unwrap_or_default());
+            self.blocks.push(block);
+        }
+    }
+
+    /// Shows the screen of the program running under a pseudo-terminal and
+    /// forwards the keys pressed in the tab to it.
+    ///
+    /// When the program exits, its last screen becomes the output of its
+    /// block and its exit status becomes `$?`.
+    fn terminal_ui(&mut self, ui: &mut egui::Ui) {
+        let Some((block_id, session)) = self.pty.as_mut() else { return };
+        if let Some(status) = session.try_wait() {
+            let block_id = *block_id;
+            let contents = session.contents();
+            self.pty = None;
+            if !contents.is_empty() {
+                self.update_block(block_id, |block| {
+                    block.push_chunk(OutputChunk { stream: Stream::Stdout, text: format!("{}\n", contents) });
+                });
+            }
+            self.finish_block(block_id, status);
+            let shell_core_arc = self.shell_core.clone();
+            task::spawn(async move {
+                shell_core_arc.update(|shell_core| shell_core.last_exit_status = status).await;
+            });
+            return;
+        }
+
+        let input = ui.input(|i| terminal_view::encode_input(&i.events, session.parser().screen()));
+        if !input.is_empty() {
+            // The program may already have closed the terminal; it is
+            // cleaned up once its exit is noticed.
+            let _ = session.write(&input);
+        }
+
+        egui::CentralPanel::default().show(ui.ctx(), |ui| {
+            ui.label(format!("Current Directory: {} (running in terminal)", self.current_dir));
+            ui.separator();
+
+            self.terminal_size = terminal_view::grid_size(ui, ui.available_size());
+            let (rows, cols) = self.terminal_size;
+            let _ = session.resize(rows, cols);
+            terminal_view::show(ui, session.parser().screen());
         });
     }
-}
\ No newline at end of file
+
+    /// Records an interactive command and makes its terminal the tab's view.
+    fn start_terminal(&mut self, block_id: u64, input_command: String, session: anyhow::Result<PtySession>) {
+        self.start_block(block_id, input_command);
+        match session {
+            Ok(session) => self.pty = Some((block_id, session)),
+            Err(error) => {
+                self.update_block(block_id, |block| {
+                    block.push_chunk(OutputChunk { stream: Stream::Stderr, text: format!("Error: {}\n", error) });
+                });
+                self.finish_block(block_id, EXIT_CANNOT_EXECUTE);
+                let shell_core_arc = self.shell_core.clone();
+                task::spawn(async move {
+                    shell_core_arc.update(|shell_core| shell_core.last_exit_status = EXIT_CANNOT_EXECUTE).await;
+                });
+            }
+        }
+    }
+
+    /// Adds the block of a command that is starting, recording the directory
+    /// and git state shown in the tab.
+    ///
+    /// The block keeps as many lines of output as the `SCROLLBACK` variable
+    /// says, or `scrollback::DEFAULT_CAPACITY`.
+    fn start_block(&mut self, block_id: u64, input_command: String) {
+        let mut block = CommandBlock::new(block_id, input_command, self.current_dir.clone(), self.git_info.clone());
+        let capacity = self.shell_core.read(|shell_core| shell_core.get_var("SCROLLBACK"));
+        if let Some(capacity) = capacity.and_then(|capacity| capacity.parse().ok()) {
+            block.output = Scrollback::new(capacity);
+        }
+        self.blocks.push(block);
+    }
+
+    /// Marks a block as finished and saves the history entry of its command.
+    fn finish_block(&mut self, block_id: u64, status: i32) {
+        self.update_block(block_id, |block| block.finish(status));
+        if let Some(id) = self.history_entries.remove(&block_id) {
+            if let Err(e) = self.command_history.store().finish(id, status) {
+                eprintln!("{}: history: {}", env!("CARGO_PKG_NAME"), e);
+            }
+        }
+    }
+
+    /// Applies `update` to a block, unless it has been cleared in the meantime.
+    fn update_block(&mut self, block_id: u64, update: impl FnOnce(&mut CommandBlock)) {
+        if let Some(block) = self.blocks.iter_mut().find(|block| block.id == block_id) {
+            update(block);
+        }
+    }
+}
+
+/// Returns the current directory and the git branch with its status
+/// indicator, e.g. `(main*)`, as the tab shows them.
+fn prompt_info(shell_core: &ShellCore) -> (String, String) {
+    let new_dir = shell_core.get_current_dir().to_string_lossy().into_owned();
+    let git_info_str = if let Some(info) = &shell_core.git_info {
+        let changes_indicator = if info.has_changes { "*" } else { "" };
+        format!("({}{})", info.branch_name, changes_indicator)
+    } else {
+        String::new()
+    };
+    (new_dir, git_info_str)
+}
+
+/// Tells the tab about the parts of `after` that differ from `before`, both
+/// as returned by `prompt_info`.
+fn send_prompt_changes(events: &EventSender, before: (String, String), after: (String, String)) {
+    if after.0 != before.0 {
+        events.send(TabEvent::DirectoryChanged(after.0));
+    }
+    if after.1 != before.1 {
+        events.send(TabEvent::GitChanged(after.1));
+    }
+}
+
+/// Returns whether text is selected in the output of the blocks or in the
+/// input field, so that Ctrl+C copies it rather than interrupting.
+fn has_text_selection(ctx: &egui::Context, input_id: egui::Id) -> bool {
+    let in_input = egui::TextEdit::load_state(ctx, input_id)
+        .and_then(|state| state.cursor.char_range())
+        .is_some_and(|range| range.primary.index != range.secondary.index);
+    in_input || egui::text_selection::LabelSelectionState::load(ctx).has_selection()
+}
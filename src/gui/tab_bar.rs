@@ -15,7 +15,7 @@ pub fn show(ctx: &egui::Context, app: &mut GuiApp) {
             }
             if ui.button("+").clicked() {
                 let new_tab_index = app.tabs.len();
-                app.tabs.push(ShellTab::new(format!("Tab {}", new_tab_index + 1)));
+                app.tabs.push(ShellTab::new(format!("Tab {}", new_tab_index + 1), ctx, app.history.clone()));
                 app.selected_tab = new_tab_index;
             }
         });
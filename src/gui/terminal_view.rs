@@ -0,0 +1,215 @@
+//! Drawing a pseudo-terminal's screen and translating keystrokes for it.
+//!
+//! While an interactive program runs, the tab shows the program's VT100
+//! screen as a grid of monospace cells instead of the usual output, and the
+//! keys pressed in the tab are encoded the way an xterm would send them.
+
+use eframe::egui::{self, text::LayoutJob, Color32, FontId, TextFormat};
+
+use super::ansi::indexed_color;
+
+fn to_color32(color: vt100::Color, default: Color32) -> Color32 {
+    match color {
+        vt100::Color::Default => default,
+        vt100::Color::Idx(index) => indexed_color(index),
+        vt100::Color::Rgb(r, g, b) => Color32::from_rgb(r, g, b),
+    }
+}
+
+/// Lays out the screen as one line of text per terminal row.
+///
+/// # Arguments
+///
+/// * `screen` - The terminal screen to draw.
+/// * `font_id` - A monospace font.
+/// * `foreground`, `background` - The colors used for the terminal's defaults.
+pub fn screen_layout(screen: &vt100::Screen, font_id: FontId, foreground: Color32, background: Color32) -> LayoutJob {
+    let (rows, cols) = screen.size();
+    let cursor = (!screen.hide_cursor()).then(|| screen.cursor_position());
+    let mut job = LayoutJob::default();
+
+    for row in 0..rows {
+        for col in 0..cols {
+            let Some(cell) = screen.cell(row, col) else { continue };
+            if cell.is_wide_continuation() {
+                continue;
+            }
+            let mut fg = to_color32(cell.fgcolor(), foreground);
+            let mut bg = to_color32(cell.bgcolor(), background);
+            if cell.bold() {
+                if let vt100::Color::Idx(index @ 0..=7) = cell.fgcolor() {
+                    fg = indexed_color(index + 8);
+                }
+            }
+            if cell.inverse() != (cursor == Some((row, col))) {
+                std::mem::swap(&mut fg, &mut bg);
+            }
+            let format = TextFormat {
+                font_id: font_id.clone(),
+                color: fg,
+                background: bg,
+                italics: cell.italic(),
+                underline: if cell.underline() { egui::Stroke::new(1.0, fg) } else { egui::Stroke::NONE },
+                ..Default::default()
+            };
+            let contents = cell.contents();
+            job.append(if contents.is_empty() { " " } else { &contents }, 0.0, format);
+        }
+        if row + 1 < rows {
+            job.append("\n", 0.0, TextFormat { font_id: font_id.clone(), ..Default::default() });
+        }
+    }
+    job
+}
+
+/// Draws the screen of a terminal.
+pub fn show(ui: &mut egui::Ui, screen: &vt100::Screen) {
+    let visuals = &ui.style().visuals;
+    let job = screen_layout(screen, FontId::monospace(14.0), visuals.text_color(), visuals.extreme_bg_color);
+    ui.add(egui::Label::new(job).wrap(false));
+}
+
+/// Returns how many rows and columns of cells fit into `available`.
+pub fn grid_size(ui: &egui::Ui, available: egui::Vec2) -> (u16, u16) {
+    let font_id = FontId::monospace(14.0);
+    let (cell_width, cell_height) = ui.fonts(|f| (f.glyph_width(&font_id, 'M'), f.row_height(&font_id)));
+    let rows = (available.y / cell_height).floor().clamp(2.0, u16::MAX as f32) as u16;
+    let cols = (available.x / cell_width).floor().clamp(2.0, u16::MAX as f32) as u16;
+    (rows, cols)
+}
+
+/// Encodes the input events of a frame as the bytes a terminal would send.
+///
+/// Ctrl+C arrives as a copy event on most platforms and is sent as `^C`, so
+/// it interrupts the program instead of the shell.
+pub fn encode_input(events: &[egui::Event], screen: &vt100::Screen) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for event in events {
+        match event {
+            egui::Event::Text(text) => bytes.extend_from_slice(text.as_bytes()),
+            egui::Event::Paste(text) => {
+                let text = text.replace('\n', "\r");
+                if screen.bracketed_paste() {
+                    bytes.extend_from_slice(b"\x1b[200~");
+                    bytes.extend_from_slice(text.as_bytes());
+                    bytes.extend_from_slice(b"\x1b[201~");
+                } else {
+                    bytes.extend_from_slice(text.as_bytes());
+                }
+            }
+            egui::Event::Copy => bytes.push(0x03),
+            egui::Event::Cut => bytes.push(0x18),
+            egui::Event::Key { key, pressed: true, modifiers, .. } => {
+                if let Some(sequence) = encode_key(*key, *modifiers, screen.application_cursor()) {
+                    bytes.extend_from_slice(&sequence);
+                }
+            }
+            _ => {}
+        }
+    }
+    bytes
+}
+
+/// Encodes a key that does not produce text by itself.
+fn encode_key(key: egui::Key, modifiers: egui::Modifiers, application_cursor: bool) -> Option<Vec<u8>> {
+    use egui::Key;
+
+    // Cursor keys use SS3 instead of CSI sequences in application mode.
+    let cursor = |letter: u8| if application_cursor { vec![0x1b, b'O', letter] } else { vec![0x1b, b'[', letter] };
+    let sequence = match key {
+        Key::Enter => b"\r".to_vec(),
+        Key::Tab if modifiers.shift => b"\x1b[Z".to_vec(),
+        Key::Tab => b"\t".to_vec(),
+        Key::Backspace => b"\x7f".to_vec(),
+        Key::Escape => b"\x1b".to_vec(),
+        Key::ArrowUp => cursor(b'A'),
+        Key::ArrowDown => cursor(b'B'),
+        Key::ArrowRight => cursor(b'C'),
+        Key::ArrowLeft => cursor(b'D'),
+        Key::Home => cursor(b'H'),
+        Key::End => cursor(b'F'),
+        Key::Insert => b"\x1b[2~".to_vec(),
+        Key::Delete => b"\x1b[3~".to_vec(),
+        Key::PageUp => b"\x1b[5~".to_vec(),
+        Key::PageDown => b"\x1b[6~".to_vec(),
+        Key::F1 => b"\x1bOP".to_vec(),
+        Key::F2 => b"\x1bOQ".to_vec(),
+        Key::F3 => b"\x1bOR".to_vec(),
+        Key::F4 => b"\x1bOS".to_vec(),
+        Key::F5 => b"\x1b[15~".to_vec(),
+        Key::F6 => b"\x1b[17~".to_vec(),
+        Key::F7 => b"\x1b[18~".to_vec(),
+        Key::F8 => b"\x1b[19~".to_vec(),
+        Key::F9 => b"\x1b[20~".to_vec(),
+        Key::F10 => b"\x1b[21~".to_vec(),
+        Key::F11 => b"\x1b[23~".to_vec(),
+        Key::F12 => b"\x1b[24~".to_vec(),
+        Key::Space if modifiers.ctrl => vec![0],
+        _ if modifiers.ctrl => match key.name().as_bytes() {
+            // Ctrl+A to Ctrl+Z map to the control characters 1 to 26.
+            [letter @ b'A'..=b'Z'] => vec![letter & 0x1f],
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(sequence)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key: egui::Key, modifiers: egui::Modifiers) -> egui::Event {
+        egui::Event::Key { key, physical_key: None, pressed: true, repeat: false, modifiers }
+    }
+
+    #[test]
+    fn test_encode_text_and_keys() {
+        let parser = vt100::Parser::new(24, 80, 0);
+        let events = [
+            egui::Event::Text("ls".to_string()),
+            key(egui::Key::Enter, egui::Modifiers::NONE),
+            key(egui::Key::ArrowUp, egui::Modifiers::NONE),
+            key(egui::Key::D, egui::Modifiers::CTRL),
+            egui::Event::Copy,
+            key(egui::Key::A, egui::Modifiers::NONE),
+        ];
+        assert_eq!(encode_input(&events, parser.screen()), b"ls\r\x1b[A\x04\x03");
+    }
+
+    #[test]
+    fn test_application_cursor_keys() {
+        let mut parser = vt100::Parser::new(24, 80, 0);
+        parser.process(b"\x1b[?1h");
+        let events = [key(egui::Key::ArrowLeft, egui::Modifiers::NONE)];
+        assert_eq!(encode_input(&events, parser.screen()), b"\x1bOD");
+    }
+
+    #[test]
+    fn test_bracketed_paste() {
+        let mut parser = vt100::Parser::new(24, 80, 0);
+        let events = [egui::Event::Paste("a\nb".to_string())];
+        assert_eq!(encode_input(&events, parser.screen()), b"a\rb");
+        parser.process(b"\x1b[?2004h");
+        assert_eq!(encode_input(&events, parser.screen()), b"\x1b[200~a\rb\x1b[201~");
+    }
+
+    #[test]
+    fn test_screen_layout_colors_cells() {
+        let mut parser = vt100::Parser::new(2, 3, 0);
+        parser.process(b"\x1b[31mab\x1b[0m\x1b[?25l");
+        let job = screen_layout(parser.screen(), FontId::monospace(14.0), Color32::WHITE, Color32::BLACK);
+        assert_eq!(job.text, "ab \n   ");
+        assert_eq!(job.sections[0].format.color, indexed_color(1));
+        assert_eq!(job.sections[2].format.color, Color32::WHITE);
+    }
+
+    #[test]
+    fn test_screen_layout_shows_cursor() {
+        let mut parser = vt100::Parser::new(1, 2, 0);
+        parser.process(b"x");
+        let job = screen_layout(parser.screen(), FontId::monospace(14.0), Color32::WHITE, Color32::BLACK);
+        let cursor = &job.sections[1].format;
+        assert_eq!((cursor.color, cursor.background), (Color32::BLACK, Color32::WHITE));
+    }
+}
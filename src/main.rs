@@ -1,11 +1,16 @@
 //! This is the main entry point for the `my_cli_tool` graphical user interface (GUI) application.
 //! It sets up the eframe application, initializes the shell core and command history,
 //! and handles the main event loop for the GUI.
+//!
+//! Given a script or `-c commands`, it runs them without the GUI instead (see `cli`).
 
+mod cli;
 mod gui;
 pub mod shell;
 
+use std::process::ExitCode;
 use eframe::egui;
+use shell::core::command_executor::EXIT_SYNTAX_ERROR;
 use tokio::sync::oneshot;
 use tokio::task;
 
@@ -35,15 +40,43 @@ fn setup_fonts(ctx: &egui::Context) {
 
 /// The main entry point of the application.
 ///
-/// This asynchronous function initializes the eframe native options, sets up shared state
+/// With command-line arguments, this runs a script or the commands given
+/// with `-c` and exits with the status of the last command. Otherwise it
+/// starts the GUI.
+///
+/// # Returns
+///
+/// The exit status of the process.
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match cli::parse_args(&args) {
+        // Statuses above 255 wrap around, as they do for any process.
+        Ok(Some(invocation)) => ExitCode::from(cli::run(invocation).await as u8),
+        Ok(None) => match run_gui() {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("{}: {}", env!("CARGO_PKG_NAME"), e);
+                ExitCode::FAILURE
+            }
+        },
+        Err(message) => {
+            eprintln!("{}: {}", env!("CARGO_PKG_NAME"), message);
+            ExitCode::from(EXIT_SYNTAX_ERROR as u8)
+        }
+    }
+}
+
+/// Runs the GUI.
+///
+/// This function initializes the eframe native options, sets up shared state
 /// for the GUI (output, shell core), and runs the eframe application.
 /// It also spawns a background task to handle shutdown signals.
 ///
 /// # Returns
 ///
 /// A `eframe::Result<()>` indicating the success or failure of the application.
-#[tokio::main]
-async fn main() -> eframe::Result<()> {
+fn run_gui() -> eframe::Result<()> {
     // Configure eframe native options, such as the window size.
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default().with_inner_size([800.0, 600.0]),
@@ -79,7 +112,7 @@ async fn main() -> eframe::Result<()> {
             });
 
             // Create and return the main GUI application instance.
-            Box::new(gui::GuiApp::new(tx))
+            Box::new(gui::GuiApp::new(&cc.egui_ctx, tx))
         }),
     );
 
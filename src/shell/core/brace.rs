@@ -0,0 +1,199 @@
+//! Brace expansion, which turns one word into several before any other
+//! expansion takes place.
+//!
+//! `{a,b,c}` produces one word per alternative and `{1..10}`, `{a..e}` or
+//! `{10..0..2}` produce a sequence. Braces and commas only count when they
+//! are unquoted, and a brace without a comma or a valid sequence, such as
+//! `{}` in `find -exec`, is left alone.
+
+use crate::shell::core::parser::ast::{Word, WordPart};
+
+/// A character of a word's unquoted text, or any other part of the word.
+#[derive(Debug, Clone, PartialEq)]
+enum Item {
+    Char(char),
+    Part(WordPart),
+}
+
+/// Expands the braces of a word.
+///
+/// # Returns
+///
+/// The resulting words, in order; the word itself if it has no braces to
+/// expand.
+pub fn expand_braces(word: &Word) -> Vec<Word> {
+    let items: Vec<Item> = word
+        .parts
+        .iter()
+        .flat_map(|part| match part {
+            WordPart::Literal(text) => text.chars().map(Item::Char).collect(),
+            other => vec![Item::Part(other.clone())],
+        })
+        .collect();
+    if !items.contains(&Item::Char('{')) {
+        return vec![word.clone()];
+    }
+    expand(&items).iter().map(|items| to_word(items)).collect()
+}
+
+fn to_word(items: &[Item]) -> Word {
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    for item in items {
+        match item {
+            Item::Char(c) => literal.push(*c),
+            Item::Part(part) => {
+                if !literal.is_empty() {
+                    parts.push(WordPart::Literal(std::mem::take(&mut literal)));
+                }
+                parts.push(part.clone());
+            }
+        }
+    }
+    if !literal.is_empty() {
+        parts.push(WordPart::Literal(literal));
+    }
+    Word { parts }
+}
+
+fn expand(items: &[Item]) -> Vec<Vec<Item>> {
+    for open in 0..items.len() {
+        if items[open] != Item::Char('{') {
+            continue;
+        }
+        let Some((close, commas)) = find_close(items, open) else { continue };
+        let alternatives: Vec<Vec<Item>> = if commas.is_empty() {
+            match sequence(&items[open + 1..close]) {
+                Some(sequence) => sequence.into_iter().map(|s| s.chars().map(Item::Char).collect()).collect(),
+                None => continue,
+            }
+        } else {
+            let mut bounds = vec![open];
+            bounds.extend(&commas);
+            bounds.push(close);
+            bounds.windows(2).flat_map(|w| expand(&items[w[0] + 1..w[1]])).collect()
+        };
+
+        let prefix = &items[..open];
+        let suffixes = expand(&items[close + 1..]);
+        let mut words = Vec::new();
+        for alternative in &alternatives {
+            for suffix in &suffixes {
+                words.push([prefix, alternative, suffix].concat());
+            }
+        }
+        return words;
+    }
+    vec![items.to_vec()]
+}
+
+/// Finds the brace closing the one at `open`.
+///
+/// # Returns
+///
+/// The position of the closing brace and of the commas directly inside the
+/// pair, or `None` if the brace is never closed.
+fn find_close(items: &[Item], open: usize) -> Option<(usize, Vec<usize>)> {
+    let mut depth = 0;
+    let mut commas = Vec::new();
+    for (index, item) in items.iter().enumerate().skip(open + 1) {
+        match item {
+            Item::Char('{') => depth += 1,
+            Item::Char('}') if depth == 0 => return Some((index, commas)),
+            Item::Char('}') => depth -= 1,
+            Item::Char(',') if depth == 0 => commas.push(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Expands the inside of a sequence expression such as `1..10`, `a..e` or
+/// `1..10..3`. Numbers written with leading zeros are padded to the same
+/// width, as in `{01..10}`.
+fn sequence(items: &[Item]) -> Option<Vec<String>> {
+    let text: String = items
+        .iter()
+        .map(|item| match item {
+            Item::Char(c) => Some(*c),
+            Item::Part(_) => None,
+        })
+        .collect::<Option<_>>()?;
+    let fields: Vec<&str> = text.split("..").collect();
+    let (start, end, step) = match fields.as_slice() {
+        [start, end] => (*start, *end, 1),
+        [start, end, step] => (*start, *end, step.parse::<i64>().ok()?.checked_abs()?.max(1)),
+        _ => return None,
+    };
+
+    if let (Ok(first), Ok(last)) = (start.parse::<i64>(), end.parse::<i64>()) {
+        let padded = |s: &str| s.trim_start_matches('-').len() > 1 && s.trim_start_matches('-').starts_with('0');
+        let width = if padded(start) || padded(end) { start.len().max(end.len()) } else { 0 };
+        return Some(steps(first, last, step).map(|n| format!("{:0width$}", n, width = width)).collect());
+    }
+
+    let (mut first, mut last) = (start.chars(), end.chars());
+    match (first.next(), first.next(), last.next(), last.next()) {
+        (Some(first), None, Some(last), None) if first.is_ascii_alphabetic() && last.is_ascii_alphabetic() => {
+            let letters = steps(first as i64, last as i64, step);
+            Some(letters.map(|c| char::from(c as u8).to_string()).collect())
+        }
+        _ => None,
+    }
+}
+
+/// Counts from `first` to `last` inclusive, up or down, by `step`.
+fn steps(first: i64, last: i64, step: i64) -> impl Iterator<Item = i64> {
+    let count = (first - last).abs() / step + 1;
+    let step = if first <= last { step } else { -step };
+    (0..count).map(move |i| first + i * step)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shell::core::parser::ast::{Parameter, ParameterOp};
+
+    fn expand_text(text: &str) -> Vec<String> {
+        expand_braces(&Word::literal(text)).iter().map(Word::unquoted).collect()
+    }
+
+    #[test]
+    fn test_alternatives() {
+        assert_eq!(expand_text("src/{a,b}.rs"), ["src/a.rs", "src/b.rs"]);
+        assert_eq!(expand_text("{a,b}{1,2}"), ["a1", "a2", "b1", "b2"]);
+        assert_eq!(expand_text("x{a,{b,c}d,}y"), ["xay", "xbdy", "xcdy", "xy"]);
+    }
+
+    #[test]
+    fn test_sequences() {
+        assert_eq!(expand_text("{1..5}"), ["1", "2", "3", "4", "5"]);
+        assert_eq!(expand_text("{3..-1}"), ["3", "2", "1", "0", "-1"]);
+        assert_eq!(expand_text("{0..10..5}"), ["0", "5", "10"]);
+        assert_eq!(expand_text("{08..10}"), ["08", "09", "10"]);
+        assert_eq!(expand_text("{a..c}"), ["a", "b", "c"]);
+        assert_eq!(expand_text("{a..3}"), ["{a..3}"]);
+    }
+
+    #[test]
+    fn test_braces_that_stay_literal() {
+        assert_eq!(expand_text("{}"), ["{}"]);
+        assert_eq!(expand_text("{single}"), ["{single}"]);
+        assert_eq!(expand_text("{a,b"), ["{a,b"]);
+        assert_eq!(expand_text("{x}{a,b}"), ["{x}a", "{x}b"]);
+    }
+
+    #[test]
+    fn test_quoted_braces_are_not_expanded() {
+        let word = Word { parts: vec![WordPart::Quoted("{a,b}".to_string())] };
+        assert_eq!(expand_braces(&word), vec![word.clone()]);
+    }
+
+    #[test]
+    fn test_other_parts_are_kept() {
+        let parameter = WordPart::Parameter(Parameter { name: "X".to_string(), op: ParameterOp::Value });
+        let word = Word { parts: vec![WordPart::Literal("{".to_string()), parameter.clone(), WordPart::Literal(",b}".to_string())] };
+        let words = expand_braces(&word);
+        assert_eq!(words, vec![Word { parts: vec![parameter] }, Word::literal("b")]);
+    }
+}
@@ -1,6 +1,10 @@
 //! Built-in command to manage aliases.
 
+use anyhow::{anyhow, Result};
 use std::collections::HashMap;
+use super::{Builtin, BuiltinContext, BuiltinFuture};
+use crate::shell::core::command_executor::EXIT_SUCCESS;
+use tokio::io::AsyncWriteExt;
 
 /// Handles the `alias` and `unalias` commands.
 ///
@@ -11,35 +15,35 @@ use std::collections::HashMap;
 ///
 /// # Returns
 ///
-/// A `String` containing the output of the command.
-pub fn alias_builtin(aliases: &mut HashMap<String, String>, args: &[&str]) -> String {
+/// A `Result<String>` containing the output of the command, or an error if
+/// an alias to print or remove does not exist.
+pub fn alias_builtin(aliases: &mut HashMap<String, String>, args: &[&str]) -> Result<String> {
     if args.is_empty() {
         // No arguments, print all aliases
         if aliases.is_empty() {
-            return "No aliases defined.\n".to_string();
+            return Ok("No aliases defined.\n".to_string());
         }
         let mut output = String::new();
         for (alias, command) in aliases.iter() {
             output.push_str(&format!("alias {}='{}'\n", alias, command));
         }
-        return output;
+        return Ok(output);
     }
 
     // Handle `unalias`
     if args[0] == "unalias" {
         if args.len() < 2 {
-            return "unalias: usage: unalias <alias_name>\n".to_string();
+            return Err(anyhow!("unalias: usage: unalias <alias_name>"));
         }
         let alias_name = args[1];
         if aliases.remove(alias_name).is_some() {
-            return format!("Alias '{}' removed.\n", alias_name);
+            return Ok(format!("Alias '{}' removed.\n", alias_name));
         } else {
-            return format!("unalias: {}: not found\n", alias_name);
+            return Err(anyhow!("unalias: {}: not found", alias_name));
         }
     }
 
     // Handle `alias name=value`
-    let mut new_aliases = 0;
     for arg in args {
         if let Some((name, value)) = arg.split_once('=') {
             if value.is_empty() {
@@ -47,32 +51,77 @@ pub fn alias_builtin(aliases: &mut HashMap<String, String>, args: &[&str]) -> St
                 aliases.remove(name);
             } else {
                 // Set alias, removing quotes if present
-                let clean_value = if value.starts_with('(') && value.ends_with('(') {
-                    value[1..value.len() - 1].to_string()
-                } else if value.starts_with('"') && value.ends_with('"') {
+                let clean_value = if (value.starts_with('(') && value.ends_with('('))
+                    || (value.starts_with('"') && value.ends_with('"'))
+                {
                     value[1..value.len() - 1].to_string()
                 } else {
                     value.to_string()
                 };
                 aliases.insert(name.to_string(), clean_value);
-                new_aliases += 1;
             }
         } else {
             // If not in `name=value` format, check if it's a name of an existing alias to print
             if let Some(command) = aliases.get(*arg) {
-                return format!("alias {}='{}'\n", arg, command);
+                return Ok(format!("alias {}='{}'\n", arg, command));
             } else {
-                return format!("alias: {}: not found\n", arg);
+                return Err(anyhow!("alias: {}: not found", arg));
             }
         }
     }
 
-    if new_aliases > 0 {
-        String::new() // No output on successful setting
-    } else {
-        // This case is for when `alias` is called with an argument that is not a `name=value` pair and not an existing alias.
-        // The loop above already handles the error message, but we need a default return.
-        String::new()
+    Ok(String::new()) // No output on successful setting
+}
+
+/// The `alias` builtin.
+pub struct Alias;
+
+impl Builtin for Alias {
+    fn name(&self) -> &'static str {
+        "alias"
+    }
+
+    fn summary(&self) -> &'static str {
+        "Define or display aliases."
+    }
+
+    fn usage(&self) -> &'static str {
+        "alias [name[=value] ...]"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut BuiltinContext<'_>, args: &'a [&'a str]) -> BuiltinFuture<'a> {
+        Box::pin(async move {
+            let output = alias_builtin(&mut ctx.shell.aliases, args)?;
+            ctx.stdout.write_all(output.as_bytes()).await?;
+            Ok(EXIT_SUCCESS)
+        })
+    }
+}
+
+/// The `unalias` builtin.
+pub struct Unalias;
+
+impl Builtin for Unalias {
+    fn name(&self) -> &'static str {
+        "unalias"
+    }
+
+    fn summary(&self) -> &'static str {
+        "Remove an alias."
+    }
+
+    fn usage(&self) -> &'static str {
+        "unalias name"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut BuiltinContext<'_>, args: &'a [&'a str]) -> BuiltinFuture<'a> {
+        Box::pin(async move {
+            let mut unalias_args = vec!["unalias"];
+            unalias_args.extend_from_slice(args);
+            let output = alias_builtin(&mut ctx.shell.aliases, &unalias_args)?;
+            ctx.stdout.write_all(output.as_bytes()).await?;
+            Ok(EXIT_SUCCESS)
+        })
     }
 }
 
@@ -84,7 +133,7 @@ mod tests {
     fn test_set_new_alias() {
         let mut aliases = HashMap::new();
         let args = vec!["ll=ls -l"];
-        let output = alias_builtin(&mut aliases, &args);
+        let output = alias_builtin(&mut aliases, &args).unwrap();
         assert!(output.is_empty());
         assert_eq!(aliases.get("ll"), Some(&"ls -l".to_string()));
     }
@@ -93,7 +142,7 @@ mod tests {
     fn test_set_alias_with_quotes() {
         let mut aliases = HashMap::new();
         let args = vec!["greet=\"echo 'Hello World'\""];
-        alias_builtin(&mut aliases, &args);
+        alias_builtin(&mut aliases, &args).unwrap();
         assert_eq!(aliases.get("greet"), Some(&"echo 'Hello World'".to_string()));
     }
 
@@ -103,7 +152,7 @@ mod tests {
         aliases.insert("ll".to_string(), "ls -l".to_string());
         aliases.insert("c".to_string(), "clear".to_string());
         
-        let output = alias_builtin(&mut aliases, &[]);
+        let output = alias_builtin(&mut aliases, &[]).unwrap();
         assert!(output.contains("alias ll='ls -l'\n"));
         assert!(output.contains("alias c='clear'\n"));
     }
@@ -113,7 +162,7 @@ mod tests {
         let mut aliases = HashMap::new();
         aliases.insert("ll".to_string(), "ls -l".to_string());
         let args = vec!["ll"];
-        let output = alias_builtin(&mut aliases, &args);
+        let output = alias_builtin(&mut aliases, &args).unwrap();
         assert_eq!(output, "alias ll='ls -l'\n");
     }
 
@@ -121,8 +170,8 @@ mod tests {
     fn test_alias_not_found() {
         let mut aliases = HashMap::new();
         let args = vec!["nonexistent"];
-        let output = alias_builtin(&mut aliases, &args);
-        assert_eq!(output, "alias: nonexistent: not found\n");
+        let err = alias_builtin(&mut aliases, &args).unwrap_err();
+        assert_eq!(err.to_string(), "alias: nonexistent: not found");
     }
 
     #[test]
@@ -131,24 +180,24 @@ mod tests {
         aliases.insert("ll".to_string(), "ls -l".to_string());
         
         let args = vec!["unalias", "ll"];
-        let output = alias_builtin(&mut aliases, &args);
+        let output = alias_builtin(&mut aliases, &args).unwrap();
         assert_eq!(output, "Alias 'll' removed.\n");
-        assert!(aliases.get("ll").is_none());
+        assert!(!aliases.contains_key("ll"));
     }
 
     #[test]
     fn test_unalias_not_found() {
         let mut aliases = HashMap::new();
         let args = vec!["unalias", "nonexistent"];
-        let output = alias_builtin(&mut aliases, &args);
-        assert_eq!(output, "unalias: nonexistent: not found\n");
+        let err = alias_builtin(&mut aliases, &args).unwrap_err();
+        assert_eq!(err.to_string(), "unalias: nonexistent: not found");
     }
 
     #[test]
     fn test_unalias_usage() {
         let mut aliases = HashMap::new();
         let args = vec!["unalias"];
-        let output = alias_builtin(&mut aliases, &args);
-        assert_eq!(output, "unalias: usage: unalias <alias_name>\n");
+        let err = alias_builtin(&mut aliases, &args).unwrap_err();
+        assert_eq!(err.to_string(), "unalias: usage: unalias <alias_name>");
     }
 }
@@ -0,0 +1,68 @@
+//! Built-in commands that do nothing but report success or failure, for
+//! use in conditions such as `while true`.
+
+use super::{Builtin, BuiltinContext, BuiltinFuture};
+use crate::shell::core::command_executor::{EXIT_FAILURE, EXIT_SUCCESS};
+
+/// The `true` builtin.
+pub struct True;
+
+impl Builtin for True {
+    fn name(&self) -> &'static str {
+        "true"
+    }
+
+    fn summary(&self) -> &'static str {
+        "Do nothing, successfully."
+    }
+
+    fn usage(&self) -> &'static str {
+        "true"
+    }
+
+    fn run<'a>(&'a self, _ctx: &'a mut BuiltinContext<'_>, _args: &'a [&'a str]) -> BuiltinFuture<'a> {
+        Box::pin(async { Ok(EXIT_SUCCESS) })
+    }
+}
+
+/// The `:` builtin, which is the same as `true`.
+pub struct Colon;
+
+impl Builtin for Colon {
+    fn name(&self) -> &'static str {
+        ":"
+    }
+
+    fn summary(&self) -> &'static str {
+        "Do nothing, successfully; the arguments are still expanded."
+    }
+
+    fn usage(&self) -> &'static str {
+        ": [arguments]"
+    }
+
+    fn run<'a>(&'a self, _ctx: &'a mut BuiltinContext<'_>, _args: &'a [&'a str]) -> BuiltinFuture<'a> {
+        Box::pin(async { Ok(EXIT_SUCCESS) })
+    }
+}
+
+/// The `false` builtin.
+pub struct False;
+
+impl Builtin for False {
+    fn name(&self) -> &'static str {
+        "false"
+    }
+
+    fn summary(&self) -> &'static str {
+        "Do nothing, unsuccessfully."
+    }
+
+    fn usage(&self) -> &'static str {
+        "false"
+    }
+
+    fn run<'a>(&'a self, _ctx: &'a mut BuiltinContext<'_>, _args: &'a [&'a str]) -> BuiltinFuture<'a> {
+        Box::pin(async { Ok(EXIT_FAILURE) })
+    }
+}
@@ -1,40 +1,77 @@
 //! Built-in command to concatenate and display file contents.
 
 use anyhow::{anyhow, Result};
-use std::fs;
-use std::path::PathBuf;
+use std::path::Path;
+use tokio::io::{AsyncRead, AsyncWrite};
+use super::{Builtin, BuiltinContext, BuiltinFuture};
+use crate::shell::core::command_executor::EXIT_SUCCESS;
 
 /// Handles the `cat` command.
 ///
-/// Reads the content of specified files and returns them as a single string.
+/// Copies the content of specified files to `output`, one after another.
+/// With no files, or for a `-` argument, the command's standard input is read.
 ///
 /// # Arguments
 ///
 /// * `current_dir` - The current working directory.
 /// * `args` - A slice of strings representing the arguments to the command (file paths).
+/// * `input` - The command's standard input.
+/// * `output` - Where the contents are written.
 ///
 /// # Returns
 ///
-/// A `Result<String>` containing the concatenated file contents on success,
-/// or an error message if a file cannot be read.
-pub async fn cat_builtin(current_dir: &PathBuf, args: &[&str]) -> Result<String> {
+/// A `Result<()>` that is an error message if a file cannot be read.
+pub async fn cat_builtin(
+    current_dir: &Path,
+    args: &[&str],
+    mut input: impl AsyncRead + Unpin,
+    output: &mut (impl AsyncWrite + Unpin),
+) -> Result<()> {
     if args.is_empty() {
-        return Err(anyhow!("cat: missing operand"));
+        tokio::io::copy(&mut input, output).await?;
+        return Ok(());
     }
 
-    let mut output = String::new();
     for arg in args {
+        if *arg == "-" {
+            tokio::io::copy(&mut input, output).await?;
+            continue;
+        }
         let path = current_dir.join(arg);
-        match fs::read_to_string(&path) {
-            Ok(content) => {
-                output.push_str(&content);
+        match tokio::fs::File::open(&path).await {
+            Ok(mut file) => {
+                tokio::io::copy(&mut file, output).await?;
             }
             Err(e) => {
                 return Err(anyhow!("cat: {}: {}", path.display(), e));
             }
         }
     }
-    Ok(output)
+    Ok(())
+}
+
+/// The `cat` builtin.
+pub struct Cat;
+
+impl Builtin for Cat {
+    fn name(&self) -> &'static str {
+        "cat"
+    }
+
+    fn summary(&self) -> &'static str {
+        "Concatenate files and print them."
+    }
+
+    fn usage(&self) -> &'static str {
+        "cat [file ...]"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut BuiltinContext<'_>, args: &'a [&'a str]) -> BuiltinFuture<'a> {
+        Box::pin(async move {
+            cat_builtin(&ctx.shell.current_dir, args, &mut ctx.stdin, &mut ctx.stdout).await?;
+            Ok(EXIT_SUCCESS)
+        })
+    }
 }
 
 #[cfg(test)]
@@ -51,8 +88,9 @@ mod tests {
         let path = file.path().to_path_buf();
         let current_dir = env::current_dir()?;
 
-        let output = cat_builtin(&current_dir, &[path.to_str().unwrap()]).await?;
-        assert_eq!(output.trim(), "Hello, world!");
+        let mut output = Vec::new();
+        cat_builtin(&current_dir, &[path.to_str().unwrap()], &b""[..], &mut output).await?;
+        assert_eq!(String::from_utf8(output)?.trim(), "Hello, world!");
         Ok(())
     }
 
@@ -68,8 +106,9 @@ mod tests {
 
         let current_dir = env::current_dir()?;
 
-        let output = cat_builtin(&current_dir, &[path1.to_str().unwrap(), path2.to_str().unwrap()]).await?;
-        assert_eq!(output.trim(), "Line 1
+        let mut output = Vec::new();
+        cat_builtin(&current_dir, &[path1.to_str().unwrap(), path2.to_str().unwrap()], &b""[..], &mut output).await?;
+        assert_eq!(String::from_utf8(output)?.trim(), "Line 1
 Line 2");
         Ok(())
     }
@@ -77,7 +116,7 @@ Line 2");
     #[tokio::test]
     async fn test_cat_nonexistent_file() -> Result<()> {
         let current_dir = env::current_dir()?;
-        let result = cat_builtin(&current_dir, &["nonexistent_file.txt"]).await;
+        let result = cat_builtin(&current_dir, &["nonexistent_file.txt"], &b""[..], &mut Vec::new()).await;
         assert!(result.is_err());
         let err_msg = result.unwrap_err().to_string();
         // Check for common "file not found" phrases across OSes
@@ -91,11 +130,24 @@ Line 2");
     }
 
     #[tokio::test]
-    async fn test_cat_missing_operand() -> Result<()> {
+    async fn test_cat_reads_input_without_operands() -> Result<()> {
         let current_dir = env::current_dir()?;
-        let result = cat_builtin(&current_dir, &[]).await;
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err().to_string(), "cat: missing operand");
+        let mut output = Vec::new();
+        cat_builtin(&current_dir, &[], &b"from stdin\n"[..], &mut output).await?;
+        assert_eq!(output, b"from stdin\n");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cat_dash_reads_input() -> Result<()> {
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "from file")?;
+        let path = file.path().to_path_buf();
+        let current_dir = env::current_dir()?;
+
+        let mut output = Vec::new();
+        cat_builtin(&current_dir, &["-", path.to_str().unwrap()], &b"from stdin\n"[..], &mut output).await?;
+        assert_eq!(output, b"from stdin\nfrom file\n");
         Ok(())
     }
 }
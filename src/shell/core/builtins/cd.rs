@@ -1,22 +1,166 @@
-use std::path::PathBuf;
-
-pub async fn cd_builtin(current_dir: &mut PathBuf, args: &[&str]) -> String {
-    if args.len() != 1 {
-        return "Usage: cd <directory>\n".to_string();
-    }
+use anyhow::{anyhow, Result};
+use std::path::{Component, Path, PathBuf};
+use super::{Builtin, BuiltinContext, BuiltinFuture};
+use crate::shell::core::command_executor::EXIT_SUCCESS;
+use crate::shell::core::ShellCore;
+use tokio::io::AsyncWriteExt;
 
+/// Handles the `cd` command.
+///
+/// Without arguments, `cd` goes to `HOME`, and `cd -` goes back to the
+/// previous directory (`OLDPWD`). A relative directory that does not start
+/// with `.` or `..` is looked up in the colon-separated directories of
+/// `CDPATH` before the current directory. On success, `OLDPWD` and `PWD`
+/// are updated.
+///
+/// # Arguments
+///
+/// * `shell` - The shell whose directory changes.
+/// * `args` - The arguments to the command.
+///
+/// # Returns
+///
+/// The new directory when it was not given literally (`cd -` or a `CDPATH`
+/// match), so the user sees where they ended up, or an empty string.
+pub async fn cd_builtin(shell: &mut ShellCore, args: &[&str]) -> Result<String> {
+    let (new_dir, mut announce) = match args {
+        [] => (shell.get_var("HOME").filter(|home| !home.is_empty()).ok_or_else(|| anyhow!("cd: HOME not set"))?, false),
+        ["-"] => (shell.get_var("OLDPWD").ok_or_else(|| anyhow!("cd: OLDPWD not set"))?, true),
+        [dir] => (dir.to_string(), false),
+        _ => return Err(anyhow!("cd: too many arguments")),
+    };
 
-    let new_dir = args[0];
-    let path = current_dir.join(new_dir);
+    let path = match search_cdpath(shell, &new_dir) {
+        Some((path, from_cdpath)) => {
+            announce |= from_cdpath;
+            path
+        }
+        None => shell.current_dir.join(&new_dir),
+    };
 
     if !path.exists() {
-        return format!("cd: '{}': No such file or directory\n", new_dir);
+        return Err(anyhow!("cd: '{}': No such file or directory", new_dir));
     }
 
     if !path.is_dir() {
-        return format!("cd: '{}': Not a directory\n", new_dir);
+        return Err(anyhow!("cd: '{}': Not a directory", new_dir));
+    }
+
+    let path = dunce::canonicalize(&path).map_err(|e| anyhow!("cd: '{}': {}", new_dir, e))?;
+    let old_dir = std::mem::replace(&mut shell.current_dir, path);
+    shell.set_var("OLDPWD", &old_dir.to_string_lossy());
+    let new_dir = shell.current_dir.to_string_lossy().into_owned();
+    shell.set_var("PWD", &new_dir);
+    Ok(if announce { format!("{}\n", new_dir) } else { String::new() })
+}
+
+/// Looks a directory up in `CDPATH`.
+///
+/// # Returns
+///
+/// The directory found and whether it came from a non-empty `CDPATH` entry,
+/// or `None` if `CDPATH` does not apply to `dir` or has no match.
+fn search_cdpath(shell: &ShellCore, dir: &str) -> Option<(PathBuf, bool)> {
+    let first = Path::new(dir).components().next()?;
+    if !matches!(first, Component::Normal(_)) {
+        return None;
+    }
+    let cdpath = shell.get_var("CDPATH")?;
+    cdpath.split(':').find_map(|entry| {
+        // An empty entry stands for the current directory.
+        let path = shell.current_dir.join(entry).join(dir);
+        path.is_dir().then_some((path, !entry.is_empty()))
+    })
+}
+
+/// The `cd` builtin.
+pub struct Cd;
+
+impl Builtin for Cd {
+    fn name(&self) -> &'static str {
+        "cd"
+    }
+
+    fn summary(&self) -> &'static str {
+        "Change the current directory."
     }
 
-    *current_dir = dunce::canonicalize(&path).unwrap();
-    String::new()
+    fn usage(&self) -> &'static str {
+        "cd [directory | -]"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut BuiltinContext<'_>, args: &'a [&'a str]) -> BuiltinFuture<'a> {
+        Box::pin(async move {
+            let output = cd_builtin(ctx.shell, args).await?;
+            ctx.stdout.write_all(output.as_bytes()).await?;
+            Ok(EXIT_SUCCESS)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shell_in(dir: &Path) -> ShellCore {
+        let mut shell = ShellCore::new();
+        shell.current_dir = dunce::canonicalize(dir).unwrap();
+        shell.variables.insert("CDPATH".to_string(), String::new());
+        shell
+    }
+
+    #[tokio::test]
+    async fn test_home_and_previous_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("home/sub")).unwrap();
+        let root = dunce::canonicalize(dir.path()).unwrap();
+        let mut shell = shell_in(&root);
+        shell.variables.insert("HOME".to_string(), root.join("home").to_string_lossy().into_owned());
+
+        assert_eq!(cd_builtin(&mut shell, &[]).await.unwrap(), "");
+        assert_eq!(shell.current_dir, root.join("home"));
+        assert_eq!(shell.get_var("OLDPWD"), Some(root.to_string_lossy().into_owned()));
+
+        cd_builtin(&mut shell, &["sub"]).await.unwrap();
+        assert_eq!(shell.get_var("PWD"), Some(root.join("home/sub").to_string_lossy().into_owned()));
+        assert_eq!(cd_builtin(&mut shell, &["-"]).await.unwrap(), format!("{}\n", root.join("home").display()));
+        assert_eq!(shell.current_dir, root.join("home"));
+        assert_eq!(shell.get_var("OLDPWD"), Some(root.join("home/sub").to_string_lossy().into_owned()));
+    }
+
+    #[tokio::test]
+    async fn test_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = shell_in(dir.path());
+        shell.variables.insert("HOME".to_string(), String::new());
+        assert_eq!(cd_builtin(&mut shell, &[]).await.unwrap_err().to_string(), "cd: HOME not set");
+        assert_eq!(cd_builtin(&mut shell, &["a", "b"]).await.unwrap_err().to_string(), "cd: too many arguments");
+
+        std::fs::write(dir.path().join("file"), "").unwrap();
+        assert_eq!(cd_builtin(&mut shell, &["file"]).await.unwrap_err().to_string(), "cd: 'file': Not a directory");
+    }
+
+    #[tokio::test]
+    async fn test_cdpath() {
+        let dir = tempfile::tempdir().unwrap();
+        for path in ["projects/app", "work", "app"] {
+            std::fs::create_dir_all(dir.path().join(path)).unwrap();
+        }
+        let root = dunce::canonicalize(dir.path()).unwrap();
+        let mut shell = shell_in(&root.join("work"));
+        shell.variables.insert("CDPATH".to_string(), format!(":{}", root.join("projects").display()));
+
+        assert_eq!(cd_builtin(&mut shell, &["app"]).await.unwrap(), format!("{}\n", root.join("projects/app").display()));
+        assert_eq!(shell.current_dir, root.join("projects/app"));
+
+        // Paths starting with `.` or `..` ignore `CDPATH`.
+        shell.current_dir = root.join("work");
+        assert_eq!(cd_builtin(&mut shell, &["../app"]).await.unwrap(), "");
+        assert_eq!(shell.current_dir, root.join("app"));
+
+        // The empty entry finds directories in the current one first.
+        shell.current_dir = root.clone();
+        assert_eq!(cd_builtin(&mut shell, &["app"]).await.unwrap(), "");
+        assert_eq!(shell.current_dir, root.join("app"));
+    }
 }
@@ -1,9 +1,13 @@
-use std::path::PathBuf;
+use anyhow::{anyhow, Result};
+use std::path::Path;
 use tokio::fs;
+use super::{Builtin, BuiltinContext, BuiltinFuture};
+use crate::shell::core::command_executor::EXIT_SUCCESS;
+use tokio::io::AsyncWriteExt;
 
-pub async fn cp_builtin(current_dir: &PathBuf, args: &[&str]) -> String {
+pub async fn cp_builtin(current_dir: &Path, args: &[&str]) -> Result<String> {
     if args.len() < 2 {
-        return "cp: missing file operand\nTry 'cp --help' for more information.\n".to_string();
+        return Err(anyhow!("cp: missing file operand\nTry 'cp --help' for more information."));
     }
 
     let source_path_str = args[0];
@@ -13,17 +17,42 @@ pub async fn cp_builtin(current_dir: &PathBuf, args: &[&str]) -> String {
     let destination_path = current_dir.join(destination_path_str);
 
     if !source_path.exists() {
-        return format!("cp: cannot stat '{}': No such file or directory\n", source_path.display());
+        return Err(anyhow!("cp: cannot stat '{}': No such file or directory", source_path.display()));
     }
 
     if source_path.is_dir() {
         // Recursive copy for directories is not yet implemented
-        return format!("cp: -r not specified; omitting directory '{}'\n", source_path.display());
+        return Err(anyhow!("cp: -r not specified; omitting directory '{}'", source_path.display()));
     }
 
     match fs::copy(&source_path, &destination_path).await {
-        Ok(_) => String::new(),
-        Err(e) => format!("cp: cannot copy '{}' to '{}': {}\n", source_path.display(), destination_path.display(), e),
+        Ok(_) => Ok(String::new()),
+        Err(e) => Err(anyhow!("cp: cannot copy '{}' to '{}': {}", source_path.display(), destination_path.display(), e)),
+    }
+}
+
+/// The `cp` builtin.
+pub struct Cp;
+
+impl Builtin for Cp {
+    fn name(&self) -> &'static str {
+        "cp"
+    }
+
+    fn summary(&self) -> &'static str {
+        "Copy a file."
+    }
+
+    fn usage(&self) -> &'static str {
+        "cp source destination"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut BuiltinContext<'_>, args: &'a [&'a str]) -> BuiltinFuture<'a> {
+        Box::pin(async move {
+            let output = cp_builtin(&ctx.shell.current_dir, args).await?;
+            ctx.stdout.write_all(output.as_bytes()).await?;
+            Ok(EXIT_SUCCESS)
+        })
     }
 }
 
@@ -43,7 +72,7 @@ mod tests {
         fs::write(&src_file, "hello world").await.unwrap();
 
         let args = ["source.txt", "destination.txt"];
-        let output = cp_builtin(&temp_dir, &args).await;
+        let output = cp_builtin(&temp_dir, &args).await.unwrap();
 
         assert!(output.is_empty(), "Expected no output for successful cp, but got: {}", output);
         assert!(fs::metadata(&dest_file).await.is_ok(), "Destination file should exist");
@@ -58,7 +87,7 @@ mod tests {
         fs::create_dir_all(&temp_dir).await.unwrap();
 
         let args = ["nonexistent.txt", "destination.txt"];
-        let output = cp_builtin(&temp_dir, &args).await;
+        let output = cp_builtin(&temp_dir, &args).await.unwrap_err().to_string();
 
         assert!(output.contains("No such file or directory"));
 
@@ -73,7 +102,7 @@ mod tests {
         fs::create_dir(&src_dir).await.unwrap();
 
         let args = ["source_dir", "destination_dir"];
-        let output = cp_builtin(&temp_dir, &args).await;
+        let output = cp_builtin(&temp_dir, &args).await.unwrap_err().to_string();
 
         assert!(output.contains("-r not specified"));
 
@@ -86,7 +115,7 @@ mod tests {
         fs::create_dir_all(&temp_dir).await.unwrap();
 
         let args: [&str; 0] = [];
-        let output = cp_builtin(&temp_dir, &args).await;
+        let output = cp_builtin(&temp_dir, &args).await.unwrap_err().to_string();
 
         assert!(output.contains("missing file operand"));
 
@@ -1,32 +1,55 @@
-use std::collections::HashMap;
-use regex::Regex;
+use super::{Builtin, BuiltinContext, BuiltinFuture};
+use crate::shell::core::command_executor::EXIT_SUCCESS;
+use tokio::io::AsyncWriteExt;
 
-/// Implements the `echo` command, which prints its arguments to the output.
-/// It handles basic escape sequences like `\n` and `\t` and expands environment variables.
+/// Implements the `echo` command, which prints its arguments to the output
+/// followed by a newline, unless the first argument is `-n`.
+/// It handles basic escape sequences like `\n` and `\t`. Variables have
+/// already been expanded by the executor.
 ///
 /// # Arguments
 ///
 /// * `args` - A slice of string slices, where each element is an argument to `echo`.
-/// * `env_vars` - A reference to the `HashMap` storing the environment variables.
 ///
 /// # Returns
 ///
 /// A `String` containing the concatenated arguments, separated by spaces,
-/// with escape sequences and environment variables interpreted.
-pub async fn echo_builtin(args: &[&str], env_vars: &HashMap<String, String>) -> String {
-    let raw_str = args.join(" ");
-    let mut processed_str = raw_str.replace("\\n", "\n").replace("\\t", "\t");
+/// with escape sequences interpreted.
+pub async fn echo_builtin(args: &[&str]) -> String {
+    let (args, newline) = match args {
+        ["-n", rest @ ..] => (rest, false),
+        _ => (args, true),
+    };
+    let mut output = args.join(" ").replace("\\n", "\n").replace("\\t", "\t");
+    if newline {
+        output.push('\n');
+    }
+    output
+}
+
+/// The `echo` builtin.
+pub struct Echo;
+
+impl Builtin for Echo {
+    fn name(&self) -> &'static str {
+        "echo"
+    }
 
-    // Regex to find %VAR% patterns
-    let re = Regex::new(r"%([A-Za-z_][A-Za-z0-9_]*?)%").unwrap();
+    fn summary(&self) -> &'static str {
+        "Display a line of text."
+    }
 
-    // Replace environment variables
-    processed_str = re.replace_all(&processed_str, |caps: &regex::Captures| {
-        let var_name = &caps[1];
-        env_vars.get(var_name).map_or(caps[0].to_string(), |val| val.clone())
-    }).to_string();
+    fn usage(&self) -> &'static str {
+        "echo [-n] [text ...]"
+    }
 
-    processed_str
+    fn run<'a>(&'a self, ctx: &'a mut BuiltinContext<'_>, args: &'a [&'a str]) -> BuiltinFuture<'a> {
+        Box::pin(async move {
+            let output = echo_builtin(args).await;
+            ctx.stdout.write_all(output.as_bytes()).await?;
+            Ok(EXIT_SUCCESS)
+        })
+    }
 }
 
 #[cfg(test)]
@@ -35,68 +58,42 @@ mod tests {
 
     #[tokio::test]
     async fn test_echo_builtin_no_args() {
-        let env_vars = HashMap::new();
-        let output = echo_builtin(&[], &env_vars).await;
-        assert_eq!(output, "");
+        let output = echo_builtin(&[]).await;
+        assert_eq!(output, "\n");
     }
 
     #[tokio::test]
     async fn test_echo_builtin_single_arg() {
-        let env_vars = HashMap::new();
-        let output = echo_builtin(&["hello"], &env_vars).await;
-        assert_eq!(output, "hello");
+        let output = echo_builtin(&["hello"]).await;
+        assert_eq!(output, "hello\n");
     }
 
     #[tokio::test]
     async fn test_echo_builtin_multiple_args() {
-        let env_vars = HashMap::new();
-        let output = echo_builtin(&["hello", "world", "from", "rust"], &env_vars).await;
-        assert_eq!(output, "hello world from rust");
+        let output = echo_builtin(&["hello", "world", "from", "rust"]).await;
+        assert_eq!(output, "hello world from rust\n");
     }
 
     #[tokio::test]
     async fn test_echo_builtin_with_special_chars() {
-        let env_vars = HashMap::new();
-        let output = echo_builtin(&["$PATH", "&&", "||", ">", "output.txt"], &env_vars).await;
-        assert_eq!(output, "$PATH && || > output.txt");
+        let output = echo_builtin(&["$PATH", "&&", "||", ">", "output.txt"]).await;
+        assert_eq!(output, "$PATH && || > output.txt\n");
     }
 
     #[tokio::test]
     async fn test_echo_builtin_with_newline_escape() {
-        let env_vars = HashMap::new();
-        let output = echo_builtin(&["hello\\nworld"], &env_vars).await;
-        assert_eq!(output, "hello\nworld");
+        let output = echo_builtin(&["hello\\nworld"]).await;
+        assert_eq!(output, "hello\nworld\n");
     }
 
     #[tokio::test]
     async fn test_echo_builtin_with_tab_escape() {
-        let env_vars = HashMap::new();
-        let output = echo_builtin(&["hello\\tworld"], &env_vars).await;
-        assert_eq!(output, "hello\tworld");
-    }
-
-    #[tokio::test]
-    async fn test_echo_builtin_env_var_expansion() {
-        let mut env_vars = HashMap::new();
-        env_vars.insert("MY_VAR".to_string(), "test_value".to_string());
-        env_vars.insert("ANOTHER_VAR".to_string(), "another_value".to_string());
-
-        let output = echo_builtin(&["Hello", "%MY_VAR%", "and", "%ANOTHER_VAR%"], &env_vars).await;
-        assert_eq!(output, "Hello test_value and another_value");
-    }
-
-    #[tokio::test]
-    async fn test_echo_builtin_nonexistent_env_var() {
-        let env_vars = HashMap::new();
-        let output = echo_builtin(&["Hello", "%NON_EXISTENT_VAR%"], &env_vars).await;
-        assert_eq!(output, "Hello %NON_EXISTENT_VAR%");
+        let output = echo_builtin(&["hello\\tworld"]).await;
+        assert_eq!(output, "hello\tworld\n");
     }
 
     #[tokio::test]
-    async fn test_echo_builtin_env_var_with_no_value() {
-        let mut env_vars = HashMap::new();
-        env_vars.insert("EMPTY_VAR".to_string(), String::new());
-        let output = echo_builtin(&["Value is: %EMPTY_VAR%"], &env_vars).await;
-        assert_eq!(output, "Value is: ");
+    async fn test_echo_builtin_without_newline() {
+        assert_eq!(echo_builtin(&["-n", "hello"]).await, "hello");
     }
 }
@@ -1,6 +1,10 @@
 //! Built-in command to set and display environment variables.
 
+use anyhow::{anyhow, Result};
 use std::collections::HashMap;
+use super::{Builtin, BuiltinContext, BuiltinFuture};
+use crate::shell::core::command_executor::EXIT_SUCCESS;
+use tokio::io::AsyncWriteExt;
 
 /// Handles the `export` command.
 ///
@@ -13,41 +17,60 @@ use std::collections::HashMap;
 ///
 /// # Returns
 ///
-/// A `String` containing the output of the command.
-pub fn export_builtin(env_vars: &mut HashMap<String, String>, args: &[&str]) -> String {
+/// A `Result<String>` containing the output of the command, or an error if
+/// a variable to print does not exist.
+pub fn export_builtin(env_vars: &mut HashMap<String, String>, args: &[&str]) -> Result<String> {
     if args.is_empty() {
         // No arguments, print all environment variables
         if env_vars.is_empty() {
-            println!("DEBUG: export_builtin returning 'No environment variables defined in this session.'");
-            return "No environment variables defined in this session.\n".to_string();
+            return Ok("No environment variables defined in this session.\n".to_string());
         }
         let mut output = String::new();
         for (key, value) in env_vars.iter() {
             output.push_str(&format!("export {}={}\n", key, value));
         }
-        return output;
+        return Ok(output);
     }
 
-    let mut new_vars_set = 0;
     for arg in args {
         if let Some((key, value)) = arg.split_once('=') {
             // Set environment variable
             env_vars.insert(key.to_string(), value.to_string());
-            new_vars_set += 1;
         } else {
             // If not in `key=value` format, check if it's a key of an existing var to print
             if let Some(value) = env_vars.get(*arg) {
-                return format!("export {}={}\n", arg, value);
+                return Ok(format!("export {}={}\n", arg, value));
             } else {
-                return format!("export: {}: not found\n", arg);
+                return Err(anyhow!("export: {}: not found", arg));
             }
         }
     }
 
-    if new_vars_set > 0 {
-        String::new() // No output on successful setting
-    } else {
-        String::new()
+    Ok(String::new()) // No output on successful setting
+}
+
+/// The `export` builtin.
+pub struct Export;
+
+impl Builtin for Export {
+    fn name(&self) -> &'static str {
+        "export"
+    }
+
+    fn summary(&self) -> &'static str {
+        "Set or display environment variables."
+    }
+
+    fn usage(&self) -> &'static str {
+        "export [name[=value] ...]"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut BuiltinContext<'_>, args: &'a [&'a str]) -> BuiltinFuture<'a> {
+        Box::pin(async move {
+            let output = export_builtin(&mut ctx.shell.env_vars, args)?;
+            ctx.stdout.write_all(output.as_bytes()).await?;
+            Ok(EXIT_SUCCESS)
+        })
     }
 }
 
@@ -59,7 +82,7 @@ mod tests {
     fn test_export_set_new_var() {
         let mut env_vars = HashMap::new();
         let args = vec!["MY_VAR=test_value"];
-        let output = export_builtin(&mut env_vars, &args);
+        let output = export_builtin(&mut env_vars, &args).unwrap();
         assert!(output.is_empty());
         assert_eq!(env_vars.get("MY_VAR"), Some(&"test_value".to_string()));
     }
@@ -70,7 +93,7 @@ mod tests {
         env_vars.insert("VAR1".to_string(), "value1".to_string());
         env_vars.insert("VAR2".to_string(), "value2".to_string());
         
-        let output = export_builtin(&mut env_vars, &[]);
+        let output = export_builtin(&mut env_vars, &[]).unwrap();
         assert!(output.contains("export VAR1=value1\n"));
         assert!(output.contains("export VAR2=value2\n"));
     }
@@ -80,7 +103,7 @@ mod tests {
         let mut env_vars = HashMap::new();
         env_vars.insert("MY_VAR".to_string(), "test_value".to_string());
         let args = vec!["MY_VAR"];
-        let output = export_builtin(&mut env_vars, &args);
+        let output = export_builtin(&mut env_vars, &args).unwrap();
         assert_eq!(output, "export MY_VAR=test_value\n");
     }
 
@@ -88,8 +111,8 @@ mod tests {
     fn test_export_var_not_found() {
         let mut env_vars = HashMap::new();
         let args = vec!["NON_EXISTENT_VAR"];
-        let output = export_builtin(&mut env_vars, &args);
-        assert_eq!(output, "export: NON_EXISTENT_VAR: not found\n");
+        let err = export_builtin(&mut env_vars, &args).unwrap_err();
+        assert_eq!(err.to_string(), "export: NON_EXISTENT_VAR: not found");
     }
 
     #[test]
@@ -97,7 +120,7 @@ mod tests {
         let mut env_vars = HashMap::new();
         env_vars.insert("MY_VAR".to_string(), "old_value".to_string());
         let args = vec!["MY_VAR=new_value"];
-        let output = export_builtin(&mut env_vars, &args);
+        let output = export_builtin(&mut env_vars, &args).unwrap();
         assert!(output.is_empty());
         assert_eq!(env_vars.get("MY_VAR"), Some(&"new_value".to_string()));
     }
@@ -0,0 +1,172 @@
+//! Built-in commands used inside shell functions: `return` and `local`.
+
+use anyhow::{anyhow, Result};
+use tokio::io::AsyncWriteExt;
+use super::{Builtin, BuiltinContext, BuiltinFuture};
+use crate::shell::core::command_executor::{ControlFlow, EXIT_SUCCESS};
+use crate::shell::core::parser::is_valid_name;
+use crate::shell::core::ShellCore;
+
+/// Works out the status `return` leaves the function with.
+///
+/// # Arguments
+///
+/// * `shell` - The shell, which knows whether a function or a script run
+///   by `source` is running.
+/// * `args` - The arguments: an optional status, `$?` by default.
+///
+/// # Returns
+///
+/// The status, reduced to the range `0..=255`, or an error outside a
+/// function or sourced script, or for an invalid status.
+pub fn return_status(shell: &ShellCore, args: &[&str]) -> Result<i32> {
+    if shell.local_scopes.is_empty() && shell.source_depth == 0 {
+        return Err(anyhow!("return: can only `return' from a function or sourced script"));
+    }
+    match args {
+        [] => Ok(shell.last_exit_status),
+        [status] => status
+            .parse::<i64>()
+            .map(|status| status.rem_euclid(256) as i32)
+            .map_err(|_| anyhow!("return: {}: numeric argument required", status)),
+        _ => Err(anyhow!("return: too many arguments")),
+    }
+}
+
+/// Handles the `local` command.
+///
+/// Each `name=value` argument makes `name` local to the running function
+/// and assigns it; a bare `name` makes it local and unset. Without
+/// arguments, the function's local variables are listed.
+///
+/// # Arguments
+///
+/// * `shell` - The shell whose innermost function gets the variables.
+/// * `args` - The arguments to the command.
+///
+/// # Returns
+///
+/// The listing of local variables, or an empty string.
+pub fn local_builtin(shell: &mut ShellCore, args: &[&str]) -> Result<String> {
+    let Some(scope) = shell.local_scopes.last() else {
+        return Err(anyhow!("local: can only be used in a function"));
+    };
+
+    if args.is_empty() {
+        let mut names: Vec<&String> = scope.keys().collect();
+        names.sort();
+        let mut output = String::new();
+        for name in names {
+            if let Some(value) = shell.variables.get(name) {
+                output.push_str(&format!("{}={}\n", name, value));
+            }
+        }
+        return Ok(output);
+    }
+
+    for arg in args {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (*arg, None),
+        };
+        if !is_valid_name(name) {
+            return Err(anyhow!("local: `{}': not a valid identifier", arg));
+        }
+        shell.make_local(name);
+        match value {
+            Some(value) => shell.set_var(name, value),
+            None => {
+                shell.variables.remove(name);
+            }
+        }
+    }
+    Ok(String::new())
+}
+
+/// The `return` builtin.
+pub struct Return;
+
+impl Builtin for Return {
+    fn name(&self) -> &'static str {
+        "return"
+    }
+
+    fn summary(&self) -> &'static str {
+        "Leave the running function or sourced script with the given status, or that of the last command."
+    }
+
+    fn usage(&self) -> &'static str {
+        "return [n]"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut BuiltinContext<'_>, args: &'a [&'a str]) -> BuiltinFuture<'a> {
+        Box::pin(async move {
+            let status = return_status(ctx.shell, args)?;
+            ctx.shell.control_flow = Some(ControlFlow::Return);
+            Ok(status)
+        })
+    }
+}
+
+/// The `local` builtin.
+pub struct Local;
+
+impl Builtin for Local {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    fn summary(&self) -> &'static str {
+        "Declare variables that only exist until the running function returns."
+    }
+
+    fn usage(&self) -> &'static str {
+        "local [name[=value] ...]"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut BuiltinContext<'_>, args: &'a [&'a str]) -> BuiltinFuture<'a> {
+        Box::pin(async move {
+            let output = local_builtin(ctx.shell, args)?;
+            ctx.stdout.write_all(output.as_bytes()).await?;
+            Ok(EXIT_SUCCESS)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_return_status() {
+        let mut shell = ShellCore::new();
+        assert!(return_status(&shell, &[]).unwrap_err().to_string().contains("from a function"));
+
+        shell.local_scopes.push(HashMap::new());
+        shell.last_exit_status = 3;
+        assert_eq!(return_status(&shell, &[]).unwrap(), 3);
+        assert_eq!(return_status(&shell, &["257"]).unwrap(), 1);
+        assert_eq!(return_status(&shell, &["-1"]).unwrap(), 255);
+        assert_eq!(return_status(&shell, &["x"]).unwrap_err().to_string(), "return: x: numeric argument required");
+    }
+
+    #[test]
+    fn test_local_saves_previous_values() {
+        let mut shell = ShellCore::new();
+        assert!(local_builtin(&mut shell, &["x=1"]).is_err());
+
+        shell.variables.insert("x".to_string(), "global".to_string());
+        shell.local_scopes.push(HashMap::new());
+        local_builtin(&mut shell, &["x=1", "y"]).unwrap();
+        local_builtin(&mut shell, &["x=2"]).unwrap();
+        assert_eq!(shell.get_var("x"), Some("2".to_string()));
+        assert_eq!(local_builtin(&mut shell, &[]).unwrap(), "x=2\n");
+
+        let scope = shell.local_scopes.pop().unwrap();
+        assert_eq!(scope.get("x"), Some(&Some("global".to_string())));
+        assert_eq!(scope.get("y"), Some(&None));
+        shell.local_scopes.push(HashMap::new());
+        assert_eq!(local_builtin(&mut shell, &["1x=a"]).unwrap_err().to_string(), "local: `1x=a': not a valid identifier");
+    }
+}
@@ -1,65 +1,90 @@
 //! This module provides a built-in `grep` command.
 
 use anyhow::{anyhow, Result};
-use std::io::{BufRead, BufReader, Read};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use super::{Builtin, BuiltinContext, BuiltinFuture};
+use crate::shell::core::command_executor::EXIT_SUCCESS;
 
-/// The core logic for grep, reading from a BufRead source.
-fn grep_logic(pattern: &str, mut reader: impl BufRead) -> Result<String> {
-    let mut output = String::new();
-    let mut line = String::new();
+/// A simple `grep` implementation that reads from a given input stream.
+/// This function is designed to be used in pipelines.
+///
+/// Matching lines are written to `output` as soon as they are read, so
+/// `grep` can filter a stream that never ends, such as `tail -f`.
+pub async fn grep_builtin(
+    args: &[&str],
+    input: impl AsyncRead + Unpin,
+    output: &mut (impl AsyncWrite + Unpin),
+) -> Result<()> {
+    if args.is_empty() {
+        return Err(anyhow!("grep: missing pattern"));
+    }
+    let pattern = args[0];
 
-    while reader.read_line(&mut line)? > 0 {
-        if line.contains(pattern) {
-            output.push_str(&line);
+    let mut reader = BufReader::new(input);
+    let mut line = Vec::new();
+    while reader.read_until(b'\n', &mut line).await? > 0 {
+        if String::from_utf8_lossy(&line).contains(pattern) {
+            output.write_all(&line).await?;
         }
         line.clear();
     }
-    Ok(output)
+    Ok(())
 }
 
-/// A simple `grep` implementation that reads from a given input stream.
-/// This function is designed to be used in pipelines.
-pub async fn grep_builtin(args: &[&str], input: Box<dyn Read + Send>) -> Result<String> {
-    if args.is_empty() {
-        return Err(anyhow!("grep: missing pattern"));
+/// The `grep` builtin.
+pub struct Grep;
+
+impl Builtin for Grep {
+    fn name(&self) -> &'static str {
+        "grep"
+    }
+
+    fn summary(&self) -> &'static str {
+        "Print lines that contain a pattern."
     }
-    let pattern = args[0];
 
-    let reader = BufReader::new(input);
-    grep_logic(pattern, reader)
+    fn usage(&self) -> &'static str {
+        "grep pattern"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut BuiltinContext<'_>, args: &'a [&'a str]) -> BuiltinFuture<'a> {
+        Box::pin(async move {
+            grep_builtin(args, &mut ctx.stdin, &mut ctx.stdout).await?;
+            Ok(EXIT_SUCCESS)
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::io::Cursor;
 
     #[tokio::test]
     async fn test_grep_builtin_with_matches() {
         let pattern = "hello";
         let input_str = "hello world\ngoodbye world\nhello again\n";
-        let input = Box::new(Cursor::new(input_str));
-        
-        let result = grep_builtin(&[pattern], input).await.unwrap();
-        assert_eq!(result, "hello world\nhello again\n");
+        let mut output = Vec::new();
+
+        grep_builtin(&[pattern], input_str.as_bytes(), &mut output).await.unwrap();
+        assert_eq!(output, b"hello world\nhello again\n");
     }
 
     #[tokio::test]
     async fn test_grep_builtin_no_matches() {
         let pattern = "rust";
         let input_str = "hello world\ngoodbye world\nhello again";
-        let input = Box::new(Cursor::new(input_str));
+        let mut output = Vec::new();
 
-        let result = grep_builtin(&[pattern], input).await.unwrap();
-        assert!(result.is_empty());
+        grep_builtin(&[pattern], input_str.as_bytes(), &mut output).await.unwrap();
+        assert!(output.is_empty());
     }
 
     #[tokio::test]
     async fn test_grep_builtin_no_pattern() {
         let input_str = "hello world";
-        let input = Box::new(Cursor::new(input_str));
+        let mut output = Vec::new();
 
-        let result = grep_builtin(&[], input).await;
+        let result = grep_builtin(&[], input_str.as_bytes(), &mut output).await;
         assert!(result.is_err());
     }
 }
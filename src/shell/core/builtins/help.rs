@@ -0,0 +1,84 @@
+//! Built-in command to describe the shell's builtins.
+
+use anyhow::{anyhow, Result};
+use tokio::io::AsyncWriteExt;
+use super::{registry, Builtin, BuiltinContext, BuiltinFuture, BuiltinRegistry};
+use crate::shell::core::command_executor::EXIT_SUCCESS;
+
+/// Handles the `help` command.
+///
+/// # Arguments
+///
+/// * `registry` - The builtins to describe.
+/// * `args` - Names of builtins; when empty, every builtin is listed.
+///
+/// # Returns
+///
+/// A `Result<String>` containing a summary of every builtin, or the usage of
+/// each named builtin, or an error if a name is not a builtin.
+pub fn help_builtin(registry: &BuiltinRegistry, args: &[&str]) -> Result<String> {
+    let mut output = String::new();
+    if args.is_empty() {
+        let width = registry.iter().map(|b| b.name().len()).max().unwrap_or(0);
+        for builtin in registry.iter() {
+            output.push_str(&format!("{:<width$}  {}\n", builtin.name(), builtin.summary()));
+        }
+        return Ok(output);
+    }
+
+    for name in args {
+        match registry.get(name) {
+            Some(builtin) => output.push_str(&format!("{}\n    {}\n", builtin.usage(), builtin.summary())),
+            None => return Err(anyhow!("help: no help topics match '{}'", name)),
+        }
+    }
+    Ok(output)
+}
+
+/// The `help` builtin.
+pub struct Help;
+
+impl Builtin for Help {
+    fn name(&self) -> &'static str {
+        "help"
+    }
+
+    fn summary(&self) -> &'static str {
+        "Display information about builtin commands."
+    }
+
+    fn usage(&self) -> &'static str {
+        "help [name ...]"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut BuiltinContext<'_>, args: &'a [&'a str]) -> BuiltinFuture<'a> {
+        Box::pin(async move {
+            let output = help_builtin(registry(), args)?;
+            ctx.stdout.write_all(output.as_bytes()).await?;
+            Ok(EXIT_SUCCESS)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_help_lists_every_builtin() {
+        let output = help_builtin(registry(), &[]).unwrap();
+        for builtin in registry().iter() {
+            assert!(output.contains(builtin.name()), "missing {}", builtin.name());
+        }
+        assert!(output.contains("Change the current directory."));
+    }
+
+    #[test]
+    fn test_help_for_one_builtin() {
+        let output = help_builtin(registry(), &["ls"]).unwrap();
+        assert_eq!(output, "ls [-al] [path]\n    List directory contents.\n");
+
+        let error = help_builtin(registry(), &["nonexistent"]).unwrap_err();
+        assert_eq!(error.to_string(), "help: no help topics match 'nonexistent'");
+    }
+}
@@ -0,0 +1,235 @@
+//! Built-in command to list the command history.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use super::{Builtin, BuiltinContext, BuiltinFuture};
+use crate::shell::core::command_executor::EXIT_SUCCESS;
+use crate::shell::history::{format_duration, HistoryEntry};
+
+/// The commands `history` lists.
+#[derive(Debug, Default, PartialEq)]
+struct Filter {
+    /// Only commands run in this directory or below it.
+    cwd: Option<PathBuf>,
+    /// Only commands that finished with a non-zero status.
+    failed: bool,
+    /// Only commands started at or after this time.
+    since: Option<DateTime<Local>>,
+    /// Only commands containing this text.
+    grep: Option<String>,
+    /// Only the last `count` commands that match.
+    count: Option<usize>,
+}
+
+impl Filter {
+    fn matches(&self, entry: &HistoryEntry) -> bool {
+        let in_cwd = |dir: &PathBuf| entry.cwd.as_ref().is_some_and(|cwd| Path::new(cwd).starts_with(dir));
+        self.cwd.as_ref().is_none_or(in_cwd)
+            && (!self.failed || entry.failed())
+            && self.since.is_none_or(|since| entry.started_at.is_some_and(|started_at| started_at >= since))
+            && self.grep.as_ref().is_none_or(|text| entry.command.contains(text.as_str()))
+    }
+}
+
+/// Handles the `history` command.
+///
+/// Each command is listed with its number, which `!n` refers to, when it
+/// started, its exit status and how long it ran. Details that were not
+/// recorded are shown as `-`.
+///
+/// # Arguments
+///
+/// * `entries` - The history, oldest first.
+/// * `args` - `[--cwd[=dir]] [--failed] [--since time] [--grep text] [count]`.
+/// * `current_dir` - The directory `--cwd` stands for, and that a relative
+///   `--cwd=dir` is resolved against.
+/// * `now` - The time `--since` is relative to.
+///
+/// # Returns
+///
+/// One line per matching command, oldest first, or an error for an invalid
+/// argument.
+pub fn history_builtin(entries: &[HistoryEntry], args: &[&str], current_dir: &Path, now: DateTime<Local>) -> Result<String> {
+    let filter = parse_args(args, current_dir, now)?;
+    let matching: Vec<(usize, &HistoryEntry)> = entries.iter().enumerate().filter(|(_, entry)| filter.matches(entry)).collect();
+    let skipped = filter.count.map_or(0, |count| matching.len().saturating_sub(count));
+
+    let mut output = String::new();
+    for (index, entry) in &matching[skipped..] {
+        let started_at = entry.started_at.map_or("-".to_string(), |started_at| started_at.format("%Y-%m-%d %H:%M:%S").to_string());
+        let status = entry.exit_status.map_or("-".to_string(), |status| status.to_string());
+        let duration = entry.duration_ms.map_or("-".to_string(), |millis| format_duration(Duration::milliseconds(millis as i64)));
+        output.push_str(&format!("{:>5}  {:<19}  {:>3}  {:>7}  {}\n", index + 1, started_at, status, duration, entry.command));
+    }
+    Ok(output)
+}
+
+/// Parses the arguments of `history`.
+fn parse_args(args: &[&str], current_dir: &Path, now: DateTime<Local>) -> Result<Filter> {
+    let mut filter = Filter::default();
+    let mut args = args.iter();
+    while let Some(&arg) = args.next() {
+        let (option, value) = match arg.split_once('=') {
+            Some((option, value)) if option.starts_with("--") => (option, Some(value)),
+            _ => (arg, None),
+        };
+        let mut option_value = || {
+            value
+                .or_else(|| args.next().copied())
+                .ok_or_else(|| anyhow!("history: {}: option requires an argument", option))
+        };
+        match option {
+            "--cwd" => {
+                let dir = value.map_or_else(|| current_dir.to_path_buf(), |dir| current_dir.join(dir));
+                filter.cwd = Some(dunce::canonicalize(&dir).unwrap_or(dir));
+            }
+            "--failed" if value.is_none() => filter.failed = true,
+            "--since" => filter.since = Some(parse_since(option_value()?, now)?),
+            "--grep" => filter.grep = Some(option_value()?.to_string()),
+            _ if option.starts_with('-') => return Err(anyhow!("history: {}: invalid option", arg)),
+            _ if filter.count.is_some() => return Err(anyhow!("history: too many arguments")),
+            _ => {
+                let count = arg.parse().map_err(|_| anyhow!("history: {}: numeric argument required", arg))?;
+                filter.count = Some(count);
+            }
+        }
+    }
+    Ok(filter)
+}
+
+/// Parses the time given to `--since`.
+///
+/// # Arguments
+///
+/// * `text` - A time ago such as `30m`, `2h`, `3d` or `1w` (`s` for
+///   seconds), `today`, `yesterday`, a date such as `2024-05-01` or a date
+///   and time such as `2024-05-01 14:30`, in local time.
+/// * `now` - The time a time ago counts back from.
+fn parse_since(text: &str, now: DateTime<Local>) -> Result<DateTime<Local>> {
+    let invalid = || anyhow!("history: {}: invalid time", text);
+    let midnight = |date: NaiveDate| Local.from_local_datetime(&date.and_time(NaiveTime::MIN)).earliest();
+    match text {
+        "today" => return midnight(now.date_naive()).ok_or_else(invalid),
+        "yesterday" => return now.date_naive().pred_opt().and_then(midnight).ok_or_else(invalid),
+        _ => {}
+    }
+    if let Some(unit) = text.chars().last().filter(char::is_ascii_alphabetic) {
+        let amount: i64 = text[..text.len() - 1].parse().map_err(|_| invalid())?;
+        let ago = match unit {
+            's' => Duration::try_seconds(amount),
+            'm' => Duration::try_minutes(amount),
+            'h' => Duration::try_hours(amount),
+            'd' => Duration::try_days(amount),
+            'w' => Duration::try_weeks(amount),
+            _ => None,
+        };
+        return ago.and_then(|ago| now.checked_sub_signed(ago)).ok_or_else(invalid);
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        return midnight(date).ok_or_else(invalid);
+    }
+    ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .and_then(|time| Local.from_local_datetime(&time).earliest())
+        .ok_or_else(invalid)
+}
+
+/// The `history` builtin.
+pub struct History;
+
+impl Builtin for History {
+    fn name(&self) -> &'static str {
+        "history"
+    }
+
+    fn summary(&self) -> &'static str {
+        "List the commands entered, with when, how long and how they ran."
+    }
+
+    fn usage(&self) -> &'static str {
+        "history [--cwd[=dir]] [--failed] [--since time] [--grep text] [count]"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut BuiltinContext<'_>, args: &'a [&'a str]) -> BuiltinFuture<'a> {
+        Box::pin(async move {
+            let current_dir = ctx.shell.current_dir.clone();
+            let output = ctx.shell.history.read(|entries| history_builtin(entries, args, &current_dir, Local::now()))?;
+            ctx.stdout.write_all(output.as_bytes()).await?;
+            Ok(EXIT_SUCCESS)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(text: &str) -> DateTime<Local> {
+        parse_since(text, Local::now()).unwrap()
+    }
+
+    fn entries() -> Vec<HistoryEntry> {
+        let entry = |command: &str, started_at: &str, cwd: &str, exit_status: i32, duration_ms: u64| HistoryEntry {
+            started_at: Some(time(started_at)),
+            cwd: Some(cwd.to_string()),
+            exit_status: Some(exit_status),
+            duration_ms: Some(duration_ms),
+            ..HistoryEntry::new(command.to_string())
+        };
+        vec![
+            entry("cargo build", "2024-05-01 09:00", "/src/app", 0, 4210),
+            entry("cargo test", "2024-05-02 10:30", "/src/app/core", 101, 185_000),
+            entry("ls", "2024-05-02 11:00", "/tmp", 0, 3),
+            HistoryEntry { started_at: None, ..HistoryEntry::new("make".to_string()) },
+        ]
+    }
+
+    fn commands(output: &str) -> Vec<&str> {
+        output.lines().map(|line| line.rsplit("  ").next().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_history_lists_every_command() {
+        let output = history_builtin(&entries(), &[], Path::new("/"), Local::now()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "    1  2024-05-01 09:00:00    0     4.2s  cargo build");
+        assert_eq!(lines[1], "    2  2024-05-02 10:30:00  101   3m 05s  cargo test");
+        assert_eq!(lines[3], "    4  -                      -        -  make");
+        assert_eq!(commands(&history_builtin(&entries(), &["2"], Path::new("/"), Local::now()).unwrap()), vec!["ls", "make"]);
+    }
+
+    #[test]
+    fn test_history_filters() {
+        let entries = entries();
+        let now = time("2024-05-02 12:00");
+        let run = |args: &[&str]| commands(&history_builtin(&entries, args, Path::new("/src/app"), now).unwrap()).join(", ");
+        assert_eq!(run(&["--cwd"]), "cargo build, cargo test");
+        assert_eq!(run(&["--cwd=/tmp"]), "ls");
+        assert_eq!(run(&["--failed"]), "cargo test");
+        assert_eq!(run(&["--since", "2h"]), "cargo test, ls");
+        assert_eq!(run(&["--since=today", "--grep", "cargo"]), "cargo test");
+        assert_eq!(run(&["--since", "yesterday", "1"]), "ls");
+        assert_eq!(run(&["--grep=build"]), "cargo build");
+    }
+
+    #[test]
+    fn test_parse_since() {
+        let now = time("2024-05-02 12:00:30");
+        assert_eq!(parse_since("30s", now).unwrap(), time("2024-05-02 12:00"));
+        assert_eq!(parse_since("1w", now).unwrap(), time("2024-04-25T12:00:30"));
+        assert_eq!(parse_since("2024-05-01", now).unwrap(), time("2024-05-01 00:00"));
+        assert_eq!(parse_since("soon", now).unwrap_err().to_string(), "history: soon: invalid time");
+        assert!(parse_since("3y", now).is_err());
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        let run = |args: &[&str]| history_builtin(&[], args, Path::new("/"), Local::now()).unwrap_err().to_string();
+        assert_eq!(run(&["-x"]), "history: -x: invalid option");
+        assert_eq!(run(&["--grep"]), "history: --grep: option requires an argument");
+        assert_eq!(run(&["ten"]), "history: ten: numeric argument required");
+    }
+}
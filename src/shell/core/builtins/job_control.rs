@@ -0,0 +1,428 @@
+//! Built-in commands for background jobs: `jobs`, `fg`, `bg`, `wait` and
+//! `kill`.
+
+use anyhow::{anyhow, Result};
+use tokio::io::AsyncWriteExt;
+use super::{Builtin, BuiltinContext, BuiltinFuture};
+use crate::shell::core::command_executor::{EXIT_INTERRUPTED, EXIT_SUCCESS};
+use crate::shell::core::jobs::JobTable;
+
+/// The signals `kill` knows by name.
+#[cfg(unix)]
+const SIGNALS: [(&str, i32); 9] = [
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("USR2", libc::SIGUSR2),
+    ("TERM", libc::SIGTERM),
+    ("CONT", libc::SIGCONT),
+    ("STOP", libc::SIGSTOP),
+];
+#[cfg(not(unix))]
+const SIGNALS: [(&str, i32); 5] = [("HUP", 1), ("INT", 2), ("QUIT", 3), ("KILL", 9), ("TERM", 15)];
+
+/// The signals that stop a job the way Ctrl+C stops the foreground.
+#[cfg(unix)]
+const STOPPING_SIGNALS: [i32; 3] = [libc::SIGINT, libc::SIGKILL, libc::SIGTERM];
+#[cfg(not(unix))]
+const STOPPING_SIGNALS: [i32; 3] = [2, 9, 15];
+
+/// Handles the `jobs` command.
+///
+/// The current job, which `fg` and `%%` refer to, is marked with `+` and
+/// the previous one with `-`. Finished jobs are removed from the table once
+/// listed.
+///
+/// # Arguments
+///
+/// * `table` - The shell's jobs.
+/// * `args` - Job specifications to list, or none for every job.
+///
+/// # Returns
+///
+/// One line per job, or an error for an unknown job.
+pub fn jobs_builtin(table: &JobTable, args: &[&str]) -> Result<String> {
+    let ids = args
+        .iter()
+        .map(|spec| table.resolve(spec).map_err(|e| anyhow!("jobs: {}", e)))
+        .collect::<Result<Vec<_>>>()?;
+    let jobs = table.list();
+    let mut output = String::new();
+    for (index, job) in jobs.iter().enumerate() {
+        if !ids.is_empty() && !ids.contains(&job.id) {
+            continue;
+        }
+        let marker = match jobs.len() - index {
+            1 => '+',
+            2 => '-',
+            _ => ' ',
+        };
+        output.push_str(&job.describe(marker));
+        output.push('\n');
+        if job.status.is_some() {
+            table.remove(job.id);
+        }
+    }
+    Ok(output)
+}
+
+/// Finds the job `fg` or `bg` applies to: the given one, where `%` may be
+/// left out, or the current job.
+fn job_argument(table: &JobTable, name: &str, args: &[&str]) -> Result<usize> {
+    match args {
+        [] => table.resolve("%%").map_err(|_| anyhow!("{}: current: no such job", name)),
+        [spec] if spec.starts_with('%') => table.resolve(spec).map_err(|e| anyhow!("{}: {}", name, e)),
+        [spec] => table.resolve(&format!("%{}", spec)).map_err(|e| anyhow!("{}: {}", name, e)),
+        _ => Err(anyhow!("{}: too many arguments", name)),
+    }
+}
+
+/// Parses the arguments of `kill` into a signal number and the targets.
+///
+/// # Arguments
+///
+/// * `args` - `[-s signal | -signal] target ...`, where a signal is a name
+///   such as `TERM` or `SIGTERM`, or a number.
+///
+/// # Returns
+///
+/// The signal, `SIGTERM` by default, and the job specifications or
+/// process ids to send it to.
+pub fn parse_kill_args<'a>(args: &[&'a str]) -> Result<(i32, Vec<&'a str>)> {
+    let (signal, targets) = match args {
+        ["-s", signal, targets @ ..] => (signal_number(signal)?, targets),
+        [option, targets @ ..] if option.starts_with('-') && option.len() > 1 => {
+            (signal_number(&option[1..])?, targets)
+        }
+        targets => (signal_number("TERM")?, targets),
+    };
+    if targets.is_empty() {
+        return Err(anyhow!("kill: usage: kill [-s signal | -signal] pid | jobspec ... or kill -l"));
+    }
+    Ok((signal, targets.to_vec()))
+}
+
+/// Looks up a signal by name, with or without the `SIG` prefix, or number.
+fn signal_number(signal: &str) -> Result<i32> {
+    if let Ok(number) = signal.parse::<i32>() {
+        return Ok(number);
+    }
+    let upper = signal.to_ascii_uppercase();
+    let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+    SIGNALS
+        .iter()
+        .find(|(known, _)| *known == name)
+        .map(|(_, number)| *number)
+        .ok_or_else(|| anyhow!("kill: {}: invalid signal specification", signal))
+}
+
+/// Sends a signal to a process.
+#[cfg(unix)]
+fn send_signal(pid: &str, signal: i32) -> Result<()> {
+    let Ok(number) = pid.parse::<libc::pid_t>() else {
+        return Err(anyhow!("kill: {}: arguments must be process or job IDs", pid));
+    };
+    // SAFETY: `kill` has no memory-safety preconditions.
+    if unsafe { libc::kill(number, signal) } != 0 {
+        return Err(anyhow!("kill: ({}) - {}", pid, std::io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+/// Sending signals to processes needs Unix; jobs can still be killed.
+#[cfg(not(unix))]
+fn send_signal(pid: &str, _signal: i32) -> Result<()> {
+    Err(anyhow!("kill: {}: only job IDs are supported on this platform", pid))
+}
+
+/// Sends a signal to a job.
+///
+/// `INT`, `KILL` and `TERM` stop the job's commands, builtins included, and
+/// signal `0` only checks that the job exists. Other signals, such as
+/// `STOP` and `CONT`, go to the process groups of the job's running
+/// processes.
+fn signal_job(table: &JobTable, spec: &str, id: usize, signal: i32) -> Result<()> {
+    if signal == 0 {
+        return Ok(());
+    }
+    if STOPPING_SIGNALS.contains(&signal) {
+        table.kill(id);
+        return Ok(());
+    }
+    send_job_signal(table, spec, id, signal)
+}
+
+#[cfg(unix)]
+fn send_job_signal(table: &JobTable, spec: &str, id: usize, signal: i32) -> Result<()> {
+    table.signal(id, signal).map_err(|e| anyhow!("kill: {}: {}", spec, e))
+}
+
+/// Jobs have no process groups to signal outside Unix.
+#[cfg(not(unix))]
+fn send_job_signal(_table: &JobTable, spec: &str, _id: usize, signal: i32) -> Result<()> {
+    Err(anyhow!("kill: {}: signal {} cannot be sent to jobs on this platform", spec, signal))
+}
+
+/// Handles the `kill` command.
+///
+/// A job given as `%n` is stopped the way Ctrl+C stops the foreground by
+/// `INT`, `KILL` and `TERM`, and its processes receive any other signal; a
+/// process id receives the signal itself.
+///
+/// # Arguments
+///
+/// * `table` - The shell's jobs.
+/// * `args` - The arguments to the command.
+///
+/// # Returns
+///
+/// The signal names for `kill -l`, otherwise an empty string, or an error
+/// for the first target that could not be signalled.
+pub fn kill_builtin(table: &JobTable, args: &[&str]) -> Result<String> {
+    if args.first() == Some(&"-l") {
+        let names: Vec<String> = SIGNALS.iter().map(|(name, number)| format!("{}) SIG{}", number, name)).collect();
+        return Ok(format!("{}\n", names.join("\n")));
+    }
+    let (signal, targets) = parse_kill_args(args)?;
+    for target in targets {
+        if target.starts_with('%') {
+            let id = table.resolve(target).map_err(|e| anyhow!("kill: {}", e))?;
+            signal_job(table, target, id, signal)?;
+        } else {
+            send_signal(target, signal)?;
+        }
+    }
+    Ok(String::new())
+}
+
+/// The `jobs` builtin.
+pub struct Jobs;
+
+impl Builtin for Jobs {
+    fn name(&self) -> &'static str {
+        "jobs"
+    }
+
+    fn summary(&self) -> &'static str {
+        "List the background jobs and whether they are still running."
+    }
+
+    fn usage(&self) -> &'static str {
+        "jobs [jobspec ...]"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut BuiltinContext<'_>, args: &'a [&'a str]) -> BuiltinFuture<'a> {
+        Box::pin(async move {
+            let output = jobs_builtin(&ctx.shell.jobs, args)?;
+            ctx.stdout.write_all(output.as_bytes()).await?;
+            Ok(EXIT_SUCCESS)
+        })
+    }
+}
+
+/// The `fg` builtin.
+pub struct Fg;
+
+impl Builtin for Fg {
+    fn name(&self) -> &'static str {
+        "fg"
+    }
+
+    fn summary(&self) -> &'static str {
+        "Wait for a background job in the foreground, so that Ctrl+C stops it."
+    }
+
+    fn usage(&self) -> &'static str {
+        "fg [jobspec]"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut BuiltinContext<'_>, args: &'a [&'a str]) -> BuiltinFuture<'a> {
+        Box::pin(async move {
+            let table = ctx.shell.jobs.clone();
+            let id = job_argument(&table, "fg", args)?;
+            if let Some(job) = table.list().into_iter().find(|job| job.id == id) {
+                ctx.stdout.write_all(format!("{}\n", job.command).as_bytes()).await?;
+            }
+            let status = tokio::select! {
+                status = table.wait(id) => status,
+                _ = ctx.cancellation.cancelled() => {
+                    table.kill(id);
+                    table.wait(id).await
+                }
+            };
+            table.remove(id);
+            Ok(status.unwrap_or(EXIT_SUCCESS))
+        })
+    }
+}
+
+/// The `bg` builtin.
+pub struct Bg;
+
+impl Builtin for Bg {
+    fn name(&self) -> &'static str {
+        "bg"
+    }
+
+    fn summary(&self) -> &'static str {
+        "Check that a job runs in the background. Jobs cannot be suspended, so they always do."
+    }
+
+    fn usage(&self) -> &'static str {
+        "bg [jobspec]"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut BuiltinContext<'_>, args: &'a [&'a str]) -> BuiltinFuture<'a> {
+        Box::pin(async move {
+            let id = job_argument(&ctx.shell.jobs, "bg", args)?;
+            let finished = ctx.shell.jobs.list().iter().any(|job| job.id == id && job.status.is_some());
+            if finished {
+                return Err(anyhow!("bg: job has terminated"));
+            }
+            ctx.stderr.write_error(&format!("bg: job {} already in background", id)).await?;
+            Ok(EXIT_SUCCESS)
+        })
+    }
+}
+
+/// The `wait` builtin.
+pub struct Wait;
+
+impl Builtin for Wait {
+    fn name(&self) -> &'static str {
+        "wait"
+    }
+
+    fn summary(&self) -> &'static str {
+        "Wait for the given background jobs, or all of them, to finish."
+    }
+
+    fn usage(&self) -> &'static str {
+        "wait [jobspec ...]"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut BuiltinContext<'_>, args: &'a [&'a str]) -> BuiltinFuture<'a> {
+        Box::pin(async move {
+            let table = ctx.shell.jobs.clone();
+            let ids = if args.is_empty() {
+                table.running().iter().map(|job| job.id).collect()
+            } else {
+                args.iter()
+                    .map(|spec| {
+                        if spec.starts_with('%') {
+                            table.resolve(spec).map_err(|e| anyhow!("wait: {}", e))
+                        } else {
+                            Err(anyhow!("wait: `{}': not a valid job spec", spec))
+                        }
+                    })
+                    .collect::<Result<Vec<_>>>()?
+            };
+
+            let mut status = EXIT_SUCCESS;
+            for id in ids {
+                // An interrupted `wait` leaves the jobs running.
+                status = tokio::select! {
+                    job_status = table.wait(id) => job_status.unwrap_or(EXIT_SUCCESS),
+                    _ = ctx.cancellation.cancelled() => return Ok(EXIT_INTERRUPTED),
+                };
+                table.remove(id);
+            }
+            // Without arguments, only the exit status of `wait` itself counts.
+            Ok(if args.is_empty() { EXIT_SUCCESS } else { status })
+        })
+    }
+}
+
+/// The `kill` builtin.
+pub struct Kill;
+
+impl Builtin for Kill {
+    fn name(&self) -> &'static str {
+        "kill"
+    }
+
+    fn summary(&self) -> &'static str {
+        "Stop a background job, or send a signal to a process."
+    }
+
+    fn usage(&self) -> &'static str {
+        "kill [-s signal | -signal] pid | jobspec ... or kill -l"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut BuiltinContext<'_>, args: &'a [&'a str]) -> BuiltinFuture<'a> {
+        Box::pin(async move {
+            let output = kill_builtin(&ctx.shell.jobs, args)?;
+            ctx.stdout.write_all(output.as_bytes()).await?;
+            Ok(EXIT_SUCCESS)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shell::core::cancellation::CancellationToken;
+    use crate::shell::core::command_executor::execute_shell_command;
+    use crate::shell::core::ShellCore;
+
+    #[test]
+    fn test_jobs_lists_and_forgets_finished_jobs() {
+        let table = JobTable::new();
+        let make = table.add("make".to_string(), CancellationToken::new());
+        table.add("sleep 10".to_string(), CancellationToken::new());
+        table.finish(make, 0);
+
+        let output = jobs_builtin(&table, &[]).unwrap();
+        assert_eq!(output, format!("[1]-  {:<24}make\n[2]+  {:<24}sleep 10 &\n", "Done", "Running"));
+        assert_eq!(jobs_builtin(&table, &[]).unwrap().lines().count(), 1);
+        assert_eq!(jobs_builtin(&table, &["%1"]).unwrap_err().to_string(), "jobs: %1: no such job");
+    }
+
+    #[test]
+    fn test_parse_kill_args() {
+        #[cfg(unix)]
+        {
+            assert_eq!(parse_kill_args(&["%1"]).unwrap(), (libc::SIGTERM, vec!["%1"]));
+            assert_eq!(parse_kill_args(&["-9", "12", "%2"]).unwrap(), (9, vec!["12", "%2"]));
+            assert_eq!(parse_kill_args(&["-s", "sigint", "12"]).unwrap(), (libc::SIGINT, vec!["12"]));
+            assert_eq!(parse_kill_args(&["-KILL", "12"]).unwrap(), (libc::SIGKILL, vec!["12"]));
+        }
+        assert_eq!(parse_kill_args(&["-FOO", "1"]).unwrap_err().to_string(), "kill: FOO: invalid signal specification");
+        assert!(parse_kill_args(&["-9"]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_background_jobs_run_while_the_shell_continues() {
+        let mut shell = ShellCore::new();
+        let output = execute_shell_command(&mut shell, "X=1; sleep 5 & X=2 sleep 5 & echo $X; jobs").await;
+        assert_eq!(output, format!("1\n[1]-  {:<24}sleep 5 &\n[2]+  {:<24}X=2 sleep 5 &\n", "Running", "Running"));
+
+        let output = execute_shell_command(&mut shell, "kill %sleep; wait %1; echo $?; kill %2; wait; jobs").await;
+        assert_eq!(output, format!("{}\n", EXIT_INTERRUPTED));
+        assert!(shell.jobs.list().is_empty());
+
+        let output = execute_shell_command(&mut shell, "{ sleep 0.1; false; } & fg").await;
+        assert_eq!(output, "{ sleep 0.1; false; }\n");
+        assert_eq!(shell.last_exit_status, 1);
+        assert!(execute_shell_command(&mut shell, "fg").await.contains("fg: current: no such job"));
+    }
+
+    #[tokio::test]
+    async fn test_kill_signals_that_leave_a_job_running() {
+        let mut shell = ShellCore::new();
+        let running = format!("[1]+  {:<24}sleep 5 &\n", "Running");
+        let output = execute_shell_command(&mut shell, "sleep 5 & kill -0 %1; echo $?; jobs").await;
+        assert_eq!(output, format!("0\n{}", running));
+        #[cfg(unix)]
+        {
+            let output = execute_shell_command(&mut shell, "kill -STOP %1; kill -CONT %1; echo $?; jobs").await;
+            assert_eq!(output, format!("0\n{}", running));
+        }
+        assert!(execute_shell_command(&mut shell, "kill -0 %2").await.contains("kill: %2: no such job"));
+
+        let output = execute_shell_command(&mut shell, "kill %1; wait %1; echo $?").await;
+        assert_eq!(output, format!("{}\n", EXIT_INTERRUPTED));
+    }
+}
@@ -0,0 +1,102 @@
+//! Built-in commands to leave a loop or skip to its next iteration.
+
+use anyhow::{anyhow, Result};
+use super::{Builtin, BuiltinContext, BuiltinFuture};
+use crate::shell::core::command_executor::{ControlFlow, EXIT_SUCCESS};
+use crate::shell::core::ShellCore;
+
+/// Works out how many loops `break` or `continue` applies to.
+///
+/// # Arguments
+///
+/// * `shell` - The shell, which knows how many loops are running.
+/// * `name` - `break` or `continue`, for error messages.
+/// * `args` - The arguments: an optional number of enclosing loops,
+///   `1` by default.
+///
+/// # Returns
+///
+/// The number of loops, limited to the loops that are running, or an error
+/// outside a loop or for an invalid count.
+pub fn loop_count(shell: &ShellCore, name: &str, args: &[&str]) -> Result<usize> {
+    let count = match args {
+        [] => 1,
+        [count] => match count.parse::<usize>() {
+            Ok(0) => return Err(anyhow!("{}: {}: loop count out of range", name, count)),
+            Ok(count) => count,
+            Err(_) => return Err(anyhow!("{}: {}: numeric argument required", name, count)),
+        },
+        _ => return Err(anyhow!("{}: too many arguments", name)),
+    };
+    if shell.loop_depth == 0 {
+        return Err(anyhow!("{}: only meaningful in a `for', `while', or `until' loop", name));
+    }
+    Ok(count.min(shell.loop_depth))
+}
+
+/// The `break` builtin.
+pub struct Break;
+
+impl Builtin for Break {
+    fn name(&self) -> &'static str {
+        "break"
+    }
+
+    fn summary(&self) -> &'static str {
+        "Leave the innermost for, while or until loop, or the nth enclosing one."
+    }
+
+    fn usage(&self) -> &'static str {
+        "break [n]"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut BuiltinContext<'_>, args: &'a [&'a str]) -> BuiltinFuture<'a> {
+        Box::pin(async move {
+            let count = loop_count(ctx.shell, "break", args)?;
+            ctx.shell.control_flow = Some(ControlFlow::Break(count));
+            Ok(EXIT_SUCCESS)
+        })
+    }
+}
+
+/// The `continue` builtin.
+pub struct Continue;
+
+impl Builtin for Continue {
+    fn name(&self) -> &'static str {
+        "continue"
+    }
+
+    fn summary(&self) -> &'static str {
+        "Skip to the next iteration of the innermost loop, or of the nth enclosing one."
+    }
+
+    fn usage(&self) -> &'static str {
+        "continue [n]"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut BuiltinContext<'_>, args: &'a [&'a str]) -> BuiltinFuture<'a> {
+        Box::pin(async move {
+            let count = loop_count(ctx.shell, "continue", args)?;
+            ctx.shell.control_flow = Some(ControlFlow::Continue(count));
+            Ok(EXIT_SUCCESS)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loop_count() {
+        let mut shell = ShellCore::new();
+        assert!(loop_count(&shell, "break", &[]).unwrap_err().to_string().contains("only meaningful"));
+
+        shell.loop_depth = 2;
+        assert_eq!(loop_count(&shell, "break", &[]).unwrap(), 1);
+        assert_eq!(loop_count(&shell, "break", &["5"]).unwrap(), 2);
+        assert_eq!(loop_count(&shell, "continue", &["0"]).unwrap_err().to_string(), "continue: 0: loop count out of range");
+        assert_eq!(loop_count(&shell, "break", &["x"]).unwrap_err().to_string(), "break: x: numeric argument required");
+    }
+}
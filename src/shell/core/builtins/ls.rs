@@ -1,8 +1,12 @@
-use std::path::PathBuf;
+use anyhow::{anyhow, Result};
+use std::path::Path;
 use tokio::fs;
 use chrono::{DateTime, Local};
+use super::{Builtin, BuiltinContext, BuiltinFuture};
+use crate::shell::core::command_executor::EXIT_SUCCESS;
+use tokio::io::AsyncWriteExt;
 
-pub async fn ls_builtin(current_dir: &PathBuf, args: &[&str]) -> String {
+pub async fn ls_builtin(current_dir: &Path, args: &[&str]) -> Result<String> {
     let mut show_hidden = false;
     let mut long_format = false;
     let mut target_path_str = ".";
@@ -13,7 +17,7 @@ pub async fn ls_builtin(current_dir: &PathBuf, args: &[&str]) -> String {
                 match char_flag {
                     'a' => show_hidden = true,
                     'l' => long_format = true,
-                    _ => return format!("ls: invalid option -- '{}'\n", char_flag),
+                    _ => return Err(anyhow!("ls: invalid option -- '{}'", char_flag)),
                 }
             }
         } else {
@@ -24,14 +28,14 @@ pub async fn ls_builtin(current_dir: &PathBuf, args: &[&str]) -> String {
     let path = current_dir.join(target_path_str);
 
     if !path.exists() {
-        return format!("ls: cannot access '{}': No such file or directory\n", path.display());
+        return Err(anyhow!("ls: cannot access '{}': No such file or directory", path.display()));
     }
 
     if !path.is_dir() {
         if long_format {
-            return format_long_entry(&path).await;
+            return Ok(format_long_entry(&path).await);
         } else {
-            return format!("{}\n", path.display());
+            return Ok(format!("{}\n", path.display()));
         }
     }
 
@@ -39,7 +43,7 @@ pub async fn ls_builtin(current_dir: &PathBuf, args: &[&str]) -> String {
     let mut entries = match fs::read_dir(path.clone()).await {
         Ok(entries) => entries,
         Err(e) => {
-            return format!("ls: error reading directory '{}': {}\n", path.display(), e);
+            return Err(anyhow!("ls: error reading directory '{}': {}", path.display(), e));
         }
     };
 
@@ -61,10 +65,10 @@ pub async fn ls_builtin(current_dir: &PathBuf, args: &[&str]) -> String {
             output.push_str(&format!("{}\n", entry_path.file_name().unwrap().to_string_lossy()));
         }
     }
-    output
+    Ok(output)
 }
 
-async fn format_long_entry(path: &PathBuf) -> String {
+async fn format_long_entry(path: &Path) -> String {
     let metadata = match fs::metadata(path).await {
         Ok(meta) => meta,
         Err(_) => return String::new(), // Should not happen if path exists
@@ -84,6 +88,31 @@ async fn format_long_entry(path: &PathBuf) -> String {
             file_name)
 }
 
+/// The `ls` builtin.
+pub struct Ls;
+
+impl Builtin for Ls {
+    fn name(&self) -> &'static str {
+        "ls"
+    }
+
+    fn summary(&self) -> &'static str {
+        "List directory contents."
+    }
+
+    fn usage(&self) -> &'static str {
+        "ls [-al] [path]"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut BuiltinContext<'_>, args: &'a [&'a str]) -> BuiltinFuture<'a> {
+        Box::pin(async move {
+            let output = ls_builtin(&ctx.shell.current_dir, args).await?;
+            ctx.stdout.write_all(output.as_bytes()).await?;
+            Ok(EXIT_SUCCESS)
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -98,7 +127,7 @@ mod tests {
         fs::write(temp_dir.join("file1.txt"), "").await.unwrap();
         fs::create_dir(temp_dir.join("dir1")).await.unwrap();
 
-        let output = ls_builtin(&temp_dir, &[]).await;
+        let output = ls_builtin(&temp_dir, &[]).await.unwrap();
         assert!(output.contains("file1.txt"));
         assert!(output.contains("dir1"));
         assert!(!output.contains(".hidden"));
@@ -113,7 +142,7 @@ mod tests {
         fs::write(temp_dir.join("file1.txt"), "").await.unwrap();
         fs::write(temp_dir.join(".hidden"), "").await.unwrap();
 
-        let output = ls_builtin(&temp_dir, &["-a"]).await;
+        let output = ls_builtin(&temp_dir, &["-a"]).await.unwrap();
         assert!(output.contains("file1.txt"));
         assert!(output.contains(".hidden"));
 
@@ -126,7 +155,7 @@ mod tests {
         fs::create_dir_all(&temp_dir).await.unwrap();
         fs::write(temp_dir.join("file1.txt"), "test content").await.unwrap();
 
-        let output = ls_builtin(&temp_dir, &["-l"]).await;
+        let output = ls_builtin(&temp_dir, &["-l"]).await.unwrap();
         assert!(output.contains("file1.txt"));
         assert!(output.contains("rwx------")); // Simplified permissions
         assert!(output.contains("12")); // Size of "test content"
@@ -142,7 +171,7 @@ mod tests {
         fs::write(temp_dir.join("file1.txt"), "").await.unwrap();
         fs::write(temp_dir.join(".hidden"), "hidden content").await.unwrap();
 
-        let output = ls_builtin(&temp_dir, &["-al"]).await;
+        let output = ls_builtin(&temp_dir, &["-al"]).await.unwrap();
         assert!(output.contains("file1.txt"));
         assert!(output.contains(".hidden"));
         assert!(output.contains("rwx------"));
@@ -156,7 +185,7 @@ mod tests {
         let temp_dir = env::temp_dir().join("test_ls_invalid_flag");
         fs::create_dir_all(&temp_dir).await.unwrap();
 
-        let output = ls_builtin(&temp_dir, &["-x"]).await;
+        let output = ls_builtin(&temp_dir, &["-x"]).await.unwrap_err().to_string();
         assert!(output.contains("ls: invalid option -- 'x'"));
 
         fs::remove_dir_all(&temp_dir).await.unwrap();
@@ -170,7 +199,7 @@ mod tests {
         fs::create_dir(&sub_dir).await.unwrap();
         fs::write(sub_dir.join("sub_file.txt"), "").await.unwrap();
 
-        let output = ls_builtin(&temp_dir, &["sub_dir"]).await;
+        let output = ls_builtin(&temp_dir, &["sub_dir"]).await.unwrap();
         assert!(output.contains("sub_file.txt"));
         assert!(!output.contains("sub_dir")); // Should not list itself
 
@@ -184,7 +213,7 @@ mod tests {
         let file_path = temp_dir.join("single_file.txt");
         fs::write(&file_path, "file content").await.unwrap();
 
-        let output = ls_builtin(&temp_dir, &["-l", "single_file.txt"]).await;
+        let output = ls_builtin(&temp_dir, &["-l", "single_file.txt"]).await.unwrap();
         assert!(output.contains("single_file.txt"));
         assert!(output.contains("file content".len().to_string().as_str()));
         assert!(output.starts_with("-")); // Should indicate it's a file
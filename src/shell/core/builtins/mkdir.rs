@@ -1,19 +1,53 @@
-use std::path::PathBuf;
+use anyhow::{anyhow, Result};
+use std::path::Path;
 use tokio::fs;
+use super::{Builtin, BuiltinContext, BuiltinFuture};
+use crate::shell::core::command_executor::EXIT_SUCCESS;
+use tokio::io::AsyncWriteExt;
 
-pub async fn mkdir_builtin(current_dir: &PathBuf, args: &[&str]) -> String {
+pub async fn mkdir_builtin(current_dir: &Path, args: &[&str]) -> Result<String> {
     if args.is_empty() {
-        return "mkdir: missing operand\n".to_string();
+        return Err(anyhow!("mkdir: missing operand"));
     }
 
-    let mut output = String::new();
+    let mut errors = Vec::new();
     for &path_str in args {
         let path = current_dir.join(path_str);
         if let Err(e) = fs::create_dir(&path).await {
-            output.push_str(&format!("mkdir: cannot create directory '{}': {}\n", path.display(), e));
+            errors.push(format!("mkdir: cannot create directory '{}': {}", path.display(), e));
         }
     }
-    output
+
+    if errors.is_empty() {
+        Ok(String::new())
+    } else {
+        Err(anyhow!(errors.join("\n")))
+    }
+}
+
+/// The `mkdir` builtin.
+pub struct Mkdir;
+
+impl Builtin for Mkdir {
+    fn name(&self) -> &'static str {
+        "mkdir"
+    }
+
+    fn summary(&self) -> &'static str {
+        "Create directories."
+    }
+
+    fn usage(&self) -> &'static str {
+        "mkdir directory ..."
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut BuiltinContext<'_>, args: &'a [&'a str]) -> BuiltinFuture<'a> {
+        Box::pin(async move {
+            let output = mkdir_builtin(&ctx.shell.current_dir, args).await?;
+            ctx.stdout.write_all(output.as_bytes()).await?;
+            Ok(EXIT_SUCCESS)
+        })
+    }
 }
 
 #[cfg(test)]
@@ -31,7 +65,7 @@ mod tests {
         let new_dir_name = "new_test_dir";
         let args = [new_dir_name];
         
-        let output = mkdir_builtin(&temp_dir, &args).await;
+        let output = mkdir_builtin(&temp_dir, &args).await.unwrap();
         
         assert!(output.is_empty(), "Expected no output for successful mkdir, but got: {}", output);
 
@@ -50,7 +84,7 @@ mod tests {
         fs::create_dir_all(&existing_dir).await.unwrap(); // Ensure the directory exists
 
         let args = ["existing_dir"];
-        let output = mkdir_builtin(&temp_dir, &args).await;
+        let output = mkdir_builtin(&temp_dir, &args).await.unwrap_err().to_string();
 
         assert!(output.contains("파일이 이미 있으므로 만들 수 없습니다."), "Expected 'File exists' error, but got: {}", output);
 
@@ -1,3 +1,19 @@
+//! Commands implemented by the shell itself.
+//!
+//! Every builtin implements the `Builtin` trait and is registered by name in
+//! the `BuiltinRegistry`. The executor, `help`, `type` and autocompletion all
+//! look builtins up through `registry()`.
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::LazyLock;
+
+use crate::shell::core::cancellation::CancellationToken;
+use crate::shell::core::output::OutputSink;
+use crate::shell::core::stage_io::{Reader, Writer};
+use crate::shell::core::ShellCore;
+
 pub mod cd;
 pub mod ls;
 pub mod ping;
@@ -12,3 +28,149 @@ pub mod alias;
 pub mod cat;
 pub mod export;
 pub mod unset;
+pub mod shopt;
+pub mod help;
+pub mod type_cmd;
+pub mod loop_control;
+pub mod boolean;
+pub mod function;
+pub mod source;
+pub mod job_control;
+pub mod history;
+
+/// The future returned by `Builtin::run`.
+///
+/// It resolves to the exit status of the builtin. An error is reported on
+/// standard error and gives a status of `1`.
+pub type BuiltinFuture<'a> = Pin<Box<dyn Future<Output = anyhow::Result<i32>> + Send + 'a>>;
+
+/// Everything a builtin can read or change while it runs.
+pub struct BuiltinContext<'a> {
+    /// The shell the builtin runs in, including its current directory,
+    /// environment variables and aliases.
+    pub shell: &'a mut ShellCore,
+    pub stdin: Reader,
+    pub stdout: Writer,
+    pub stderr: Writer,
+    /// The sink of the command line, for builtins such as `source` that run
+    /// commands of their own.
+    pub output: OutputSink,
+    /// Cancelled when the user interrupts the command line. A running
+    /// builtin is dropped at its next await point; builtins that do long
+    /// blocking work should check it themselves.
+    pub cancellation: CancellationToken,
+}
+
+/// A command implemented by the shell itself.
+pub trait Builtin: Send + Sync {
+    /// The name the builtin is invoked by.
+    fn name(&self) -> &'static str;
+
+    /// A one-line description shown by `help`.
+    fn summary(&self) -> &'static str;
+
+    /// The synopsis shown by `help <name>`, e.g. `ls [-al] [path]`.
+    fn usage(&self) -> &'static str;
+
+    /// Runs the builtin with the given arguments, not including its name.
+    fn run<'a>(&'a self, ctx: &'a mut BuiltinContext<'_>, args: &'a [&'a str]) -> BuiltinFuture<'a>;
+}
+
+/// The builtins known to the shell, keyed by name.
+pub struct BuiltinRegistry {
+    builtins: BTreeMap<&'static str, Box<dyn Builtin>>,
+}
+
+impl BuiltinRegistry {
+    /// Creates a registry containing every builtin of the shell.
+    pub fn new() -> Self {
+        let mut registry = Self { builtins: BTreeMap::new() };
+        registry.register(Box::new(source::Dot));
+        registry.register(Box::new(alias::Alias));
+        registry.register(Box::new(alias::Unalias));
+        registry.register(Box::new(boolean::Colon));
+        registry.register(Box::new(job_control::Bg));
+        registry.register(Box::new(loop_control::Break));
+        registry.register(Box::new(cat::Cat));
+        registry.register(Box::new(cd::Cd));
+        registry.register(Box::new(loop_control::Continue));
+        registry.register(Box::new(cp::Cp));
+        registry.register(Box::new(echo::Echo));
+        registry.register(Box::new(export::Export));
+        registry.register(Box::new(boolean::False));
+        registry.register(Box::new(job_control::Fg));
+        registry.register(Box::new(grep::Grep));
+        registry.register(Box::new(help::Help));
+        registry.register(Box::new(history::History));
+        registry.register(Box::new(job_control::Jobs));
+        registry.register(Box::new(job_control::Kill));
+        registry.register(Box::new(function::Local));
+        registry.register(Box::new(ls::Ls));
+        registry.register(Box::new(mkdir::Mkdir));
+        registry.register(Box::new(mv::Mv));
+        registry.register(Box::new(open::Open));
+        registry.register(Box::new(ping::Ping));
+        registry.register(Box::new(function::Return));
+        registry.register(Box::new(rm::Rm));
+        registry.register(Box::new(shopt::Shopt));
+        registry.register(Box::new(source::Source));
+        registry.register(Box::new(boolean::True));
+        registry.register(Box::new(type_cmd::Type));
+        registry.register(Box::new(unset::Unset));
+        registry.register(Box::new(job_control::Wait));
+        registry
+    }
+
+    /// Adds a builtin, replacing any builtin with the same name.
+    pub fn register(&mut self, builtin: Box<dyn Builtin>) {
+        self.builtins.insert(builtin.name(), builtin);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Builtin> {
+        self.builtins.get(name).map(AsRef::as_ref)
+    }
+
+    /// Iterates over the builtins in alphabetical order.
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Builtin> {
+        self.builtins.values().map(AsRef::as_ref)
+    }
+}
+
+impl Default for BuiltinRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+static REGISTRY: LazyLock<BuiltinRegistry> = LazyLock::new(BuiltinRegistry::new);
+
+/// Returns the registry of the shell's builtins.
+pub fn registry() -> &'static BuiltinRegistry {
+    &REGISTRY
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_is_keyed_by_name() {
+        let registry = registry();
+        for builtin in registry.iter() {
+            assert_eq!(registry.get(builtin.name()).unwrap().name(), builtin.name());
+            assert!(!builtin.summary().is_empty());
+            assert!(builtin.usage().starts_with(builtin.name()));
+        }
+        assert!(registry.get("cd").is_some());
+        assert!(registry.get("unalias").is_some());
+        assert!(registry.get("nonexistent").is_none());
+    }
+
+    #[test]
+    fn test_registry_iterates_alphabetically() {
+        let names: Vec<_> = registry().iter().map(|b| b.name()).collect();
+        let mut sorted = names.clone();
+        sorted.sort_unstable();
+        assert_eq!(names, sorted);
+    }
+}
@@ -1,9 +1,13 @@
-use std::path::PathBuf;
+use anyhow::{anyhow, Result};
+use std::path::Path;
 use tokio::fs;
+use super::{Builtin, BuiltinContext, BuiltinFuture};
+use crate::shell::core::command_executor::EXIT_SUCCESS;
+use tokio::io::AsyncWriteExt;
 
-pub async fn mv_builtin(current_dir: &PathBuf, args: &[&str]) -> String {
+pub async fn mv_builtin(current_dir: &Path, args: &[&str]) -> Result<String> {
     if args.len() < 2 {
-        return "mv: missing file operand\nTry 'mv --help' for more information.\n".to_string();
+        return Err(anyhow!("mv: missing file operand\nTry 'mv --help' for more information."));
     }
 
     let source_path_str = args[0];
@@ -13,12 +17,37 @@ pub async fn mv_builtin(current_dir: &PathBuf, args: &[&str]) -> String {
     let destination_path = current_dir.join(destination_path_str);
 
     if !source_path.exists() {
-        return format!("mv: cannot stat '{}': No such file or directory\n", source_path.display());
+        return Err(anyhow!("mv: cannot stat '{}': No such file or directory", source_path.display()));
     }
 
     match fs::rename(&source_path, &destination_path).await {
-        Ok(_) => String::new(),
-        Err(e) => format!("mv: cannot move '{}' to '{}': {}\n", source_path.display(), destination_path.display(), e),
+        Ok(_) => Ok(String::new()),
+        Err(e) => Err(anyhow!("mv: cannot move '{}' to '{}': {}", source_path.display(), destination_path.display(), e)),
+    }
+}
+
+/// The `mv` builtin.
+pub struct Mv;
+
+impl Builtin for Mv {
+    fn name(&self) -> &'static str {
+        "mv"
+    }
+
+    fn summary(&self) -> &'static str {
+        "Move or rename a file."
+    }
+
+    fn usage(&self) -> &'static str {
+        "mv source destination"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut BuiltinContext<'_>, args: &'a [&'a str]) -> BuiltinFuture<'a> {
+        Box::pin(async move {
+            let output = mv_builtin(&ctx.shell.current_dir, args).await?;
+            ctx.stdout.write_all(output.as_bytes()).await?;
+            Ok(EXIT_SUCCESS)
+        })
     }
 }
 
@@ -38,7 +67,7 @@ mod tests {
         fs::write(&src_file, "hello world").await.unwrap();
 
         let args = ["source.txt", "destination.txt"];
-        let output = mv_builtin(&temp_dir, &args).await;
+        let output = mv_builtin(&temp_dir, &args).await.unwrap();
 
         assert!(output.is_empty(), "Expected no output for successful mv, but got: {}", output);
         assert!(!src_file.exists(), "Source file should not exist");
@@ -54,7 +83,7 @@ mod tests {
         fs::create_dir_all(&temp_dir).await.unwrap();
 
         let args = ["nonexistent.txt", "destination.txt"];
-        let output = mv_builtin(&temp_dir, &args).await;
+        let output = mv_builtin(&temp_dir, &args).await.unwrap_err().to_string();
 
         assert!(output.contains("No such file or directory"));
 
@@ -67,7 +96,7 @@ mod tests {
         fs::create_dir_all(&temp_dir).await.unwrap();
 
         let args: [&str; 0] = [];
-        let output = mv_builtin(&temp_dir, &args).await;
+        let output = mv_builtin(&temp_dir, &args).await.unwrap_err().to_string();
 
         assert!(output.contains("missing file operand"));
 
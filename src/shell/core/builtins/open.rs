@@ -1,21 +1,50 @@
 //! Implements the built-in `open` command for opening files and directories.
 
-use std::path::PathBuf;
+use anyhow::{anyhow, Result};
+use std::path::Path;
+use super::{Builtin, BuiltinContext, BuiltinFuture};
+use crate::shell::core::command_executor::EXIT_SUCCESS;
+use tokio::io::AsyncWriteExt;
 
-pub async fn open_builtin(current_dir: &PathBuf, args: &[&str]) -> String {
+pub async fn open_builtin(current_dir: &Path, args: &[&str]) -> Result<String> {
     if args.is_empty() {
-        return "Usage: open <file_or_directory>\n".to_string();
+        return Err(anyhow!("Usage: open <file_or_directory>"));
     }
 
     let target = args[0];
     let path = current_dir.join(target);
 
     if !path.exists() {
-        return format!("open: '{}': No such file or directory\n", target);
+        return Err(anyhow!("open: '{}': No such file or directory", target));
     }
 
     match open::that(&path) {
-        Ok(_) => String::new(),
-        Err(e) => format!("open: Failed to open '{}': {}\n", target, e),
+        Ok(_) => Ok(String::new()),
+        Err(e) => Err(anyhow!("open: Failed to open '{}': {}", target, e)),
     }
-}
\ No newline at end of file
+}
+
+/// The `open` builtin.
+pub struct Open;
+
+impl Builtin for Open {
+    fn name(&self) -> &'static str {
+        "open"
+    }
+
+    fn summary(&self) -> &'static str {
+        "Open a file or directory with its default application."
+    }
+
+    fn usage(&self) -> &'static str {
+        "open path"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut BuiltinContext<'_>, args: &'a [&'a str]) -> BuiltinFuture<'a> {
+        Box::pin(async move {
+            let output = open_builtin(&ctx.shell.current_dir, args).await?;
+            ctx.stdout.write_all(output.as_bytes()).await?;
+            Ok(EXIT_SUCCESS)
+        })
+    }
+}
@@ -1,5 +1,6 @@
 
 
+use anyhow::{anyhow, Result};
 use std::net::ToSocketAddrs;
 use std::time::{Duration, Instant};
 use pnet::packet::icmp::{echo_request, echo_reply, IcmpTypes, IcmpPacket};
@@ -7,12 +8,15 @@ use pnet::packet::icmp::checksum;
 use pnet::packet::ip::IpNextHeaderProtocols;
 use pnet::transport::{transport_channel, TransportChannelType, TransportProtocol, icmp_packet_iter};
 use pnet::packet::Packet;
+use super::{Builtin, BuiltinContext, BuiltinFuture};
+use crate::shell::core::command_executor::EXIT_SUCCESS;
+use tokio::io::AsyncWriteExt;
 
 
 
-pub async fn ping_builtin(args: &[&str]) -> String {
+pub async fn ping_builtin(args: &[&str]) -> Result<String> {
     if args.is_empty() {
-        return "Usage: ping <host>\n".to_string();
+        return Err(anyhow!("Usage: ping <host>"));
     }
 
     let host = args[0];
@@ -21,23 +25,23 @@ pub async fn ping_builtin(args: &[&str]) -> String {
     let ip_addr = match (cleaned_host, 80).to_socket_addrs() {
         Ok(mut addrs) => match addrs.next() {
             Some(addr) => addr.ip(),
-            None => return format!("ping: unknown host {}\n", host),
+            None => return Err(anyhow!("ping: unknown host {}", host)),
         },
-        Err(e) => return format!("ping: failed to resolve host {}: {}\n", host, e),
+        Err(e) => return Err(anyhow!("ping: failed to resolve host {}: {}", host, e)),
     };
 
     // Set up transport channel for ICMP
     let protocol = TransportProtocol::Ipv4(IpNextHeaderProtocols::Icmp);
     let (mut tx, mut rx) = match transport_channel(4096, TransportChannelType::Layer4(protocol)) {
         Ok((tx, rx)) => (tx, rx),
-        Err(e) => return format!("ping: failed to create transport channel: {}\n", e),
+        Err(e) => return Err(anyhow!("ping: failed to create transport channel: {}", e)),
     };
 
     // Create and send ICMP echo request packet
     let mut echo_packet = echo_request::MutableEchoRequestPacket::owned(vec![0; 16]).unwrap();
     echo_packet.set_identifier(1);
     echo_packet.set_sequence_number(1);
-    echo_packet.set_icmp_type(IcmpTypes::EchoRequest.into());
+    echo_packet.set_icmp_type(IcmpTypes::EchoRequest);
     
     let icmp_packet = IcmpPacket::new(echo_packet.packet()).unwrap();
     let checksum = checksum(&icmp_packet);
@@ -47,7 +51,7 @@ pub async fn ping_builtin(args: &[&str]) -> String {
 
     match tx.send_to(echo_packet.to_immutable(), ip_addr) {
         Ok(_) => {},
-        Err(e) => return format!("ping: failed to send packet: {}\n", e),
+        Err(e) => return Err(anyhow!("ping: failed to send packet: {}", e)),
     }
 
     // Wait for ICMP echo reply
@@ -72,11 +76,36 @@ pub async fn ping_builtin(args: &[&str]) -> String {
         }
     })).await {
         Ok(Ok(Some(addr))) => addr,
-        Ok(Ok(None)) => return "ping: Request timed out (no packet received).\n".to_string(),
-        Ok(Err(e)) => return format!("ping: error in blocking task: {}\n", e),
-        Err(_) => return "ping: Request timed out (blocking task).\n".to_string(),
+        Ok(Ok(None)) => return Err(anyhow!("ping: Request timed out (no packet received).")),
+        Ok(Err(e)) => return Err(anyhow!("ping: error in blocking task: {}", e)),
+        Err(_) => return Err(anyhow!("ping: Request timed out (blocking task).")),
     };
 
     let duration = start_time.elapsed();
-    format!("Reply from {}: time={:?}\n", received_addr, duration)
+    Ok(format!("Reply from {}: time={:?}\n", received_addr, duration))
+}
+
+/// The `ping` builtin.
+pub struct Ping;
+
+impl Builtin for Ping {
+    fn name(&self) -> &'static str {
+        "ping"
+    }
+
+    fn summary(&self) -> &'static str {
+        "Send an ICMP echo request to a host."
+    }
+
+    fn usage(&self) -> &'static str {
+        "ping host"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut BuiltinContext<'_>, args: &'a [&'a str]) -> BuiltinFuture<'a> {
+        Box::pin(async move {
+            let output = ping_builtin(args).await?;
+            ctx.stdout.write_all(output.as_bytes()).await?;
+            Ok(EXIT_SUCCESS)
+        })
+    }
 }
@@ -1,30 +1,63 @@
-use std::path::PathBuf;
+use anyhow::{anyhow, Result};
+use std::path::Path;
 use tokio::fs;
+use super::{Builtin, BuiltinContext, BuiltinFuture};
+use crate::shell::core::command_executor::EXIT_SUCCESS;
+use tokio::io::AsyncWriteExt;
 
-pub async fn rm_builtin(current_dir: &PathBuf, args: &[&str]) -> String {
+pub async fn rm_builtin(current_dir: &Path, args: &[&str]) -> Result<String> {
     if args.is_empty() {
-        return "rm: missing operand\n".to_string();
+        return Err(anyhow!("rm: missing operand"));
     }
 
-    let mut output = String::new();
+    // Keep going after a failure so every operand is attempted.
+    let mut errors = Vec::new();
     for &path_str in args {
         let path = current_dir.join(path_str);
         if !path.exists() {
-            output.push_str(&format!("rm: cannot remove '{}': No such file or directory\n", path.display()));
+            errors.push(format!("rm: cannot remove '{}': No such file or directory", path.display()));
             continue;
         }
 
         if path.is_dir() {
             if let Err(e) = fs::remove_dir_all(&path).await {
-                output.push_str(&format!("rm: cannot remove directory '{}': {}\n", path.display(), e));
-            }
-        } else {
-            if let Err(e) = fs::remove_file(&path).await {
-                output.push_str(&format!("rm: cannot remove file '{}': {}\n", path.display(), e));
+                errors.push(format!("rm: cannot remove directory '{}': {}", path.display(), e));
             }
+        } else if let Err(e) = fs::remove_file(&path).await {
+            errors.push(format!("rm: cannot remove file '{}': {}", path.display(), e));
         }
     }
-    output
+
+    if errors.is_empty() {
+        Ok(String::new())
+    } else {
+        Err(anyhow!(errors.join("\n")))
+    }
+}
+
+/// The `rm` builtin.
+pub struct Rm;
+
+impl Builtin for Rm {
+    fn name(&self) -> &'static str {
+        "rm"
+    }
+
+    fn summary(&self) -> &'static str {
+        "Remove files and directories."
+    }
+
+    fn usage(&self) -> &'static str {
+        "rm path ..."
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut BuiltinContext<'_>, args: &'a [&'a str]) -> BuiltinFuture<'a> {
+        Box::pin(async move {
+            let output = rm_builtin(&ctx.shell.current_dir, args).await?;
+            ctx.stdout.write_all(output.as_bytes()).await?;
+            Ok(EXIT_SUCCESS)
+        })
+    }
 }
 
 #[cfg(test)]
@@ -42,7 +75,7 @@ mod tests {
         fs::write(&file_path, "test content").await.unwrap();
 
         let args = ["test_file.txt"];
-        let output = rm_builtin(&temp_dir, &args).await;
+        let output = rm_builtin(&temp_dir, &args).await.unwrap();
 
         assert!(output.is_empty(), "Expected no output for successful rm, but got: {}", output);
         assert!(!file_path.exists(), "File should have been removed");
@@ -58,7 +91,7 @@ mod tests {
         fs::create_dir(&dir_path).await.unwrap();
 
         let args = ["test_dir"];
-        let output = rm_builtin(&temp_dir, &args).await;
+        let output = rm_builtin(&temp_dir, &args).await.unwrap();
 
         assert!(output.is_empty(), "Expected no output for successful rm, but got: {}", output);
         assert!(!dir_path.exists(), "Directory should have been removed");
@@ -72,7 +105,7 @@ mod tests {
         fs::create_dir_all(&temp_dir).await.unwrap();
 
         let args = ["nonexistent_file.txt"];
-        let output = rm_builtin(&temp_dir, &args).await;
+        let output = rm_builtin(&temp_dir, &args).await.unwrap_err().to_string();
 
         assert!(output.contains("No such file or directory"), "Expected 'No such file or directory' error, but got: {}", output);
 
@@ -0,0 +1,115 @@
+//! Built-in command to set and display shell options.
+
+use anyhow::{anyhow, Result};
+use super::{Builtin, BuiltinContext, BuiltinFuture};
+use crate::shell::core::command_executor::{EXIT_FAILURE, EXIT_SUCCESS};
+use crate::shell::core::options::ShellOptions;
+use tokio::io::AsyncWriteExt;
+
+/// Handles the `shopt` command.
+///
+/// `shopt -s name ...` turns options on, `shopt -u name ...` turns them off,
+/// and `shopt [name ...]` shows whether the named options, or all of them,
+/// are on.
+///
+/// # Arguments
+///
+/// * `options` - The shell's options.
+/// * `args` - The arguments to the command.
+///
+/// # Returns
+///
+/// The output of the command and whether all shown options are on, or an
+/// error for an unknown option.
+pub fn shopt_builtin(options: &mut ShellOptions, args: &[&str]) -> Result<(String, bool)> {
+    let (value, names) = match args.split_first() {
+        Some((&"-s", names)) => (Some(true), names),
+        Some((&"-u", names)) => (Some(false), names),
+        Some((flag, _)) if flag.starts_with('-') => return Err(anyhow!("shopt: {}: invalid option", flag)),
+        _ => (None, args),
+    };
+    if let Some(name) = names.iter().find(|name| options.get(name).is_none()) {
+        return Err(anyhow!("shopt: {}: invalid shell option name", name));
+    }
+
+    match value {
+        Some(value) if !names.is_empty() => {
+            for name in names {
+                options.set(name, value);
+            }
+            Ok((String::new(), true))
+        }
+        _ => {
+            let shown: Vec<&str> = if names.is_empty() { ShellOptions::NAMES.to_vec() } else { names.to_vec() };
+            let mut output = String::new();
+            let mut all_on = true;
+            for name in shown {
+                let on = options.get(name).unwrap_or_default();
+                // `-s` or `-u` without names lists the options in that state.
+                if value.is_some_and(|value| value != on) {
+                    continue;
+                }
+                all_on &= on;
+                output.push_str(&format!("{:<15} {}\n", name, if on { "on" } else { "off" }));
+            }
+            Ok((output, all_on))
+        }
+    }
+}
+
+/// The `shopt` builtin.
+pub struct Shopt;
+
+impl Builtin for Shopt {
+    fn name(&self) -> &'static str {
+        "shopt"
+    }
+
+    fn summary(&self) -> &'static str {
+        "Set and display shell options such as nullglob, failglob, dotglob and projectrc."
+    }
+
+    fn usage(&self) -> &'static str {
+        "shopt [-s|-u] [name ...]"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut BuiltinContext<'_>, args: &'a [&'a str]) -> BuiltinFuture<'a> {
+        Box::pin(async move {
+            let (output, all_on) = shopt_builtin(&mut ctx.shell.options, args)?;
+            ctx.stdout.write_all(output.as_bytes()).await?;
+            Ok(if all_on { EXIT_SUCCESS } else { EXIT_FAILURE })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_and_unset_options() {
+        let mut options = ShellOptions::default();
+        shopt_builtin(&mut options, &["-s", "nullglob", "dotglob"]).unwrap();
+        assert!(options.nullglob && options.dotglob && !options.failglob);
+        shopt_builtin(&mut options, &["-u", "dotglob"]).unwrap();
+        assert!(!options.dotglob);
+    }
+
+    #[test]
+    fn test_show_options() {
+        let mut options = ShellOptions { nullglob: true, ..Default::default() };
+        let (output, all_on) = shopt_builtin(&mut options, &[]).unwrap();
+        assert_eq!(output, "dotglob         off\nfailglob        off\nnullglob        on\nprojectrc       off\n");
+        assert!(!all_on);
+        assert_eq!(shopt_builtin(&mut options, &["nullglob"]).unwrap(), ("nullglob        on\n".to_string(), true));
+        assert_eq!(shopt_builtin(&mut options, &["-s"]).unwrap().0, "nullglob        on\n");
+    }
+
+    #[test]
+    fn test_unknown_option() {
+        let mut options = ShellOptions::default();
+        let err = shopt_builtin(&mut options, &["-s", "globstar"]).unwrap_err();
+        assert_eq!(err.to_string(), "shopt: globstar: invalid shell option name");
+        assert!(shopt_builtin(&mut options, &["-x"]).is_err());
+    }
+}
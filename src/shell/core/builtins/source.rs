@@ -0,0 +1,159 @@
+//! Built-in `source` command, also available as `.`, which runs the
+//! commands of a file in the current shell.
+
+use anyhow::{anyhow, Result};
+use super::{Builtin, BuiltinContext, BuiltinFuture};
+use crate::shell::core::command_executor::{self, ControlFlow, MAX_FUNCTION_DEPTH};
+use crate::shell::core::cancellation::CancellationToken;
+use crate::shell::core::output::OutputSink;
+use crate::shell::core::stage_io::StageIo;
+use crate::shell::core::ShellCore;
+
+/// Handles the `source` and `.` commands.
+///
+/// The file is read relative to the current directory and its commands run
+/// against the shell itself, so the variables, functions, aliases and
+/// directory changes it makes remain afterwards. Arguments after the file
+/// name become the positional parameters while it runs. `return` leaves
+/// the file early.
+///
+/// # Arguments
+///
+/// * `ctx` - The context of the builtin, whose descriptors the commands of
+///   the file share.
+/// * `name` - `source` or `.`, for error messages.
+/// * `args` - The file name followed by its arguments.
+///
+/// # Returns
+///
+/// The exit status of the last command of the file, or an error if the
+/// file cannot be read.
+pub async fn source_builtin(ctx: &mut BuiltinContext<'_>, name: &str, args: &[&str]) -> Result<i32> {
+    let Some((file, script_args)) = args.split_first() else {
+        return Err(anyhow!("{}: filename argument required", name));
+    };
+    if ctx.shell.source_depth >= MAX_FUNCTION_DEPTH {
+        return Err(anyhow!("{}: {}: maximum source nesting level exceeded ({})", name, file, MAX_FUNCTION_DEPTH));
+    }
+    let path = ctx.shell.current_dir.join(file);
+    let script = tokio::fs::read_to_string(&path).await.map_err(|e| anyhow!("{}: {}: {}", name, file, e))?;
+
+    let io = StageIo {
+        stdin: ctx.stdin.to_source().await?,
+        stdout: ctx.stdout.to_target().await?,
+        stderr: ctx.stderr.to_target().await?,
+    };
+    let script_args = script_args.iter().map(|arg| arg.to_string()).collect();
+    let status = source_script(ctx.shell, script, script_args, io, ctx.output.clone(), ctx.cancellation.clone()).await;
+    Ok(status)
+}
+
+/// Runs the commands of a file that has been read, as `source` does.
+///
+/// # Arguments
+///
+/// * `shell` - The shell the commands run against.
+/// * `script` - The contents of the file.
+/// * `args` - The positional parameters while the commands run, or empty
+///   to keep those of the shell.
+/// * `io` - The descriptors of the commands.
+/// * `output` - The sink their output is sent to.
+/// * `cancellation` - Interrupts the commands when cancelled.
+///
+/// # Returns
+///
+/// The exit status of the last command.
+pub async fn source_script(
+    shell: &mut ShellCore,
+    script: String,
+    args: Vec<String>,
+    io: StageIo,
+    output: OutputSink,
+    cancellation: CancellationToken,
+) -> i32 {
+    command_executor::run_on_own_task(shell, move |mut shell| async move {
+        let positional_params = (!args.is_empty()).then(|| std::mem::replace(&mut shell.positional_params, args));
+        // Loops around `source` cannot be left from inside the file.
+        let loop_depth = std::mem::take(&mut shell.loop_depth);
+        shell.source_depth += 1;
+
+        let status = command_executor::execute_script(&mut shell, &script, &io, &output, &cancellation).await;
+
+        shell.source_depth -= 1;
+        shell.loop_depth = loop_depth;
+        if shell.control_flow == Some(ControlFlow::Return) {
+            shell.control_flow = None;
+        }
+        if let Some(positional_params) = positional_params {
+            shell.positional_params = positional_params;
+        }
+        (shell, status)
+    })
+    .await
+}
+
+/// The `source` builtin.
+pub struct Source;
+
+impl Builtin for Source {
+    fn name(&self) -> &'static str {
+        "source"
+    }
+
+    fn summary(&self) -> &'static str {
+        "Run the commands of a file in the current shell."
+    }
+
+    fn usage(&self) -> &'static str {
+        "source file [arguments ...]"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut BuiltinContext<'_>, args: &'a [&'a str]) -> BuiltinFuture<'a> {
+        Box::pin(source_builtin(ctx, "source", args))
+    }
+}
+
+/// The `.` builtin, another name for `source`.
+pub struct Dot;
+
+impl Builtin for Dot {
+    fn name(&self) -> &'static str {
+        "."
+    }
+
+    fn summary(&self) -> &'static str {
+        "Run the commands of a file in the current shell, like `source`."
+    }
+
+    fn usage(&self) -> &'static str {
+        ". file [arguments ...]"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut BuiltinContext<'_>, args: &'a [&'a str]) -> BuiltinFuture<'a> {
+        Box::pin(source_builtin(ctx, ".", args))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shell::core::command_executor::execute_shell_command;
+    use crate::shell::core::ShellCore;
+
+    #[tokio::test]
+    async fn test_source_runs_in_current_shell() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("lib.sh"), "greet() { echo \"hi $1\"; }\nLOADED=\"$#:$1\"\nreturn 3\necho never\n").unwrap();
+        let mut shell = ShellCore::new();
+        shell.current_dir = dir.path().to_path_buf();
+        shell.positional_params = vec!["outer".to_string()];
+
+        let output = execute_shell_command(&mut shell, "source lib.sh a b; echo \"$? $LOADED $1\"; greet you").await;
+        assert_eq!(output, "3 2:a outer\nhi you\n");
+        assert_eq!(execute_shell_command(&mut shell, ". lib.sh; echo \"$LOADED\"").await, "1:outer\n");
+        assert_eq!(shell.source_depth, 0);
+
+        let output = execute_shell_command(&mut shell, "source missing.sh").await;
+        assert!(output.contains("source: missing.sh: "), "{}", output);
+        assert_eq!(shell.last_exit_status, 1);
+    }
+}
@@ -0,0 +1,120 @@
+//! Built-in `type` command, which tells how a name would be run.
+
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use super::{registry, Builtin, BuiltinContext, BuiltinFuture, BuiltinRegistry};
+use crate::shell::core::command_executor::EXIT_SUCCESS;
+use crate::shell::core::external::find_executable_in_path;
+use crate::shell::core::parser::ast::FunctionDefinition;
+
+/// Handles the `type` command.
+///
+/// Names are resolved in the order the shell uses: aliases first, then
+/// functions, then builtins, then executables in `PATH`.
+///
+/// # Arguments
+///
+/// * `aliases` - The aliases defined in the shell.
+/// * `functions` - The functions defined in the shell.
+/// * `registry` - The shell's builtins.
+/// * `args` - The names to look up.
+///
+/// # Returns
+///
+/// A `Result<String>` with one line per name, or an error if a name cannot
+/// be found.
+pub fn type_builtin(
+    aliases: &HashMap<String, String>,
+    functions: &HashMap<String, Arc<FunctionDefinition>>,
+    registry: &BuiltinRegistry,
+    args: &[&str],
+) -> Result<String> {
+    if args.is_empty() {
+        return Err(anyhow!("type: usage: type name [name ...]"));
+    }
+
+    let mut output = String::new();
+    for name in args {
+        if let Some(command) = aliases.get(*name) {
+            output.push_str(&format!("{} is aliased to `{}'\n", name, command));
+        } else if functions.contains_key(*name) {
+            output.push_str(&format!("{} is a function\n", name));
+        } else if registry.get(name).is_some() {
+            output.push_str(&format!("{} is a shell builtin\n", name));
+        } else if let Some(path) = find_executable_in_path(name) {
+            output.push_str(&format!("{} is {}\n", name, path.display()));
+        } else {
+            return Err(anyhow!("type: {}: not found", name));
+        }
+    }
+    Ok(output)
+}
+
+/// The `type` builtin.
+pub struct Type;
+
+impl Builtin for Type {
+    fn name(&self) -> &'static str {
+        "type"
+    }
+
+    fn summary(&self) -> &'static str {
+        "Show how each name would be interpreted as a command."
+    }
+
+    fn usage(&self) -> &'static str {
+        "type name [name ...]"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut BuiltinContext<'_>, args: &'a [&'a str]) -> BuiltinFuture<'a> {
+        Box::pin(async move {
+            let output = type_builtin(&ctx.shell.aliases, &ctx.shell.functions, registry(), args)?;
+            ctx.stdout.write_all(output.as_bytes()).await?;
+            Ok(EXIT_SUCCESS)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shell::core::parser::ast::{CommandList, CompoundCommand};
+
+    #[test]
+    fn test_type_resolves_aliases_before_builtins() {
+        let mut aliases = HashMap::new();
+        aliases.insert("ls".to_string(), "ls -a".to_string());
+
+        let output = type_builtin(&aliases, &HashMap::new(), registry(), &["ls", "cd"]).unwrap();
+        assert_eq!(output, "ls is aliased to `ls -a'\ncd is a shell builtin\n");
+    }
+
+    #[test]
+    fn test_type_resolves_functions_before_builtins() {
+        let definition = FunctionDefinition {
+            name: "cd".to_string(),
+            body: CompoundCommand::BraceGroup(CommandList::default()),
+            redirections: Vec::new(),
+        };
+        let mut functions = HashMap::new();
+        functions.insert("cd".to_string(), Arc::new(definition));
+
+        let output = type_builtin(&HashMap::new(), &functions, registry(), &["cd", "echo"]).unwrap();
+        assert_eq!(output, "cd is a function\necho is a shell builtin\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_type_finds_executables() {
+        let output = type_builtin(&HashMap::new(), &HashMap::new(), registry(), &["sh"]).unwrap();
+        assert!(output.starts_with("sh is /"), "Unexpected output: {}", output);
+    }
+
+    #[test]
+    fn test_type_not_found() {
+        let error = type_builtin(&HashMap::new(), &HashMap::new(), registry(), &["nonexistent_command_12345"]).unwrap_err();
+        assert_eq!(error.to_string(), "type: nonexistent_command_12345: not found");
+    }
+}
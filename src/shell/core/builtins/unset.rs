@@ -1,6 +1,10 @@
 //! Built-in command to unset environment variables.
 
+use anyhow::{anyhow, Result};
 use std::collections::HashMap;
+use super::{Builtin, BuiltinContext, BuiltinFuture};
+use crate::shell::core::command_executor::EXIT_SUCCESS;
+use tokio::io::AsyncWriteExt;
 
 /// Handles the `unset` command.
 ///
@@ -13,26 +17,44 @@ use std::collections::HashMap;
 ///
 /// # Returns
 ///
-/// A `String` containing the output of the command (usually empty on success,
-/// or an error message if a variable is not found).
-pub fn unset_builtin(env_vars: &mut HashMap<String, String>, args: &[&str]) -> String {
+/// A `Result<String>` that is empty on success, or an error if a variable
+/// is not found.
+pub fn unset_builtin(env_vars: &mut HashMap<String, String>, args: &[&str]) -> Result<String> {
     if args.is_empty() {
-        return "unset: usage: unset <variable_name>\n".to_string();
+        return Err(anyhow!("unset: usage: unset <variable_name>"));
     }
 
-    let mut removed_count = 0;
     for arg in args {
-        if env_vars.remove(*arg).is_some() {
-            removed_count += 1;
-        } else {
-            return format!("unset: {}: not found\n", arg);
+        if env_vars.remove(*arg).is_none() {
+            return Err(anyhow!("unset: {}: not found", arg));
         }
     }
 
-    if removed_count > 0 {
-        String::new() // No output on successful unsetting
-    } else {
-        String::new()
+    Ok(String::new()) // No output on successful unsetting
+}
+
+/// The `unset` builtin.
+pub struct Unset;
+
+impl Builtin for Unset {
+    fn name(&self) -> &'static str {
+        "unset"
+    }
+
+    fn summary(&self) -> &'static str {
+        "Remove environment variables."
+    }
+
+    fn usage(&self) -> &'static str {
+        "unset name ..."
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut BuiltinContext<'_>, args: &'a [&'a str]) -> BuiltinFuture<'a> {
+        Box::pin(async move {
+            let output = unset_builtin(&mut ctx.shell.env_vars, args)?;
+            ctx.stdout.write_all(output.as_bytes()).await?;
+            Ok(EXIT_SUCCESS)
+        })
     }
 }
 
@@ -46,17 +68,17 @@ mod tests {
         env_vars.insert("MY_VAR".to_string(), "test_value".to_string());
         
         let args = vec!["MY_VAR"];
-        let output = unset_builtin(&mut env_vars, &args);
+        let output = unset_builtin(&mut env_vars, &args).unwrap();
         assert!(output.is_empty());
-        assert!(env_vars.get("MY_VAR").is_none());
+        assert!(!env_vars.contains_key("MY_VAR"));
     }
 
     #[test]
     fn test_unset_nonexistent_var() {
         let mut env_vars = HashMap::new();
         let args = vec!["NON_EXISTENT_VAR"];
-        let output = unset_builtin(&mut env_vars, &args);
-        assert_eq!(output, "unset: NON_EXISTENT_VAR: not found\n");
+        let err = unset_builtin(&mut env_vars, &args).unwrap_err();
+        assert_eq!(err.to_string(), "unset: NON_EXISTENT_VAR: not found");
     }
 
     #[test]
@@ -66,17 +88,17 @@ mod tests {
         env_vars.insert("VAR2".to_string(), "value2".to_string());
 
         let args = vec!["VAR1", "VAR2"];
-        let output = unset_builtin(&mut env_vars, &args);
+        let output = unset_builtin(&mut env_vars, &args).unwrap();
         assert!(output.is_empty());
-        assert!(env_vars.get("VAR1").is_none());
-        assert!(env_vars.get("VAR2").is_none());
+        assert!(!env_vars.contains_key("VAR1"));
+        assert!(!env_vars.contains_key("VAR2"));
     }
 
     #[test]
     fn test_unset_usage() {
         let mut env_vars = HashMap::new();
         let args = vec![];
-        let output = unset_builtin(&mut env_vars, &args);
-        assert_eq!(output, "unset: usage: unset <variable_name>\n");
+        let err = unset_builtin(&mut env_vars, &args).unwrap_err();
+        assert_eq!(err.to_string(), "unset: usage: unset <variable_name>");
     }
 }
@@ -1,178 +1,1046 @@
 //! This module provides the core logic for executing shell commands.
 
-use anyhow::{anyhow, Context, Result};
-use std::fs::File;
-use std::io::{Cursor, Write};
-use std::process::Stdio;
-use crate::shell::core::builtins;
-use crate::shell::core::ShellCore;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
 use tokio::process::Command as TokioCommand;
+use tokio::task::JoinHandle;
+use crate::shell::core::builtins::{self, BuiltinContext};
+use crate::shell::core::cancellation::CancellationToken;
+use crate::shell::core::expansion::Expander;
+use crate::shell::core::output::{self, OutputSink, Stream};
+use crate::shell::core::parser::{self, ast};
+use crate::shell::core::stage_io::{self, Source, StageIo, Target};
+use crate::shell::core::ShellCore;
+
+/// Exit status of a command that succeeded.
+pub const EXIT_SUCCESS: i32 = 0;
+/// Exit status of a command that failed for a general reason.
+pub const EXIT_FAILURE: i32 = 1;
+/// Exit status when a command line could not be parsed.
+pub const EXIT_SYNTAX_ERROR: i32 = 2;
+/// Exit status when a command was found but could not be executed.
+pub const EXIT_CANNOT_EXECUTE: i32 = 126;
+/// Exit status when a command could not be found.
+pub const EXIT_NOT_FOUND: i32 = 127;
+/// Exit status of a command interrupted with Ctrl+C, as for `SIGINT`.
+pub const EXIT_INTERRUPTED: i32 = 130;
+
+/// The deepest that function calls may nest, which stops runaway recursion
+/// before it exhausts the stack.
+pub const MAX_FUNCTION_DEPTH: usize = 1000;
+
+/// A pending `break`, `continue` or `return`. While it is set, command
+/// lists stop running commands, until the loop or function it is aimed at
+/// consumes it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlFlow {
+    /// `break n`: leave the `n`th enclosing loop.
+    Break(usize),
+    /// `continue n`: go on with the next iteration of the `n`th enclosing loop.
+    Continue(usize),
+    /// `return`: leave the running function.
+    Return,
+}
+
+/// Applies a command's redirections to its descriptors.
+///
+/// Redirections are processed left to right, so `> out 2>&1` sends both
+/// streams to `out` while `2>&1 > out` only sends standard output there.
+/// Paths are resolved against `current_dir`.
+async fn apply_redirections(
+    expander: &mut Expander<'_>,
+    current_dir: &Path,
+    io: &mut StageIo,
+    redirections: &[ast::Redirection],
+) -> Result<(), String> {
+    for redirection in redirections {
+        let fd = redirection.target_fd();
+        match &redirection.kind {
+            ast::RedirectionKind::Input(word) => {
+                check_input_fd(fd)?;
+                let path = current_dir.join(expander.expand_word(word).await?);
+                let file = File::open(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+                io.stdin = Source::File(file);
+            }
+            ast::RedirectionKind::HereDoc(heredoc) => {
+                check_input_fd(fd)?;
+                let body = if heredoc.expand {
+                    expander.expand_heredoc(&heredoc.body).await?
+                } else {
+                    heredoc.body.clone()
+                };
+                io.stdin = Source::Bytes(body.into_bytes());
+            }
+            ast::RedirectionKind::DuplicateInput(word) => {
+                check_input_fd(fd)?;
+                match expander.expand_word(word).await?.as_str() {
+                    "-" => io.stdin = Source::Null,
+                    "0" => {}
+                    other => return Err(format!("{}: bad file descriptor", other)),
+                }
+            }
+            ast::RedirectionKind::Output(word) => {
+                let file = open_output(current_dir, &expander.expand_word(word).await?, false)?;
+                set_output(io, fd, Target::File(file))?;
+            }
+            ast::RedirectionKind::Append(word) => {
+                let file = open_output(current_dir, &expander.expand_word(word).await?, true)?;
+                set_output(io, fd, Target::File(file))?;
+            }
+            ast::RedirectionKind::OutputAndError(word) | ast::RedirectionKind::AppendAndError(word) => {
+                let append = matches!(redirection.kind, ast::RedirectionKind::AppendAndError(_));
+                let file = Target::File(open_output(current_dir, &expander.expand_word(word).await?, append)?);
+                io.stdout = duplicate(&file)?;
+                io.stderr = file;
+            }
+            ast::RedirectionKind::DuplicateOutput(word) => {
+                let target = expander.expand_word(word).await?;
+                let duplicated = match target.as_str() {
+                    "-" => Target::Closed,
+                    "1" => duplicate(&io.stdout)?,
+                    "2" => duplicate(&io.stderr)?,
+                    _ if target.chars().all(|c| c.is_ascii_digit()) => {
+                        return Err(format!("{}: bad file descriptor", target));
+                    }
+                    // `>&file` is another way to write `&> file`.
+                    _ if redirection.fd.is_none() => {
+                        let file = Target::File(open_output(current_dir, &target, false)?);
+                        io.stderr = duplicate(&file)?;
+                        file
+                    }
+                    _ => return Err(format!("{}: ambiguous redirect", target)),
+                };
+                set_output(io, fd, duplicated)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_input_fd(fd: u32) -> Result<(), String> {
+    if fd == 0 {
+        Ok(())
+    } else {
+        Err(format!("{}: bad file descriptor", fd))
+    }
+}
+
+fn open_output(current_dir: &Path, name: &str, append: bool) -> Result<File, String> {
+    let path = current_dir.join(name);
+    let file = if append {
+        OpenOptions::new().create(true).append(true).open(&path)
+    } else {
+        File::create(&path)
+    };
+    file.map_err(|e| format!("Failed to open redirection file '{}': {}", path.display(), e))
+}
+
+fn duplicate(target: &Target) -> Result<Target, String> {
+    target.try_clone().map_err(|e| format!("Failed to duplicate file descriptor: {}", e))
+}
+
+fn set_output(io: &mut StageIo, fd: u32, target: Target) -> Result<(), String> {
+    match fd {
+        1 => io.stdout = target,
+        2 => io.stderr = target,
+        _ => return Err(format!("{}: bad file descriptor", fd)),
+    }
+    Ok(())
+}
 
-// Data structures for parsing
-#[derive(Debug, PartialEq, Clone)]
-struct Command {
-    name: String,
-    args: Vec<String>,
+/// Writes a diagnostic message to a command's standard error.
+async fn report_error(stderr: Target, output: &OutputSink, message: &str) {
+    let mut writer = stderr.into_writer(output);
+    let _ = writer.write_error(message).await;
+    let _ = writer.flush().await;
 }
 
-#[derive(Debug, PartialEq, Clone)]
-enum Redirection {
-    ToFile(String),
+/// Writes a diagnostic message to the standard error of a compound
+/// command, which stays open for the commands inside it.
+async fn report_error_to(io: &StageIo, output: &OutputSink, message: &str) {
+    match io.stderr.try_clone() {
+        Ok(stderr) => report_error(stderr, output, message).await,
+        Err(_) => output.write_error(message),
+    }
 }
 
-#[derive(Debug, PartialEq, Clone)]
-struct Pipeline {
-    commands: Vec<Command>,
-    redirection: Option<Redirection>,
+/// Converts the exit status of a finished process into a shell status code.
+///
+/// A process killed by a signal reports `128 + signal`, as in POSIX shells.
+fn exit_code(status: std::process::ExitStatus) -> i32 {
+    if let Some(code) = status.code() {
+        return code;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    EXIT_FAILURE
 }
 
-// Parser function
-fn parse_line(line: &str) -> Result<Pipeline, String> {
-    let mut commands = Vec::new();
-    let mut redirection = None;
+/// Runs a single command with the given descriptors.
+///
+/// Functions take precedence over builtins, which take precedence over
+/// external commands.
+///
+/// # Arguments
+///
+/// * `shell_core` - The shell state the command runs against.
+/// * `name` - The command name.
+/// * `args` - The expanded arguments, not including the name.
+/// * `io` - The command's standard input, output and error.
+/// * `env_overrides` - Variables assigned on the command line (`FOO=bar cmd`),
+///   passed to external commands and local to functions.
+/// * `output` - The sink for output that is not redirected.
+/// * `cancellation` - Cancelled when the command line is interrupted.
+///
+/// # Returns
+///
+/// The exit status of the command.
+async fn execute_command(
+    shell_core: &mut ShellCore,
+    name: &str,
+    args: &[String],
+    io: StageIo,
+    env_overrides: &HashMap<String, String>,
+    output: &OutputSink,
+    cancellation: &CancellationToken,
+) -> i32 {
+    if let Some(function) = shell_core.functions.get(name).cloned() {
+        return call_function(shell_core, function, args, io, env_overrides, output, cancellation).await;
+    }
+    let args: Vec<&str> = args.iter().map(AsRef::as_ref).collect();
+    let Some(builtin) = builtins::registry().get(name) else {
+        return execute_external(shell_core, name, &args, io, env_overrides, output, cancellation).await;
+    };
 
-    let line_part = match line.rsplit_once('>') {
-        Some((left, right)) => {
-            let filename = right.trim();
-            if filename.is_empty() { return Err("Redirection filename is missing.".to_string()); }
-            redirection = Some(Redirection::ToFile(filename.to_string()));
-            left
+    let mut ctx = BuiltinContext {
+        shell: shell_core,
+        stdin: io.stdin.into_reader(),
+        stdout: io.stdout.into_writer(output),
+        stderr: io.stderr.into_writer(output),
+        output: output.clone(),
+        cancellation: cancellation.clone(),
+    };
+    // Dropping the builtin's future stops it at its next await point.
+    let result = tokio::select! {
+        result = builtin.run(&mut ctx, &args) => result,
+        _ = cancellation.cancelled() => Ok(EXIT_INTERRUPTED),
+    };
+    let flushed = ctx.stdout.flush().await;
+    let status = match result.and_then(|status| flushed.map(|()| status).map_err(anyhow::Error::from)) {
+        Ok(status) => status,
+        // The next command stopped reading; like a process killed by
+        // `SIGPIPE`, the builtin fails without a message.
+        Err(e) if e.downcast_ref::<std::io::Error>().is_some_and(|e| e.kind() == std::io::ErrorKind::BrokenPipe) => {
+            EXIT_FAILURE
+        }
+        Err(e) => {
+            let _ = ctx.stderr.write_error(&e.to_string()).await;
+            EXIT_FAILURE
         }
-        None => line,
     };
+    let _ = ctx.stderr.flush().await;
+    status
+}
 
-    for part in line_part.split('|') {
-        let trimmed_part = part.trim();
-        if trimmed_part.is_empty() { return Err("Empty command in pipeline.".to_string()); }
-        let args = shlex::split(trimmed_part).ok_or_else(|| format!("Invalid quoting: '{}'", trimmed_part))?;
-        if args.is_empty() { return Err("Empty command in pipeline.".to_string()); }
-        commands.push(Command { name: args[0].clone(), args: args.into_iter().skip(1).collect() });
+/// Runs `run` on a task of its own, against a copy of the shell state that
+/// replaces the original once it finishes.
+///
+/// Polling a command nests many futures inside one another, so a recursive
+/// function or script would soon exhaust the stack if each call were polled
+/// inside its caller. A new task is polled from the bottom of a worker's
+/// stack again.
+///
+/// # Returns
+///
+/// The status `run` finishes with, or `1` if it panicked.
+pub(crate) async fn run_on_own_task<F, Fut>(shell_core: &mut ShellCore, run: F) -> i32
+where
+    F: FnOnce(ShellCore) -> Fut,
+    Fut: Future<Output = (ShellCore, i32)> + Send + 'static,
+{
+    match tokio::spawn(run(shell_core.clone())).await {
+        Ok((state, status)) => {
+            *shell_core = state;
+            status
+        }
+        Err(_) => EXIT_FAILURE,
     }
+}
 
-    if commands.is_empty() { return Err("No commands provided.".to_string()); }
-    Ok(Pipeline { commands, redirection })
+/// Calls a shell function on a task of its own (see `run_on_own_task`).
+async fn call_function(
+    shell_core: &mut ShellCore,
+    function: Arc<ast::FunctionDefinition>,
+    args: &[String],
+    io: StageIo,
+    env_overrides: &HashMap<String, String>,
+    output: &OutputSink,
+    cancellation: &CancellationToken,
+) -> i32 {
+    let args = args.to_vec();
+    let env_overrides = env_overrides.clone();
+    let output = output.clone();
+    let cancellation = cancellation.clone();
+    run_on_own_task(shell_core, move |mut function_core| async move {
+        let status =
+            execute_function(&mut function_core, &function, &args, io, &env_overrides, &output, &cancellation).await;
+        (function_core, status)
+    })
+    .await
 }
 
-// --- New Execution Logic ---
+/// Runs the body of a shell function.
+///
+/// The arguments become the positional parameters, and variables assigned
+/// on the command line are local to the call. Both are restored when the
+/// function returns, together with the variables it declared `local`.
+///
+/// # Returns
+///
+/// The exit status of the function's body, or the status given to `return`.
+async fn execute_function(
+    shell_core: &mut ShellCore,
+    function: &ast::FunctionDefinition,
+    args: &[String],
+    io: StageIo,
+    env_overrides: &HashMap<String, String>,
+    output: &OutputSink,
+    cancellation: &CancellationToken,
+) -> i32 {
+    if shell_core.local_scopes.len() >= MAX_FUNCTION_DEPTH {
+        let message = format!("{}: maximum function nesting level exceeded ({})", function.name, MAX_FUNCTION_DEPTH);
+        report_error(io.stderr, output, &message).await;
+        return EXIT_FAILURE;
+    }
+
+    let positional_params = std::mem::replace(&mut shell_core.positional_params, args.to_vec());
+    // Loops of the caller cannot be left from inside the function.
+    let loop_depth = std::mem::take(&mut shell_core.loop_depth);
+    shell_core.local_scopes.push(HashMap::new());
+    for (name, value) in env_overrides {
+        shell_core.make_local(name);
+        shell_core.set_var(name, value);
+    }
+
+    let status =
+        execute_compound_command(shell_core, &function.body, &function.redirections, io, output, cancellation).await;
+
+    if shell_core.control_flow == Some(ControlFlow::Return) {
+        shell_core.control_flow = None;
+    }
+    for (name, value) in shell_core.local_scopes.pop().unwrap_or_default() {
+        match value {
+            Some(value) => shell_core.variables.insert(name, value),
+            None => shell_core.variables.remove(&name),
+        };
+    }
+    shell_core.loop_depth = loop_depth;
+    shell_core.positional_params = positional_params;
+    status
+}
 
-async fn execute_pipeline_async(shell_core: &mut ShellCore, pipeline: Pipeline) -> Result<String> {
-    let mut input_data = Vec::new();
-    let mut last_command_output: Option<Vec<u8>> = None;
+/// Spawns an external command and waits for it to finish.
+///
+/// Files and pipes are handed to the process as its descriptors; output
+/// meant for the sink is forwarded as the process produces it. When
+/// `cancellation` fires, the process is killed along with any processes it
+/// started.
+async fn execute_external(
+    shell_core: &ShellCore,
+    name: &str,
+    args: &[&str],
+    io: StageIo,
+    env_overrides: &HashMap<String, String>,
+    output: &OutputSink,
+    cancellation: &CancellationToken,
+) -> i32 {
+    let StageIo { stdin, stdout, stderr } = io;
+    let mut cmd = TokioCommand::new(name);
+    cmd.args(args).current_dir(&shell_core.current_dir);
+    // A process group of its own lets an interrupt reach the children the
+    // command starts, too.
+    #[cfg(unix)]
+    cmd.process_group(0);
 
-    let Pipeline { commands, redirection } = pipeline;
-    let num_commands = commands.len();
+    // Set environment variables for the external command
+    for (key, value) in shell_core.env_vars.iter().chain(env_overrides) {
+        cmd.env(key, value);
+    }
 
-    for (i, command) in commands.into_iter().enumerate() {
-        let is_last_command = i == num_commands - 1;
-        let args: Vec<&str> = command.args.iter().map(AsRef::as_ref).collect();
+    let (stdin, input_data) = stdin.into_stdio();
+    cmd.stdin(stdin);
+    match (stdout.to_stdio(), stderr.to_stdio()) {
+        (Ok(out), Ok(err)) => {
+            cmd.stdout(out).stderr(err);
+        }
+        (Err(e), _) | (_, Err(e)) => {
+            report_error(stderr, output, &format!("Failed to duplicate file descriptor: {}", e)).await;
+            return EXIT_FAILURE;
+        }
+    }
 
-        let command_result_str = match command.name.as_str() {
-            "ls" => Ok(builtins::ls::ls_builtin(&shell_core.current_dir, &args).await),
-            "echo" => Ok(builtins::echo::echo_builtin(&args, &shell_core.env_vars).await),
-            "ping" => Ok(builtins::ping::ping_builtin(&args).await),
-            "grep" => {
-                let cursor = Cursor::new(input_data.clone());
-                builtins::grep::grep_builtin(&args, Box::new(cursor)).await
+    let spawned = cmd.spawn();
+    // The command holds copies of the pipe ends it was given; the next
+    // stage would never see end-of-file while they are open.
+    drop(cmd);
+    let mut child = match spawned {
+        Ok(child) => child,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            report_error(stderr, output, &format!("{}: command not found", name)).await;
+            return EXIT_NOT_FOUND;
+        }
+        Err(e) => {
+            report_error(stderr, output, &format!("Failed to spawn command '{}': {}", name, e)).await;
+            return EXIT_CANNOT_EXECUTE;
+        }
+    };
+
+    // A background job's processes can be signalled with `kill %n`.
+    let job_process = shell_core.job.zip(child.id());
+    if let Some((job, pid)) = job_process {
+        shell_core.jobs.add_process(job, pid);
+    }
+
+    let mut tasks = Vec::new();
+    if let (Some(data), Some(mut child_stdin)) = (input_data, child.stdin.take()) {
+        tasks.push(tokio::spawn(async move {
+            // A command that exits without reading its input closes the pipe early.
+            let _ = child_stdin.write_all(&data).await;
+        }));
+    }
+    if let (Target::Sink(stream), Some(pipe)) = (&stdout, child.stdout.take()) {
+        tasks.push(forward(pipe, output.clone(), *stream));
+    }
+    if let (Target::Sink(stream), Some(pipe)) = (&stderr, child.stderr.take()) {
+        tasks.push(forward(pipe, output.clone(), *stream));
+    }
+    drop((stdout, stderr));
+
+    let status = tokio::select! {
+        status = child.wait() => match status {
+            Ok(status) => exit_code(status),
+            Err(e) => {
+                output.write_error(&format!("{}: {}", name, e));
+                EXIT_FAILURE
             }
-            "cat" => builtins::cat::cat_builtin(&shell_core.current_dir, &args).await,
-            "alias" => Ok(builtins::alias::alias_builtin(&mut shell_core.aliases, &args)),
-            "unalias" => {
-                let mut unalias_args = vec!["unalias"];
-                unalias_args.extend_from_slice(&args);
-                Ok(builtins::alias::alias_builtin(&mut shell_core.aliases, &unalias_args))
+        },
+        _ = cancellation.cancelled() => {
+            kill_process_group(&mut child).await;
+            let _ = child.wait().await;
+            // A process that escaped the group may still hold the pipes open.
+            for task in &tasks {
+                task.abort();
             }
-            "export" => Ok(builtins::export::export_builtin(&mut shell_core.env_vars, &args)),
-            "unset" => Ok(builtins::unset::unset_builtin(&mut shell_core.env_vars, &args)),
-            "cd" => Ok(builtins::cd::cd_builtin(&mut shell_core.current_dir, &args).await),
-            "open" => Ok(builtins::open::open_builtin(&shell_core.current_dir, &args).await),
-            "mkdir" => Ok(builtins::mkdir::mkdir_builtin(&shell_core.current_dir, &args).await),
-            "rm" => Ok(builtins::rm::rm_builtin(&shell_core.current_dir, &args).await),
-            "cp" => Ok(builtins::cp::cp_builtin(&shell_core.current_dir, &args).await),
-            "mv" => Ok(builtins::mv::mv_builtin(&shell_core.current_dir, &args).await),
-            _ => {
-                // External commands
-                let mut cmd = TokioCommand::new(&command.name);
-                cmd.args(&command.args)
-                   .current_dir(&shell_core.current_dir)
-                   .stdin(Stdio::piped())
-                   .stdout(Stdio::piped())
-                   .stderr(Stdio::piped());
-
-                // Set environment variables for the external command
-                for (key, value) in &shell_core.env_vars {
-                    cmd.env(key, value);
-                }
+            EXIT_INTERRUPTED
+        }
+    };
+    if let Some((job, pid)) = job_process {
+        shell_core.jobs.remove_process(job, pid);
+    }
+    for task in tasks {
+        let _ = task.await;
+    }
+    status
+}
+
+/// Kills an interrupted child together with every process in its group.
+///
+/// On Windows, which has no process groups, `taskkill /T` kills the tree of
+/// processes the child started instead.
+async fn kill_process_group(child: &mut tokio::process::Child) {
+    #[cfg(unix)]
+    if let Some(pid) = child.id() {
+        // SAFETY: `kill` has no memory-safety preconditions. The child leads
+        // its own process group, so `-pid` addresses the whole group.
+        unsafe {
+            libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+        }
+        return;
+    }
+    #[cfg(windows)]
+    if let Some(pid) = child.id() {
+        /// Keeps `taskkill` from opening a console window from the GUI.
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        let killed = TokioCommand::new("taskkill")
+            .args(["/T", "/F", "/PID", &pid.to_string()])
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .creation_flags(CREATE_NO_WINDOW)
+            .status()
+            .await;
+        if killed.is_ok_and(|status| status.success()) {
+            return;
+        }
+    }
+    let _ = child.start_kill();
+}
+
+/// Forwards a child process's piped output to the sink until it is closed.
+fn forward(mut pipe: impl AsyncRead + Send + Unpin + 'static, output: OutputSink, stream: Stream) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut buffer = [0; 8192];
+        loop {
+            match pipe.read(&mut buffer).await {
+                Ok(0) | Err(_) => break,
+                Ok(n) => output.write(stream, &buffer[..n]),
+            }
+        }
+    })
+}
+
+/// Executes a simple command, applying its assignments and redirections.
+///
+/// # Returns
+///
+/// The exit status of the command.
+async fn execute_simple_command(
+    shell_core: &mut ShellCore,
+    command: &ast::SimpleCommand,
+    mut io: StageIo,
+    output: &OutputSink,
+    cancellation: &CancellationToken,
+) -> i32 {
+    let current_dir = shell_core.current_dir.clone();
+    let mut expander = Expander::new(shell_core, output, cancellation);
+    let expanded = async {
+        let words = expander.expand_words(&command.words).await?;
+        let mut assignments = Vec::new();
+        for assignment in &command.assignments {
+            assignments.push((assignment.name.clone(), expander.expand_assignment(&assignment.value).await?));
+        }
+        apply_redirections(&mut expander, &current_dir, &mut io, &command.redirections).await?;
+        Ok::<_, String>((words, assignments))
+    }
+    .await;
+    let substitution_status = expander.substitution_status;
+    let (words, assignments) = match expanded {
+        Ok(expanded) => expanded,
+        Err(e) => {
+            report_error(io.stderr, output, &e).await;
+            return EXIT_FAILURE;
+        }
+    };
 
-                let mut child = match cmd.spawn() {
-                    Ok(child) => child,
-                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
-                        return Err(anyhow!("{}: command not found", command.name));
+    match words.split_first() {
+        Some((name, args)) => {
+            let env_overrides: HashMap<String, String> = assignments.into_iter().collect();
+            execute_command(shell_core, name, args, io, &env_overrides, output, cancellation).await
+        }
+        None => {
+            // A bare `NAME=value` sets a shell variable, and succeeds unless
+            // a command substitution in it failed.
+            for (name, value) in &assignments {
+                shell_core.set_var(name, value);
+            }
+            substitution_status.unwrap_or(EXIT_SUCCESS)
+        }
+    }
+}
+
+async fn execute_pipeline_command(
+    shell_core: &mut ShellCore,
+    command: &ast::Command,
+    io: StageIo,
+    output: &OutputSink,
+    cancellation: &CancellationToken,
+) -> i32 {
+    match command {
+        ast::Command::Simple(simple) => execute_simple_command(shell_core, simple, io, output, cancellation).await,
+        ast::Command::Compound(compound, redirections) => {
+            execute_compound_command(shell_core, compound, redirections, io, output, cancellation).await
+        }
+        ast::Command::FunctionDefinition(definition) => {
+            shell_core.functions.insert(definition.name.clone(), Arc::new(definition.clone()));
+            EXIT_SUCCESS
+        }
+    }
+}
+
+/// Executes a compound command such as `{ ...; }`, `if`, `while`, `for` or
+/// `case`.
+///
+/// The command's redirections apply to every command inside it. The
+/// future is boxed because the commands inside may be compound commands
+/// themselves.
+///
+/// # Returns
+///
+/// The exit status of the last command run inside it, or `0` if none ran.
+fn execute_compound_command<'a>(
+    shell_core: &'a mut ShellCore,
+    compound: &'a ast::CompoundCommand,
+    redirections: &'a [ast::Redirection],
+    mut io: StageIo,
+    output: &'a OutputSink,
+    cancellation: &'a CancellationToken,
+) -> Pin<Box<dyn Future<Output = i32> + Send + 'a>> {
+    Box::pin(async move {
+        let current_dir = shell_core.current_dir.clone();
+        let mut expander = Expander::new(shell_core, output, cancellation);
+        if let Err(e) = apply_redirections(&mut expander, &current_dir, &mut io, redirections).await {
+            report_error(io.stderr, output, &e).await;
+            return EXIT_FAILURE;
+        }
+
+        match compound {
+            ast::CompoundCommand::BraceGroup(body) => execute_body(shell_core, body, &io, output, cancellation).await,
+            ast::CompoundCommand::If { branches, else_body } => {
+                for (condition, body) in branches {
+                    execute_list(shell_core, condition, &io, output, cancellation).await;
+                    if cancellation.is_cancelled() || shell_core.control_flow.is_some() {
+                        return shell_core.last_exit_status;
+                    }
+                    if shell_core.last_exit_status == EXIT_SUCCESS {
+                        return execute_body(shell_core, body, &io, output, cancellation).await;
                     }
-                    Err(e) => return Err(e).context(format!("Failed to spawn command '{}'", command.name)),
+                }
+                match else_body {
+                    Some(body) => execute_body(shell_core, body, &io, output, cancellation).await,
+                    None => EXIT_SUCCESS,
+                }
+            }
+            ast::CompoundCommand::While { condition, body, until } => {
+                execute_while(shell_core, condition, body, *until, &io, output, cancellation).await
+            }
+            ast::CompoundCommand::For { variable, words, body } => {
+                let values = match words {
+                    Some(words) => match Expander::new(shell_core, output, cancellation).expand_words(words).await {
+                        Ok(values) => values,
+                        Err(e) => {
+                            report_error_to(&io, output, &e).await;
+                            return EXIT_FAILURE;
+                        }
+                    },
+                    None => shell_core.positional_params.clone(),
                 };
-                
-                if let Some(mut stdin) = child.stdin.take() {
-                    use tokio::io::AsyncWriteExt;
-                    stdin.write_all(&input_data).await?;
+                execute_for(shell_core, variable, &values, body, &io, output, cancellation).await
+            }
+            ast::CompoundCommand::Case { word, items } => {
+                let mut expander = Expander::new(shell_core, output, cancellation);
+                let matched = async {
+                    let subject = expander.expand_word(word).await?;
+                    for item in items {
+                        for pattern in &item.patterns {
+                            if expander.compile_pattern(pattern).await?.matches(&subject) {
+                                return Ok(Some(&item.body));
+                            }
+                        }
+                    }
+                    Ok::<_, String>(None)
                 }
-
-                let output = child.wait_with_output().await?;
-                if !output.status.success() {
-                    return Err(anyhow!(String::from_utf8_lossy(&output.stderr).into_owned()));
+                .await;
+                match matched {
+                    Ok(Some(body)) => execute_body(shell_core, body, &io, output, cancellation).await,
+                    Ok(None) => EXIT_SUCCESS,
+                    Err(e) => {
+                        report_error_to(&io, output, &e).await;
+                        EXIT_FAILURE
+                    }
                 }
-                return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
             }
-        };
+        }
+    })
+}
+
+/// Runs the body of a compound command and returns its status, which is
+/// `0` for an empty body such as that of `pattern) ;;`.
+async fn execute_body(
+    shell_core: &mut ShellCore,
+    body: &ast::CommandList,
+    io: &StageIo,
+    output: &OutputSink,
+    cancellation: &CancellationToken,
+) -> i32 {
+    if body.items.is_empty() {
+        return EXIT_SUCCESS;
+    }
+    execute_list(shell_core, body, io, output, cancellation).await;
+    shell_core.last_exit_status
+}
 
-        let output_str = command_result_str?;
-        let current_command_output_bytes = output_str.into_bytes();
-
-        if is_last_command {
-            if let Some(Redirection::ToFile(ref filename)) = redirection {
-                let mut file = File::create(shell_core.current_dir.join(filename))
-                    .context("Failed to create redirection file")?;
-                file.write_all(&current_command_output_bytes)?;
-                last_command_output = Some(Vec::new()); // No output to stdout if redirected
-            } else {
-                last_command_output = Some(current_command_output_bytes);
+/// Runs a `while` loop, or an `until` loop if `until` is set.
+async fn execute_while(
+    shell_core: &mut ShellCore,
+    condition: &ast::CommandList,
+    body: &ast::CommandList,
+    until: bool,
+    io: &StageIo,
+    output: &OutputSink,
+    cancellation: &CancellationToken,
+) -> i32 {
+    let mut status = EXIT_SUCCESS;
+    shell_core.loop_depth += 1;
+    while !cancellation.is_cancelled() {
+        execute_list(shell_core, condition, io, output, cancellation).await;
+        if shell_core.control_flow.is_some() {
+            if loop_should_stop(shell_core) {
+                break;
             }
-        } else {
-            input_data = current_command_output_bytes;
+            continue;
+        }
+        if (shell_core.last_exit_status == EXIT_SUCCESS) == until {
+            break;
         }
+        status = execute_body(shell_core, body, io, output, cancellation).await;
+        if loop_should_stop(shell_core) {
+            break;
+        }
+        // A loop of builtins never waits, so let other tasks run.
+        tokio::task::yield_now().await;
     }
-
-    Ok(last_command_output.map_or(String::new(), |bytes| String::from_utf8_lossy(&bytes).into_owned()))
+    shell_core.loop_depth -= 1;
+    status
 }
 
-pub async fn execute_shell_command(shell_core: &mut ShellCore, command_str: &str) -> String {
-    if command_str.trim().is_empty() {
-        return String::new();
+/// Runs a `for` loop, assigning each value to `variable` in turn.
+async fn execute_for(
+    shell_core: &mut ShellCore,
+    variable: &str,
+    values: &[String],
+    body: &ast::CommandList,
+    io: &StageIo,
+    output: &OutputSink,
+    cancellation: &CancellationToken,
+) -> i32 {
+    let mut status = EXIT_SUCCESS;
+    shell_core.loop_depth += 1;
+    for value in values {
+        if cancellation.is_cancelled() {
+            break;
+        }
+        shell_core.set_var(variable, value);
+        status = execute_body(shell_core, body, io, output, cancellation).await;
+        if loop_should_stop(shell_core) {
+            break;
+        }
+        tokio::task::yield_now().await;
     }
+    shell_core.loop_depth -= 1;
+    status
+}
 
-    // Alias expansion
-    let mut parts = shlex::split(command_str).unwrap_or_default();
-    if parts.is_empty() {
-        return String::new();
+/// Consumes a pending `break` or `continue` aimed at the innermost loop.
+///
+/// # Returns
+///
+/// `true` if the loop must stop, either for a `break`, because the
+/// `break` or `continue` is aimed at an enclosing loop, or for a `return`.
+fn loop_should_stop(shell_core: &mut ShellCore) -> bool {
+    match shell_core.control_flow.take() {
+        None | Some(ControlFlow::Continue(1)) => false,
+        Some(ControlFlow::Break(1)) => true,
+        Some(ControlFlow::Break(n)) => {
+            shell_core.control_flow = Some(ControlFlow::Break(n - 1));
+            true
+        }
+        Some(ControlFlow::Continue(n)) => {
+            shell_core.control_flow = Some(ControlFlow::Continue(n - 1));
+            true
+        }
+        Some(ControlFlow::Return) => {
+            shell_core.control_flow = Some(ControlFlow::Return);
+            true
+        }
     }
+}
 
-    let expanded_command_str = if let Some(expanded) = shell_core.aliases.get(&parts[0]) {
-        parts[0] = expanded.clone();
-        parts.join(" ")
-    } else {
-        command_str.to_string()
+/// Executes a pipeline, running all of its commands concurrently.
+///
+/// Each command's standard output is connected to the next command's
+/// standard input with an OS pipe, and the last command's output reaches
+/// the sink while the pipeline is still running. When there are several
+/// commands, each runs against a copy of the shell state, as in a subshell,
+/// so `cd` or assignments inside the pipeline do not affect the shell.
+///
+/// A failing command does not stop the pipeline; the next command simply
+/// reads end-of-file. The status of the pipeline is the status of its last
+/// command, inverted if the pipeline starts with `!`.
+///
+/// # Arguments
+///
+/// * `io` - The descriptors of the enclosing compound command, or of the
+///   sink at the top level. The first command reads its standard input and
+///   the last writes its standard output.
+async fn execute_pipeline(
+    shell_core: &mut ShellCore,
+    pipeline: &ast::Pipeline,
+    io: &StageIo,
+    output: &OutputSink,
+    cancellation: &CancellationToken,
+) -> i32 {
+    let status = match execute_pipeline_commands(shell_core, &pipeline.commands, io, output, cancellation).await {
+        Ok(status) => status,
+        Err(e) => {
+            report_error_to(io, output, &e).await;
+            EXIT_FAILURE
+        }
     };
+    match (pipeline.negated, status) {
+        (false, status) => status,
+        (true, EXIT_SUCCESS) => EXIT_FAILURE,
+        (true, _) => EXIT_SUCCESS,
+    }
+}
+
+async fn execute_pipeline_commands(
+    shell_core: &mut ShellCore,
+    commands: &[ast::Command],
+    io: &StageIo,
+    output: &OutputSink,
+    cancellation: &CancellationToken,
+) -> Result<i32, String> {
+    let duplicate_failed = |e: std::io::Error| format!("Failed to duplicate file descriptor: {}", e);
+    if let [command] = commands {
+        let io = io.try_clone().map_err(duplicate_failed)?;
+        return Ok(execute_pipeline_command(shell_core, command, io, output, cancellation).await);
+    }
+
+    let mut stages = Vec::new();
+    let mut stdin = io.stdin.try_clone().map_err(duplicate_failed)?;
+    let mut error = None;
+    for (index, command) in commands.iter().enumerate() {
+        let (stdout, next_stdin) = if index + 1 == commands.len() {
+            match io.stdout.try_clone() {
+                Ok(stdout) => (stdout, Source::Null),
+                Err(e) => {
+                    error = Some(duplicate_failed(e));
+                    break;
+                }
+            }
+        } else {
+            match stage_io::pipe() {
+                Ok((reader, writer)) => (Target::File(writer), Source::File(reader)),
+                Err(e) => {
+                    error = Some(format!("Failed to create pipe: {}", e));
+                    break;
+                }
+            }
+        };
+        let stderr = match io.stderr.try_clone() {
+            Ok(stderr) => stderr,
+            Err(e) => {
+                error = Some(duplicate_failed(e));
+                break;
+            }
+        };
+        let io = StageIo { stdin: std::mem::replace(&mut stdin, next_stdin), stdout, stderr };
+
+        let mut stage_core = shell_core.clone();
+        let command = command.clone();
+        let output = output.clone();
+        let cancellation = cancellation.clone();
+        stages.push(tokio::spawn(async move {
+            execute_pipeline_command(&mut stage_core, &command, io, &output, &cancellation).await
+        }));
+    }
+    drop(stdin);
+
+    let mut status = EXIT_SUCCESS;
+    for stage in stages {
+        status = stage.await.unwrap_or(EXIT_FAILURE);
+    }
+    match error {
+        Some(error) => Err(error),
+        None => Ok(status),
+    }
+}
+
+/// Executes a command list.
+///
+/// Items separated by `;` always run; within an and-or list, `&&` and `||`
+/// decide whether the next pipeline runs based on the last exit status,
+/// which is recorded in `ShellCore::last_exit_status` as `$?`. A pending
+/// `break`, `continue` or `return` stops the list.
+async fn execute_list(
+    shell_core: &mut ShellCore,
+    list: &ast::CommandList,
+    io: &StageIo,
+    output: &OutputSink,
+    cancellation: &CancellationToken,
+) {
+    for and_or in &list.items {
+        if let Some(command) = &and_or.job {
+            if cancellation.is_cancelled() || shell_core.control_flow.is_some() {
+                return;
+            }
+            shell_core.last_exit_status = start_job(shell_core, and_or, command, io, output);
+            continue;
+        }
+        let pipelines = std::iter::once((None, &and_or.first))
+            .chain(and_or.rest.iter().map(|(op, pipeline)| (Some(*op), pipeline)));
 
-    let pipeline = match parse_line(&expanded_command_str) {
-        Ok(p) => p,
-        Err(e) => return e,
+        for (operator, pipeline) in pipelines {
+            if cancellation.is_cancelled() || shell_core.control_flow.is_some() {
+                return;
+            }
+            let succeeded = shell_core.last_exit_status == EXIT_SUCCESS;
+            match operator {
+                Some(ast::AndOrOperator::And) if !succeeded => continue,
+                Some(ast::AndOrOperator::Or) if succeeded => continue,
+                _ => {}
+            }
+
+            shell_core.last_exit_status = execute_pipeline(shell_core, pipeline, io, output, cancellation).await;
+            output.end_pipeline();
+        }
+    }
+}
+
+/// Starts an and-or list ending in `&` as a background job.
+///
+/// The job runs on a task of its own against a copy of the shell state, so
+/// changes it makes to variables or the directory are not seen by the
+/// shell. It reads no input unless redirected, writes to the same output
+/// as the command line that started it, and is not interrupted by Ctrl+C,
+/// only by `kill`.
+///
+/// # Returns
+///
+/// `0`, or `1` if the descriptors could not be duplicated.
+fn start_job(
+    shell_core: &mut ShellCore,
+    and_or: &ast::AndOrList,
+    command: &str,
+    io: &StageIo,
+    output: &OutputSink,
+) -> i32 {
+    let (stdout, stderr) = match (io.stdout.try_clone(), io.stderr.try_clone()) {
+        (Ok(stdout), Ok(stderr)) => (stdout, stderr),
+        (Err(e), _) | (_, Err(e)) => {
+            output.write_error(&format!("Failed to duplicate file descriptor: {}", e));
+            return EXIT_FAILURE;
+        }
     };
-    
-    match execute_pipeline_async(shell_core, pipeline).await {
-        Ok(output) => output,
-        Err(e) => format!("Error: {}", e),
+    let io = StageIo { stdin: Source::Null, stdout, stderr };
+    let list = ast::CommandList { items: vec![ast::AndOrList { job: None, ..and_or.clone() }] };
+    let cancellation = CancellationToken::new();
+    let id = shell_core.jobs.add(command.to_string(), cancellation.clone());
+
+    let mut job_core = shell_core.clone();
+    job_core.loop_depth = 0;
+    job_core.job = Some(id);
+    let output = output.clone();
+    tokio::spawn(async move {
+        execute_list(&mut job_core, &list, &io, &output, &cancellation).await;
+        drop(io);
+        // A job killed before its first command started still counts as interrupted.
+        let status = if cancellation.is_cancelled() { EXIT_INTERRUPTED } else { job_core.last_exit_status };
+        job_core.jobs.finish(id, status);
+    });
+    EXIT_SUCCESS
+}
+
+/// Runs the commands of a command substitution against the shell state and
+/// returns everything they wrote to standard output.
+///
+/// Their standard error is passed on to `output`, and `$?` is left set to
+/// the status of the last pipeline.
+///
+/// # Arguments
+///
+/// * `shell_core` - The shell state the commands run against.
+/// * `source` - The text between `$(` and `)`.
+/// * `output` - Receives the standard error of the commands.
+/// * `cancellation` - Interrupts the commands.
+pub(crate) fn command_substitution<'a>(
+    shell_core: &'a mut ShellCore,
+    source: &'a str,
+    output: &'a OutputSink,
+    cancellation: &'a CancellationToken,
+) -> Pin<Box<dyn Future<Output = Result<String, String>> + Send + 'a>> {
+    Box::pin(async move {
+        let list = parser::parse(source, &shell_core.aliases).map_err(|e| e.to_string())?;
+        let (sink, mut receiver) = OutputSink::new();
+        execute_list(shell_core, &list, &StageIo::sink(), &sink, cancellation).await;
+
+        // Every command of the list has finished, so its output is all queued.
+        let mut stdout = String::new();
+        while let Ok(chunk) = receiver.try_recv() {
+            match chunk.stream {
+                Stream::Stdout => stdout.push_str(&chunk.text),
+                Stream::Stderr => output.write(Stream::Stderr, chunk.text.as_bytes()),
+            }
+        }
+        Ok(stdout)
+    })
+}
+
+/// Parses and runs a script against the shell state with the given
+/// descriptors, as `source` and the command-line entry points do.
+///
+/// # Returns
+///
+/// The exit status of the last pipeline, or `2` if the script cannot be
+/// parsed, in which case none of it runs.
+pub async fn execute_script(
+    shell_core: &mut ShellCore,
+    script: &str,
+    io: &StageIo,
+    output: &OutputSink,
+    cancellation: &CancellationToken,
+) -> i32 {
+    match parser::parse(script, &shell_core.aliases) {
+        Ok(list) => {
+            execute_list(shell_core, &list, io, output, cancellation).await;
+            shell_core.last_exit_status
+        }
+        Err(e) => {
+            report_error_to(io, output, &e.to_string()).await;
+            shell_core.last_exit_status = EXIT_SYNTAX_ERROR;
+            EXIT_SYNTAX_ERROR
+        }
     }
 }
 
+/// Parses and executes a command line, writing its output to `output` as
+/// it is produced.
+///
+/// Cancelling `cancellation` stops the running commands and skips the rest
+/// of the line; the output is then marked as interrupted. The exit status of
+/// the last pipeline is left in `ShellCore::last_exit_status`.
+pub async fn execute_shell_command_streaming(
+    shell_core: &mut ShellCore,
+    command_str: &str,
+    output: &OutputSink,
+    cancellation: &CancellationToken,
+) {
+    if command_str.trim().is_empty() {
+        return;
+    }
+
+    match parser::parse(command_str, &shell_core.aliases) {
+        Ok(list) => execute_list(shell_core, &list, &StageIo::sink(), output, cancellation).await,
+        Err(e) => {
+            shell_core.last_exit_status = EXIT_SYNTAX_ERROR;
+            output.write_error(&e.to_string());
+        }
+    }
+    shell_core.control_flow = None;
+    if cancellation.is_cancelled() {
+        shell_core.last_exit_status = EXIT_INTERRUPTED;
+        output.write_interrupted();
+    }
+}
+
+/// Parses and executes a command line, returning all of its output once it
+/// has finished.
+pub async fn execute_shell_command(shell_core: &mut ShellCore, command_str: &str) -> String {
+    let (sink, receiver) = OutputSink::new();
+    execute_shell_command_streaming(shell_core, command_str, &sink, &CancellationToken::new()).await;
+    output::collect(receiver)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -283,4 +1151,708 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_variables_are_expanded_for_every_command() -> io::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let mut shell_core = ShellCore::new();
+        shell_core.current_dir = temp_dir.path().to_path_buf();
+
+        execute_shell_command(&mut shell_core, "DIR=made; FILE='a b.txt'").await;
+        execute_shell_command(&mut shell_core, "mkdir $DIR; echo data > \"$DIR/$FILE\"").await;
+        assert_eq!(fs::read_to_string(temp_dir.path().join("made/a b.txt"))?, "data\n");
+
+        let output = execute_shell_command(&mut shell_core, "cat \"${DIR}/$FILE\" | grep ${MISSING:-data}").await;
+        assert_eq!(output.trim(), "data");
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_external_commands_receive_expanded_arguments() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        execute_shell_command(&mut shell_core, "WORDS='one two'").await;
+        let output = execute_shell_command(&mut shell_core, "sh -c 'echo $#' x $WORDS \"$WORDS\"").await;
+        assert_eq!(output.trim(), "3");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_assignment_updates_exported_variable() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        execute_shell_command(&mut shell_core, "export MODE=debug").await;
+        execute_shell_command(&mut shell_core, "MODE=${MODE}-verbose").await;
+        assert_eq!(shell_core.env_vars.get("MODE"), Some(&"debug-verbose".to_string()));
+        assert!(!shell_core.variables.contains_key("MODE"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_expansion_error_fails_command() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        let output = execute_shell_command(&mut shell_core, "echo ${REQUIRED_123:?must be set}; echo status $?").await;
+        assert_eq!(output, "Error: REQUIRED_123: must be set\nstatus 1\n");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_command_substitution_runs_against_shell() -> io::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        fs::create_dir(temp_dir.path().join("sub"))?;
+        fs::write(temp_dir.path().join("VERSION"), "1.2.3\n\n")?;
+        let mut shell_core = ShellCore::new();
+        shell_core.current_dir = dunce::canonicalize(temp_dir.path())?;
+
+        execute_shell_command(&mut shell_core, "export VERSION=$(cat VERSION)").await;
+        assert_eq!(shell_core.env_vars.get("VERSION"), Some(&"1.2.3".to_string()));
+
+        execute_shell_command(&mut shell_core, "cd $(echo sub)").await;
+        assert_eq!(shell_core.current_dir, dunce::canonicalize(temp_dir.path().join("sub"))?);
+
+        let output = execute_shell_command(&mut shell_core, "echo `echo one; echo two` | grep two").await;
+        assert_eq!(output.trim(), "one two");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_command_substitution_status_and_errors() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        let output = execute_shell_command(&mut shell_core, "X=$(cat missing_file_123); echo \"[$X] $?\"").await;
+        assert!(output.starts_with("Error: cat:"), "Unexpected output: {}", output);
+        assert!(output.ends_with("[] 1\n"), "Unexpected output: {}", output);
+
+        execute_shell_command(&mut shell_core, "X=$(echo fine)").await;
+        assert_eq!(shell_core.last_exit_status, EXIT_SUCCESS);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_globs_and_braces_expand_to_files() -> io::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        for file in ["a.tmp", "b.tmp", "keep.txt", ".hidden.tmp"] {
+            fs::write(temp_dir.path().join(file), "")?;
+        }
+        let mut shell_core = ShellCore::new();
+        shell_core.current_dir = temp_dir.path().to_path_buf();
+
+        let output = execute_shell_command(&mut shell_core, "echo *.tmp '*.tmp' {keep,other}.txt").await;
+        assert_eq!(output, "a.tmp b.tmp *.tmp keep.txt other.txt\n");
+
+        execute_shell_command(&mut shell_core, "PATTERN='*.tmp'").await;
+        let output = execute_shell_command(&mut shell_core, "echo $PATTERN \"$PATTERN\"").await;
+        assert_eq!(output, "a.tmp b.tmp *.tmp\n");
+
+        execute_shell_command(&mut shell_core, "rm *.tmp").await;
+        assert!(!temp_dir.path().join("a.tmp").exists());
+        assert!(temp_dir.path().join(".hidden.tmp").exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_glob_options() -> io::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        fs::write(temp_dir.path().join(".hidden"), "")?;
+        let mut shell_core = ShellCore::new();
+        shell_core.current_dir = temp_dir.path().to_path_buf();
+
+        let output = execute_shell_command(&mut shell_core, "echo none*; echo *").await;
+        assert_eq!(output, "none*\n*\n");
+
+        execute_shell_command(&mut shell_core, "shopt -s nullglob dotglob").await;
+        let output = execute_shell_command(&mut shell_core, "echo none* x; echo *").await;
+        assert_eq!(output, "x\n.hidden\n");
+
+        execute_shell_command(&mut shell_core, "shopt -s failglob").await;
+        let output = execute_shell_command(&mut shell_core, "echo none*; echo $?").await;
+        assert_eq!(output, "Error: no match: none*\n1\n");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_quoted_pipe_is_not_split() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        let output = execute_shell_command(&mut shell_core, "echo \"a|b\" | grep '|'").await;
+        assert_eq!(output.trim(), "a|b");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_quoted_greater_than_is_not_redirection() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        let output = execute_shell_command(&mut shell_core, "echo \"1 > 0\" | grep '>'").await;
+        assert_eq!(output.trim(), "1 > 0");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_redirection_in_middle_of_pipeline() -> io::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let mut shell_core = ShellCore::new();
+        shell_core.current_dir = temp_dir.path().to_path_buf();
+
+        let output = execute_shell_command(&mut shell_core, "echo hello > first.txt | grep hello").await;
+        assert!(output.is_empty(), "Output should be empty, but was: {}", output);
+        assert_eq!(fs::read_to_string(temp_dir.path().join("first.txt"))?, "hello\n");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_command_list_runs_in_order() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        let output = execute_shell_command(&mut shell_core, "echo one; echo two\necho three").await;
+        assert_eq!(output, "one\ntwo\nthree\n");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bare_assignment_sets_shell_variable() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        let output = execute_shell_command(&mut shell_core, "GREETING='hello world'").await;
+        assert!(output.is_empty());
+        assert_eq!(shell_core.variables.get("GREETING"), Some(&"hello world".to_string()));
+        assert!(!shell_core.env_vars.contains_key("GREETING"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_and_or_short_circuit() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        let output = execute_shell_command(&mut shell_core, "echo first && echo second || echo third").await;
+        assert_eq!(output, "first\nsecond\n");
+
+        let output = execute_shell_command(&mut shell_core, "cd nonexistent_dir_123 && echo moved || echo fallback").await;
+        assert!(output.contains("No such file or directory"));
+        assert!(!output.contains("moved"));
+        assert!(output.ends_with("fallback\n"));
+        assert_eq!(shell_core.last_exit_status, EXIT_SUCCESS);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_semicolon_runs_regardless_of_status() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        let output = execute_shell_command(&mut shell_core, "cd nonexistent_dir_123 ; echo after").await;
+        assert!(output.ends_with("after\n"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_exit_status_variable() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        let output = execute_shell_command(&mut shell_core, "cd nonexistent_dir_123; echo $?").await;
+        assert!(output.ends_with("\n1\n"), "Unexpected output: {}", output);
+
+        let output = execute_shell_command(&mut shell_core, "nonexistent_command_12345; echo \"$?\" '$?'").await;
+        assert!(output.ends_with("127 $?\n"), "Unexpected output: {}", output);
+
+        execute_shell_command(&mut shell_core, "echo ok").await;
+        assert_eq!(shell_core.last_exit_status, EXIT_SUCCESS);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_status_is_last_command() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        execute_shell_command(&mut shell_core, "nonexistent_command_12345 | echo hi").await;
+        assert_eq!(shell_core.last_exit_status, EXIT_SUCCESS);
+        execute_shell_command(&mut shell_core, "echo hi | grep bye").await;
+        assert_eq!(shell_core.last_exit_status, EXIT_SUCCESS);
+        execute_shell_command(&mut shell_core, "echo hi | nonexistent_command_12345").await;
+        assert_eq!(shell_core.last_exit_status, EXIT_NOT_FOUND);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_external_exit_status() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        execute_shell_command(&mut shell_core, "sh -c 'exit 3'").await;
+        assert_eq!(shell_core.last_exit_status, 3);
+        let output = execute_shell_command(&mut shell_core, "sh -c 'exit 3' || echo recovered").await;
+        assert!(output.ends_with("recovered\n"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_syntax_error_is_reported() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        let output = execute_shell_command(&mut shell_core, "echo a | | grep a").await;
+        assert!(output.starts_with("Error: syntax error"), "Unexpected output: {}", output);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_input_redirection() -> io::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let mut shell_core = ShellCore::new();
+        shell_core.current_dir = temp_dir.path().to_path_buf();
+        fs::write(temp_dir.path().join("input.txt"), "apple\nbanana\ncherry\n")?;
+
+        let output = execute_shell_command(&mut shell_core, "grep an < input.txt").await;
+        assert_eq!(output.trim(), "banana");
+        let output = execute_shell_command(&mut shell_core, "cat < missing.txt").await;
+        assert!(output.starts_with("Error: "), "Unexpected output: {}", output);
+        assert_eq!(shell_core.last_exit_status, EXIT_FAILURE);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_append_redirection() -> io::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let mut shell_core = ShellCore::new();
+        shell_core.current_dir = temp_dir.path().to_path_buf();
+
+        execute_shell_command(&mut shell_core, "echo one > log.txt; echo two >> log.txt; echo three >>log.txt").await;
+        assert_eq!(fs::read_to_string(temp_dir.path().join("log.txt"))?, "one\ntwo\nthree\n");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_append_keeps_the_lines_already_written() -> io::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let mut shell_core = ShellCore::new();
+        shell_core.current_dir = temp_dir.path().to_path_buf();
+
+        let output = execute_shell_command(&mut shell_core, "echo one > f; echo two >> f; cat f").await;
+        assert_eq!(output, "one\ntwo\n");
+        assert_eq!(fs::read_to_string(temp_dir.path().join("f"))?, "one\ntwo\n");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stderr_redirection() -> io::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let mut shell_core = ShellCore::new();
+        shell_core.current_dir = temp_dir.path().to_path_buf();
+
+        let output = execute_shell_command(&mut shell_core, "cat missing.txt 2> err.txt").await;
+        assert!(output.is_empty(), "Output should be empty, but was: {}", output);
+        assert!(fs::read_to_string(temp_dir.path().join("err.txt"))?.contains("missing.txt"));
+        assert_eq!(shell_core.last_exit_status, EXIT_FAILURE);
+
+        let output = execute_shell_command(&mut shell_core, "cat missing.txt 2>&-").await;
+        assert!(output.is_empty(), "Output should be empty, but was: {}", output);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_output_order() -> io::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let mut shell_core = ShellCore::new();
+        shell_core.current_dir = temp_dir.path().to_path_buf();
+
+        // `> file 2>&1` sends both streams to the file.
+        let output = execute_shell_command(&mut shell_core, "cat missing.txt > both.txt 2>&1").await;
+        assert!(output.is_empty(), "Output should be empty, but was: {}", output);
+        assert!(fs::read_to_string(temp_dir.path().join("both.txt"))?.contains("missing.txt"));
+
+        // `2>&1 > file` leaves standard error where standard output was.
+        let output = execute_shell_command(&mut shell_core, "cat missing.txt 2>&1 > out.txt").await;
+        assert!(output.contains("missing.txt"), "Unexpected output: {}", output);
+        assert_eq!(fs::read_to_string(temp_dir.path().join("out.txt"))?, "");
+
+        // Errors merged into standard output flow through the pipe.
+        let output = execute_shell_command(&mut shell_core, "cat missing.txt 2>&1 | grep missing").await;
+        assert!(output.contains("missing.txt"), "Unexpected output: {}", output);
+        assert_eq!(shell_core.last_exit_status, EXIT_SUCCESS);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_output_and_error_redirection() -> io::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let mut shell_core = ShellCore::new();
+        shell_core.current_dir = temp_dir.path().to_path_buf();
+
+        let output = execute_shell_command(&mut shell_core, "cat missing.txt &> all.txt; echo ok &>> all.txt").await;
+        assert!(output.is_empty(), "Output should be empty, but was: {}", output);
+        let contents = fs::read_to_string(temp_dir.path().join("all.txt"))?;
+        assert!(contents.contains("missing.txt"));
+        assert!(contents.ends_with("ok\n"));
+
+        // A command that cannot be started reports it on its own standard
+        // error, alone or in a pipeline.
+        #[cfg(unix)]
+        {
+            fs::write(temp_dir.path().join("not_executable.sh"), "echo never\n")?;
+            let output = execute_shell_command(&mut shell_core, "./not_executable.sh 2> err.log; echo $?").await;
+            assert_eq!(output, format!("{}\n", EXIT_CANNOT_EXECUTE));
+            let contents = fs::read_to_string(temp_dir.path().join("err.log"))?;
+            assert!(contents.starts_with("Error: Failed to spawn command './not_executable.sh'"), "{}", contents);
+            let output = execute_shell_command(&mut shell_core, "./not_executable.sh 2>&1 | wc -l").await;
+            assert_eq!(output.trim(), "1");
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_heredoc() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        let output = execute_shell_command(&mut shell_core, "cat <<EOF\nfirst line\nsecond line\nEOF").await;
+        assert_eq!(output.trim_end(), "first line\nsecond line");
+
+        let output = execute_shell_command(&mut shell_core, "grep second <<EOF | cat\nfirst\nsecond\nEOF").await;
+        assert_eq!(output.trim(), "second");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_heredoc_expansion() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        execute_shell_command(&mut shell_core, "nonexistent_command_12345").await;
+        let output = execute_shell_command(&mut shell_core, "cat <<EOF\nstatus $?\nEOF").await;
+        assert_eq!(output.trim_end(), "status 127");
+        let output = execute_shell_command(&mut shell_core, "cat <<'EOF'\nstatus $?\nEOF").await;
+        assert_eq!(output.trim_end(), "status $?");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_redirection_on_any_pipeline_stage() -> io::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let mut shell_core = ShellCore::new();
+        shell_core.current_dir = temp_dir.path().to_path_buf();
+        fs::write(temp_dir.path().join("fruits.txt"), "apple\nbanana\napricot\n")?;
+
+        let output = execute_shell_command(&mut shell_core, "cat < fruits.txt | grep ap > ap.txt | echo done").await;
+        assert_eq!(output, "done\n");
+        assert_eq!(fs::read_to_string(temp_dir.path().join("ap.txt"))?, "apple\napricot\n");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_redirection_paths_follow_current_dir() -> io::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        fs::create_dir(temp_dir.path().join("sub"))?;
+        let mut shell_core = ShellCore::new();
+        shell_core.current_dir = temp_dir.path().to_path_buf();
+
+        execute_shell_command(&mut shell_core, "cd sub; echo inside > note.txt").await;
+        assert_eq!(fs::read_to_string(temp_dir.path().join("sub").join("note.txt"))?, "inside\n");
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_external_commands_connected_by_pipe() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        let output = execute_shell_command(&mut shell_core, "printf 'b\\na\\nc\\n' | sort | head -n 2").await;
+        assert_eq!(output, "a\nb\n");
+
+        // The producer never ends on its own; it stops when `head` exits.
+        let output = execute_shell_command(&mut shell_core, "yes | head -n 2").await;
+        assert_eq!(output, "y\ny\n");
+        assert_eq!(shell_core.last_exit_status, EXIT_SUCCESS);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_stderr_merged_into_pipe() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        let output = execute_shell_command(&mut shell_core, "sh -c 'echo oops >&2' 2>&1 | grep oops").await;
+        assert_eq!(output, "oops\n");
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_output_streams_while_pipeline_runs() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        let (sink, mut receiver) = OutputSink::new();
+        let pipeline = tokio::spawn(async move {
+            let command = "sh -c 'echo first; sleep 3; echo last' | grep first";
+            execute_shell_command_streaming(&mut shell_core, command, &sink, &CancellationToken::new()).await;
+        });
+
+        let chunk = tokio::time::timeout(std::time::Duration::from_secs(2), receiver.recv()).await;
+        let chunk = chunk.expect("output should arrive before the pipeline finishes").unwrap();
+        assert_eq!(chunk, output::OutputChunk { stream: Stream::Stdout, text: "first\n".to_string() });
+        assert!(!pipeline.is_finished());
+        pipeline.await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_commands_run_in_subshell() -> io::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        fs::create_dir(temp_dir.path().join("sub"))?;
+        let mut shell_core = ShellCore::new();
+        shell_core.current_dir = temp_dir.path().to_path_buf();
+
+        let output = execute_shell_command(&mut shell_core, "cd sub | echo still here").await;
+        assert_eq!(output, "still here\n");
+        assert_eq!(shell_core.current_dir, temp_dir.path());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_builtins_are_looked_up_in_registry() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        let output = execute_shell_command(&mut shell_core, "type cd | grep builtin").await;
+        assert_eq!(output, "cd is a shell builtin\n");
+        let output = execute_shell_command(&mut shell_core, "help cd").await;
+        assert!(output.starts_with("cd [directory | -]\n"), "Unexpected output: {}", output);
+        Ok(())
+    }
+
+    /// Runs `command` and returns the chunks it wrote, in order.
+    async fn run_chunks(shell_core: &mut ShellCore, command: &str) -> Vec<output::OutputChunk> {
+        let (sink, mut receiver) = OutputSink::new();
+        execute_shell_command_streaming(shell_core, command, &sink, &CancellationToken::new()).await;
+        drop(sink);
+        std::iter::from_fn(|| receiver.try_recv().ok()).collect()
+    }
+
+    fn stream_text(chunks: &[output::OutputChunk], stream: Stream) -> String {
+        chunks.iter().filter(|c| c.stream == stream).map(|c| c.text.as_str()).collect()
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_external_streams_are_kept_apart() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        let chunks = run_chunks(&mut shell_core, "sh -c 'echo out; echo err >&2; exit 1'").await;
+        assert_eq!(stream_text(&chunks, Stream::Stdout), "out\n");
+        assert_eq!(stream_text(&chunks, Stream::Stderr), "err\n");
+        assert_eq!(shell_core.last_exit_status, 1);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_output_of_pipelines_is_not_separated() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        assert_eq!(execute_shell_command(&mut shell_core, "printf a; printf b").await, "ab");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_builtin_errors_go_to_stderr() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        let chunks = run_chunks(&mut shell_core, "echo before; cat nonexistent_file.txt; echo after").await;
+        assert_eq!(chunks.len(), 3, "{:?}", chunks);
+        assert_eq!(chunks[0], output::OutputChunk { stream: Stream::Stdout, text: "before\n".to_string() });
+        assert_eq!(chunks[1].stream, Stream::Stderr);
+        assert!(chunks[1].text.starts_with("Error: cat:"));
+        assert_eq!(chunks[2], output::OutputChunk { stream: Stream::Stdout, text: "after\n".to_string() });
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_redirections_move_output_between_streams() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        let chunks = run_chunks(&mut shell_core, "sh -c 'echo err >&2' 2>&1").await;
+        assert_eq!(chunks, vec![output::OutputChunk { stream: Stream::Stdout, text: "err\n".to_string() }]);
+
+        let chunks = run_chunks(&mut shell_core, "echo out >&2").await;
+        assert_eq!(chunks, vec![output::OutputChunk { stream: Stream::Stderr, text: "out\n".to_string() }]);
+
+        let chunks = run_chunks(&mut shell_core, "cat nonexistent_file.txt 2>/dev/null; echo done").await;
+        assert_eq!(chunks, vec![output::OutputChunk { stream: Stream::Stdout, text: "done\n".to_string() }]);
+        Ok(())
+    }
+
+    /// Runs `command` and interrupts it after `delay`, returning its output.
+    async fn run_interrupted(shell_core: &mut ShellCore, command: &str, delay: std::time::Duration) -> String {
+        let (sink, receiver) = OutputSink::new();
+        let cancellation = CancellationToken::new();
+        tokio::spawn({
+            let cancellation = cancellation.clone();
+            async move {
+                tokio::time::sleep(delay).await;
+                cancellation.cancel();
+            }
+        });
+        let run = execute_shell_command_streaming(shell_core, command, &sink, &cancellation);
+        tokio::time::timeout(std::time::Duration::from_secs(5), run)
+            .await
+            .expect("the command line should stop when interrupted");
+        output::collect(receiver)
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_interrupt_kills_external_command() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        let delay = std::time::Duration::from_millis(200);
+        let output = run_interrupted(&mut shell_core, "echo before; sleep 30; echo after", delay).await;
+        assert_eq!(output, "before\n^C [interrupted]\n");
+        assert_eq!(shell_core.last_exit_status, EXIT_INTERRUPTED);
+
+        let output = run_interrupted(&mut shell_core, "sleep 30 | cat", delay).await;
+        assert_eq!(output, "^C [interrupted]\n");
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_interrupt_stops_builtin() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        let delay = std::time::Duration::from_millis(200);
+        let output = run_interrupted(&mut shell_core, "cat /dev/zero > /dev/null", delay).await;
+        assert_eq!(output, "^C [interrupted]\n");
+        assert_eq!(shell_core.last_exit_status, EXIT_INTERRUPTED);
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_interrupt_kills_process_group() -> io::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let mut shell_core = ShellCore::new();
+        shell_core.current_dir = temp_dir.path().to_path_buf();
+
+        let delay = std::time::Duration::from_millis(300);
+        run_interrupted(&mut shell_core, "sh -c 'sleep 30 & echo $! > pid.txt; wait'", delay).await;
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        // The background `sleep` was started by the command, so it must be
+        // gone too (or at most a zombie waiting to be reaped).
+        let pid = fs::read_to_string(temp_dir.path().join("pid.txt"))?;
+        let stat = fs::read_to_string(format!("/proc/{}/stat", pid.trim())).unwrap_or_default();
+        let state = stat.rsplit(')').next().unwrap_or("").split_whitespace().next();
+        assert!(matches!(state, None | Some("Z")), "sleep still running: {}", stat);
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    #[tokio::test]
+    async fn test_interrupt_kills_process_tree() -> io::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let mut shell_core = ShellCore::new();
+        shell_core.current_dir = temp_dir.path().to_path_buf();
+
+        // The inner `cmd` is a grandchild of the shell, which writes the
+        // marker a few seconds later unless it is killed with its parent.
+        let command = "cmd /C cmd /C 'ping -n 4 127.0.0.1 > nul & echo survived> marker.txt'";
+        run_interrupted(&mut shell_core, command, std::time::Duration::from_millis(500)).await;
+        tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+        assert!(!temp_dir.path().join("marker.txt").exists(), "the grandchild kept running");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_if_follows_exit_status() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        let command = "for x in a b c; do if test_missing_cmd_123 2> /dev/null; then echo never; elif case $x in b) true;; *) false;; esac; then echo B; else echo other; fi; done";
+        assert_eq!(execute_shell_command(&mut shell_core, command).await, "other\nB\nother\n");
+        assert_eq!(execute_shell_command(&mut shell_core, "if false; then echo x; fi; echo $?").await, "0\n");
+        assert_eq!(execute_shell_command(&mut shell_core, "! false && ! true || echo negated").await, "negated\n");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_while_and_until_loops() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        let command = "n=; while case $n in xxx) false;; esac; do n=x$n; done; echo $n";
+        assert_eq!(execute_shell_command(&mut shell_core, command).await, "xxx\n");
+        let command = "n=\nuntil case $n in xx) true;; *) false;; esac\ndo\n  n=x$n\ndone\necho $n";
+        assert_eq!(execute_shell_command(&mut shell_core, command).await, "xx\n");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_for_loops_with_break_and_continue() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        let command = "for i in 1 2 3 4 5; do case $i in 2) continue;; 4) break;; esac; echo $i; done";
+        assert_eq!(execute_shell_command(&mut shell_core, command).await, "1\n3\n");
+        let command = "for a in 1 2; do for b in x y; do if case $b in y) true;; *) false;; esac; then continue 2; fi; echo $a$b; done; done";
+        assert_eq!(execute_shell_command(&mut shell_core, command).await, "1x\n2x\n");
+        let command = "for a in 1 2; do while true; do break 2; done; echo unreachable; done; echo $a";
+        assert_eq!(execute_shell_command(&mut shell_core, command).await, "1\n");
+
+        shell_core.positional_params = vec!["p q".to_string(), "r".to_string()];
+        assert_eq!(execute_shell_command(&mut shell_core, "for p do echo \"[$p]\"; done").await, "[p q]\n[r]\n");
+        assert_eq!(shell_core.loop_depth, 0);
+
+        let output = execute_shell_command(&mut shell_core, "break; echo after").await;
+        assert!(output.contains("break: only meaningful in a `for', `while', or `until' loop"));
+        assert!(output.ends_with("after\n"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_case_patterns() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        let command = "for f in main.rs Cargo.toml README 'a*'; do case $f in *.rs) echo rust;; [A-Z]*.toml|*.json) echo config;; 'a*') echo star;; *) echo other;; esac; done";
+        assert_eq!(execute_shell_command(&mut shell_core, command).await, "rust\nconfig\nother\nstar\n");
+        assert_eq!(execute_shell_command(&mut shell_core, "false; case x in y) echo y;; esac; echo $?").await, "0\n");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_compound_commands_share_redirections_and_pipes() -> io::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let mut shell_core = ShellCore::new();
+        shell_core.current_dir = temp_dir.path().to_path_buf();
+
+        let output = execute_shell_command(&mut shell_core, "for x in a b c; do echo $x; done | grep b").await;
+        assert_eq!(output, "b\n");
+        let output = execute_shell_command(&mut shell_core, "{ echo b1; echo b2; } | grep b2").await;
+        assert_eq!(output, "b2\n");
+        execute_shell_command(&mut shell_core, "if true; then echo one; echo two; fi > out.txt").await;
+        assert_eq!(fs::read_to_string(temp_dir.path().join("out.txt"))?, "one\ntwo\n");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_interrupt_stops_loop() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        let delay = std::time::Duration::from_millis(100);
+        let output = run_interrupted(&mut shell_core, "while true; do :; done; echo after", delay).await;
+        assert_eq!(output, "^C [interrupted]\n");
+        assert_eq!(shell_core.loop_depth, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_functions_get_positional_parameters() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        shell_core.positional_params = vec!["outer".to_string()];
+        execute_shell_command(&mut shell_core, "greet() {\n  echo \"$# $1 [$@]\"\n}").await;
+        assert_eq!(execute_shell_command(&mut shell_core, "greet 'a b' c; echo \" $1\"").await, "2 a b [a b c]\n outer\n");
+        assert_eq!(execute_shell_command(&mut shell_core, "type greet").await, "greet is a function\n");
+
+        // Functions take precedence over builtins and run inside pipelines.
+        execute_shell_command(&mut shell_core, "function ls { echo \"listing $1\"; }").await;
+        assert_eq!(execute_shell_command(&mut shell_core, "ls src | grep listing").await.trim(), "listing src");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_local_variables_and_return() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        let command = "x=global; f() { local x=inner y; y=set; echo \"$x $y \"; g; return 3; echo unreachable; }; g() { echo \"$x\"; }";
+        execute_shell_command(&mut shell_core, command).await;
+        assert_eq!(execute_shell_command(&mut shell_core, "f; echo \" $? $x [$y]\"").await, "inner set \ninner\n 3 global []\n");
+
+        // `return` leaves loops inside the function, but not the caller's.
+        let command = "first() { for a in 1 2; do for b in x y; do return $a$a; done; done; }; for i in 1 2; do first; echo $?; done";
+        assert_eq!(execute_shell_command(&mut shell_core, command).await, "11\n11\n");
+        assert_eq!(shell_core.local_scopes.len(), 0);
+
+        let output = execute_shell_command(&mut shell_core, "return 1; echo after").await;
+        assert!(output.contains("return: can only `return' from a function"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_command_line_assignments_are_local_to_functions() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        execute_shell_command(&mut shell_core, "show() { echo \"[$MODE]\"; }; MODE=outer").await;
+        assert_eq!(execute_shell_command(&mut shell_core, "MODE=inner show; show").await, "[inner]\n[outer]\n");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_runaway_recursion_is_stopped() -> io::Result<()> {
+        let mut shell_core = ShellCore::new();
+        let output = execute_shell_command(&mut shell_core, "f() { f; }; f; echo $?").await;
+        assert!(output.contains("f: maximum function nesting level exceeded (1000)"), "{}", output);
+        assert!(output.ends_with("1\n"));
+        assert!(shell_core.local_scopes.is_empty());
+        Ok(())
+    }
 }
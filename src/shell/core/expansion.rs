@@ -0,0 +1,590 @@
+//! Word expansion, run on every command before it executes.
+//!
+//! Braces such as `{a,b}` are expanded first (`brace.rs`), followed by a
+//! leading `~` (`tilde.rs`). Then parameters such as `$HOME`, `${VAR:-default}` or `$?` are replaced with their
+//! values, Windows-style `%VAR%` references are replaced for variables that
+//! are set, command substitutions (`$(...)` and `` `...` ``) are replaced
+//! with the output of their commands, the results of unquoted expansions are
+//! split into fields at the characters of `IFS`, and quotes are removed.
+//! Finally, fields with unquoted wildcards are replaced with the files they
+//! match (`glob.rs`). Text in single quotes is never expanded, and
+//! expansions inside double quotes are not split.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+use crate::shell::core::brace;
+use crate::shell::core::cancellation::CancellationToken;
+use crate::shell::core::command_executor;
+use crate::shell::core::glob;
+use crate::shell::core::output::OutputSink;
+use crate::shell::core::parser::{ast, lexer};
+use crate::shell::core::pattern::Pattern;
+use crate::shell::core::tilde;
+use crate::shell::core::ShellCore;
+
+/// Field separators used when `IFS` is not set.
+const DEFAULT_IFS: &str = " \t\n";
+
+/// Where a character of an expanded word came from, which decides whether
+/// it is subject to field splitting and pattern matching.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Origin {
+    /// Unquoted text of the command line.
+    Literal,
+    /// The result of an unquoted expansion.
+    Expanded,
+    /// Quoted text or the result of a quoted expansion.
+    Quoted,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Piece {
+    Char(char, Origin),
+    /// Ends the current field, as between the parameters of `"$@"`.
+    Break,
+    /// Marks a word containing quotes, which yields a field even if empty.
+    Quoted,
+}
+
+/// Expands the words of a command.
+///
+/// Command substitutions run against the same shell state as the command
+/// itself; their standard error goes to `output`.
+pub struct Expander<'a> {
+    shell_core: &'a mut ShellCore,
+    output: &'a OutputSink,
+    cancellation: &'a CancellationToken,
+    /// Whether command substitutions may run, see `expand_words_without_commands`.
+    run_commands: bool,
+    /// The exit status of the last command substitution, which becomes the
+    /// status of a command consisting only of assignments.
+    pub substitution_status: Option<i32>,
+}
+
+impl<'a> Expander<'a> {
+    /// Creates an expander.
+    ///
+    /// # Arguments
+    ///
+    /// * `shell_core` - The shell state providing variables and parameters.
+    /// * `output` - Receives the standard error of command substitutions.
+    /// * `cancellation` - Interrupts command substitutions.
+    pub fn new(shell_core: &'a mut ShellCore, output: &'a OutputSink, cancellation: &'a CancellationToken) -> Self {
+        Self { shell_core, output, cancellation, run_commands: true, substitution_status: None }
+    }
+
+    /// Expands words into the fields that become a command's name and
+    /// arguments.
+    ///
+    /// A word may produce several fields through brace expansion, field
+    /// splitting, `"$@"` or filename generation, or none at all if it is an
+    /// unquoted expansion of an empty value.
+    ///
+    /// # Returns
+    ///
+    /// The fields, or an error message from `${VAR:?message}`, a pattern
+    /// matching nothing under `failglob`, and the like.
+    pub async fn expand_words(&mut self, words: &[ast::Word]) -> Result<Vec<String>, String> {
+        let mut fields = Vec::new();
+        for word in words.iter().flat_map(brace::expand_braces) {
+            let word = tilde::expand_tilde(self.shell_core, &word, false);
+            let mut pieces = Vec::new();
+            self.expand_parts(&word.parts, Origin::Literal, &mut pieces).await?;
+            // `IFS` is read for every word, as an earlier one may assign it.
+            for field in split_fields(&pieces, &ifs(self.shell_core)) {
+                fields.extend(self.generate_filenames(&field)?);
+            }
+        }
+        Ok(fields)
+    }
+
+    /// Replaces a field containing unquoted wildcards with the files it
+    /// matches. What happens when nothing matches depends on the `failglob`
+    /// and `nullglob` options.
+    fn generate_filenames(&self, field: &[(char, Origin)]) -> Result<Vec<String>, String> {
+        let text: String = field.iter().map(|(c, _)| c).collect();
+        let pattern: Vec<(char, bool)> = field.iter().map(|(c, origin)| (*c, *origin == Origin::Quoted)).collect();
+        if !glob::has_wildcards(&pattern) {
+            return Ok(vec![text]);
+        }
+        let options = &self.shell_core.options;
+        let matches = glob::glob(&self.shell_core.current_dir, &pattern, options.dotglob);
+        if !matches.is_empty() {
+            Ok(matches)
+        } else if options.failglob {
+            Err(format!("no match: {}", text))
+        } else if options.nullglob {
+            Ok(Vec::new())
+        } else {
+            Ok(vec![text])
+        }
+    }
+
+    /// Expands a word into a single string, without field splitting, as for
+    /// the value of an assignment or the target of a redirection.
+    pub async fn expand_word(&mut self, word: &ast::Word) -> Result<String, String> {
+        let word = tilde::expand_tilde(self.shell_core, word, false);
+        let mut pieces = Vec::new();
+        self.expand_parts(&word.parts, Origin::Literal, &mut pieces).await?;
+        Ok(join_pieces(&pieces))
+    }
+
+    /// Expands the value of an assignment, which is like `expand_word` except
+    /// that a `~` after any `:` is expanded too.
+    pub async fn expand_assignment(&mut self, word: &ast::Word) -> Result<String, String> {
+        let word = tilde::expand_tilde(self.shell_core, word, true);
+        let mut pieces = Vec::new();
+        self.expand_parts(&word.parts, Origin::Literal, &mut pieces).await?;
+        Ok(join_pieces(&pieces))
+    }
+
+    /// Expands the body of an unquoted here-document, in which parameters
+    /// and command substitutions are expanded as inside double quotes.
+    pub async fn expand_heredoc(&mut self, body: &str) -> Result<String, String> {
+        let parts = lexer::parse_heredoc_body(body).map_err(|e| e.to_string())?;
+        let mut pieces = Vec::new();
+        self.expand_parts(&parts, Origin::Quoted, &mut pieces).await?;
+        Ok(join_pieces(&pieces))
+    }
+
+    /// Expands the parts of a word.
+    ///
+    /// # Arguments
+    ///
+    /// * `parts` - The parts to expand.
+    /// * `literal` - The origin given to literal text: `Literal` for the
+    ///   words of a command, `Quoted` inside double quotes, and `Expanded`
+    ///   for the operand of an unquoted `${VAR:-word}`, whose result is split.
+    /// * `pieces` - Receives the expanded characters.
+    fn expand_parts<'b>(
+        &'b mut self,
+        parts: &'b [ast::WordPart],
+        literal: Origin,
+        pieces: &'b mut Vec<Piece>,
+    ) -> Pin<Box<dyn Future<Output = Result<(), String>> + Send + 'b>> {
+        Box::pin(async move {
+            let expanded = if literal == Origin::Quoted { Origin::Quoted } else { Origin::Expanded };
+            for part in parts {
+                match part {
+                    ast::WordPart::Literal(text) => {
+                        expand_percent_vars(self.shell_core, text, literal, expanded, pieces);
+                    }
+                    ast::WordPart::Quoted(text) => {
+                        pieces.push(Piece::Quoted);
+                        push_str(pieces, text, Origin::Quoted);
+                    }
+                    ast::WordPart::DoubleQuoted(inner) => {
+                        // `"$@"` without positional parameters yields no field at all.
+                        let only_all_params = matches!(
+                            inner.as_slice(),
+                            [ast::WordPart::Parameter(ast::Parameter { name, op: ast::ParameterOp::Value })] if name == "@"
+                        );
+                        if !(only_all_params && self.shell_core.positional_params.is_empty()) {
+                            pieces.push(Piece::Quoted);
+                        }
+                        self.expand_parts(inner, Origin::Quoted, pieces).await?;
+                    }
+                    ast::WordPart::Parameter(parameter) => self.expand_parameter(parameter, expanded, pieces).await?,
+                    ast::WordPart::CommandSubstitution(source) => {
+                        let text = self.substitute(source).await?;
+                        push_str(pieces, &text, expanded);
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Runs the commands of a command substitution and returns their
+    /// standard output without trailing newlines.
+    async fn substitute(&mut self, source: &str) -> Result<String, String> {
+        if !self.run_commands {
+            return Err("command substitution is not allowed here".to_string());
+        }
+        let stdout = command_executor::command_substitution(self.shell_core, source, self.output, self.cancellation).await?;
+        self.substitution_status = Some(self.shell_core.last_exit_status);
+        Ok(stdout.trim_end_matches('\n').to_string())
+    }
+
+    async fn expand_parameter(&mut self, parameter: &ast::Parameter, origin: Origin, pieces: &mut Vec<Piece>) -> Result<(), String> {
+        let name = parameter.name.as_str();
+        let value = lookup(self.shell_core, name);
+        // Whether the `:`-less or `:` form of an operator should use its word.
+        let use_word = |colon: bool| match &value {
+            None => true,
+            Some(value) => colon && value.is_empty(),
+        };
+        // The operand is split like the expansion itself when unquoted.
+        let operand_origin = if origin == Origin::Quoted { Origin::Quoted } else { Origin::Expanded };
+
+        match &parameter.op {
+            ast::ParameterOp::Value => push_value(self.shell_core, name, value, origin, pieces),
+            ast::ParameterOp::Length => {
+                let length = match name {
+                    "@" | "*" => self.shell_core.positional_params.len(),
+                    _ => value.map_or(0, |value| value.chars().count()),
+                };
+                push_str(pieces, &length.to_string(), origin);
+            }
+            ast::ParameterOp::Default { word, colon } => {
+                if use_word(*colon) {
+                    let word = tilde::expand_tilde(self.shell_core, word, false);
+                    self.expand_parts(&word.parts, operand_origin, pieces).await?;
+                } else {
+                    push_value(self.shell_core, name, value, origin, pieces);
+                }
+            }
+            ast::ParameterOp::Assign { word, colon } => {
+                if use_word(*colon) {
+                    if !is_name(name) {
+                        return Err(format!("${{{}}}: cannot assign in this way", name));
+                    }
+                    let value = self.expand_word(word).await?;
+                    self.shell_core.set_var(name, &value);
+                    push_str(pieces, &value, origin);
+                } else {
+                    push_value(self.shell_core, name, value, origin, pieces);
+                }
+            }
+            ast::ParameterOp::Alternative { word, colon } => {
+                if !use_word(*colon) {
+                    let word = tilde::expand_tilde(self.shell_core, word, false);
+                    self.expand_parts(&word.parts, operand_origin, pieces).await?;
+                }
+            }
+            ast::ParameterOp::Error { word, colon } => {
+                if use_word(*colon) {
+                    let message = if word.parts.is_empty() {
+                        "parameter null or not set".to_string()
+                    } else {
+                        self.expand_word(word).await?
+                    };
+                    return Err(format!("{}: {}", name, message));
+                }
+                push_value(self.shell_core, name, value, origin, pieces);
+            }
+            ast::ParameterOp::RemovePrefix { pattern, longest } => {
+                let pattern = self.compile_pattern(pattern).await?;
+                push_str(pieces, &pattern.strip_prefix(&value.unwrap_or_default(), *longest), origin);
+            }
+            ast::ParameterOp::RemoveSuffix { pattern, longest } => {
+                let pattern = self.compile_pattern(pattern).await?;
+                push_str(pieces, &pattern.strip_suffix(&value.unwrap_or_default(), *longest), origin);
+            }
+        }
+        Ok(())
+    }
+
+    /// Expands the pattern of `${VAR#pattern}` or of a `case` item, keeping
+    /// track of which of its characters were quoted.
+    pub async fn compile_pattern(&mut self, word: &ast::Word) -> Result<Pattern, String> {
+        let mut pieces = Vec::new();
+        self.expand_parts(&word.parts, Origin::Literal, &mut pieces).await?;
+        let chars: Vec<(char, bool)> = pieces
+            .iter()
+            .filter_map(|piece| match piece {
+                Piece::Char(c, origin) => Some((*c, *origin == Origin::Quoted)),
+                _ => None,
+            })
+            .collect();
+        Ok(Pattern::new(&chars))
+    }
+}
+
+/// Expands words without running command substitutions, to decide how a
+/// command line will run before running it.
+///
+/// # Returns
+///
+/// The fields, or `None` if the words contain a command substitution or
+/// fail to expand.
+pub fn expand_words_without_commands(shell_core: &mut ShellCore, words: &[ast::Word]) -> Option<Vec<String>> {
+    let (output, _) = OutputSink::new();
+    let cancellation = CancellationToken::new();
+    let mut expander = Expander::new(shell_core, &output, &cancellation);
+    expander.run_commands = false;
+    let mut expansion = std::pin::pin!(expander.expand_words(words));
+    // Only command substitutions wait for anything, so without them the
+    // expansion completes on the first poll.
+    match expansion.as_mut().poll(&mut Context::from_waker(Waker::noop())) {
+        Poll::Ready(fields) => fields.ok(),
+        Poll::Pending => None,
+    }
+}
+
+fn ifs(shell_core: &ShellCore) -> String {
+    shell_core.get_var("IFS").unwrap_or_else(|| DEFAULT_IFS.to_string())
+}
+
+fn join_pieces(pieces: &[Piece]) -> String {
+    pieces
+        .iter()
+        .filter_map(|piece| match piece {
+            Piece::Char(c, _) => Some(*c),
+            Piece::Break => Some(' '),
+            Piece::Quoted => None,
+        })
+        .collect()
+}
+
+fn push_str(pieces: &mut Vec<Piece>, text: &str, origin: Origin) {
+    pieces.extend(text.chars().map(|c| Piece::Char(c, origin)));
+}
+
+/// Replaces `%NAME%` in literal text with the value of the variable `NAME`,
+/// leaving references to unset variables as they are.
+fn expand_percent_vars(shell_core: &ShellCore, text: &str, literal: Origin, expanded: Origin, pieces: &mut Vec<Piece>) {
+    let mut rest = text;
+    while let Some(start) = rest.find('%') {
+        let after = &rest[start + 1..];
+        let reference = after.find('%').map(|end| &after[..end]).filter(|name| is_name(name));
+        match reference.and_then(|name| Some((name, shell_core.get_var(name)?))) {
+            Some((name, value)) => {
+                push_str(pieces, &rest[..start], literal);
+                push_str(pieces, &value, expanded);
+                rest = &after[name.len() + 1..];
+            }
+            None => {
+                push_str(pieces, &rest[..start + 1], literal);
+                rest = after;
+            }
+        }
+    }
+    push_str(pieces, rest, literal);
+}
+
+/// Returns whether `name` is a valid variable name.
+pub fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Looks up a parameter, returning `None` if it is unset.
+fn lookup(shell_core: &ShellCore, name: &str) -> Option<String> {
+    match name {
+        "?" => Some(shell_core.last_exit_status.to_string()),
+        "$" => Some(std::process::id().to_string()),
+        "#" => Some(shell_core.positional_params.len().to_string()),
+        "0" => Some(shell_core.shell_name.clone()),
+        "@" | "*" => Some(shell_core.positional_params.join(" ")),
+        _ if name.chars().all(|c| c.is_ascii_digit()) => {
+            let index: usize = name.parse().ok()?;
+            shell_core.positional_params.get(index.checked_sub(1)?).cloned()
+        }
+        _ => shell_core.get_var(name),
+    }
+}
+
+/// Adds the value of a parameter. Inside double quotes, `$@` becomes one
+/// field per positional parameter and `$*` joins them with the first
+/// character of `IFS`.
+fn push_value(shell_core: &ShellCore, name: &str, value: Option<String>, origin: Origin, pieces: &mut Vec<Piece>) {
+    match name {
+        "@" if origin == Origin::Quoted => {
+            for (index, param) in shell_core.positional_params.iter().enumerate() {
+                if index > 0 {
+                    pieces.push(Piece::Break);
+                }
+                push_str(pieces, param, origin);
+            }
+        }
+        "*" if origin == Origin::Quoted => {
+            let separator: String = ifs(shell_core).chars().take(1).collect();
+            push_str(pieces, &shell_core.positional_params.join(&separator), origin);
+        }
+        _ => push_str(pieces, &value.unwrap_or_default(), origin),
+    }
+}
+
+/// Splits expanded characters into fields.
+///
+/// Only characters produced by unquoted expansions are split. Runs of `IFS`
+/// whitespace separate fields, while every other `IFS` character ends a
+/// field, so `a::b` with `IFS=:` gives an empty field in the middle.
+fn split_fields(pieces: &[Piece], ifs: &str) -> Vec<Vec<(char, Origin)>> {
+    let mut fields = Vec::new();
+    let mut current = Vec::new();
+    // Whether the current field exists even if it is still empty.
+    let mut started = false;
+    // Whether the field was just ended by whitespace, which a following
+    // non-whitespace separator belongs to.
+    let mut after_whitespace = false;
+    for piece in pieces {
+        match piece {
+            Piece::Char(c, Origin::Expanded) if ifs.contains(*c) => {
+                if c.is_whitespace() {
+                    if started {
+                        fields.push(std::mem::take(&mut current));
+                        started = false;
+                        after_whitespace = true;
+                    }
+                } else {
+                    if started || !after_whitespace {
+                        fields.push(std::mem::take(&mut current));
+                    }
+                    started = false;
+                    after_whitespace = false;
+                }
+                continue;
+            }
+            Piece::Char(c, origin) => {
+                current.push((*c, *origin));
+                started = true;
+            }
+            Piece::Break => {
+                fields.push(std::mem::take(&mut current));
+                started = false;
+            }
+            Piece::Quoted => started = true,
+        }
+        after_whitespace = false;
+    }
+    if started {
+        fields.push(current);
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shell::core::parser::{self, ast::Command};
+
+    /// Expands the words of a simple command line.
+    async fn expand(shell_core: &mut ShellCore, line: &str) -> Result<Vec<String>, String> {
+        let list = parser::parse(line, &shell_core.aliases).unwrap();
+        let Command::Simple(command) = &list.items[0].first.commands[0] else { panic!("expected a simple command") };
+        let (output, _) = OutputSink::new();
+        let cancellation = CancellationToken::new();
+        Expander::new(shell_core, &output, &cancellation).expand_words(&command.words).await
+    }
+
+    fn shell() -> ShellCore {
+        let mut shell_core = ShellCore::new();
+        shell_core.variables.insert("NAME".to_string(), "world".to_string());
+        shell_core.variables.insert("SPACED".to_string(), " a  b ".to_string());
+        shell_core.variables.insert("EMPTY".to_string(), String::new());
+        shell_core.variables.insert("FILE".to_string(), "/tmp/archive.tar.gz".to_string());
+        shell_core
+    }
+
+    #[tokio::test]
+    async fn test_simple_parameters() {
+        let mut shell_core = shell();
+        assert_eq!(expand(&mut shell_core, "echo $NAME ${NAME}s x$NAME-y").await.unwrap(), ["echo", "world", "worlds", "xworld-y"]);
+        assert_eq!(expand(&mut shell_core, "echo $UNSET_VARIABLE_123 $ a$").await.unwrap(), ["echo", "$", "a$"]);
+    }
+
+    #[tokio::test]
+    async fn test_quoting_controls_expansion_and_splitting() {
+        let mut shell_core = shell();
+        assert_eq!(expand(&mut shell_core, "echo '$NAME' \"$NAME\" \\$NAME").await.unwrap(), ["echo", "$NAME", "world", "$NAME"]);
+        assert_eq!(expand(&mut shell_core, "echo $SPACED").await.unwrap(), ["echo", "a", "b"]);
+        assert_eq!(expand(&mut shell_core, "echo \"$SPACED\"").await.unwrap(), ["echo", " a  b "]);
+        assert_eq!(expand(&mut shell_core, "echo $EMPTY \"$EMPTY\" ''").await.unwrap(), ["echo", "", ""]);
+    }
+
+    #[tokio::test]
+    async fn test_custom_ifs() {
+        let mut shell_core = shell();
+        shell_core.variables.insert("IFS".to_string(), ":".to_string());
+        shell_core.variables.insert("LIST".to_string(), "a::b c".to_string());
+        assert_eq!(expand(&mut shell_core, "echo $LIST").await.unwrap(), ["echo", "a", "", "b c"]);
+    }
+
+    #[tokio::test]
+    async fn test_default_and_assign() {
+        let mut shell_core = shell();
+        assert_eq!(expand(&mut shell_core, "echo ${EMPTY:-fallback} \"${EMPTY-fallback}\"").await.unwrap(), ["echo", "fallback", ""]);
+        assert_eq!(expand(&mut shell_core, "echo ${UNSET_X:-a b} \"${UNSET_X:-a b}\"").await.unwrap(), ["echo", "a", "b", "a b"]);
+        assert_eq!(expand(&mut shell_core, "echo ${NAME:+set} ${UNSET_X:+set}").await.unwrap(), ["echo", "set"]);
+        assert_eq!(expand(&mut shell_core, "echo ${NEW_VAR:=$NAME!}").await.unwrap(), ["echo", "world!"]);
+        assert_eq!(shell_core.variables.get("NEW_VAR"), Some(&"world!".to_string()));
+        assert!(expand(&mut shell_core, "echo ${1:=x}").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_error_operator() {
+        let mut shell_core = shell();
+        assert_eq!(expand(&mut shell_core, "echo ${NAME:?missing}").await.unwrap(), ["echo", "world"]);
+        assert_eq!(expand(&mut shell_core, "echo ${EMPTY:?is required}").await, Err("EMPTY: is required".to_string()));
+        assert_eq!(expand(&mut shell_core, "echo ${UNSET_X?}").await, Err("UNSET_X: parameter null or not set".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_length_and_pattern_removal() {
+        let mut shell_core = shell();
+        assert_eq!(expand(&mut shell_core, "echo ${#NAME} ${#UNSET_X}").await.unwrap(), ["echo", "5", "0"]);
+        assert_eq!(
+            expand(&mut shell_core, "echo ${FILE##*/} ${FILE#*/} ${FILE%.*} ${FILE%%.*}").await.unwrap(),
+            ["echo", "archive.tar.gz", "tmp/archive.tar.gz", "/tmp/archive.tar", "/tmp/archive"]
+        );
+        assert_eq!(expand(&mut shell_core, "echo ${FILE%'.*'} ${FILE%\".gz\"}").await.unwrap(), ["echo", "/tmp/archive.tar.gz", "/tmp/archive.tar"]);
+    }
+
+    #[tokio::test]
+    async fn test_special_parameters() {
+        let mut shell_core = shell();
+        shell_core.last_exit_status = 3;
+        shell_core.positional_params = vec!["one".to_string(), "two words".to_string()];
+        assert_eq!(expand(&mut shell_core, "echo $? $# $1 ${2}").await.unwrap(), ["echo", "3", "2", "one", "two", "words"]);
+        assert_eq!(expand(&mut shell_core, "echo $$").await.unwrap(), ["echo", &std::process::id().to_string()]);
+        assert_eq!(expand(&mut shell_core, "echo $0").await.unwrap(), ["echo", env!("CARGO_PKG_NAME")]);
+        assert_eq!(expand(&mut shell_core, "echo \"$@\"").await.unwrap(), ["echo", "one", "two words"]);
+        assert_eq!(expand(&mut shell_core, "echo \"x$@y\"").await.unwrap(), ["echo", "xone", "two wordsy"]);
+        assert_eq!(expand(&mut shell_core, "echo \"$*\" $3").await.unwrap(), ["echo", "one two words"]);
+
+        shell_core.positional_params.clear();
+        assert_eq!(expand(&mut shell_core, "echo \"$@\" $#").await.unwrap(), ["echo", "0"]);
+    }
+
+    #[tokio::test]
+    async fn test_percent_variables() {
+        let mut shell_core = shell();
+        assert_eq!(
+            expand(&mut shell_core, "echo Hello %NAME% and%NAME%s '%NAME%' \"%NAME%\"").await.unwrap(),
+            ["echo", "Hello", "world", "andworlds", "%NAME%", "world"]
+        );
+        assert_eq!(expand(&mut shell_core, "echo %NON_EXISTENT_VAR% 100% %EMPTY%").await.unwrap(), ["echo", "%NON_EXISTENT_VAR%", "100%"]);
+    }
+
+    #[tokio::test]
+    async fn test_command_substitution() {
+        let mut shell_core = shell();
+        assert_eq!(expand(&mut shell_core, "echo $(echo a  b) \"$(echo a  b)\" x`echo $NAME`").await.unwrap(), ["echo", "a", "b", "a b", "xworld"]);
+        assert_eq!(expand(&mut shell_core, "echo $(echo $(echo nested)) \"$(echo 'a)b')\"").await.unwrap(), ["echo", "nested", "a)b"]);
+        assert_eq!(expand(&mut shell_core, "echo ${UNSET_X:-$(echo lazy)}").await.unwrap(), ["echo", "lazy"]);
+    }
+
+    #[tokio::test]
+    async fn test_tilde() {
+        let mut shell_core = shell();
+        shell_core.variables.insert("HOME".to_string(), "/home/my user".to_string());
+        let here = shell_core.current_dir.to_string_lossy().into_owned();
+        assert_eq!(
+            expand(&mut shell_core, "echo ~ ~/x '~' \\~ \"~\" ${UNSET_X:-~/y} {~,~+}").await.unwrap(),
+            ["echo", "/home/my user", "/home/my user/x", "~", "~", "~", "/home/my user/y", "/home/my user", here.as_str()]
+        );
+    }
+
+    #[test]
+    fn test_expansion_without_commands() {
+        let mut shell_core = shell();
+        let list = parser::parse("vim $NAME $(rm -rf /tmp/x)", &shell_core.aliases).unwrap();
+        let Command::Simple(command) = &list.items[0].first.commands[0] else { panic!("expected a simple command") };
+        assert_eq!(expand_words_without_commands(&mut shell_core, &command.words[..2]), Some(vec!["vim".to_string(), "world".to_string()]));
+        assert_eq!(expand_words_without_commands(&mut shell_core, &command.words), None);
+    }
+
+    #[tokio::test]
+    async fn test_heredoc_body() {
+        let mut shell_core = shell();
+        let (output, _) = OutputSink::new();
+        let cancellation = CancellationToken::new();
+        let mut expander = Expander::new(&mut shell_core, &output, &cancellation);
+        assert_eq!(expander.expand_heredoc("Hi $NAME, \\$NAME '${#NAME}'\n").await.unwrap(), "Hi world, $NAME '5'\n");
+    }
+}
@@ -3,10 +3,24 @@
 use std::process::{Command, Stdio};
 use std::path::PathBuf;
 use std::env;
+use crate::shell::core::command_executor::{EXIT_CANNOT_EXECUTE, EXIT_FAILURE, EXIT_NOT_FOUND};
+
+/// The captured output of an external command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExternalOutput {
+    /// The exit status; `127` if the command was not found.
+    pub status: i32,
+    pub stdout: String,
+    pub stderr: String,
+}
 
 /// Executes an external command that is not a built-in shell command.
 /// It searches for the command in the system's PATH.
-pub async fn execute_external_command(command: &str, args: &[&str]) -> String {
+///
+/// Standard output and standard error are captured separately and both are
+/// returned, whether the command succeeds or fails.
+pub async fn execute_external_command(command: &str, args: &[&str]) -> ExternalOutput {
+    let failure = |status, stderr| ExternalOutput { status, stdout: String::new(), stderr };
     match find_executable_in_path(command) {
         Some(path) => {
             match Command::new(&path)
@@ -15,17 +29,15 @@ pub async fn execute_external_command(command: &str, args: &[&str]) -> String {
                 .stderr(Stdio::piped())
                 .output()
             {
-                Ok(output) => {
-                    if output.status.success() {
-                        decode_output(&output.stdout)
-                    } else {
-                        decode_output(&output.stderr)
-                    }
-                }
-                Err(e) => format!("Error executing command: {}", e),
+                Ok(output) => ExternalOutput {
+                    status: output.status.code().unwrap_or(EXIT_FAILURE),
+                    stdout: decode_output(&output.stdout),
+                    stderr: decode_output(&output.stderr),
+                },
+                Err(e) => failure(EXIT_CANNOT_EXECUTE, format!("Error executing command: {}", e)),
             }
         }
-        None => format!("command not found: {}", command),
+        None => failure(EXIT_NOT_FOUND, format!("command not found: {}", command)),
     }
 }
 
@@ -47,7 +59,7 @@ fn decode_output(bytes: &[u8]) -> String {
 }
 
 /// Searches for an executable in the directories listed in the PATH environment variable.
-fn find_executable_in_path(command: &str) -> Option<PathBuf> {
+pub fn find_executable_in_path(command: &str) -> Option<PathBuf> {
     env::var_os("PATH").and_then(|paths| {
         env::split_paths(&paths).find_map(|dir| {
             // Append extensions for Windows executables
@@ -82,6 +94,17 @@ mod tests {
     #[tokio::test]
     async fn test_command_not_found() {
         let output = execute_external_command("nonexistentcommand12345", &[]).await;
-        assert!(output.contains("command not found"));
+        assert!(output.stderr.contains("command not found"));
+        assert_eq!(output.status, EXIT_NOT_FOUND);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_both_streams_are_captured() {
+        let output = execute_external_command("sh", &["-c", "echo out; echo err >&2; exit 3"]).await;
+        assert_eq!(output, ExternalOutput { status: 3, stdout: "out\n".to_string(), stderr: "err\n".to_string() });
+
+        let output = execute_external_command("sh", &["-c", "echo out; echo err >&2"]).await;
+        assert_eq!((output.status, output.stderr.as_str()), (0, "err\n"));
     }
 }
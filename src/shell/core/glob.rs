@@ -0,0 +1,187 @@
+//! Filename generation: matching the words of a command against files.
+//!
+//! A word with unquoted wildcards (`*`, `?`, `[...]`) is replaced with the
+//! sorted paths that match it, resolved against the shell's current
+//! directory. A path component that is just `**` matches any number of
+//! directories, so `src/**/*.rs` finds Rust files at any depth.
+//!
+//! Files whose names start with a dot are only matched by a pattern that
+//! starts with a dot, unless the `dotglob` option is set, in which case the
+//! same entries are matched that `ls -a` lists (never `.` and `..`).
+
+use std::fs;
+use std::path::Path;
+
+use crate::shell::core::pattern::Pattern;
+
+/// Returns whether a word, given as characters with whether each was
+/// quoted, contains wildcards and is therefore subject to globbing.
+pub fn has_wildcards(word: &[(char, bool)]) -> bool {
+    Pattern::new(word).has_wildcards()
+}
+
+/// Finds the paths matching a pattern.
+///
+/// # Arguments
+///
+/// * `current_dir` - The directory relative patterns are resolved against.
+/// * `pattern` - The characters of the pattern, with whether each was quoted.
+/// * `dotglob` - Whether wildcards also match names starting with a dot.
+///
+/// # Returns
+///
+/// The matching paths, written relative to `current_dir` unless the pattern
+/// is absolute, in sorted order.
+pub fn glob(current_dir: &Path, pattern: &[(char, bool)], dotglob: bool) -> Vec<String> {
+    let absolute = pattern.first().is_some_and(|(c, _)| *c == '/');
+    // A trailing slash only matches directories, and is kept in the results.
+    let trailing_slash = pattern.len() > 1 && pattern.last().is_some_and(|(c, _)| *c == '/');
+    let components: Vec<&[(char, bool)]> = pattern.split(|(c, _)| *c == '/').filter(|c| !c.is_empty()).collect();
+
+    let mut paths = vec![if absolute { "/".to_string() } else { String::new() }];
+    for (index, component) in components.iter().enumerate() {
+        let last = index + 1 == components.len() && !trailing_slash;
+        let text: String = component.iter().map(|(c, _)| c).collect();
+        let compiled = Pattern::new(component);
+        let mut next = Vec::new();
+        for prefix in &paths {
+            let dir = current_dir.join(if prefix.is_empty() { "." } else { prefix.as_str() });
+            if text == "**" && component.iter().all(|(_, quoted)| !quoted) {
+                if !last {
+                    next.push(prefix.clone());
+                }
+                walk(&dir, prefix, dotglob, last, &mut next);
+            } else if compiled.has_wildcards() {
+                let show_hidden = dotglob || text.starts_with('.');
+                for (name, is_dir) in read_dir(&dir) {
+                    if (show_hidden || !name.starts_with('.')) && (last || is_dir) && compiled.matches(&name) {
+                        next.push(join(prefix, &name));
+                    }
+                }
+            } else {
+                let path = join(prefix, &text);
+                let full = current_dir.join(&path);
+                if (last && full.symlink_metadata().is_ok()) || full.is_dir() {
+                    next.push(path);
+                }
+            }
+        }
+        paths = next;
+    }
+
+    if components.is_empty() {
+        return Vec::new();
+    }
+    if trailing_slash {
+        for path in &mut paths {
+            path.push('/');
+        }
+    }
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+/// Lists a directory as `(name, is_dir)` pairs, ignoring unreadable entries.
+fn read_dir(dir: &Path) -> Vec<(String, bool)> {
+    let Ok(entries) = fs::read_dir(dir) else { return Vec::new() };
+    entries
+        .flatten()
+        .map(|entry| {
+            let is_dir = entry.path().is_dir();
+            (entry.file_name().to_string_lossy().into_owned(), is_dir)
+        })
+        .collect()
+}
+
+/// Adds everything below `dir` for `**`: only directories when more
+/// components follow, or every entry when `**` ends the pattern. Symbolic
+/// links to directories are not followed, so cycles cannot occur.
+fn walk(dir: &Path, prefix: &str, dotglob: bool, include_files: bool, out: &mut Vec<String>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !dotglob && name.starts_with('.') {
+            continue;
+        }
+        let path = join(prefix, &name);
+        let real_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+        if real_dir || include_files {
+            out.push(path.clone());
+        }
+        if real_dir {
+            walk(&entry.path(), &path, dotglob, include_files, out);
+        }
+    }
+}
+
+fn join(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else if prefix.ends_with('/') {
+        format!("{}{}", prefix, name)
+    } else {
+        format!("{}/{}", prefix, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unquoted(pattern: &str) -> Vec<(char, bool)> {
+        pattern.chars().map(|c| (c, false)).collect()
+    }
+
+    fn tree() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for file in ["a.rs", "b.rs", "c.txt", ".hidden.rs", "src/main.rs", "src/deep/lib.rs", "src/.cache/x.rs"] {
+            let path = dir.path().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_wildcards_in_current_dir() {
+        let dir = tree();
+        assert_eq!(glob(dir.path(), &unquoted("*.rs"), false), ["a.rs", "b.rs"]);
+        assert_eq!(glob(dir.path(), &unquoted("?.*"), false), ["a.rs", "b.rs", "c.txt"]);
+        assert_eq!(glob(dir.path(), &unquoted("[ac].*"), false), ["a.rs", "c.txt"]);
+        assert_eq!(glob(dir.path(), &unquoted("*/"), false), ["src/"]);
+        assert!(glob(dir.path(), &unquoted("*.md"), false).is_empty());
+    }
+
+    #[test]
+    fn test_hidden_files() {
+        let dir = tree();
+        assert_eq!(glob(dir.path(), &unquoted(".*.rs"), false), [".hidden.rs"]);
+        assert_eq!(glob(dir.path(), &unquoted("*.rs"), true), [".hidden.rs", "a.rs", "b.rs"]);
+        assert!(!glob(dir.path(), &unquoted(".*"), false).iter().any(|p| p == "." || p == ".."));
+    }
+
+    #[test]
+    fn test_recursive_matching() {
+        let dir = tree();
+        assert_eq!(glob(dir.path(), &unquoted("src/**/*.rs"), false), ["src/deep/lib.rs", "src/main.rs"]);
+        assert_eq!(glob(dir.path(), &unquoted("**/lib.rs"), false), ["src/deep/lib.rs"]);
+        assert_eq!(glob(dir.path(), &unquoted("src/**"), false), ["src/deep", "src/deep/lib.rs", "src/main.rs"]);
+        assert_eq!(glob(dir.path(), &unquoted("src/**/*.rs"), true), ["src/.cache/x.rs", "src/deep/lib.rs", "src/main.rs"]);
+    }
+
+    #[test]
+    fn test_quoted_wildcards_are_literal() {
+        let dir = tree();
+        let pattern = vec![('*', true), ('.', false), ('r', false), ('s', false)];
+        assert!(!has_wildcards(&pattern));
+        assert!(glob(dir.path(), &pattern, false).is_empty());
+    }
+
+    #[test]
+    fn test_absolute_patterns() {
+        let dir = tree();
+        let pattern = format!("{}/*.txt", dir.path().display());
+        assert_eq!(glob(Path::new("/"), &unquoted(&pattern), false), [format!("{}/c.txt", dir.path().display())]);
+    }
+}
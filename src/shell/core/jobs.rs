@@ -0,0 +1,335 @@
+//! The table of background jobs, started by ending a command list with `&`.
+//!
+//! Each job runs on a task of its own against a copy of the shell state,
+//! so the shell is free for other commands meanwhile. The table is shared
+//! by every copy of a `ShellCore` and by the GUI, which shows the running
+//! jobs and is told about the ones that finish.
+
+use std::sync::{Arc, Mutex};
+use chrono::{DateTime, Local};
+use tokio::sync::watch;
+use crate::shell::core::cancellation::CancellationToken;
+
+/// A snapshot of a job.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobInfo {
+    /// The number the job is referred to by, as in `%1`.
+    pub id: usize,
+    /// The command list, as shown by `jobs`.
+    pub command: String,
+    pub started_at: DateTime<Local>,
+    /// The exit status, or `None` while the job is running.
+    pub status: Option<i32>,
+    /// Whether the job was stopped with `kill`.
+    pub killed: bool,
+}
+
+impl JobInfo {
+    /// Formats the job as `jobs` lists it, e.g. `[1]+  Running    make &`.
+    ///
+    /// # Arguments
+    ///
+    /// * `marker` - `+` for the current job, `-` for the previous one, or
+    ///   a space.
+    pub fn describe(&self, marker: char) -> String {
+        let (state, suffix) = match self.status {
+            None => ("Running".to_string(), " &"),
+            Some(_) if self.killed => ("Killed".to_string(), ""),
+            Some(0) => ("Done".to_string(), ""),
+            Some(status) => (format!("Exit {}", status), ""),
+        };
+        format!("[{}]{}  {:<24}{}{}", self.id, marker, state, self.command, suffix)
+    }
+}
+
+struct Job {
+    info: JobInfo,
+    /// Interrupts the job's commands.
+    cancellation: CancellationToken,
+    /// Receives the exit status once the job finishes.
+    status: watch::Sender<Option<i32>>,
+    /// The external processes the job is running, each the leader of its
+    /// own process group.
+    processes: Vec<u32>,
+}
+
+/// Called with each job that finishes.
+type FinishListener = Box<dyn Fn(&JobInfo) + Send + Sync>;
+
+/// The background jobs of a shell.
+///
+/// Clones share the same table. Finished jobs stay in it until they have
+/// been reported, by `jobs` or to the user.
+#[derive(Clone, Default)]
+pub struct JobTable {
+    jobs: Arc<Mutex<Vec<Job>>>,
+    on_finish: Arc<Mutex<Option<FinishListener>>>,
+}
+
+impl JobTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a job that is starting now.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The text shown for the job.
+    /// * `cancellation` - The token its commands run with, used by `kill`.
+    ///
+    /// # Returns
+    ///
+    /// The id of the job, one more than the highest id in use.
+    pub fn add(&self, command: String, cancellation: CancellationToken) -> usize {
+        let mut jobs = self.jobs.lock().unwrap();
+        let id = jobs.iter().map(|job| job.info.id).max().unwrap_or(0) + 1;
+        let info = JobInfo { id, command, started_at: Local::now(), status: None, killed: false };
+        jobs.push(Job { info, cancellation, status: watch::Sender::new(None), processes: Vec::new() });
+        id
+    }
+
+    /// Records that a job finished with `status`, waking anyone waiting for it
+    /// and calling the listener set with `on_finish`.
+    pub fn finish(&self, id: usize, status: i32) {
+        let info = {
+            let mut jobs = self.jobs.lock().unwrap();
+            let Some(job) = jobs.iter_mut().find(|job| job.info.id == id) else {
+                return;
+            };
+            job.info.status = Some(status);
+            job.status.send_replace(Some(status));
+            job.info.clone()
+        };
+        if let Some(listener) = self.on_finish.lock().unwrap().as_ref() {
+            listener(&info);
+        }
+    }
+
+    /// Sets the function called with each job that finishes, from the task
+    /// that ran it, replacing any previous one.
+    pub fn on_finish(&self, listener: impl Fn(&JobInfo) + Send + Sync + 'static) {
+        *self.on_finish.lock().unwrap() = Some(Box::new(listener));
+    }
+
+    /// Returns every job in the table, oldest first.
+    pub fn list(&self) -> Vec<JobInfo> {
+        self.jobs.lock().unwrap().iter().map(|job| job.info.clone()).collect()
+    }
+
+    /// Returns the jobs that are still running, oldest first.
+    pub fn running(&self) -> Vec<JobInfo> {
+        self.list().into_iter().filter(|job| job.status.is_none()).collect()
+    }
+
+    /// Removes the finished jobs from the table, so that each one is
+    /// reported once.
+    ///
+    /// # Returns
+    ///
+    /// The removed jobs, oldest first.
+    pub fn take_finished(&self) -> Vec<JobInfo> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let (finished, running) = std::mem::take(&mut *jobs).into_iter().partition(|job| job.info.status.is_some());
+        *jobs = running;
+        finished.into_iter().map(|job: Job| job.info).collect()
+    }
+
+    /// Removes a job from the table, as when `fg` has waited for it.
+    pub fn remove(&self, id: usize) {
+        self.jobs.lock().unwrap().retain(|job| job.info.id != id);
+    }
+
+    /// Finds the job a job specification refers to.
+    ///
+    /// `%n` is job `n`, `%%`, `%+` and `%` the current (most recent) job,
+    /// `%-` the previous one, `%text` the job whose command starts with
+    /// `text` and `%?text` the job whose command contains it.
+    ///
+    /// # Returns
+    ///
+    /// The id of the job, or an error message naming the specification.
+    pub fn resolve(&self, spec: &str) -> Result<usize, String> {
+        let jobs = self.jobs.lock().unwrap();
+        let Some(pattern) = spec.strip_prefix('%') else {
+            return Err(format!("{}: no such job", spec));
+        };
+        let found: Vec<usize> = match pattern {
+            "" | "%" | "+" => jobs.last().map(|job| job.info.id).into_iter().collect(),
+            "-" => jobs.iter().rev().nth(1).map(|job| job.info.id).into_iter().collect(),
+            _ => match pattern.parse::<usize>() {
+                Ok(id) => jobs.iter().filter(|job| job.info.id == id).map(|job| job.info.id).collect(),
+                Err(_) => {
+                    let matches = |command: &str| match pattern.strip_prefix('?') {
+                        Some(text) => command.contains(text),
+                        None => command.starts_with(pattern),
+                    };
+                    jobs.iter().filter(|job| matches(&job.info.command)).map(|job| job.info.id).collect()
+                }
+            },
+        };
+        match found.as_slice() {
+            [id] => Ok(*id),
+            [] => Err(format!("{}: no such job", spec)),
+            _ => Err(format!("{}: ambiguous job spec", spec)),
+        }
+    }
+
+    /// Stops a job's commands, as Ctrl+C does for the foreground.
+    ///
+    /// # Returns
+    ///
+    /// `false` if there is no such job.
+    pub fn kill(&self, id: usize) -> bool {
+        let mut jobs = self.jobs.lock().unwrap();
+        let Some(job) = jobs.iter_mut().find(|job| job.info.id == id) else {
+            return false;
+        };
+        if job.info.status.is_none() {
+            job.info.killed = true;
+            job.cancellation.cancel();
+        }
+        true
+    }
+
+    /// Records a process started by a job's commands, until
+    /// `remove_process` is called once it has exited.
+    pub fn add_process(&self, id: usize, pid: u32) {
+        if let Some(job) = self.jobs.lock().unwrap().iter_mut().find(|job| job.info.id == id) {
+            job.processes.push(pid);
+        }
+    }
+
+    /// Forgets a process recorded with `add_process`.
+    pub fn remove_process(&self, id: usize, pid: u32) {
+        if let Some(job) = self.jobs.lock().unwrap().iter_mut().find(|job| job.info.id == id) {
+            job.processes.retain(|&process| process != pid);
+        }
+    }
+
+    /// Sends a signal to the process group of every process a job is
+    /// running, as `kill -STOP %1` does.
+    ///
+    /// # Returns
+    ///
+    /// The first error other than a process having exited meanwhile.
+    #[cfg(unix)]
+    pub fn signal(&self, id: usize, signal: i32) -> std::io::Result<()> {
+        let jobs = self.jobs.lock().unwrap();
+        let processes = jobs.iter().find(|job| job.info.id == id).map_or(&[][..], |job| &job.processes);
+        for &pid in processes {
+            // SAFETY: `kill` has no memory-safety preconditions. Each process
+            // leads its own group, so `-pid` addresses the whole group.
+            if unsafe { libc::kill(-(pid as libc::pid_t), signal) } != 0 {
+                let error = std::io::Error::last_os_error();
+                if error.raw_os_error() != Some(libc::ESRCH) {
+                    return Err(error);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Waits for a job to finish.
+    ///
+    /// # Returns
+    ///
+    /// The exit status of the job, or `None` if there is no such job.
+    pub async fn wait(&self, id: usize) -> Option<i32> {
+        let mut receiver = {
+            let jobs = self.jobs.lock().unwrap();
+            let job = jobs.iter().find(|job| job.info.id == id)?;
+            if job.info.status.is_some() {
+                return job.info.status;
+            }
+            job.status.subscribe()
+        };
+        let status = receiver.wait_for(Option::is_some).await.ok()?;
+        *status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_jobs_are_numbered_and_reported_once() {
+        let table = JobTable::new();
+        let first = table.add("make".to_string(), CancellationToken::new());
+        let second = table.add("sleep 10".to_string(), CancellationToken::new());
+        assert_eq!((first, second), (1, 2));
+        assert_eq!(table.resolve("%%"), Ok(2));
+        assert_eq!(table.resolve("%-"), Ok(1));
+        assert_eq!(table.resolve("%ma"), Ok(1));
+        assert_eq!(table.resolve("%?10"), Ok(2));
+        assert_eq!(table.resolve("%3"), Err("%3: no such job".to_string()));
+
+        table.finish(first, 2);
+        assert_eq!(table.wait(first).await, Some(2));
+        assert_eq!(table.list()[0].describe('-'), format!("[1]-  {:<24}make", "Exit 2"));
+        assert_eq!(table.list()[1].describe('+'), format!("[2]+  {:<24}sleep 10 &", "Running"));
+        assert_eq!(table.take_finished().len(), 1);
+        assert!(table.take_finished().is_empty());
+        assert_eq!(table.wait(first).await, None);
+
+        // Ids are reused once the jobs with higher ids are gone.
+        assert_eq!(table.add("ls".to_string(), CancellationToken::new()), 3);
+        table.remove(2);
+        table.remove(3);
+        assert_eq!(table.add("ls".to_string(), CancellationToken::new()), 1);
+    }
+
+    #[test]
+    fn test_kill_cancels_a_running_job() {
+        let table = JobTable::new();
+        let cancellation = CancellationToken::new();
+        let id = table.add("sleep 10".to_string(), cancellation.clone());
+        assert!(table.kill(id));
+        assert!(cancellation.is_cancelled());
+        table.finish(id, 130);
+        assert!(table.list()[0].describe('+').contains("Killed"));
+        assert!(!table.kill(id + 1));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_signal_reaches_the_job_processes() {
+        use std::os::unix::process::CommandExt;
+        let state = |pid: u32| {
+            let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).unwrap();
+            stat.rsplit(')').next().unwrap().split_whitespace().next().unwrap().to_string()
+        };
+        let mut child = std::process::Command::new("sleep").arg("30").process_group(0).spawn().unwrap();
+        let table = JobTable::new();
+        let id = table.add("sleep 30".to_string(), CancellationToken::new());
+        table.add_process(id, child.id());
+
+        table.signal(id, libc::SIGSTOP).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert_eq!(state(child.id()), "T");
+        table.signal(id, libc::SIGCONT).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert_eq!(state(child.id()), "S");
+
+        table.remove_process(id, child.id());
+        table.signal(id, libc::SIGKILL).unwrap();
+        assert_eq!(child.try_wait().unwrap(), None);
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+
+    #[test]
+    fn test_finishing_a_job_calls_the_listener() {
+        let table = JobTable::new();
+        let finished = Arc::new(Mutex::new(Vec::new()));
+        table.on_finish({
+            let finished = finished.clone();
+            move |job| finished.lock().unwrap().push(job.describe(' '))
+        });
+        let id = table.add("make".to_string(), CancellationToken::new());
+        table.clone().finish(id, 0);
+        table.finish(id + 1, 0);
+        assert_eq!(*finished.lock().unwrap(), vec![format!("[1]   {:<24}make", "Done")]);
+    }
+}
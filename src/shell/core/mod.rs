@@ -4,19 +4,78 @@
 use std::collections::HashMap;
 use std::env;
 use std::path::PathBuf;
+use std::sync::Arc;
 use crate::shell::features::git::GitInfo;
+use crate::shell::history::HistoryStore;
+use cancellation::CancellationToken;
+use command_executor::ControlFlow;
+use jobs::JobTable;
+use options::ShellOptions;
+use output::OutputSink;
+use parser::ast::FunctionDefinition;
 
+pub mod brace;
 pub mod builtins;
+pub mod cancellation;
 pub mod command_executor;
+pub mod expansion;
 pub mod external;
+pub mod glob;
+pub mod jobs;
+pub mod options;
+pub mod output;
+pub mod parser;
+pub mod pattern;
+pub mod pty;
+pub mod shared;
+pub mod stage_io;
+pub mod startup;
+pub mod tilde;
 
 /// `ShellCore` manages the shell's state, including the current working directory
 /// and provides methods for executing commands.
+#[derive(Clone)]
 pub struct ShellCore {
     pub current_dir: PathBuf,
     pub git_info: Option<GitInfo>,
     pub aliases: HashMap<String, String>,
     pub env_vars: HashMap<String, String>,
+    /// Shell variables set with `NAME=value`. Unlike `env_vars`, these are
+    /// not passed to external commands.
+    pub variables: HashMap<String, String>,
+    /// Exit status of the most recently executed pipeline, exposed as `$?`.
+    pub last_exit_status: i32,
+    /// The positional parameters `$1`, `$2`, ..., also counted by `$#`.
+    pub positional_params: Vec<String>,
+    /// The name of the shell, exposed as `$0`.
+    pub shell_name: String,
+    pub options: ShellOptions,
+    /// A `break` or `continue` that has not reached its loop yet.
+    pub control_flow: Option<ControlFlow>,
+    /// The number of loops currently running, which bounds `break n`.
+    pub loop_depth: usize,
+    /// Functions defined with `name() { ... }`, by name.
+    pub functions: HashMap<String, Arc<FunctionDefinition>>,
+    /// One scope per running function, innermost last, holding the value
+    /// each of its `local` variables had before (`None` if it was unset),
+    /// so it can be restored when the function returns.
+    pub local_scopes: Vec<HashMap<String, Option<String>>>,
+    /// The number of scripts being run by `source`, which `return` can leave.
+    pub source_depth: usize,
+    /// The background jobs, shared by every copy of the shell.
+    pub jobs: JobTable,
+    /// The id of the background job these commands run in, which records
+    /// the processes they start so that `kill` can signal them.
+    pub job: Option<usize>,
+    /// The command history shown by `history`. The GUI shares one history
+    /// between all its tabs; a new shell has an empty one.
+    pub history: HistoryStore,
+}
+
+impl Default for ShellCore {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ShellCore {
@@ -28,6 +87,19 @@ impl ShellCore {
             git_info: None,
             aliases: HashMap::new(),
             env_vars: HashMap::new(),
+            variables: HashMap::new(),
+            last_exit_status: 0,
+            positional_params: Vec::new(),
+            shell_name: env!("CARGO_PKG_NAME").to_string(),
+            options: ShellOptions::default(),
+            control_flow: None,
+            loop_depth: 0,
+            functions: HashMap::new(),
+            local_scopes: Vec::new(),
+            source_depth: 0,
+            jobs: JobTable::new(),
+            job: None,
+            history: HistoryStore::new(),
         };
         core.update_git_info();
         core
@@ -38,6 +110,44 @@ impl ShellCore {
         self.git_info = crate::shell::features::git::get_git_info(&self.current_dir);
     }
 
+    /// Returns the value of a variable.
+    ///
+    /// Shell variables take precedence over exported ones, which in turn take
+    /// precedence over the environment the shell was started with.
+    pub fn get_var(&self, name: &str) -> Option<String> {
+        self.variables
+            .get(name)
+            .or_else(|| self.env_vars.get(name))
+            .cloned()
+            .or_else(|| env::var(name).ok())
+    }
+
+    /// Sets a variable, keeping it exported if it already was. A variable
+    /// made local by a running function is set as a shell variable, which
+    /// hides an exported one of the same name.
+    pub fn set_var(&mut self, name: &str, value: &str) {
+        let local = self.local_scopes.iter().any(|scope| scope.contains_key(name));
+        if let (false, Some(exported)) = (local, self.env_vars.get_mut(name)) {
+            *exported = value.to_string();
+        } else {
+            self.variables.insert(name.to_string(), value.to_string());
+        }
+    }
+
+    /// Makes a variable local to the innermost running function, saving its
+    /// current value to be restored when the function returns.
+    ///
+    /// # Returns
+    ///
+    /// `false` if no function is running.
+    pub fn make_local(&mut self, name: &str) -> bool {
+        let Some(scope) = self.local_scopes.last_mut() else { return false };
+        if !scope.contains_key(name) {
+            scope.insert(name.to_string(), self.variables.get(name).cloned());
+        }
+        true
+    }
+
     /// Returns the current working directory of the shell.
     ///
     /// # Returns
@@ -66,6 +176,24 @@ impl ShellCore {
         self.update_git_info();
         result
     }
+
+    /// Executes a given shell command, sending its output to `output` while
+    /// it runs.
+    ///
+    /// # Arguments
+    ///
+    /// * `command_str` - A string slice representing the command to execute.
+    /// * `output` - The sink that receives the command's output incrementally.
+    /// * `cancellation` - Interrupts the command when cancelled.
+    pub async fn execute_shell_command_streaming(
+        &mut self,
+        command_str: &str,
+        output: &OutputSink,
+        cancellation: &CancellationToken,
+    ) {
+        command_executor::execute_shell_command_streaming(self, command_str, output, cancellation).await;
+        self.update_git_info();
+    }
 }
 
 #[cfg(test)]
@@ -79,7 +207,7 @@ mod tests {
     async fn test_ls_builtin_current_dir() -> io::Result<()> {
         let mut shell_core = ShellCore::new();
         shell_core.current_dir = dunce::canonicalize(PathBuf::from(env!("CARGO_MANIFEST_DIR"))).unwrap();
-        let output = super::builtins::ls::ls_builtin(&shell_core.current_dir, &[]).await;
+        let output = super::builtins::ls::ls_builtin(&shell_core.current_dir, &[]).await.unwrap();
         assert!(output.contains("Cargo.toml"));
         assert!(output.contains("src"));
         assert!(output.contains("lib"));
@@ -90,7 +218,7 @@ mod tests {
     #[tokio::test]
     async fn test_ls_builtin_nonexistent_dir() -> io::Result<()> {
         let shell_core = ShellCore::new();
-        let output = super::builtins::ls::ls_builtin(&shell_core.current_dir, &["nonexistent_dir_123"]).await;
+        let output = super::builtins::ls::ls_builtin(&shell_core.current_dir, &["nonexistent_dir_123"]).await.unwrap_err().to_string();
         println!("Test Output: {}", output);
         assert!(output.contains("No such file or directory"));
         Ok(())
@@ -173,7 +301,7 @@ mod tests {
     #[ignore]
     async fn test_ping_builtin() -> io::Result<()> {
         let _shell_core = ShellCore::new();
-        let output = super::builtins::ping::ping_builtin(&["google.com"]).await;
+        let output = super::builtins::ping::ping_builtin(&["google.com"]).await.unwrap();
         println!("Test Output: {}", output);
         assert!(output.contains("Reply from"));
         Ok(())
@@ -0,0 +1,49 @@
+//! Options that change how the shell behaves, toggled with `shopt`.
+
+/// The shell's options. All of them are off by default.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShellOptions {
+    /// Let wildcards match file names that start with a dot.
+    pub dotglob: bool,
+    /// Make a command fail when one of its patterns matches no files.
+    pub failglob: bool,
+    /// Remove a pattern that matches no files instead of passing it on as is.
+    pub nullglob: bool,
+    /// Run `.my_cli_toolrc` in the directory a new tab starts in, after the
+    /// user's startup file. Only takes effect when set by that file.
+    pub projectrc: bool,
+}
+
+impl ShellOptions {
+    /// The names of all options, in alphabetical order.
+    pub const NAMES: [&'static str; 4] = ["dotglob", "failglob", "nullglob", "projectrc"];
+
+    /// Returns whether the named option is on, or `None` if there is no
+    /// such option.
+    pub fn get(&self, name: &str) -> Option<bool> {
+        match name {
+            "dotglob" => Some(self.dotglob),
+            "failglob" => Some(self.failglob),
+            "nullglob" => Some(self.nullglob),
+            "projectrc" => Some(self.projectrc),
+            _ => None,
+        }
+    }
+
+    /// Turns the named option on or off.
+    ///
+    /// # Returns
+    ///
+    /// `false` if there is no such option.
+    pub fn set(&mut self, name: &str, value: bool) -> bool {
+        let option = match name {
+            "dotglob" => &mut self.dotglob,
+            "failglob" => &mut self.failglob,
+            "nullglob" => &mut self.nullglob,
+            "projectrc" => &mut self.projectrc,
+            _ => return false,
+        };
+        *option = value;
+        true
+    }
+}
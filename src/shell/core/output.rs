@@ -0,0 +1,209 @@
+//! Incremental delivery of command output to the user interface.
+//!
+//! Commands write their output to an `OutputSink` while they run. The sink
+//! decodes the bytes and forwards them as text chunks over a channel, so a
+//! tab can display the output of a long-running pipeline as it arrives.
+
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+/// One of the output streams of a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// A piece of text written by a command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputChunk {
+    pub stream: Stream,
+    pub text: String,
+}
+
+/// Forwards the output of running commands to a receiver as it is produced.
+///
+/// Clones share the same channel, so every stage of a pipeline can write to
+/// the sink concurrently.
+#[derive(Clone)]
+pub struct OutputSink {
+    state: Arc<Mutex<SinkState>>,
+}
+
+struct SinkState {
+    sender: UnboundedSender<OutputChunk>,
+    /// Trailing bytes of an incomplete UTF-8 sequence, per stream.
+    partial: [Vec<u8>; 2],
+    /// Whether the last text sent ended with a newline.
+    at_line_start: bool,
+    /// Whether the next text should start on a new line.
+    line_break_pending: bool,
+    /// What diagnostic messages from the shell start with.
+    error_prefix: &'static str,
+}
+
+impl OutputSink {
+    /// Creates a sink and the receiver its output is delivered to.
+    pub fn new() -> (Self, UnboundedReceiver<OutputChunk>) {
+        Self::with_error_prefix("Error: ")
+    }
+
+    /// Creates a sink whose diagnostic messages start with `error_prefix`
+    /// rather than `Error: `, such as the program name on the command line.
+    pub fn with_error_prefix(error_prefix: &'static str) -> (Self, UnboundedReceiver<OutputChunk>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let state = SinkState {
+            sender,
+            partial: [Vec::new(), Vec::new()],
+            at_line_start: true,
+            line_break_pending: false,
+            error_prefix,
+        };
+        (Self { state: Arc::new(Mutex::new(state)) }, receiver)
+    }
+
+    /// Returns what diagnostic messages from the shell start with.
+    pub fn error_prefix(&self) -> &'static str {
+        self.state.lock().unwrap().error_prefix
+    }
+
+    /// Writes raw bytes to one of the streams.
+    ///
+    /// Bytes that end in the middle of a UTF-8 character are held back until
+    /// the rest of the character arrives; invalid sequences are replaced.
+    pub fn write(&self, stream: Stream, bytes: &[u8]) {
+        let mut state = self.state.lock().unwrap();
+        let mut pending = std::mem::take(&mut state.partial[stream as usize]);
+        pending.extend_from_slice(bytes);
+
+        let mut text = String::new();
+        let mut rest = pending.as_slice();
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    rest = &[];
+                    break;
+                }
+                Err(e) => {
+                    let (valid, invalid) = rest.split_at(e.valid_up_to());
+                    text.push_str(std::str::from_utf8(valid).unwrap());
+                    match e.error_len() {
+                        Some(len) => {
+                            text.push(char::REPLACEMENT_CHARACTER);
+                            rest = &invalid[len..];
+                        }
+                        None => {
+                            rest = invalid;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        state.partial[stream as usize] = rest.to_vec();
+        state.send(stream, text);
+    }
+
+    /// Writes a diagnostic message from the shell itself on a line of its own.
+    pub fn write_error(&self, message: &str) {
+        let line = format!("{}{}\n", self.error_prefix(), message);
+        self.write_line(line);
+    }
+
+    /// Marks the output of a command line that was interrupted with Ctrl+C.
+    pub fn write_interrupted(&self) {
+        self.write_line("^C [interrupted]\n".to_string());
+    }
+
+    fn write_line(&self, line: String) {
+        let mut state = self.state.lock().unwrap();
+        if !state.at_line_start {
+            state.line_break_pending = true;
+        }
+        state.send(Stream::Stderr, line);
+    }
+
+    /// Marks the end of a pipeline's output.
+    ///
+    /// Held-back bytes are sent as they are; the output of the next pipeline
+    /// follows it unchanged, on the same line if it did not end with one.
+    pub fn end_pipeline(&self) {
+        let mut state = self.state.lock().unwrap();
+        for stream in [Stream::Stdout, Stream::Stderr] {
+            let partial = std::mem::take(&mut state.partial[stream as usize]);
+            let text = String::from_utf8_lossy(&partial).into_owned();
+            state.send(stream, text);
+        }
+    }
+}
+
+impl SinkState {
+    fn send(&mut self, stream: Stream, text: String) {
+        if text.is_empty() {
+            return;
+        }
+        if self.line_break_pending {
+            self.line_break_pending = false;
+            let _ = self.sender.send(OutputChunk { stream, text: "\n".to_string() });
+        }
+        self.at_line_start = text.ends_with('\n');
+        // The receiver may have gone away, e.g. when a tab is closed.
+        let _ = self.sender.send(OutputChunk { stream, text });
+    }
+}
+
+/// Collects everything sent to a sink into a single string.
+pub fn collect(mut receiver: UnboundedReceiver<OutputChunk>) -> String {
+    let mut output = String::new();
+    while let Ok(chunk) = receiver.try_recv() {
+        output.push_str(&chunk.text);
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_utf8_character_is_reassembled() {
+        let (sink, receiver) = OutputSink::new();
+        let bytes = "héllo".as_bytes();
+        sink.write(Stream::Stdout, &bytes[..2]);
+        sink.write(Stream::Stdout, &bytes[2..]);
+        drop(sink);
+        assert_eq!(collect(receiver), "héllo");
+    }
+
+    #[test]
+    fn test_invalid_utf8_is_replaced() {
+        let (sink, receiver) = OutputSink::new();
+        sink.write(Stream::Stdout, b"a\xffb");
+        assert_eq!(collect(receiver), "a\u{FFFD}b");
+    }
+
+    #[test]
+    fn test_end_pipeline_leaves_output_unchanged() {
+        let (sink, receiver) = OutputSink::new();
+        sink.write(Stream::Stdout, b"one");
+        sink.end_pipeline();
+        sink.write(Stream::Stdout, b"two\n");
+        sink.end_pipeline();
+        // An incomplete character is sent once its pipeline has ended.
+        sink.write(Stream::Stdout, &"é".as_bytes()[..1]);
+        sink.end_pipeline();
+        sink.write(Stream::Stdout, b"three");
+        assert_eq!(collect(receiver), "onetwo\n\u{FFFD}three");
+    }
+
+    #[test]
+    fn test_errors_start_on_their_own_line() {
+        let (sink, mut receiver) = OutputSink::new();
+        sink.write(Stream::Stdout, b"partial");
+        sink.write_error("failed");
+        let chunks: Vec<_> = std::iter::from_fn(|| receiver.try_recv().ok()).collect();
+        assert_eq!(chunks.last().unwrap(), &OutputChunk { stream: Stream::Stderr, text: "Error: failed\n".to_string() });
+        assert_eq!(chunks.iter().map(|c| c.text.as_str()).collect::<String>(), "partial\nError: failed\n");
+    }
+}
@@ -0,0 +1,277 @@
+//! Abstract syntax tree produced by the shell parser.
+//!
+//! The tree mirrors the POSIX shell grammar: a command line is a list of
+//! and-or lists, an and-or list is a chain of pipelines joined by `&&` and
+//! `||`, a pipeline is a chain of commands, and a command is either a set of
+//! words, variable assignments and redirections, a compound command such as
+//! `if` or `while` that contains command lists of its own, or a function
+//! definition. Words keep their quoting, parameter expansions and command
+//! substitutions, which are resolved when the command runs.
+
+/// A sequence of and-or lists separated by `;`, `&` or newlines.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CommandList {
+    pub items: Vec<AndOrList>,
+}
+
+/// Pipelines joined by `&&` and `||`, evaluated left to right.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AndOrList {
+    pub first: Pipeline,
+    pub rest: Vec<(AndOrOperator, Pipeline)>,
+    /// For a list followed by `&`, which runs it as a background job, the
+    /// text `jobs` shows for it.
+    pub job: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AndOrOperator {
+    /// `&&`: run the next pipeline only if the previous one succeeded.
+    And,
+    /// `||`: run the next pipeline only if the previous one failed.
+    Or,
+}
+
+/// One or more commands connected with `|`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pipeline {
+    pub commands: Vec<Command>,
+    /// Whether the pipeline starts with `!`, which inverts its exit status.
+    pub negated: bool,
+}
+
+/// A single command inside a pipeline.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Simple(SimpleCommand),
+    /// A compound command with the redirections written after it, as in
+    /// `while ...; done < input`.
+    Compound(CompoundCommand, Vec<Redirection>),
+    /// `name() compound-command` or `function name compound-command`,
+    /// which defines a function rather than running anything.
+    FunctionDefinition(FunctionDefinition),
+}
+
+/// A shell function: a compound command run under a name, with its
+/// arguments as the positional parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionDefinition {
+    pub name: String,
+    pub body: CompoundCommand,
+    /// Redirections written after the body, applied on every call.
+    pub redirections: Vec<Redirection>,
+}
+
+/// A command that groups other commands.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompoundCommand {
+    /// `{ list; }`, which runs the list in the current shell.
+    BraceGroup(CommandList),
+    /// `if list; then list; [elif list; then list;]... [else list;] fi`
+    If {
+        /// Each condition with the body run when it succeeds, in order.
+        branches: Vec<(CommandList, CommandList)>,
+        else_body: Option<CommandList>,
+    },
+    /// `while list; do list; done`, or `until` when `until` is set, which
+    /// runs the body while the condition fails instead.
+    While { condition: CommandList, body: CommandList, until: bool },
+    /// `for name [in word...]; do list; done`. Without `in`, the loop runs
+    /// over the positional parameters.
+    For { variable: String, words: Option<Vec<Word>>, body: CommandList },
+    /// `case word in pattern|pattern) list;; ... esac`
+    Case { word: Word, items: Vec<CaseItem> },
+}
+
+/// One `pattern|pattern) list;;` item of a `case` command.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaseItem {
+    pub patterns: Vec<Word>,
+    pub body: CommandList,
+}
+
+/// A command name with its arguments, leading assignments and redirections.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SimpleCommand {
+    pub assignments: Vec<Assignment>,
+    pub words: Vec<Word>,
+    pub redirections: Vec<Redirection>,
+}
+
+/// A `NAME=value` prefix of a simple command.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assignment {
+    pub name: String,
+    pub value: Word,
+}
+
+/// An I/O redirection such as `> file` or `2>&1`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Redirection {
+    /// The file descriptor written before the operator, as in `2>`.
+    /// `None` means the operator's default (see `Redirection::target_fd`).
+    pub fd: Option<u32>,
+    pub kind: RedirectionKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RedirectionKind {
+    /// `<`: read from a file.
+    Input(Word),
+    /// `>`: write to a file, truncating it.
+    Output(Word),
+    /// `>>`: append to a file.
+    Append(Word),
+    /// `&>`: write both standard output and standard error to a file.
+    OutputAndError(Word),
+    /// `&>>`: append both standard output and standard error to a file.
+    AppendAndError(Word),
+    /// `<&`: duplicate an input descriptor, or close it with `-`.
+    DuplicateInput(Word),
+    /// `>&`: duplicate an output descriptor, or close it with `-`.
+    DuplicateOutput(Word),
+    /// `<<` / `<<-`: read from an inline here-document.
+    HereDoc(HereDoc),
+}
+
+/// The body of a here-document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HereDoc {
+    pub body: String,
+    /// Whether expansions apply to the body, which is the case when the
+    /// delimiter word was not quoted.
+    pub expand: bool,
+}
+
+impl Redirection {
+    /// Returns the file descriptor this redirection applies to.
+    pub fn target_fd(&self) -> u32 {
+        match self.kind {
+            RedirectionKind::Input(_) | RedirectionKind::DuplicateInput(_) | RedirectionKind::HereDoc(_) => {
+                self.fd.unwrap_or(0)
+            }
+            _ => self.fd.unwrap_or(1),
+        }
+    }
+}
+
+/// A shell word, kept as a sequence of parts so that later stages know
+/// which characters were quoted.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Word {
+    pub parts: Vec<WordPart>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WordPart {
+    /// Unquoted text.
+    Literal(String),
+    /// Text from single quotes or a backslash escape; taken verbatim.
+    Quoted(String),
+    /// The contents of a double-quoted string.
+    DoubleQuoted(Vec<WordPart>),
+    /// A parameter expansion such as `$HOME`, `$?` or `${VAR:-default}`.
+    Parameter(Parameter),
+    /// A command substitution, `$(...)` or `` `...` ``, holding the source of
+    /// the commands whose output replaces it.
+    CommandSubstitution(String),
+}
+
+/// A parameter expansion: the parameter to look up and what to do with its
+/// value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameter {
+    /// A variable name, a positional parameter (`1`, `10`) or one of the
+    /// special parameters `?`, `$`, `#`, `@`, `*` and `0`.
+    pub name: String,
+    pub op: ParameterOp,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParameterOp {
+    /// `$NAME` or `${NAME}`: the value itself.
+    Value,
+    /// `${#NAME}`: the length of the value in characters.
+    Length,
+    /// `${NAME:-word}`: `word` if the parameter is unset or null. Without
+    /// the colon (`-`), only if it is unset; likewise for the operators below.
+    Default { word: Word, colon: bool },
+    /// `${NAME:=word}`: like `Default`, but also assigns `word` to the variable.
+    Assign { word: Word, colon: bool },
+    /// `${NAME:+word}`: `word` if the parameter is set and not null, else nothing.
+    Alternative { word: Word, colon: bool },
+    /// `${NAME:?word}`: fails with the message `word` if the parameter is
+    /// unset or null.
+    Error { word: Word, colon: bool },
+    /// `${NAME#pattern}` / `${NAME##pattern}`: removes the shortest or
+    /// longest prefix matching the pattern.
+    RemovePrefix { pattern: Word, longest: bool },
+    /// `${NAME%pattern}` / `${NAME%%pattern}`: removes the shortest or
+    /// longest suffix matching the pattern.
+    RemoveSuffix { pattern: Word, longest: bool },
+}
+
+impl Word {
+    /// Creates a word consisting of a single unquoted literal.
+    pub fn literal(text: impl Into<String>) -> Self {
+        Self { parts: vec![WordPart::Literal(text.into())] }
+    }
+
+    /// Returns the word's text if it consists solely of unquoted characters.
+    ///
+    /// Reserved words, alias names and assignment prefixes are only
+    /// recognised when they are not quoted.
+    pub fn as_literal(&self) -> Option<&str> {
+        match self.parts.as_slice() {
+            [WordPart::Literal(text)] => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Returns the word with all quoting removed.
+    pub fn unquoted(&self) -> String {
+        let mut out = String::new();
+        for part in &self.parts {
+            part.push_unquoted(&mut out);
+        }
+        out
+    }
+}
+
+impl WordPart {
+    fn push_unquoted(&self, out: &mut String) {
+        match self {
+            WordPart::Literal(text) | WordPart::Quoted(text) => out.push_str(text),
+            WordPart::DoubleQuoted(parts) => {
+                for part in parts {
+                    part.push_unquoted(out);
+                }
+            }
+            WordPart::Parameter(parameter) => out.push_str(&parameter.to_string()),
+            WordPart::CommandSubstitution(source) => {
+                out.push_str("$(");
+                out.push_str(source);
+                out.push(')');
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Parameter {
+    /// Writes the expansion as it would appear in a command line, with the
+    /// quoting of its operand removed.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let colon = |colon: bool| if colon { ":" } else { "" };
+        let (operator, operand) = match &self.op {
+            ParameterOp::Value => return write!(f, "${{{}}}", self.name),
+            ParameterOp::Length => return write!(f, "${{#{}}}", self.name),
+            ParameterOp::Default { word, colon: c } => (format!("{}-", colon(*c)), word),
+            ParameterOp::Assign { word, colon: c } => (format!("{}=", colon(*c)), word),
+            ParameterOp::Alternative { word, colon: c } => (format!("{}+", colon(*c)), word),
+            ParameterOp::Error { word, colon: c } => (format!("{}?", colon(*c)), word),
+            ParameterOp::RemovePrefix { pattern, longest } => ((if *longest { "##" } else { "#" }).to_string(), pattern),
+            ParameterOp::RemoveSuffix { pattern, longest } => ((if *longest { "%%" } else { "%" }).to_string(), pattern),
+        };
+        write!(f, "${{{}{}{}}}", self.name, operator, operand.unquoted())
+    }
+}
@@ -0,0 +1,873 @@
+//! Tokenizer that splits a raw command line into words and operators.
+//!
+//! Quoting is resolved here, so an operator character inside quotes
+//! (e.g. `echo "a|b"`) is part of a word rather than a pipe.
+
+use std::fmt;
+
+use super::ast::{HereDoc, Parameter, ParameterOp, Word, WordPart};
+use super::ParseError;
+
+/// A lexical token of the shell language.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Word(Word),
+    /// `|`
+    Pipe,
+    /// `&&`
+    AndIf,
+    /// `||`
+    OrIf,
+    /// `&`, which runs the list before it in the background.
+    Ampersand,
+    /// `;`
+    Semicolon,
+    /// `;;`, which ends an item of a `case` command.
+    DSemi,
+    /// `(`
+    LParen,
+    /// `)`
+    RParen,
+    /// A line break, which separates commands like `;`.
+    Newline,
+    /// A file descriptor number written directly before a redirection, as in `2>`.
+    IoNumber(u32),
+    /// `<`
+    Less,
+    /// `>`
+    Great,
+    /// `>>`
+    DGreat,
+    /// `<&`
+    LessAnd,
+    /// `>&`
+    GreatAnd,
+    /// `&>`
+    AndGreat,
+    /// `&>>`
+    AndDGreat,
+    /// `<<` or `<<-` together with the here-document body read after the
+    /// end of the line.
+    HereDoc(HereDoc),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "{}", word.unquoted()),
+            Token::Pipe => write!(f, "|"),
+            Token::AndIf => write!(f, "&&"),
+            Token::OrIf => write!(f, "||"),
+            Token::Ampersand => write!(f, "&"),
+            Token::Semicolon => write!(f, ";"),
+            Token::DSemi => write!(f, ";;"),
+            Token::LParen => write!(f, "("),
+            Token::RParen => write!(f, ")"),
+            Token::Newline => write!(f, "newline"),
+            Token::IoNumber(fd) => write!(f, "{}", fd),
+            Token::Less => write!(f, "<"),
+            Token::Great => write!(f, ">"),
+            Token::DGreat => write!(f, ">>"),
+            Token::LessAnd => write!(f, "<&"),
+            Token::GreatAnd => write!(f, ">&"),
+            Token::AndGreat => write!(f, "&>"),
+            Token::AndDGreat => write!(f, "&>>"),
+            Token::HereDoc(_) => write!(f, "<<"),
+        }
+    }
+}
+
+/// Special parameters that are a single punctuation character, as in `$?`.
+const SPECIAL_PARAMETERS: &str = "?$#@*";
+
+/// Splits `input` into tokens.
+///
+/// # Returns
+///
+/// The list of tokens, or a `ParseError::Incomplete` if a quote or a
+/// here-document is left open.
+pub fn tokenize(input: &str) -> Result<Vec<Token>, ParseError> {
+    Lexer::new(input).run()
+}
+
+/// Splits the body of an unquoted here-document into literal text, escaped
+/// characters, parameter expansions and command substitutions, as if it were
+/// inside double quotes.
+pub fn parse_heredoc_body(body: &str) -> Result<Vec<WordPart>, ParseError> {
+    Lexer::new(body).expandable(None)
+}
+
+/// Moves `literal` into `parts` as a `WordPart::Literal`, if it is not empty.
+fn push_literal(parts: &mut Vec<WordPart>, literal: &mut String) {
+    if !literal.is_empty() {
+        parts.push(WordPart::Literal(std::mem::take(literal)));
+    }
+}
+
+struct Lexer {
+    chars: Vec<char>,
+    pos: usize,
+    tokens: Vec<Token>,
+    /// Parts of the word currently being built.
+    parts: Vec<WordPart>,
+    /// Unquoted characters not yet flushed into `parts`.
+    literal: String,
+    /// Whether a word has been started, even if it is still empty (`""`).
+    in_word: bool,
+    /// Here-document operators on the current line, as `(token index, strip_tabs)`.
+    /// Their bodies start on the next line.
+    pending_heredocs: Vec<(usize, bool)>,
+}
+
+impl Lexer {
+    fn new(input: &str) -> Self {
+        Self {
+            chars: input.chars().collect(),
+            pos: 0,
+            tokens: Vec::new(),
+            parts: Vec::new(),
+            literal: String::new(),
+            in_word: false,
+            pending_heredocs: Vec::new(),
+        }
+    }
+
+    fn run(mut self) -> Result<Vec<Token>, ParseError> {
+        while let Some(c) = self.peek() {
+            match c {
+                ' ' | '\t' | '\r' => {
+                    self.finish_word();
+                    self.pos += 1;
+                }
+                '\n' => {
+                    self.finish_word();
+                    self.tokens.push(Token::Newline);
+                    self.pos += 1;
+                    self.read_heredoc_bodies()?;
+                }
+                '#' if !self.in_word => self.skip_comment(),
+                '|' if self.next_is('|') => self.operator2(Token::OrIf),
+                '|' => self.operator(Token::Pipe),
+                '&' if self.next_is('&') => self.operator2(Token::AndIf),
+                '&' if self.next_is('>') => {
+                    // `&>` always redirects both streams, so no descriptor number precedes it.
+                    if self.chars.get(self.pos + 2) == Some(&'>') {
+                        self.operator2(Token::AndDGreat);
+                        self.pos += 1;
+                    } else {
+                        self.operator2(Token::AndGreat);
+                    }
+                }
+                '&' => self.operator(Token::Ampersand),
+                ';' if self.next_is(';') => self.operator2(Token::DSemi),
+                ';' => self.operator(Token::Semicolon),
+                '(' => self.operator(Token::LParen),
+                ')' => self.operator(Token::RParen),
+                '>' if self.next_is('>') => self.redirect_operator(Token::DGreat, 2),
+                '>' if self.next_is('&') => self.redirect_operator(Token::GreatAnd, 2),
+                '>' => self.redirect_operator(Token::Great, 1),
+                '<' if self.next_is('<') => {
+                    let strip_tabs = self.chars.get(self.pos + 2) == Some(&'-');
+                    self.redirect_operator(Token::Less, if strip_tabs { 3 } else { 2 });
+                    // The body is filled in once the rest of the line has been read.
+                    self.pending_heredocs.push((self.tokens.len() - 1, strip_tabs));
+                }
+                '<' if self.next_is('&') => self.redirect_operator(Token::LessAnd, 2),
+                '<' => self.redirect_operator(Token::Less, 1),
+                '\'' => {
+                    let text = self.single_quoted()?;
+                    self.push_part(WordPart::Quoted(text));
+                }
+                '"' => {
+                    let parts = self.double_quoted()?;
+                    self.push_part(WordPart::DoubleQuoted(parts));
+                }
+                '\\' => self.read_escape(),
+                '`' => {
+                    let source = self.backquoted()?;
+                    self.push_part(WordPart::CommandSubstitution(source));
+                }
+                '$' => match self.expansion()? {
+                    Some(part) => self.push_part(part),
+                    None => {
+                        self.in_word = true;
+                        self.literal.push('$');
+                        self.pos += 1;
+                    }
+                },
+                _ => {
+                    self.in_word = true;
+                    self.literal.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+        self.finish_word();
+        if !self.pending_heredocs.is_empty() {
+            return Err(ParseError::Incomplete("here-document body is missing".to_string()));
+        }
+        Ok(self.tokens)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next_is(&self, c: char) -> bool {
+        self.chars.get(self.pos + 1) == Some(&c)
+    }
+
+    fn operator(&mut self, token: Token) {
+        self.finish_word();
+        self.tokens.push(token);
+        self.pos += 1;
+    }
+
+    /// Pushes a two-character operator.
+    fn operator2(&mut self, token: Token) {
+        self.operator(token);
+        self.pos += 1;
+    }
+
+    /// Pushes a redirection operator `len` characters long.
+    ///
+    /// If the word right before it consists only of digits, as in `2>`, that
+    /// word becomes the file descriptor of the redirection.
+    fn redirect_operator(&mut self, token: Token, len: usize) {
+        let io_number = if self.in_word && self.parts.is_empty() && !self.literal.is_empty() {
+            self.literal.parse::<u32>().ok().filter(|_| self.literal.chars().all(|c| c.is_ascii_digit()))
+        } else {
+            None
+        };
+        match io_number {
+            Some(fd) => {
+                self.literal.clear();
+                self.in_word = false;
+                self.tokens.push(Token::IoNumber(fd));
+            }
+            None => self.finish_word(),
+        }
+        self.tokens.push(token);
+        self.pos += len;
+    }
+
+    /// Reads the bodies of the here-documents opened on the line that just
+    /// ended, and replaces each `<<` and its delimiter word with a
+    /// `Token::HereDoc`.
+    fn read_heredoc_bodies(&mut self) -> Result<(), ParseError> {
+        let pending = std::mem::take(&mut self.pending_heredocs);
+        let mut bodies = Vec::new();
+
+        for &(index, strip_tabs) in &pending {
+            // Without a delimiter word the parser reports the error.
+            let Some(Token::Word(delimiter)) = self.tokens.get(index + 1) else { continue };
+            let expand = delimiter.parts.iter().all(|p| matches!(p, WordPart::Literal(_)));
+            let delimiter = delimiter.unquoted();
+
+            let mut body = String::new();
+            loop {
+                if self.pos >= self.chars.len() {
+                    return Err(ParseError::Incomplete(format!("here-document is missing its `{}' delimiter", delimiter)));
+                }
+                let mut line = String::new();
+                while let Some(c) = self.peek() {
+                    self.pos += 1;
+                    if c == '\n' {
+                        break;
+                    }
+                    line.push(c);
+                }
+                let line = line.strip_suffix('\r').unwrap_or(&line);
+                let line = if strip_tabs { line.trim_start_matches('\t') } else { line };
+                if line == delimiter {
+                    break;
+                }
+                body.push_str(line);
+                body.push('\n');
+            }
+            bodies.push((index, HereDoc { body, expand }));
+        }
+
+        // Replace from the back so earlier indices stay valid.
+        for (index, heredoc) in bodies.into_iter().rev() {
+            self.tokens[index] = Token::HereDoc(heredoc);
+            self.tokens.remove(index + 1);
+        }
+        Ok(())
+    }
+
+    fn skip_comment(&mut self) {
+        while let Some(c) = self.peek() {
+            if c == '\n' {
+                break;
+            }
+            self.pos += 1;
+        }
+    }
+
+    /// Moves pending unquoted characters into the current word's parts.
+    fn flush_literal(&mut self) {
+        push_literal(&mut self.parts, &mut self.literal);
+    }
+
+    /// Adds a quoted or expanded part to the current word.
+    fn push_part(&mut self, part: WordPart) {
+        self.flush_literal();
+        self.parts.push(part);
+        self.in_word = true;
+    }
+
+    fn finish_word(&mut self) {
+        self.flush_literal();
+        if self.in_word {
+            let parts = std::mem::take(&mut self.parts);
+            self.tokens.push(Token::Word(Word { parts }));
+            self.in_word = false;
+        }
+    }
+
+    /// Reads a single-quoted string, starting at the opening quote.
+    fn single_quoted(&mut self) -> Result<String, ParseError> {
+        self.pos += 1; // opening quote
+        let mut text = String::new();
+        loop {
+            match self.peek() {
+                Some('\'') => break,
+                Some(c) => {
+                    text.push(c);
+                    self.pos += 1;
+                }
+                None => return Err(ParseError::Incomplete("unterminated single quote".to_string())),
+            }
+        }
+        self.pos += 1; // closing quote
+        Ok(text)
+    }
+
+    /// Reads a double-quoted string, starting at the opening quote.
+    fn double_quoted(&mut self) -> Result<Vec<WordPart>, ParseError> {
+        self.pos += 1; // opening quote
+        self.expandable(Some('"'))
+    }
+
+    /// Reads text in which only backslash escapes, parameter expansions and
+    /// command substitutions are special, up to `terminator` or, without
+    /// one, to the end.
+    fn expandable(&mut self, terminator: Option<char>) -> Result<Vec<WordPart>, ParseError> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        loop {
+            match self.peek() {
+                None if terminator.is_none() => break,
+                None => return Err(ParseError::Incomplete("unterminated double quote".to_string())),
+                Some(c) if Some(c) == terminator => {
+                    self.pos += 1;
+                    break;
+                }
+                Some('\\') => {
+                    // Inside double quotes a backslash only escapes a few characters.
+                    match self.chars.get(self.pos + 1).copied() {
+                        Some(next @ ('"' | '\\' | '$' | '`')) => {
+                            push_literal(&mut parts, &mut literal);
+                            parts.push(WordPart::Quoted(next.to_string()));
+                            self.pos += 2;
+                        }
+                        Some('\n') => self.pos += 2,
+                        _ => {
+                            literal.push('\\');
+                            self.pos += 1;
+                        }
+                    }
+                }
+                Some('`') => {
+                    push_literal(&mut parts, &mut literal);
+                    parts.push(WordPart::CommandSubstitution(self.backquoted()?));
+                }
+                Some('$') => match self.expansion()? {
+                    Some(part) => {
+                        push_literal(&mut parts, &mut literal);
+                        parts.push(part);
+                    }
+                    None => {
+                        literal.push('$');
+                        self.pos += 1;
+                    }
+                },
+                Some(c) => {
+                    literal.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+        push_literal(&mut parts, &mut literal);
+        Ok(parts)
+    }
+
+    /// Reads a parameter expansion or command substitution starting at a `$`.
+    ///
+    /// # Returns
+    ///
+    /// `None`, without consuming anything, if the `$` is a literal character.
+    fn expansion(&mut self) -> Result<Option<WordPart>, ParseError> {
+        if self.next_is('(') {
+            self.pos += 2;
+            return self.command_substitution().map(|source| Some(WordPart::CommandSubstitution(source)));
+        }
+        Ok(self.parameter()?.map(WordPart::Parameter))
+    }
+
+    /// Reads the commands of a `$(...)` substitution, after the opening
+    /// parenthesis, up to and including the matching closing one.
+    fn command_substitution(&mut self) -> Result<String, ParseError> {
+        let start = self.pos;
+        let mut depth = 0;
+        loop {
+            let Some(c) = self.peek() else {
+                return Err(ParseError::Incomplete("unterminated command substitution".to_string()));
+            };
+            match c {
+                '\\' => self.pos += 2,
+                '\'' => {
+                    self.single_quoted()?;
+                }
+                '"' => {
+                    self.double_quoted()?;
+                }
+                '`' => {
+                    self.backquoted()?;
+                }
+                '$' => {
+                    if self.expansion()?.is_none() {
+                        self.pos += 1;
+                    }
+                }
+                '#' if self.pos == start || " \t\n;|&(".contains(self.chars[self.pos - 1]) => {
+                    while self.peek().is_some_and(|c| c != '\n') {
+                        self.pos += 1;
+                    }
+                }
+                '(' => {
+                    depth += 1;
+                    self.pos += 1;
+                }
+                ')' if depth == 0 => {
+                    let source = self.chars[start..self.pos].iter().collect();
+                    self.pos += 1;
+                    return Ok(source);
+                }
+                ')' => {
+                    depth -= 1;
+                    self.pos += 1;
+                }
+                _ => self.pos += 1,
+            }
+        }
+    }
+
+    /// Reads a `` `...` `` substitution, starting at the opening backquote.
+    ///
+    /// Inside backquotes a backslash only escapes `$`, `` ` `` and `\`.
+    fn backquoted(&mut self) -> Result<String, ParseError> {
+        self.pos += 1; // opening backquote
+        let mut source = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(ParseError::Incomplete("unterminated command substitution".to_string())),
+                Some('`') => break,
+                Some('\\') if matches!(self.chars.get(self.pos + 1), Some('$' | '`' | '\\')) => {
+                    source.push(self.chars[self.pos + 1]);
+                    self.pos += 2;
+                }
+                Some(c) => {
+                    source.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+        self.pos += 1; // closing backquote
+        Ok(source)
+    }
+
+    /// Reads a parameter expansion starting at a `$`.
+    ///
+    /// # Returns
+    ///
+    /// `None`, without consuming anything, if the `$` does not start an
+    /// expansion and is therefore a literal character.
+    fn parameter(&mut self) -> Result<Option<Parameter>, ParseError> {
+        let name = match self.chars.get(self.pos + 1).copied() {
+            Some('{') => {
+                self.pos += 2;
+                return self.braced_parameter().map(Some);
+            }
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                self.pos += 1;
+                self.read_name()
+            }
+            // Only one digit belongs to the name: `$10` is `${1}0`.
+            Some(c) if c.is_ascii_digit() || SPECIAL_PARAMETERS.contains(c) => {
+                self.pos += 2;
+                c.to_string()
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(Parameter { name, op: ParameterOp::Value }))
+    }
+
+    fn read_name(&mut self) -> String {
+        let mut name = String::new();
+        while let Some(c) = self.peek().filter(|c| c.is_ascii_alphanumeric() || *c == '_') {
+            name.push(c);
+            self.pos += 1;
+        }
+        name
+    }
+
+    /// Reads the rest of a `${...}` expansion, after the opening brace.
+    fn braced_parameter(&mut self) -> Result<Parameter, ParseError> {
+        let unterminated = || ParseError::Incomplete("unterminated parameter expansion".to_string());
+        let bad_substitution = || ParseError::Syntax("bad substitution".to_string());
+
+        // `${#NAME}` is the length of NAME, while `${#}` is the number of
+        // positional parameters.
+        let length = self.peek() == Some('#') && !matches!(self.chars.get(self.pos + 1), Some('}') | None);
+        if length {
+            self.pos += 1;
+        }
+        let name = match self.peek() {
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => self.read_name(),
+            Some(c) if c.is_ascii_digit() => {
+                let mut digits = String::new();
+                while let Some(c) = self.peek().filter(char::is_ascii_digit) {
+                    digits.push(c);
+                    self.pos += 1;
+                }
+                digits
+            }
+            Some(c) if SPECIAL_PARAMETERS.contains(c) => {
+                self.pos += 1;
+                c.to_string()
+            }
+            None => return Err(unterminated()),
+            Some(_) => return Err(bad_substitution()),
+        };
+        if length {
+            return match self.peek() {
+                Some('}') => {
+                    self.pos += 1;
+                    Ok(Parameter { name, op: ParameterOp::Length })
+                }
+                None => Err(unterminated()),
+                Some(_) => Err(bad_substitution()),
+            };
+        }
+
+        let colon = self.peek() == Some(':');
+        if colon {
+            self.pos += 1;
+        }
+        let Some(operator) = self.peek() else { return Err(unterminated()) };
+        self.pos += 1;
+        let op = match operator {
+            '}' if !colon => ParameterOp::Value,
+            '-' => ParameterOp::Default { word: self.operand()?, colon },
+            '=' => ParameterOp::Assign { word: self.operand()?, colon },
+            '+' => ParameterOp::Alternative { word: self.operand()?, colon },
+            '?' => ParameterOp::Error { word: self.operand()?, colon },
+            '#' | '%' if !colon => {
+                let longest = self.peek() == Some(operator);
+                if longest {
+                    self.pos += 1;
+                }
+                let pattern = self.operand()?;
+                if operator == '#' {
+                    ParameterOp::RemovePrefix { pattern, longest }
+                } else {
+                    ParameterOp::RemoveSuffix { pattern, longest }
+                }
+            }
+            _ => return Err(bad_substitution()),
+        };
+        Ok(Parameter { name, op })
+    }
+
+    /// Reads the word after the operator of a `${...}` expansion, up to and
+    /// including the closing brace. Whitespace in it is literal.
+    fn operand(&mut self) -> Result<Word, ParseError> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(ParseError::Incomplete("unterminated parameter expansion".to_string())),
+                Some('}') => {
+                    self.pos += 1;
+                    break;
+                }
+                Some('\'') => {
+                    push_literal(&mut parts, &mut literal);
+                    parts.push(WordPart::Quoted(self.single_quoted()?));
+                }
+                Some('"') => {
+                    push_literal(&mut parts, &mut literal);
+                    parts.push(WordPart::DoubleQuoted(self.double_quoted()?));
+                }
+                Some('\\') => match self.chars.get(self.pos + 1).copied() {
+                    Some(next) => {
+                        push_literal(&mut parts, &mut literal);
+                        parts.push(WordPart::Quoted(next.to_string()));
+                        self.pos += 2;
+                    }
+                    None => {
+                        literal.push('\\');
+                        self.pos += 1;
+                    }
+                },
+                Some('`') => {
+                    push_literal(&mut parts, &mut literal);
+                    parts.push(WordPart::CommandSubstitution(self.backquoted()?));
+                }
+                Some('$') => match self.expansion()? {
+                    Some(part) => {
+                        push_literal(&mut parts, &mut literal);
+                        parts.push(part);
+                    }
+                    None => {
+                        literal.push('$');
+                        self.pos += 1;
+                    }
+                },
+                Some(c) => {
+                    literal.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+        push_literal(&mut parts, &mut literal);
+        Ok(Word { parts })
+    }
+
+    fn read_escape(&mut self) {
+        match self.chars.get(self.pos + 1).copied() {
+            // A backslash before a newline continues the line.
+            Some('\n') => self.pos += 2,
+            Some(next) => {
+                self.push_part(WordPart::Quoted(next.to_string()));
+                self.pos += 2;
+            }
+            None => {
+                self.in_word = true;
+                self.literal.push('\\');
+                self.pos += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(tokens: &[Token]) -> Vec<String> {
+        tokens
+            .iter()
+            .map(|t| match t {
+                Token::Word(w) => w.unquoted(),
+                other => other.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_splits_on_whitespace_and_operators() {
+        let tokens = tokenize("ls -l|grep foo > out.txt; echo").unwrap();
+        assert_eq!(words(&tokens), vec!["ls", "-l", "|", "grep", "foo", ">", "out.txt", ";", "echo"]);
+    }
+
+    #[test]
+    fn test_and_or_operators() {
+        let tokens = tokenize("make&&make test || echo failed").unwrap();
+        assert_eq!(words(&tokens), vec!["make", "&&", "make", "test", "||", "echo", "failed"]);
+        // A single `&` separates commands, even without spaces around it.
+        let tokens = tokenize("a&b").unwrap();
+        assert_eq!(words(&tokens), vec!["a", "&", "b"]);
+    }
+
+    #[test]
+    fn test_case_operators() {
+        let tokens = tokenize("case $x in (a|b) echo;; *) ;;esac").unwrap();
+        assert_eq!(words(&tokens), vec!["case", "${x}", "in", "(", "a", "|", "b", ")", "echo", ";;", "*", ")", ";;", "esac"]);
+    }
+
+    #[test]
+    fn test_redirection_operators() {
+        let tokens = tokenize("cmd <in >>out 2>err 2>&1 &>all &>>log <&-").unwrap();
+        assert_eq!(
+            tokens[1..],
+            [
+                Token::Less,
+                Token::Word(Word::literal("in")),
+                Token::DGreat,
+                Token::Word(Word::literal("out")),
+                Token::IoNumber(2),
+                Token::Great,
+                Token::Word(Word::literal("err")),
+                Token::IoNumber(2),
+                Token::GreatAnd,
+                Token::Word(Word::literal("1")),
+                Token::AndGreat,
+                Token::Word(Word::literal("all")),
+                Token::AndDGreat,
+                Token::Word(Word::literal("log")),
+                Token::LessAnd,
+                Token::Word(Word::literal("-")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_io_number_needs_unquoted_digits() {
+        let tokens = tokenize("echo 2 > a '2'>b x2>c 2&>d").unwrap();
+        assert_eq!(words(&tokens), vec!["echo", "2", ">", "a", "2", ">", "b", "x2", ">", "c", "2", "&>", "d"]);
+        assert!(!tokens.iter().any(|t| matches!(t, Token::IoNumber(_))));
+    }
+
+    #[test]
+    fn test_heredoc() {
+        let tokens = tokenize("cat <<EOF | grep b\na $X\nb\nEOF\necho done").unwrap();
+        assert_eq!(
+            tokens[1],
+            Token::HereDoc(HereDoc { body: "a $X\nb\n".to_string(), expand: true })
+        );
+        assert_eq!(words(&tokens[2..]), vec!["|", "grep", "b", "newline", "echo", "done"]);
+    }
+
+    #[test]
+    fn test_heredoc_quoted_delimiter_and_tab_stripping() {
+        let tokens = tokenize("cat <<-'END'\n\tindented $X\n\tEND").unwrap();
+        assert_eq!(
+            tokens[1],
+            Token::HereDoc(HereDoc { body: "indented $X\n".to_string(), expand: false })
+        );
+    }
+
+    #[test]
+    fn test_unterminated_heredoc_is_incomplete() {
+        assert!(matches!(tokenize("cat <<EOF"), Err(ParseError::Incomplete(_))));
+        assert!(matches!(tokenize("cat <<EOF\nline"), Err(ParseError::Incomplete(_))));
+    }
+
+    #[test]
+    fn test_operators_inside_quotes_are_literal() {
+        let tokens = tokenize("echo \"a|b\" 'c>d' e\\;f").unwrap();
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(words(&tokens), vec!["echo", "a|b", "c>d", "e;f"]);
+    }
+
+    #[test]
+    fn test_quoted_parts_are_preserved() {
+        let tokens = tokenize("pre'single'\"double\"").unwrap();
+        let Token::Word(word) = &tokens[0] else { panic!("expected a word") };
+        assert_eq!(
+            word.parts,
+            vec![
+                WordPart::Literal("pre".to_string()),
+                WordPart::Quoted("single".to_string()),
+                WordPart::DoubleQuoted(vec![WordPart::Literal("double".to_string())]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_empty_quotes_make_a_word() {
+        let tokens = tokenize("echo \"\" ''").unwrap();
+        assert_eq!(words(&tokens), vec!["echo", "", ""]);
+    }
+
+    #[test]
+    fn test_double_quote_escapes() {
+        let tokens = tokenize(r#"echo "say \"hi\" \n""#).unwrap();
+        assert_eq!(words(&tokens), vec!["echo", "say \"hi\" \\n"]);
+    }
+
+    #[test]
+    fn test_comments_and_newlines() {
+        let tokens = tokenize("echo a # comment | not a pipe\necho b#c").unwrap();
+        assert_eq!(words(&tokens), vec!["echo", "a", "newline", "echo", "b#c"]);
+    }
+
+    #[test]
+    fn test_unterminated_quote_is_incomplete() {
+        assert!(matches!(tokenize("echo 'abc"), Err(ParseError::Incomplete(_))));
+        assert!(matches!(tokenize("echo \"abc"), Err(ParseError::Incomplete(_))));
+    }
+
+    fn parameter(name: &str, op: ParameterOp) -> WordPart {
+        WordPart::Parameter(Parameter { name: name.to_string(), op })
+    }
+
+    #[test]
+    fn test_parameters() {
+        let tokens = tokenize("a$HOME$1x\"$?\"$").unwrap();
+        let Token::Word(word) = &tokens[0] else { panic!("expected a word") };
+        assert_eq!(
+            word.parts,
+            vec![
+                WordPart::Literal("a".to_string()),
+                parameter("HOME", ParameterOp::Value),
+                parameter("1", ParameterOp::Value),
+                WordPart::Literal("x".to_string()),
+                WordPart::DoubleQuoted(vec![parameter("?", ParameterOp::Value)]),
+                WordPart::Literal("$".to_string()),
+            ]
+        );
+        let tokens = tokenize("'$HOME' \\$HOME").unwrap();
+        assert_eq!(words(&tokens), vec!["$HOME", "$HOME"]);
+    }
+
+    #[test]
+    fn test_braced_parameters() {
+        let tokens = tokenize("${#PATH} ${#} ${X:-a b} ${10%%.*}").unwrap();
+        let parts: Vec<&WordPart> = tokens
+            .iter()
+            .map(|t| match t {
+                Token::Word(word) => &word.parts[0],
+                other => panic!("unexpected token {}", other),
+            })
+            .collect();
+        assert_eq!(parts[0], &parameter("PATH", ParameterOp::Length));
+        assert_eq!(parts[1], &parameter("#", ParameterOp::Value));
+        assert_eq!(parts[2], &parameter("X", ParameterOp::Default { word: Word::literal("a b"), colon: true }));
+        assert_eq!(parts[3], &parameter("10", ParameterOp::RemoveSuffix { pattern: Word::literal(".*"), longest: true }));
+    }
+
+    #[test]
+    fn test_bad_parameters() {
+        assert!(matches!(tokenize("echo ${X"), Err(ParseError::Incomplete(_))));
+        assert!(matches!(tokenize("echo ${X:-'}"), Err(ParseError::Incomplete(_))));
+        assert!(matches!(tokenize("echo ${-x}"), Err(ParseError::Syntax(_))));
+        assert!(matches!(tokenize("echo ${X!}"), Err(ParseError::Syntax(_))));
+    }
+
+    #[test]
+    fn test_command_substitutions() {
+        let tokens = tokenize("echo $(git rev-parse --show-toplevel) \"v$(cat 'VER)')\" `echo \\`x\\``").unwrap();
+        let parts: Vec<&[WordPart]> = tokens
+            .iter()
+            .map(|t| match t {
+                Token::Word(word) => word.parts.as_slice(),
+                other => panic!("unexpected token {}", other),
+            })
+            .collect();
+        assert_eq!(parts[1], [WordPart::CommandSubstitution("git rev-parse --show-toplevel".to_string())]);
+        assert_eq!(
+            parts[2],
+            [WordPart::DoubleQuoted(vec![
+                WordPart::Literal("v".to_string()),
+                WordPart::CommandSubstitution("cat 'VER)'".to_string()),
+            ])]
+        );
+        assert_eq!(parts[3], [WordPart::CommandSubstitution("echo `x`".to_string())]);
+        assert!(matches!(tokenize("echo $(echo (a)"), Err(ParseError::Incomplete(_))));
+        assert!(matches!(tokenize("echo `date"), Err(ParseError::Incomplete(_))));
+    }
+}
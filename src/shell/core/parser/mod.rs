@@ -0,0 +1,893 @@
+//! Recursive-descent parser for the shell command language.
+//!
+//! Input is first split into tokens by the `lexer`, which resolves quoting,
+//! and then assembled into the tree defined in `ast`. Alias substitution
+//! happens here as well, since it applies to the command word of each
+//! simple command rather than to the whole line.
+
+pub mod ast;
+pub mod lexer;
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use ast::{
+    AndOrList, AndOrOperator, Assignment, CaseItem, Command, CommandList, CompoundCommand, FunctionDefinition,
+    Pipeline, Redirection, RedirectionKind, SimpleCommand, Word, WordPart,
+};
+use lexer::Token;
+
+/// An error produced while parsing a command line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// The input ended while a construct was still open, such as a quote
+    /// or a trailing `|`. More input could make it valid.
+    Incomplete(String),
+    /// The input can never be valid.
+    Syntax(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Incomplete(msg) => write!(f, "syntax error: {}", msg),
+            ParseError::Syntax(msg) => write!(f, "syntax error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parses a command line into a `CommandList`.
+///
+/// # Arguments
+///
+/// * `input` - The raw command line.
+/// * `aliases` - Aliases to substitute for command words.
+pub fn parse(input: &str, aliases: &HashMap<String, String>) -> Result<CommandList, ParseError> {
+    let tokens = lexer::tokenize(input)?;
+    Parser { tokens, pos: 0, aliases }.parse_list()
+}
+
+/// Reserved words that close a compound command, and so cannot start one.
+const CLOSING_WORDS: [&str; 8] = ["then", "elif", "else", "fi", "do", "done", "esac", "}"];
+
+/// Returns whether `input` is an unfinished command line, such as an `if`
+/// without its `fi`, an open quote or a trailing `|`, which more lines
+/// could complete.
+pub fn needs_more_input(input: &str) -> bool {
+    matches!(parse(input, &HashMap::new()), Err(ParseError::Incomplete(_)))
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    aliases: &'a HashMap<String, String>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    /// Returns the word at the current position if it is unquoted, which
+    /// is when it can be a reserved word such as `if` or `done`.
+    fn peek_literal(&self) -> Option<&str> {
+        match self.peek() {
+            Some(Token::Word(word)) => word.as_literal(),
+            _ => None,
+        }
+    }
+
+    /// Consumes the reserved word `reserved`, or fails if another token
+    /// comes next.
+    fn expect_reserved(&mut self, reserved: &str) -> Result<(), ParseError> {
+        if self.peek_literal() == Some(reserved) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.peek() == Some(&Token::Newline) {
+            self.pos += 1;
+        }
+    }
+
+    /// Builds the error for the token at the current position.
+    fn unexpected(&self) -> ParseError {
+        match self.peek() {
+            Some(token) => ParseError::Syntax(format!("unexpected token `{}'", token)),
+            None => ParseError::Incomplete("unexpected end of input".to_string()),
+        }
+    }
+
+    /// `list := and_or ((';' | '&' | newline) and_or)* [';' | '&']`
+    fn parse_list(&mut self) -> Result<CommandList, ParseError> {
+        let mut list = CommandList::default();
+        self.skip_newlines();
+        while self.peek().is_some() {
+            list.items.push(self.parse_and_or()?);
+            match self.peek() {
+                Some(Token::Semicolon) | Some(Token::Newline) | Some(Token::Ampersand) => {
+                    self.pos += 1;
+                    self.skip_newlines();
+                }
+                None => break,
+                Some(_) => return Err(self.unexpected()),
+            }
+        }
+        Ok(list)
+    }
+
+    /// `compound_list := linebreak and_or (separator and_or)* [separator]`
+    ///
+    /// The list of commands inside a compound command, which ends before
+    /// one of the reserved words in `terminators`, `;;` or `)`.
+    fn parse_compound_list(&mut self, terminators: &[&str]) -> Result<CommandList, ParseError> {
+        let mut list = CommandList::default();
+        self.skip_newlines();
+        loop {
+            let at_end = match self.peek() {
+                None | Some(Token::DSemi) | Some(Token::RParen) => true,
+                _ => self.peek_literal().is_some_and(|word| terminators.contains(&word)),
+            };
+            if at_end {
+                break;
+            }
+            list.items.push(self.parse_and_or()?);
+            match self.peek() {
+                Some(Token::Semicolon) | Some(Token::Newline) | Some(Token::Ampersand) => {
+                    self.pos += 1;
+                    self.skip_newlines();
+                }
+                _ => break,
+            }
+        }
+        Ok(list)
+    }
+
+    /// Parses a compound list that must contain at least one command, as
+    /// the conditions and bodies of `if`, `while` and `for` do.
+    fn parse_body(&mut self, terminators: &[&str]) -> Result<CommandList, ParseError> {
+        let list = self.parse_compound_list(terminators)?;
+        if list.items.is_empty() {
+            return Err(self.unexpected());
+        }
+        Ok(list)
+    }
+
+    /// `and_or := pipeline (('&&' | '||') linebreak pipeline)*`
+    ///
+    /// A `&` after the list, which is left for the caller to consume, makes
+    /// it a background job.
+    fn parse_and_or(&mut self) -> Result<AndOrList, ParseError> {
+        let start = self.pos;
+        let first = self.parse_pipeline()?;
+        let mut rest = Vec::new();
+        loop {
+            let operator = match self.peek() {
+                Some(Token::AndIf) => AndOrOperator::And,
+                Some(Token::OrIf) => AndOrOperator::Or,
+                _ => break,
+            };
+            self.pos += 1;
+            self.skip_newlines();
+            rest.push((operator, self.parse_pipeline()?));
+        }
+        let job = (self.peek() == Some(&Token::Ampersand)).then(|| job_text(&self.tokens[start..self.pos]));
+        Ok(AndOrList { first, rest, job })
+    }
+
+    /// `pipeline := ['!'] command ('|' linebreak command)*`
+    fn parse_pipeline(&mut self) -> Result<Pipeline, ParseError> {
+        let negated = self.peek_literal() == Some("!");
+        if negated {
+            self.pos += 1;
+        }
+        let mut commands = vec![self.parse_command()?];
+        while self.peek() == Some(&Token::Pipe) {
+            self.pos += 1;
+            self.skip_newlines();
+            commands.push(self.parse_command()?);
+        }
+        Ok(Pipeline { commands, negated })
+    }
+
+    /// `command := function_definition | compound_command redirection* | simple_command`
+    fn parse_command(&mut self) -> Result<Command, ParseError> {
+        if let Some(definition) = self.parse_function_definition()? {
+            return Ok(Command::FunctionDefinition(definition));
+        }
+        match self.parse_compound_command()? {
+            Some(compound) => Ok(Command::Compound(compound, self.parse_trailing_redirections()?)),
+            None if self.peek_literal().is_some_and(|word| CLOSING_WORDS.contains(&word)) => Err(self.unexpected()),
+            None => Ok(Command::Simple(self.parse_simple_command()?)),
+        }
+    }
+
+    /// Parses the compound command starting at the current position.
+    ///
+    /// # Returns
+    ///
+    /// `None`, without consuming anything, if no compound command starts here.
+    fn parse_compound_command(&mut self) -> Result<Option<CompoundCommand>, ParseError> {
+        let compound = match self.peek_literal() {
+            Some("{") => self.parse_brace_group()?,
+            Some("if") => self.parse_if()?,
+            Some("while") => self.parse_while(false)?,
+            Some("until") => self.parse_while(true)?,
+            Some("for") => self.parse_for()?,
+            Some("case") => self.parse_case()?,
+            _ => return Ok(None),
+        };
+        Ok(Some(compound))
+    }
+
+    /// Parses the redirections written after a compound command.
+    fn parse_trailing_redirections(&mut self) -> Result<Vec<Redirection>, ParseError> {
+        let mut redirections = Vec::new();
+        while matches!(self.peek(), Some(Token::IoNumber(_)) | Some(Token::HereDoc(_)))
+            || self.peek().is_some_and(is_redirection_operator)
+        {
+            redirections.push(self.parse_redirection()?);
+        }
+        Ok(redirections)
+    }
+
+    /// `function_definition := name '(' ')' linebreak compound_command redirection*
+    ///                       | 'function' name ['(' ')'] linebreak compound_command redirection*`
+    ///
+    /// # Returns
+    ///
+    /// `None`, without consuming anything, if no function definition starts here.
+    fn parse_function_definition(&mut self) -> Result<Option<FunctionDefinition>, ParseError> {
+        let keyword = self.peek_literal() == Some("function");
+        let name_pos = if keyword { self.pos + 1 } else { self.pos };
+        let name = match self.tokens.get(name_pos) {
+            Some(Token::Word(word)) => word.as_literal().map(str::to_string),
+            _ => None,
+        };
+        let has_parens = self.tokens.get(name_pos + 1) == Some(&Token::LParen);
+        if !keyword && !has_parens {
+            return Ok(None);
+        }
+        let name = match name {
+            Some(name) if is_valid_function_name(&name) => name,
+            Some(name) => return Err(ParseError::Syntax(format!("`{}': not a valid identifier", name))),
+            None => {
+                self.pos = name_pos;
+                return Err(self.unexpected());
+            }
+        };
+
+        self.pos = name_pos + 1;
+        if has_parens {
+            self.pos += 1;
+            if self.peek() != Some(&Token::RParen) {
+                return Err(self.unexpected());
+            }
+            self.pos += 1;
+        }
+        self.skip_newlines();
+        let Some(body) = self.parse_compound_command()? else { return Err(self.unexpected()) };
+        let redirections = self.parse_trailing_redirections()?;
+        Ok(Some(FunctionDefinition { name, body, redirections }))
+    }
+
+    /// `brace_group := '{' compound_list '}'`
+    fn parse_brace_group(&mut self) -> Result<CompoundCommand, ParseError> {
+        self.pos += 1; // `{`
+        let body = self.parse_body(&["}"])?;
+        self.expect_reserved("}")?;
+        Ok(CompoundCommand::BraceGroup(body))
+    }
+
+    /// `if_clause := 'if' list 'then' list ('elif' list 'then' list)* ['else' list] 'fi'`
+    fn parse_if(&mut self) -> Result<CompoundCommand, ParseError> {
+        self.pos += 1; // `if`
+        let mut branches = Vec::new();
+        let mut else_body = None;
+        loop {
+            let condition = self.parse_body(&["then"])?;
+            self.expect_reserved("then")?;
+            let body = self.parse_body(&["elif", "else", "fi"])?;
+            branches.push((condition, body));
+            match self.peek_literal() {
+                Some("elif") => self.pos += 1,
+                Some("else") => {
+                    self.pos += 1;
+                    else_body = Some(self.parse_body(&["fi"])?);
+                    self.expect_reserved("fi")?;
+                    break;
+                }
+                _ => {
+                    self.expect_reserved("fi")?;
+                    break;
+                }
+            }
+        }
+        Ok(CompoundCommand::If { branches, else_body })
+    }
+
+    /// `while_clause := ('while' | 'until') list 'do' list 'done'`
+    fn parse_while(&mut self, until: bool) -> Result<CompoundCommand, ParseError> {
+        self.pos += 1; // `while` or `until`
+        let condition = self.parse_body(&["do"])?;
+        self.expect_reserved("do")?;
+        let body = self.parse_body(&["done"])?;
+        self.expect_reserved("done")?;
+        Ok(CompoundCommand::While { condition, body, until })
+    }
+
+    /// `for_clause := 'for' name [linebreak 'in' word* separator] linebreak 'do' list 'done'`
+    fn parse_for(&mut self) -> Result<CompoundCommand, ParseError> {
+        self.pos += 1; // `for`
+        let variable = match self.peek() {
+            Some(Token::Word(word)) => match word.as_literal() {
+                Some(name) if is_valid_name(name) => name.to_string(),
+                _ => return Err(ParseError::Syntax(format!("`{}': not a valid identifier", word.unquoted()))),
+            },
+            _ => return Err(self.unexpected()),
+        };
+        self.pos += 1;
+
+        self.skip_newlines();
+        let words = if self.peek_literal() == Some("in") {
+            self.pos += 1;
+            let mut words = Vec::new();
+            while let Some(Token::Word(word)) = self.peek() {
+                words.push(word.clone());
+                self.pos += 1;
+            }
+            match self.peek() {
+                Some(Token::Semicolon) | Some(Token::Newline) => self.pos += 1,
+                _ => return Err(self.unexpected()),
+            }
+            Some(words)
+        } else {
+            if self.peek() == Some(&Token::Semicolon) {
+                self.pos += 1;
+            }
+            None
+        };
+
+        self.skip_newlines();
+        self.expect_reserved("do")?;
+        let body = self.parse_body(&["done"])?;
+        self.expect_reserved("done")?;
+        Ok(CompoundCommand::For { variable, words, body })
+    }
+
+    /// `case_clause := 'case' word linebreak 'in' linebreak case_item* 'esac'`, where
+    /// `case_item := ['('] word ('|' word)* ')' compound_list [';;'] linebreak`
+    fn parse_case(&mut self) -> Result<CompoundCommand, ParseError> {
+        self.pos += 1; // `case`
+        let word = self.parse_case_word()?;
+        self.skip_newlines();
+        self.expect_reserved("in")?;
+
+        let mut items = Vec::new();
+        loop {
+            self.skip_newlines();
+            if self.peek_literal() == Some("esac") {
+                self.pos += 1;
+                break;
+            }
+            if self.peek() == Some(&Token::LParen) {
+                self.pos += 1;
+            }
+            let mut patterns = vec![self.parse_case_word()?];
+            while self.peek() == Some(&Token::Pipe) {
+                self.pos += 1;
+                patterns.push(self.parse_case_word()?);
+            }
+            if self.peek() != Some(&Token::RParen) {
+                return Err(self.unexpected());
+            }
+            self.pos += 1;
+
+            let body = self.parse_compound_list(&["esac"])?;
+            items.push(CaseItem { patterns, body });
+            // The last item does not need its `;;`.
+            if self.peek() == Some(&Token::DSemi) {
+                self.pos += 1;
+            } else {
+                self.expect_reserved("esac")?;
+                break;
+            }
+        }
+        Ok(CompoundCommand::Case { word, items })
+    }
+
+    fn parse_case_word(&mut self) -> Result<Word, ParseError> {
+        match self.peek() {
+            Some(Token::Word(word)) => {
+                let word = word.clone();
+                self.pos += 1;
+                Ok(word)
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    /// `simple_command := (assignment | redirection)* (word | redirection)*`
+    fn parse_simple_command(&mut self) -> Result<SimpleCommand, ParseError> {
+        let mut command = SimpleCommand::default();
+        let mut expanded_aliases = HashSet::new();
+
+        loop {
+            match self.peek() {
+                Some(Token::Word(_)) => {
+                    if command.words.is_empty() && self.expand_alias(&mut expanded_aliases) {
+                        continue;
+                    }
+                    let Some(Token::Word(word)) = self.advance() else { unreachable!() };
+                    if command.words.is_empty() {
+                        if let Some(assignment) = split_assignment(&word) {
+                            command.assignments.push(assignment);
+                            continue;
+                        }
+                    }
+                    command.words.push(word);
+                }
+                Some(Token::IoNumber(_)) | Some(Token::HereDoc(_)) => {
+                    command.redirections.push(self.parse_redirection()?);
+                }
+                Some(token) if is_redirection_operator(token) => {
+                    command.redirections.push(self.parse_redirection()?);
+                }
+                _ => break,
+            }
+        }
+
+        if command.words.is_empty() && command.assignments.is_empty() && command.redirections.is_empty() {
+            return Err(self.unexpected());
+        }
+        Ok(command)
+    }
+
+    /// `redirection := [io_number] operator word | [io_number] here_doc`
+    fn parse_redirection(&mut self) -> Result<Redirection, ParseError> {
+        let fd = match self.peek() {
+            Some(Token::IoNumber(fd)) => {
+                let fd = *fd;
+                self.pos += 1;
+                Some(fd)
+            }
+            _ => None,
+        };
+
+        let kind = match self.advance() {
+            Some(Token::HereDoc(heredoc)) => RedirectionKind::HereDoc(heredoc),
+            Some(Token::Less) => RedirectionKind::Input(self.parse_redirection_target()?),
+            Some(Token::Great) => RedirectionKind::Output(self.parse_redirection_target()?),
+            Some(Token::DGreat) => RedirectionKind::Append(self.parse_redirection_target()?),
+            Some(Token::LessAnd) => RedirectionKind::DuplicateInput(self.parse_redirection_target()?),
+            Some(Token::GreatAnd) => RedirectionKind::DuplicateOutput(self.parse_redirection_target()?),
+            Some(Token::AndGreat) => RedirectionKind::OutputAndError(self.parse_redirection_target()?),
+            Some(Token::AndDGreat) => RedirectionKind::AppendAndError(self.parse_redirection_target()?),
+            _ => {
+                self.pos -= 1;
+                return Err(self.unexpected());
+            }
+        };
+        Ok(Redirection { fd, kind })
+    }
+
+    fn parse_redirection_target(&mut self) -> Result<Word, ParseError> {
+        match self.peek() {
+            Some(Token::Word(_)) => match self.advance() {
+                Some(Token::Word(word)) => Ok(word),
+                _ => unreachable!(),
+            },
+            Some(token) => Err(ParseError::Syntax(format!("unexpected token `{}' after redirection", token))),
+            None => Err(ParseError::Syntax("redirection target is missing".to_string())),
+        }
+    }
+
+    /// Replaces the word at the current position with its alias value, if
+    /// it names an alias that has not already been expanded for this command.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a substitution took place.
+    fn expand_alias(&mut self, expanded: &mut HashSet<String>) -> bool {
+        let Some(Token::Word(word)) = self.peek() else { return false };
+        let Some(name) = word.as_literal() else { return false };
+        if expanded.contains(name) {
+            return false;
+        }
+        let Some(value) = self.aliases.get(name) else { return false };
+        // An alias that does not tokenize cleanly is left unexpanded.
+        let Ok(replacement) = lexer::tokenize(value) else { return false };
+
+        expanded.insert(name.to_string());
+        self.tokens.splice(self.pos..self.pos + 1, replacement);
+        true
+    }
+}
+
+/// Writes the tokens of a background job back out as text, with quotes
+/// removed and line breaks shown as `;`.
+fn job_text(tokens: &[Token]) -> String {
+    let mut text = String::new();
+    let mut previous: Option<&Token> = None;
+    for token in tokens {
+        // `;` and the operator after a descriptor number, as in `2>`, are
+        // attached to what precedes them.
+        let attached = matches!(token, Token::Newline | Token::Semicolon) || matches!(previous, Some(Token::IoNumber(_)));
+        if !text.is_empty() && !attached {
+            text.push(' ');
+        }
+        match token {
+            Token::Newline => text.push(';'),
+            token => text.push_str(&token.to_string()),
+        }
+        previous = Some(token);
+    }
+    text
+}
+
+fn is_redirection_operator(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Less
+            | Token::Great
+            | Token::DGreat
+            | Token::LessAnd
+            | Token::GreatAnd
+            | Token::AndGreat
+            | Token::AndDGreat
+    )
+}
+
+/// Splits a `NAME=value` word into an assignment.
+fn split_assignment(word: &Word) -> Option<Assignment> {
+    let Some(WordPart::Literal(first)) = word.parts.first() else { return None };
+    let (name, rest) = first.split_once('=')?;
+    if !is_valid_name(name) {
+        return None;
+    }
+
+    let mut value_parts = Vec::new();
+    if !rest.is_empty() {
+        value_parts.push(WordPart::Literal(rest.to_string()));
+    }
+    value_parts.extend(word.parts[1..].iter().cloned());
+    Some(Assignment { name: name.to_string(), value: Word { parts: value_parts } })
+}
+
+/// Returns `true` if `name` is a valid shell variable name.
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Returns `true` if `name` can name a function: a variable name that may
+/// also contain `-`, as in `git-root`.
+fn is_valid_function_name(name: &str) -> bool {
+    is_valid_name(&name.replace('-', "_"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_simple(input: &str) -> Vec<SimpleCommand> {
+        let list = parse(input, &HashMap::new()).unwrap();
+        list.items
+            .into_iter()
+            .flat_map(|and_or| and_or.first.commands)
+            .filter_map(|command| match command {
+                Command::Simple(c) => Some(c),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn word_strings(command: &SimpleCommand) -> Vec<String> {
+        command.words.iter().map(Word::unquoted).collect()
+    }
+
+    #[test]
+    fn test_parse_pipeline() {
+        let list = parse("echo hello | grep h | grep e", &HashMap::new()).unwrap();
+        assert_eq!(list.items.len(), 1);
+        assert_eq!(list.items[0].first.commands.len(), 3);
+    }
+
+    #[test]
+    fn test_parse_and_or_list() {
+        let list = parse("cmd1 && cmd2 || cmd3 ; cmd4", &HashMap::new()).unwrap();
+        assert_eq!(list.items.len(), 2);
+        let operators: Vec<AndOrOperator> = list.items[0].rest.iter().map(|(op, _)| *op).collect();
+        assert_eq!(operators, vec![AndOrOperator::And, AndOrOperator::Or]);
+        assert!(list.items[1].rest.is_empty());
+    }
+
+    #[test]
+    fn test_and_or_continues_on_next_line() {
+        let list = parse("make &&\n  make test", &HashMap::new()).unwrap();
+        assert_eq!(list.items.len(), 1);
+        assert_eq!(list.items[0].rest.len(), 1);
+    }
+
+    #[test]
+    fn test_quoted_operators_do_not_split() {
+        let commands = parse_simple("echo \"a|b\" | grep '>'");
+        assert_eq!(commands.len(), 2);
+        assert_eq!(word_strings(&commands[0]), vec!["echo", "a|b"]);
+        assert_eq!(word_strings(&commands[1]), vec!["grep", ">"]);
+        assert!(commands[1].redirections.is_empty());
+    }
+
+    #[test]
+    fn test_redirection_on_any_command() {
+        let commands = parse_simple("ls > a.txt | grep x > b.txt");
+        assert_eq!(commands[0].redirections[0].kind, RedirectionKind::Output(Word::literal("a.txt")));
+        assert_eq!(commands[1].redirections[0].kind, RedirectionKind::Output(Word::literal("b.txt")));
+        assert_eq!(word_strings(&commands[1]), vec!["grep", "x"]);
+    }
+
+    #[test]
+    fn test_all_redirection_kinds() {
+        let commands = parse_simple("cmd < in >> out 2> err 2>&1 &> all 1>&2 <&-");
+        let redirections = &commands[0].redirections;
+        assert_eq!(word_strings(&commands[0]), vec!["cmd"]);
+        assert_eq!(redirections.len(), 7);
+        assert_eq!(redirections[0].kind, RedirectionKind::Input(Word::literal("in")));
+        assert_eq!(redirections[0].target_fd(), 0);
+        assert_eq!(redirections[1].kind, RedirectionKind::Append(Word::literal("out")));
+        assert_eq!(redirections[1].target_fd(), 1);
+        assert_eq!(redirections[2].fd, Some(2));
+        assert_eq!(redirections[2].kind, RedirectionKind::Output(Word::literal("err")));
+        assert_eq!(redirections[3].target_fd(), 2);
+        assert_eq!(redirections[3].kind, RedirectionKind::DuplicateOutput(Word::literal("1")));
+        assert_eq!(redirections[4].kind, RedirectionKind::OutputAndError(Word::literal("all")));
+        assert_eq!(redirections[5].fd, Some(1));
+        assert_eq!(redirections[6].kind, RedirectionKind::DuplicateInput(Word::literal("-")));
+    }
+
+    #[test]
+    fn test_heredoc_redirection() {
+        let commands = parse_simple("cat <<EOF > out.txt\nhello\nEOF\n");
+        assert_eq!(commands.len(), 1);
+        assert_eq!(
+            commands[0].redirections[0].kind,
+            RedirectionKind::HereDoc(ast::HereDoc { body: "hello\n".to_string(), expand: true })
+        );
+        assert_eq!(commands[0].redirections[1].kind, RedirectionKind::Output(Word::literal("out.txt")));
+    }
+
+    #[test]
+    fn test_redirection_before_command_name() {
+        let commands = parse_simple("> out.txt echo hi");
+        assert_eq!(word_strings(&commands[0]), vec!["echo", "hi"]);
+        assert_eq!(commands[0].redirections.len(), 1);
+    }
+
+    #[test]
+    fn test_assignments() {
+        let commands = parse_simple("FOO=bar BAZ='a b' cmd X=1");
+        assert_eq!(commands[0].assignments.len(), 2);
+        assert_eq!(commands[0].assignments[0].name, "FOO");
+        assert_eq!(commands[0].assignments[1].value.unquoted(), "a b");
+        // Assignments after the command name are plain arguments.
+        assert_eq!(word_strings(&commands[0]), vec!["cmd", "X=1"]);
+    }
+
+    #[test]
+    fn test_quoted_assignment_is_a_word() {
+        let commands = parse_simple("'FOO=bar'");
+        assert!(commands[0].assignments.is_empty());
+        assert_eq!(word_strings(&commands[0]), vec!["FOO=bar"]);
+    }
+
+    #[test]
+    fn test_command_list() {
+        let list = parse("echo a; echo b\n\necho c;", &HashMap::new()).unwrap();
+        assert_eq!(list.items.len(), 3);
+    }
+
+    #[test]
+    fn test_background_jobs() {
+        let list = parse("make 2>err.log && echo \"built it\" & sleep 1; echo c &", &HashMap::new()).unwrap();
+        let jobs: Vec<_> = list.items.iter().map(|and_or| and_or.job.as_deref()).collect();
+        assert_eq!(jobs, vec![Some("make 2> err.log && echo built it"), None, Some("echo c")]);
+
+        let list = parse("for i in 1 2\ndo sleep $i & done", &HashMap::new()).unwrap();
+        assert_eq!(list.items[0].job, None);
+        let Command::Compound(CompoundCommand::For { body, .. }, _) = &list.items[0].first.commands[0] else {
+            panic!("expected a for loop");
+        };
+        assert_eq!(body.items[0].job.as_deref(), Some("sleep ${i}"));
+        assert!(parse("sleep 1 & ;", &HashMap::new()).is_err());
+        assert!(parse("&", &HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        assert!(matches!(parse("| grep x", &HashMap::new()), Err(ParseError::Syntax(_))));
+        assert!(matches!(parse("echo a ;; echo b", &HashMap::new()), Err(ParseError::Syntax(_))));
+        assert!(matches!(parse("echo >", &HashMap::new()), Err(ParseError::Syntax(_))));
+        assert!(matches!(parse("cat < | x", &HashMap::new()), Err(ParseError::Syntax(_))));
+        assert!(matches!(parse("echo a |", &HashMap::new()), Err(ParseError::Incomplete(_))));
+        assert!(matches!(parse("echo a &&", &HashMap::new()), Err(ParseError::Incomplete(_))));
+        assert!(matches!(parse("&& echo a", &HashMap::new()), Err(ParseError::Syntax(_))));
+    }
+
+    #[test]
+    fn test_alias_expansion_per_command() {
+        let mut aliases = HashMap::new();
+        aliases.insert("ll".to_string(), "ls -l".to_string());
+        let list = parse("ll src | ll", &aliases).unwrap();
+        let Command::Simple(first) = &list.items[0].first.commands[0] else { panic!("expected a simple command") };
+        let Command::Simple(second) = &list.items[0].first.commands[1] else { panic!("expected a simple command") };
+        assert_eq!(word_strings(first), vec!["ls", "-l", "src"]);
+        assert_eq!(word_strings(second), vec!["ls", "-l"]);
+    }
+
+    #[test]
+    fn test_recursive_alias_expands_once() {
+        let mut aliases = HashMap::new();
+        aliases.insert("ls".to_string(), "ls -a".to_string());
+        let list = parse("ls", &aliases).unwrap();
+        let Command::Simple(command) = &list.items[0].first.commands[0] else { panic!("expected a simple command") };
+        assert_eq!(word_strings(command), vec!["ls", "-a"]);
+    }
+
+    #[test]
+    fn test_alias_only_applies_to_command_word() {
+        let mut aliases = HashMap::new();
+        aliases.insert("ll".to_string(), "ls -l".to_string());
+        let commands = {
+            let list = parse("echo ll", &aliases).unwrap();
+            list.items.into_iter().flat_map(|and_or| and_or.first.commands).collect::<Vec<_>>()
+        };
+        let Command::Simple(command) = &commands[0] else { panic!("expected a simple command") };
+        assert_eq!(word_strings(command), vec!["echo", "ll"]);
+    }
+
+    fn parse_compound(input: &str) -> CompoundCommand {
+        let list = parse(input, &HashMap::new()).unwrap();
+        match &list.items[0].first.commands[0] {
+            Command::Compound(compound, _) => compound.clone(),
+            _ => panic!("expected a compound command"),
+        }
+    }
+
+    #[test]
+    fn test_if_elif_else() {
+        let CompoundCommand::If { branches, else_body } =
+            parse_compound("if test -d a; then echo a\nelif false\nthen echo b; else echo c; fi")
+        else {
+            panic!("expected an if command")
+        };
+        assert_eq!(branches.len(), 2);
+        assert_eq!(branches[1].0.items.len(), 1);
+        assert_eq!(else_body.unwrap().items.len(), 1);
+    }
+
+    #[test]
+    fn test_while_and_until() {
+        let compound = parse_compound("while true\ndo\n  echo x; break\ndone");
+        let CompoundCommand::While { condition, body, until } = compound else { panic!("expected a loop") };
+        assert!(!until);
+        assert_eq!((condition.items.len(), body.items.len()), (1, 2));
+        assert!(matches!(parse_compound("until false; do :; done"), CompoundCommand::While { until: true, .. }));
+    }
+
+    #[test]
+    fn test_for_loops() {
+        let CompoundCommand::For { variable, words, .. } = parse_compound("for f in *.rs 'a b'; do echo $f; done") else {
+            panic!("expected a for loop")
+        };
+        assert_eq!(variable, "f");
+        assert_eq!(words.unwrap().iter().map(Word::unquoted).collect::<Vec<_>>(), vec!["*.rs", "a b"]);
+        assert!(matches!(parse_compound("for arg\ndo echo $arg; done"), CompoundCommand::For { words: None, .. }));
+        assert!(matches!(parse("for 1x in a; do :; done", &HashMap::new()), Err(ParseError::Syntax(_))));
+    }
+
+    #[test]
+    fn test_case() {
+        let CompoundCommand::Case { word, items } =
+            parse_compound("case $1 in\n  start|run) echo go;;\n  (stop) ;;\n  *) echo other\nesac")
+        else {
+            panic!("expected a case command")
+        };
+        assert_eq!(word.unquoted(), "${1}");
+        let patterns: Vec<Vec<String>> = items.iter().map(|item| item.patterns.iter().map(Word::unquoted).collect()).collect();
+        assert_eq!(patterns, vec![vec!["start", "run"], vec!["stop"], vec!["*"]]);
+        assert!(items[1].body.items.is_empty());
+    }
+
+    #[test]
+    fn test_brace_group() {
+        let CompoundCommand::BraceGroup(body) = parse_compound("{ echo a; echo b\n}") else {
+            panic!("expected a brace group")
+        };
+        assert_eq!(body.items.len(), 2);
+        // Without a separator, `}` is an argument and the group stays open.
+        assert!(needs_more_input("{ echo a }"));
+        assert!(matches!(parse("}", &HashMap::new()), Err(ParseError::Syntax(_))));
+    }
+
+    #[test]
+    fn test_function_definitions() {
+        let definitions: Vec<FunctionDefinition> = [
+            "greet() { echo hi; }",
+            "greet ( )\n{\n  echo hi\n}",
+            "function greet { echo hi; }",
+            "function greet() { echo hi; }",
+        ]
+        .iter()
+        .map(|input| match parse(input, &HashMap::new()).unwrap().items[0].first.commands[0].clone() {
+            Command::FunctionDefinition(definition) => definition,
+            command => panic!("expected a function definition, got {:?}", command),
+        })
+        .collect();
+        assert!(definitions.iter().all(|definition| definition.name == "greet"));
+        assert!(definitions.iter().all(|definition| matches!(definition.body, CompoundCommand::BraceGroup(_))));
+
+        let list = parse("log-to() if true; then echo x; fi > out.log", &HashMap::new()).unwrap();
+        let Command::FunctionDefinition(definition) = &list.items[0].first.commands[0] else {
+            panic!("expected a function definition")
+        };
+        assert_eq!(definition.name, "log-to");
+        assert!(matches!(definition.body, CompoundCommand::If { .. }));
+        assert_eq!(definition.redirections.len(), 1);
+
+        assert!(matches!(parse("f() echo hi", &HashMap::new()), Err(ParseError::Syntax(_))));
+        assert!(matches!(parse("1x() { :; }", &HashMap::new()), Err(ParseError::Syntax(_))));
+        assert!(needs_more_input("f() {\n  echo hi"));
+        assert!(needs_more_input("function f"));
+    }
+
+    #[test]
+    fn test_compound_commands_in_pipelines() {
+        let list = parse("! for x in a; do echo $x; done > out | grep a && echo found", &HashMap::new()).unwrap();
+        let pipeline = &list.items[0].first;
+        assert!(pipeline.negated);
+        assert_eq!(pipeline.commands.len(), 2);
+        let Command::Compound(_, redirections) = &pipeline.commands[0] else { panic!("expected a compound command") };
+        assert_eq!(redirections[0].kind, RedirectionKind::Output(Word::literal("out")));
+        assert_eq!(list.items[0].rest.len(), 1);
+    }
+
+    #[test]
+    fn test_needs_more_input() {
+        assert!(needs_more_input("for x in a b; do\n  echo $x"));
+        assert!(needs_more_input("echo 'open"));
+        assert!(!needs_more_input("for x in a b; do\n  echo $x\ndone"));
+        assert!(!needs_more_input("echo a )"));
+    }
+
+    #[test]
+    fn test_reserved_words_only_in_command_position() {
+        assert_eq!(word_strings(&parse_simple("echo if then done")[0]), vec!["echo", "if", "then", "done"]);
+        assert_eq!(word_strings(&parse_simple("'if' x")[0]), vec!["if", "x"]);
+    }
+
+    #[test]
+    fn test_incomplete_compound_commands() {
+        for input in ["if true; then", "if true; then echo\n", "while true; do echo x", "for x in a b", "case x in a)"] {
+            assert!(matches!(parse(input, &HashMap::new()), Err(ParseError::Incomplete(_))), "{}", input);
+        }
+        for input in ["fi", "if; then x; fi", "while true; do done", "echo a )"] {
+            assert!(matches!(parse(input, &HashMap::new()), Err(ParseError::Syntax(_))), "{}", input);
+        }
+    }
+}
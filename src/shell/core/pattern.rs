@@ -0,0 +1,232 @@
+//! Shell pattern matching, as used by `${VAR#pattern}` and friends.
+//!
+//! A pattern is made of ordinary characters and the wildcards `*` (any
+//! string), `?` (any character) and `[...]` (any character of a set, with
+//! ranges such as `a-z` and negation with `!` or `^`). Characters that were
+//! quoted in the command line always match themselves.
+
+/// A compiled shell pattern.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    tokens: Vec<Token>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Char(char),
+    /// `?`
+    AnyChar,
+    /// `*`
+    AnyString,
+    /// `[...]`
+    Class { negated: bool, items: Vec<ClassItem> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ClassItem {
+    Char(char),
+    Range(char, char),
+}
+
+impl Token {
+    /// Returns whether this token, other than `*`, matches the character `c`.
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Token::Char(expected) => *expected == c,
+            Token::AnyChar => true,
+            Token::AnyString => false,
+            Token::Class { negated, items } => {
+                let found = items.iter().any(|item| match item {
+                    ClassItem::Char(expected) => *expected == c,
+                    ClassItem::Range(start, end) => (*start..=*end).contains(&c),
+                });
+                found != *negated
+            }
+        }
+    }
+}
+
+impl Pattern {
+    /// Compiles a pattern in which no character is quoted.
+    pub fn parse(pattern: &str) -> Self {
+        let chars: Vec<(char, bool)> = pattern.chars().map(|c| (c, false)).collect();
+        Self::new(&chars)
+    }
+
+    /// Compiles a pattern.
+    ///
+    /// # Arguments
+    ///
+    /// * `chars` - The characters of the pattern, each with whether it was
+    ///   quoted. Quoted characters lose their special meaning.
+    pub fn new(chars: &[(char, bool)]) -> Self {
+        let mut tokens = Vec::new();
+        let mut index = 0;
+        while index < chars.len() {
+            let (c, quoted) = chars[index];
+            index += 1;
+            let token = match c {
+                _ if quoted => Token::Char(c),
+                '*' => Token::AnyString,
+                '?' => Token::AnyChar,
+                '[' => match parse_class(&chars[index..]) {
+                    Some((token, consumed)) => {
+                        index += consumed;
+                        token
+                    }
+                    // Without a closing bracket, `[` is an ordinary character.
+                    None => Token::Char('['),
+                },
+                _ => Token::Char(c),
+            };
+            tokens.push(token);
+        }
+        Self { tokens }
+    }
+
+    /// Returns whether the pattern contains any wildcards.
+    pub fn has_wildcards(&self) -> bool {
+        self.tokens.iter().any(|token| !matches!(token, Token::Char(_)))
+    }
+
+    /// Returns whether the pattern matches the whole of `text`.
+    pub fn matches(&self, text: &str) -> bool {
+        let chars: Vec<char> = text.chars().collect();
+        self.matches_chars(&chars)
+    }
+
+    fn matches_chars(&self, text: &[char]) -> bool {
+        let (mut token, mut position) = (0, 0);
+        // Where to resume after the most recent `*` if the rest fails to match.
+        let mut backtrack: Option<(usize, usize)> = None;
+        while position < text.len() {
+            match self.tokens.get(token) {
+                Some(Token::AnyString) => {
+                    backtrack = Some((token, position));
+                    token += 1;
+                    continue;
+                }
+                Some(t) if t.matches(text[position]) => {
+                    token += 1;
+                    position += 1;
+                    continue;
+                }
+                _ => {}
+            }
+            match backtrack {
+                Some((star, start)) => {
+                    token = star + 1;
+                    position = start + 1;
+                    backtrack = Some((star, start + 1));
+                }
+                None => return false,
+            }
+        }
+        self.tokens[token..].iter().all(|t| *t == Token::AnyString)
+    }
+
+    /// Removes the shortest or longest prefix of `text` that the pattern
+    /// matches, as `${VAR#pattern}` and `${VAR##pattern}` do.
+    pub fn strip_prefix(&self, text: &str, longest: bool) -> String {
+        let chars: Vec<char> = text.chars().collect();
+        let mut lengths: Vec<usize> = (0..=chars.len()).collect();
+        if longest {
+            lengths.reverse();
+        }
+        match lengths.into_iter().find(|&len| self.matches_chars(&chars[..len])) {
+            Some(len) => chars[len..].iter().collect(),
+            None => text.to_string(),
+        }
+    }
+
+    /// Removes the shortest or longest suffix of `text` that the pattern
+    /// matches, as `${VAR%pattern}` and `${VAR%%pattern}` do.
+    pub fn strip_suffix(&self, text: &str, longest: bool) -> String {
+        let chars: Vec<char> = text.chars().collect();
+        let mut starts: Vec<usize> = (0..=chars.len()).collect();
+        if !longest {
+            starts.reverse();
+        }
+        match starts.into_iter().find(|&start| self.matches_chars(&chars[start..])) {
+            Some(start) => chars[..start].iter().collect(),
+            None => text.to_string(),
+        }
+    }
+}
+
+/// Parses a bracket expression after its opening `[`.
+///
+/// # Returns
+///
+/// The class and the number of characters it used, including the closing
+/// `]`, or `None` if the bracket is never closed.
+fn parse_class(chars: &[(char, bool)]) -> Option<(Token, usize)> {
+    let mut index = 0;
+    let negated = matches!(chars.first(), Some(('!' | '^', false)));
+    if negated {
+        index += 1;
+    }
+    let mut items = Vec::new();
+    let start = index;
+    loop {
+        let &(c, quoted) = chars.get(index)?;
+        // A `]` right after the opening bracket is part of the set.
+        if c == ']' && !quoted && index > start {
+            return Some((Token::Class { negated, items }, index + 1));
+        }
+        match (chars.get(index + 1), chars.get(index + 2)) {
+            (Some(('-', false)), Some(&(end, end_quoted))) if end != ']' || end_quoted => {
+                items.push(ClassItem::Range(c, end));
+                index += 3;
+            }
+            _ => {
+                items.push(ClassItem::Char(c));
+                index += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wildcards() {
+        assert!(Pattern::parse("*.rs").matches("main.rs"));
+        assert!(!Pattern::parse("*.rs").matches("main.rs.bak"));
+        assert!(Pattern::parse("a*b*c").matches("a-b-b-c"));
+        assert!(Pattern::parse("?x").matches("ax"));
+        assert!(!Pattern::parse("?x").matches("x"));
+        assert!(Pattern::parse("*").matches(""));
+        assert!(!Pattern::parse("plain").has_wildcards());
+    }
+
+    #[test]
+    fn test_bracket_expressions() {
+        let pattern = Pattern::parse("file[0-9][!a]");
+        assert!(pattern.matches("file1b"));
+        assert!(!pattern.matches("file1a"));
+        assert!(!pattern.matches("filexb"));
+        assert!(Pattern::parse("[]x]").matches("]"));
+        assert!(Pattern::parse("[^x]").matches("y"));
+        assert!(Pattern::parse("a[").matches("a["));
+    }
+
+    #[test]
+    fn test_quoted_characters_are_literal() {
+        let pattern = Pattern::new(&[('*', true), ('.', false), ('*', false)]);
+        assert!(pattern.matches("*.txt"));
+        assert!(!pattern.matches("a.txt"));
+    }
+
+    #[test]
+    fn test_strip_prefix_and_suffix() {
+        let path = "/usr/local/lib/file.tar.gz";
+        assert_eq!(Pattern::parse("*/").strip_prefix(path, false), "usr/local/lib/file.tar.gz");
+        assert_eq!(Pattern::parse("*/").strip_prefix(path, true), "file.tar.gz");
+        assert_eq!(Pattern::parse(".*").strip_suffix(path, false), "/usr/local/lib/file.tar");
+        assert_eq!(Pattern::parse(".*").strip_suffix(path, true), "/usr/local/lib/file");
+        assert_eq!(Pattern::parse("x*").strip_prefix(path, true), path);
+    }
+}
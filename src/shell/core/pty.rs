@@ -0,0 +1,374 @@
+//! Running interactive programs under a pseudo-terminal.
+//!
+//! Commands normally run with piped standard streams, which programs such as
+//! `python`, `vim` or `top` detect and refuse to be interactive with. A
+//! `PtySession` instead starts the program on a pseudo-terminal and feeds
+//! everything it prints through a VT100 parser, so the GUI can draw the
+//! resulting screen and forward keystrokes back to the program.
+
+use anyhow::{anyhow, Result};
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
+use std::io::{Read, Write};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+use crate::shell::core::builtins;
+use crate::shell::core::expansion;
+use crate::shell::core::external::find_executable_in_path;
+use crate::shell::core::parser::{self, ast};
+use crate::shell::core::ShellCore;
+
+/// The word that asks for a command to run under a pseudo-terminal, as in
+/// `pty ssh-keygen`.
+pub const PTY_PREFIX: &str = "pty";
+
+/// Full-screen programs, and `ssh-keygen`, which asks for a passphrase, are
+/// started under a pseudo-terminal whatever their arguments.
+const SCREEN_PROGRAMS: &[&str] = &["emacs", "htop", "less", "man", "more", "nano", "nvim", "ssh-keygen", "top", "vi", "vim"];
+
+/// A program that is only interactive when it is not given something to
+/// run, such as `python` rather than `python script.py`.
+struct PromptProgram {
+    name: &'static str,
+    /// The operands (arguments that are not options) it may have and still
+    /// be interactive: `ssh host` logs in, while `ssh host cmd` just runs
+    /// `cmd`.
+    max_operands: usize,
+    /// The options whose value is the next argument, as in `ssh -p 22`.
+    value_options: &'static [&'static str],
+    /// The options that give it something to run, as in `bash -c cmd`.
+    batch_options: &'static [&'static str],
+}
+
+const SHELL_VALUE_OPTIONS: &[&str] = &["-o", "-O", "+o", "+O"];
+
+const PROMPT_PROGRAMS: &[PromptProgram] = &[
+    PromptProgram { name: "bash", max_operands: 0, value_options: SHELL_VALUE_OPTIONS, batch_options: &["-c"] },
+    PromptProgram { name: "fish", max_operands: 0, value_options: &[], batch_options: &["-c", "--command"] },
+    PromptProgram { name: "ftp", max_operands: 2, value_options: &[], batch_options: &[] },
+    PromptProgram { name: "ipython", max_operands: 0, value_options: &[], batch_options: &["-c", "-m"] },
+    PromptProgram { name: "irb", max_operands: 0, value_options: &["-r", "-I"], batch_options: &[] },
+    PromptProgram {
+        name: "mysql",
+        max_operands: 1,
+        value_options: &["-u", "-h", "-P", "-D", "-S", "--user", "--host", "--port", "--database", "--socket"],
+        batch_options: &["-e", "--execute"],
+    },
+    PromptProgram { name: "node", max_operands: 0, value_options: &["-r", "--require"], batch_options: &["-e", "-p", "--eval", "--print"] },
+    PromptProgram {
+        name: "psql",
+        max_operands: 2,
+        value_options: &["-U", "-h", "-p", "-d", "-v", "-P", "--username", "--host", "--port", "--dbname", "--set"],
+        batch_options: &["-c", "-f", "-l", "--command", "--file", "--list"],
+    },
+    PromptProgram { name: "python", max_operands: 0, value_options: &["-W", "-X"], batch_options: &["-c", "-m"] },
+    PromptProgram { name: "python3", max_operands: 0, value_options: &["-W", "-X"], batch_options: &["-c", "-m"] },
+    PromptProgram { name: "sh", max_operands: 0, value_options: SHELL_VALUE_OPTIONS, batch_options: &["-c"] },
+    PromptProgram { name: "sqlite3", max_operands: 1, value_options: &["-cmd", "-separator", "-nullvalue"], batch_options: &[] },
+    PromptProgram {
+        name: "ssh",
+        max_operands: 1,
+        value_options: &[
+            "-B", "-b", "-c", "-D", "-E", "-e", "-F", "-I", "-i", "-J", "-L", "-l", "-m", "-O", "-o", "-p", "-Q", "-R",
+            "-S", "-W", "-w",
+        ],
+        batch_options: &[],
+    },
+    PromptProgram { name: "zsh", max_operands: 0, value_options: SHELL_VALUE_OPTIONS, batch_options: &["-c"] },
+];
+
+impl PromptProgram {
+    /// Returns whether the program, given `args`, waits for input typed at
+    /// its prompt.
+    fn is_interactive(&self, args: &[String]) -> bool {
+        let mut operands = 0;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if self.batch_options.contains(&arg.as_str()) {
+                return false;
+            }
+            if self.value_options.contains(&arg.as_str()) {
+                args.next();
+            } else if arg == "--" {
+                operands += args.len();
+                break;
+            } else if !arg.starts_with('-') && !arg.starts_with('+') {
+                operands += 1;
+            }
+        }
+        operands <= self.max_operands
+    }
+}
+
+/// Lines of scrollback kept by the terminal emulator.
+const SCROLLBACK_LINES: usize = 1000;
+
+/// How long to wait for the last output of a program that has exited.
+const DRAIN_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Decides whether a command line should run under a pseudo-terminal.
+///
+/// That is the case for a single command without redirections that either
+/// starts with `pty` or names one of the known interactive programs. A
+/// program such as `python` is only recognised without operands, since
+/// `python script.py` prints its output like any other command, and the
+/// terminal only keeps its last screen and scrollback.
+///
+/// # Arguments
+///
+/// * `shell_core` - The shell state used to expand aliases and words.
+/// * `command_str` - The command line entered by the user.
+///
+/// # Returns
+///
+/// The program and its arguments, or `None` if the line should run normally.
+pub fn interactive_command(shell_core: &ShellCore, command_str: &str) -> Option<Vec<String>> {
+    let list = parser::parse(command_str, &shell_core.aliases).ok()?;
+    let [and_or] = list.items.as_slice() else { return None };
+    // A background job never gets the terminal.
+    if !and_or.rest.is_empty() || and_or.job.is_some() {
+        return None;
+    }
+    let [ast::Command::Simple(command)] = and_or.first.commands.as_slice() else { return None };
+    if !command.assignments.is_empty() || !command.redirections.is_empty() {
+        return None;
+    }
+
+    // Expanding may assign variables, which is only done when the command runs.
+    let mut words = expansion::expand_words_without_commands(&mut shell_core.clone(), &command.words)?;
+    if words.first().map(String::as_str) == Some(PTY_PREFIX) {
+        words.remove(0);
+        return (!words.is_empty()).then_some(words);
+    }
+
+    let (name, args) = words.split_first()?;
+    let interactive = is_known_interactive(name, args)
+        && !shell_core.functions.contains_key(name)
+        && builtins::registry().get(name).is_none()
+        && find_executable_in_path(name).is_some();
+    interactive.then_some(words)
+}
+
+/// Returns whether `name` is one of the known interactive programs, and
+/// `args` leave it waiting for input.
+fn is_known_interactive(name: &str, args: &[String]) -> bool {
+    SCREEN_PROGRAMS.contains(&name)
+        || PROMPT_PROGRAMS.iter().any(|program| program.name == name && program.is_interactive(args))
+}
+
+/// A program running under a pseudo-terminal.
+///
+/// A background thread reads the program's output into a `vt100::Parser`,
+/// whose screen can be inspected through `parser`. Dropping the session kills
+/// the program.
+pub struct PtySession {
+    master: Box<dyn MasterPty + Send>,
+    writer: Box<dyn Write + Send>,
+    child: Box<dyn Child + Send + Sync>,
+    parser: Arc<Mutex<vt100::Parser>>,
+    /// Signalled by the reader thread when the terminal is closed.
+    reader_done: Receiver<()>,
+}
+
+impl PtySession {
+    /// Starts a program under a new pseudo-terminal.
+    ///
+    /// # Arguments
+    ///
+    /// * `shell_core` - Supplies the working directory and exported variables.
+    /// * `argv` - The program followed by its arguments.
+    /// * `rows`, `cols` - The initial size of the terminal.
+    /// * `on_output` - Called from the reader thread whenever the screen changes.
+    pub fn spawn(
+        shell_core: &ShellCore,
+        argv: &[String],
+        rows: u16,
+        cols: u16,
+        on_output: impl Fn() + Send + 'static,
+    ) -> Result<Self> {
+        let (program, args) = argv.split_first().ok_or_else(|| anyhow!("pty: missing command"))?;
+        let pair = native_pty_system().openpty(PtySize { rows, cols, pixel_width: 0, pixel_height: 0 })?;
+
+        let mut cmd = CommandBuilder::new(program);
+        cmd.args(args);
+        cmd.cwd(&shell_core.current_dir);
+        cmd.env("TERM", "xterm-256color");
+        for (key, value) in &shell_core.env_vars {
+            cmd.env(key, value);
+        }
+        let child = pair
+            .slave
+            .spawn_command(cmd)
+            .map_err(|e| anyhow!("Failed to spawn command '{}': {}", program, e))?;
+        // Only the child may hold the terminal open, or reads never see its end.
+        drop(pair.slave);
+
+        let mut reader = pair.master.try_clone_reader()?;
+        let writer = pair.master.take_writer()?;
+        let parser = Arc::new(Mutex::new(vt100::Parser::new(rows, cols, SCROLLBACK_LINES)));
+        let (done_sender, reader_done) = mpsc::channel();
+        let parser_for_reader = parser.clone();
+        thread::spawn(move || {
+            let mut buffer = [0; 8192];
+            loop {
+                match reader.read(&mut buffer) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => {
+                        parser_for_reader.lock().unwrap().process(&buffer[..n]);
+                        on_output();
+                    }
+                }
+            }
+            let _ = done_sender.send(());
+            on_output();
+        });
+
+        Ok(Self { master: pair.master, writer, child, parser, reader_done })
+    }
+
+    /// Sends keystrokes or pasted text to the program.
+    pub fn write(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.writer.write_all(bytes)?;
+        self.writer.flush()
+    }
+
+    /// Changes the size of the terminal, if it differs from the current one.
+    pub fn resize(&mut self, rows: u16, cols: u16) -> Result<()> {
+        let mut parser = self.parser.lock().unwrap();
+        if parser.screen().size() == (rows, cols) {
+            return Ok(());
+        }
+        parser.set_size(rows, cols);
+        self.master.resize(PtySize { rows, cols, pixel_width: 0, pixel_height: 0 })
+    }
+
+    /// Locks the terminal emulator to read its screen.
+    pub fn parser(&self) -> MutexGuard<'_, vt100::Parser> {
+        self.parser.lock().unwrap()
+    }
+
+    /// Returns the text currently on the screen, without trailing blank lines.
+    pub fn contents(&self) -> String {
+        let contents = self.parser().screen().contents();
+        contents.trim_end().to_string()
+    }
+
+    /// Returns the program's exit status once it has finished.
+    ///
+    /// Output the program wrote just before exiting is processed before the
+    /// status is returned.
+    pub fn try_wait(&mut self) -> Option<i32> {
+        let status = self.child.try_wait().ok()??;
+        // A process the program left behind may keep the terminal open, so
+        // the wait for the remaining output is bounded.
+        let _ = self.reader_done.recv_timeout(DRAIN_TIMEOUT);
+        Some(status.exit_code() as i32)
+    }
+}
+
+impl Drop for PtySession {
+    fn drop(&mut self) {
+        if let Ok(None) = self.child.try_wait() {
+            let _ = self.child.kill();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn test_pty_prefix_is_opt_in() {
+        let shell_core = ShellCore::new();
+        assert_eq!(interactive_command(&shell_core, "pty ssh-keygen -t ed25519"), Some(vec!["ssh-keygen".into(), "-t".into(), "ed25519".into()]));
+        assert_eq!(interactive_command(&shell_core, "pty"), None);
+        assert_eq!(interactive_command(&shell_core, "echo hello"), None);
+    }
+
+    #[test]
+    fn test_only_single_commands_use_a_terminal() {
+        let shell_core = ShellCore::new();
+        assert_eq!(interactive_command(&shell_core, "pty cat | grep x"), None);
+        assert_eq!(interactive_command(&shell_core, "pty cat > out.txt"), None);
+        assert_eq!(interactive_command(&shell_core, "pty cat; ls"), None);
+        assert_eq!(interactive_command(&shell_core, "FOO=1 pty cat"), None);
+        assert_eq!(interactive_command(&shell_core, "pty cat &"), None);
+    }
+
+    #[test]
+    fn test_programs_waiting_at_their_prompt_are_interactive() {
+        let interactive = |line: &str| {
+            let words = shlex::split(line).unwrap();
+            is_known_interactive(&words[0], &words[1..])
+        };
+        for line in ["python", "python -i", "ssh host", "ssh -p 22 host", "ssh -i key -l me host", "psql -U me db", "psql db me"] {
+            assert!(interactive(line), "{}", line);
+        }
+        for line in ["mysql -u root -p", "mysql -u root -p shop", "sqlite3 data.db", "vim notes.txt", "bash -o vi"] {
+            assert!(interactive(line), "{}", line);
+        }
+        for line in ["python script.py", "python -m http.server", "bash build.sh", "ssh host uptime", "ssh -p 22 host ls -l"] {
+            assert!(!interactive(line), "{}", line);
+        }
+        for line in ["psql -U me -c 'select 1' db", "mysql -u root -e 'show tables'", "sqlite3 data.db .tables", "cat"] {
+            assert!(!interactive(line), "{}", line);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_known_programs_are_detected() {
+        let shell_core = ShellCore::new();
+        if find_executable_in_path("sh").is_some() {
+            assert_eq!(interactive_command(&shell_core, "sh"), Some(vec!["sh".to_string()]));
+        }
+        assert_eq!(interactive_command(&shell_core, "sh -c 'echo hi' | cat"), None);
+        assert_eq!(interactive_command(&shell_core, "sh build.sh"), None);
+        assert_eq!(interactive_command(&shell_core, "sh -c 'echo hi'"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_session_renders_program_output() -> Result<()> {
+        let shell_core = ShellCore::new();
+        let argv = ["sh".to_string(), "-c".to_string(), "printf 'hello\\033[1mpty'; exit 3".to_string()];
+        let mut session = PtySession::spawn(&shell_core, &argv, 24, 80, || {})?;
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let status = loop {
+            if let Some(status) = session.try_wait() {
+                break status;
+            }
+            assert!(Instant::now() < deadline, "program did not exit");
+            thread::sleep(Duration::from_millis(10));
+        };
+        assert_eq!(status, 3);
+        assert_eq!(session.contents(), "hellopty");
+        assert!(session.parser().screen().cell(0, 5).unwrap().bold());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_session_forwards_input_and_resizes() -> Result<()> {
+        let shell_core = ShellCore::new();
+        let argv = ["sh".to_string(), "-c".to_string(), "read line; stty size; echo \"got $line\"".to_string()];
+        let mut session = PtySession::spawn(&shell_core, &argv, 24, 80, || {})?;
+        session.resize(30, 100)?;
+        session.write(b"typed\r")?;
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while session.try_wait().is_none() {
+            assert!(Instant::now() < deadline, "program did not exit");
+            thread::sleep(Duration::from_millis(10));
+        }
+        let contents = session.contents();
+        assert!(contents.contains("30 100"), "{}", contents);
+        assert!(contents.contains("got typed"), "{}", contents);
+        Ok(())
+    }
+}
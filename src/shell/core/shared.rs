@@ -0,0 +1,141 @@
+//! A shell shared between the GUI and the commands it runs.
+//!
+//! Command lines run one at a time, each against a snapshot of the shell
+//! taken when it starts. The changes a line makes, such as a new directory,
+//! variables or aliases, are committed together once it finishes. Until
+//! then, the GUI keeps reading the last committed state without waiting for
+//! the command, so the prompt and completions stay responsive.
+
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+use tokio::sync::OwnedMutexGuard;
+use crate::shell::core::ShellCore;
+
+/// The committed state of a shell and the right to change it.
+///
+/// Clones refer to the same shell.
+#[derive(Clone)]
+pub struct SharedShell {
+    state: Arc<Mutex<ShellCore>>,
+    /// Held by the transaction that is running, so that only one command
+    /// line changes the shell at a time.
+    writer: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl SharedShell {
+    pub fn new(shell_core: ShellCore) -> Self {
+        Self { state: Arc::new(Mutex::new(shell_core)), writer: Arc::new(tokio::sync::Mutex::new(())) }
+    }
+
+    /// Reads the committed state.
+    ///
+    /// The state is locked only while `read` runs, which must not block.
+    pub fn read<R>(&self, read: impl FnOnce(&ShellCore) -> R) -> R {
+        read(&self.state.lock().unwrap())
+    }
+
+    /// Returns whether a transaction, such as a running command line, is open.
+    pub fn is_busy(&self) -> bool {
+        self.writer.try_lock().is_err()
+    }
+
+    /// Starts a transaction, once the one that is open has ended.
+    pub async fn begin(&self) -> Transaction {
+        let guard = self.writer.clone().lock_owned().await;
+        self.transaction(guard)
+    }
+
+    /// Starts a transaction if none is open.
+    pub fn try_begin(&self) -> Option<Transaction> {
+        let guard = self.writer.clone().try_lock_owned().ok()?;
+        Some(self.transaction(guard))
+    }
+
+    /// Applies a change in a transaction of its own.
+    pub async fn update<R>(&self, update: impl FnOnce(&mut ShellCore) -> R) -> R {
+        let mut transaction = self.begin().await;
+        let result = update(&mut transaction);
+        transaction.commit();
+        result
+    }
+
+    fn transaction(&self, guard: OwnedMutexGuard<()>) -> Transaction {
+        let snapshot = self.state.lock().unwrap().clone();
+        Transaction { snapshot, state: self.state.clone(), _guard: guard }
+    }
+}
+
+/// A snapshot of the shell that commands can change.
+///
+/// The changes are published by `commit`; dropping the transaction
+/// discards them.
+pub struct Transaction {
+    snapshot: ShellCore,
+    state: Arc<Mutex<ShellCore>>,
+    _guard: OwnedMutexGuard<()>,
+}
+
+impl Transaction {
+    /// Makes the changes visible to readers and ends the transaction.
+    pub fn commit(self) {
+        *self.state.lock().unwrap() = self.snapshot;
+    }
+}
+
+impl Deref for Transaction {
+    type Target = ShellCore;
+
+    fn deref(&self) -> &ShellCore {
+        &self.snapshot
+    }
+}
+
+impl DerefMut for Transaction {
+    fn deref_mut(&mut self) -> &mut ShellCore {
+        &mut self.snapshot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::shell::core::command_executor::execute_shell_command;
+
+    #[tokio::test]
+    async fn test_readers_see_committed_state() {
+        let shell = SharedShell::new(ShellCore::new());
+        let mut transaction = shell.begin().await;
+        execute_shell_command(&mut transaction, "alias ll='ls -l'; X=1").await;
+        assert!(shell.is_busy());
+        assert!(shell.try_begin().is_none());
+        assert_eq!(shell.read(|shell| shell.get_var("X")), None);
+
+        transaction.commit();
+        assert!(!shell.is_busy());
+        assert_eq!(shell.read(|shell| shell.get_var("X")), Some("1".to_string()));
+        assert!(shell.read(|shell| shell.aliases.contains_key("ll")));
+
+        let mut transaction = shell.try_begin().unwrap();
+        transaction.set_var("X", "2");
+        drop(transaction);
+        assert_eq!(shell.read(|shell| shell.get_var("X")), Some("1".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_transactions_run_one_at_a_time() {
+        let shell = SharedShell::new(ShellCore::new());
+        let first = shell.begin().await;
+        let waiter = tokio::spawn({
+            let shell = shell.clone();
+            async move { shell.update(|shell| shell.get_var("X")).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiter.is_finished());
+
+        let mut first = first;
+        first.set_var("X", "1");
+        first.commit();
+        assert_eq!(waiter.await.unwrap(), Some("1".to_string()));
+    }
+}
@@ -0,0 +1,288 @@
+//! The standard input, output and error of one command in a pipeline.
+//!
+//! Pipeline stages are connected with OS pipes. External processes receive
+//! the pipe ends directly, while builtins read and write them as async byte
+//! streams, so every stage runs concurrently with its neighbours.
+
+use std::fs::File;
+use std::io;
+use std::pin::Pin;
+use std::process::Stdio;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf};
+
+use crate::shell::core::output::{OutputSink, Stream};
+
+/// Where a command reads its standard input from.
+pub enum Source {
+    /// No input; reads return end-of-file immediately.
+    Null,
+    /// A file, or the read end of a pipe from the previous stage.
+    File(File),
+    /// Bytes held in memory, such as the body of a here-document.
+    Bytes(Vec<u8>),
+}
+
+/// Where a command's standard output or standard error is written.
+pub enum Target {
+    /// The output sink of the command line, which the tab displays.
+    Sink(Stream),
+    /// A file, or the write end of a pipe to the next stage.
+    File(File),
+    /// A closed descriptor (`>&-`); anything written to it is discarded.
+    Closed,
+}
+
+/// The three standard descriptors of a command.
+pub struct StageIo {
+    pub stdin: Source,
+    pub stdout: Target,
+    pub stderr: Target,
+}
+
+impl StageIo {
+    /// Returns descriptors connected directly to the output sink.
+    pub fn sink() -> Self {
+        Self { stdin: Source::Null, stdout: Target::Sink(Stream::Stdout), stderr: Target::Sink(Stream::Stderr) }
+    }
+
+    /// Duplicates all three descriptors, so that each command inside a
+    /// compound command such as `while` can be given its own.
+    pub fn try_clone(&self) -> io::Result<Self> {
+        Ok(Self { stdin: self.stdin.try_clone()?, stdout: self.stdout.try_clone()?, stderr: self.stderr.try_clone()? })
+    }
+}
+
+impl Source {
+    /// Duplicates the standard input of the shell process itself, for
+    /// commands run from the command line rather than from a tab.
+    pub fn process_stdin() -> io::Result<Self> {
+        Ok(Source::File(duplicate_process_descriptor(io::stdin())?))
+    }
+
+    /// Duplicates the source. Duplicates of a file share its read position,
+    /// while in-memory bytes are copied, so each duplicate reads all of them.
+    pub fn try_clone(&self) -> io::Result<Self> {
+        Ok(match self {
+            Source::Null => Source::Null,
+            Source::File(file) => Source::File(file.try_clone()?),
+            Source::Bytes(bytes) => Source::Bytes(bytes.clone()),
+        })
+    }
+
+    /// Converts the source into a `Stdio` for a child process.
+    ///
+    /// In-memory bytes cannot be handed to a process directly; they are
+    /// returned so the caller can write them to the child's piped stdin.
+    pub fn into_stdio(self) -> (Stdio, Option<Vec<u8>>) {
+        match self {
+            Source::Null => (Stdio::null(), None),
+            Source::File(file) => (Stdio::from(file), None),
+            Source::Bytes(bytes) => (Stdio::piped(), Some(bytes)),
+        }
+    }
+
+    /// Converts the source into an async reader for a builtin.
+    pub fn into_reader(self) -> Reader {
+        match self {
+            Source::Null => Reader::Null,
+            Source::File(file) => Reader::File(tokio::fs::File::from_std(file)),
+            Source::Bytes(bytes) => Reader::Bytes(io::Cursor::new(bytes)),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn duplicate_process_descriptor(descriptor: impl std::os::fd::AsFd) -> io::Result<File> {
+    Ok(File::from(descriptor.as_fd().try_clone_to_owned()?))
+}
+
+#[cfg(windows)]
+fn duplicate_process_descriptor(descriptor: impl std::os::windows::io::AsHandle) -> io::Result<File> {
+    Ok(File::from(descriptor.as_handle().try_clone_to_owned()?))
+}
+
+/// An async reader for a builtin's standard input.
+pub enum Reader {
+    Null,
+    File(tokio::fs::File),
+    Bytes(io::Cursor<Vec<u8>>),
+}
+
+impl Reader {
+    /// Duplicates the reader as a source, so that the commands a builtin
+    /// such as `source` runs read the rest of its input.
+    pub async fn to_source(&self) -> io::Result<Source> {
+        Ok(match self {
+            Reader::Null => Source::Null,
+            Reader::File(file) => Source::File(file.try_clone().await?.into_std().await),
+            Reader::Bytes(cursor) => Source::Bytes(cursor.get_ref()[cursor.position() as usize..].to_vec()),
+        })
+    }
+}
+
+impl AsyncRead for Reader {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Reader::Null => Poll::Ready(Ok(())),
+            Reader::File(file) => Pin::new(file).poll_read(cx, buf),
+            Reader::Bytes(cursor) => Pin::new(cursor).poll_read(cx, buf),
+        }
+    }
+}
+
+impl Target {
+    /// Duplicates the standard output of the shell process itself, so that
+    /// commands run from the command line write to it unchanged.
+    pub fn process_stdout() -> io::Result<Self> {
+        Ok(Target::File(duplicate_process_descriptor(io::stdout())?))
+    }
+
+    /// Duplicates the standard error of the shell process itself.
+    pub fn process_stderr() -> io::Result<Self> {
+        Ok(Target::File(duplicate_process_descriptor(io::stderr())?))
+    }
+
+    /// Duplicates the target, as `2>&1` does.
+    pub fn try_clone(&self) -> io::Result<Self> {
+        Ok(match self {
+            Target::Sink(stream) => Target::Sink(*stream),
+            Target::File(file) => Target::File(file.try_clone()?),
+            Target::Closed => Target::Closed,
+        })
+    }
+
+    /// Returns a `Stdio` for a child process writing to this target.
+    ///
+    /// Output meant for the sink is piped, and must be forwarded by the
+    /// caller.
+    pub fn to_stdio(&self) -> io::Result<Stdio> {
+        Ok(match self {
+            Target::Sink(_) => Stdio::piped(),
+            Target::File(file) => Stdio::from(file.try_clone()?),
+            Target::Closed => Stdio::null(),
+        })
+    }
+
+    /// Converts the target into an async writer for a builtin.
+    pub fn into_writer(self, output: &OutputSink) -> Writer {
+        match self {
+            Target::Sink(stream) => Writer::Sink(output.clone(), stream),
+            Target::File(file) => Writer::File(tokio::fs::File::from_std(file), output.error_prefix()),
+            Target::Closed => Writer::Closed,
+        }
+    }
+}
+
+/// An async writer for a builtin's standard output or standard error.
+pub enum Writer {
+    Sink(OutputSink, Stream),
+    /// A file, with what the shell's diagnostic messages start with.
+    File(tokio::fs::File, &'static str),
+    Closed,
+}
+
+impl Writer {
+    /// Duplicates the writer as a target, so that the commands a builtin
+    /// such as `source` runs write to the same place.
+    pub async fn to_target(&self) -> io::Result<Target> {
+        Ok(match self {
+            Writer::Sink(_, stream) => Target::Sink(*stream),
+            Writer::File(file, _) => Target::File(file.try_clone().await?.into_std().await),
+            Writer::Closed => Target::Closed,
+        })
+    }
+
+    /// Writes a diagnostic message from the shell, such as a builtin's error.
+    pub async fn write_error(&mut self, message: &str) -> io::Result<()> {
+        match self {
+            Writer::Sink(output, _) => {
+                output.write_error(message);
+                Ok(())
+            }
+            Writer::File(_, error_prefix) => {
+                let line = format!("{}{}\n", error_prefix, message);
+                self.write_all(line.as_bytes()).await
+            }
+            Writer::Closed => Ok(()),
+        }
+    }
+}
+
+impl AsyncWrite for Writer {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Writer::Sink(output, stream) => {
+                output.write(*stream, buf);
+                Poll::Ready(Ok(buf.len()))
+            }
+            Writer::File(file, _) => Pin::new(file).poll_write(cx, buf),
+            Writer::Closed => Poll::Ready(Ok(buf.len())),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Writer::File(file, _) => Pin::new(file).poll_flush(cx),
+            _ => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Writer::File(file, _) => Pin::new(file).poll_shutdown(cx),
+            _ => Poll::Ready(Ok(())),
+        }
+    }
+}
+
+/// Creates an OS pipe, returning its read and write ends as files.
+pub fn pipe() -> io::Result<(File, File)> {
+    let (reader, writer) = io::pipe()?;
+    Ok((into_file(reader), into_file(writer)))
+}
+
+#[cfg(unix)]
+fn into_file(end: impl Into<std::os::fd::OwnedFd>) -> File {
+    File::from(end.into())
+}
+
+#[cfg(windows)]
+fn into_file(end: impl Into<std::os::windows::io::OwnedHandle>) -> File {
+    File::from(end.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shell::core::output;
+    use std::io::Write;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn test_pipe_connects_writer_to_reader() -> io::Result<()> {
+        let (reader, mut writer) = pipe()?;
+        writer.write_all(b"through the pipe")?;
+        drop(writer);
+
+        let mut text = String::new();
+        Source::File(reader).into_reader().read_to_string(&mut text).await?;
+        assert_eq!(text, "through the pipe");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_writer_targets() -> io::Result<()> {
+        let (sink, receiver) = OutputSink::new();
+        let mut writer = Target::Sink(Stream::Stdout).into_writer(&sink);
+        writer.write_all(b"shown").await?;
+        writer.write_error("oops").await?;
+
+        let mut closed = Target::Closed.into_writer(&sink);
+        closed.write_all(b"discarded").await?;
+        drop(sink);
+        drop(writer);
+        assert_eq!(output::collect(receiver), "shown\nError: oops\n");
+        Ok(())
+    }
+}
@@ -0,0 +1,126 @@
+//! Startup files, which set up the shell of a new tab.
+//!
+//! `~/.config/my_cli_tool/rc` runs first, so the aliases, variables and
+//! functions it defines are there in every tab. A project can keep its own
+//! in `.my_cli_toolrc`, which only runs if the user's file turns on the
+//! `projectrc` option, since it comes with whatever directory is opened.
+
+use std::path::{Path, PathBuf};
+use crate::shell::core::builtins::source::source_script;
+use crate::shell::core::cancellation::CancellationToken;
+use crate::shell::core::output::OutputSink;
+use crate::shell::core::stage_io::StageIo;
+use crate::shell::core::ShellCore;
+
+/// The user's startup file, relative to the home directory.
+pub const USER_RC: &str = ".config/my_cli_tool/rc";
+/// The startup file of a project, in the directory the shell starts in.
+pub const PROJECT_RC: &str = ".my_cli_toolrc";
+
+/// Returns the path of the user's startup file, or `None` if the home
+/// directory is unknown.
+pub fn user_rc_path(shell: &ShellCore) -> Option<PathBuf> {
+    let home = shell
+        .get_var("HOME")
+        .map(PathBuf::from)
+        .or_else(std::env::home_dir)?;
+    Some(home.join(USER_RC))
+}
+
+/// Runs the startup files of a new shell, as `source` would.
+///
+/// Missing files are skipped. Files that cannot be read, and errors in the
+/// commands of a file, are reported on `output`.
+///
+/// # Arguments
+///
+/// * `shell` - The new shell.
+/// * `output` - The sink the output of the files is sent to.
+/// * `cancellation` - Interrupts the commands when cancelled.
+///
+/// # Returns
+///
+/// The files that were run, in order.
+pub async fn run_startup_files(shell: &mut ShellCore, output: &OutputSink, cancellation: &CancellationToken) -> Vec<PathBuf> {
+    // The user's file may change directory; the project is where the shell started.
+    let project_rc = shell.current_dir.join(PROJECT_RC);
+    let mut files = Vec::new();
+    if let Some(path) = user_rc_path(shell) {
+        if run_startup_file(shell, &path, output, cancellation).await {
+            files.push(path);
+        }
+    }
+    if shell.options.projectrc && run_startup_file(shell, &project_rc, output, cancellation).await {
+        files.push(project_rc);
+    }
+    files
+}
+
+/// Runs one startup file.
+///
+/// # Returns
+///
+/// `false` if the file does not exist.
+async fn run_startup_file(shell: &mut ShellCore, path: &Path, output: &OutputSink, cancellation: &CancellationToken) -> bool {
+    let script = match tokio::fs::read_to_string(path).await {
+        Ok(script) => script,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return false,
+        Err(e) => {
+            output.write_error(&format!("{}: {}", path.display(), e));
+            return true;
+        }
+    };
+    source_script(shell, script, Vec::new(), StageIo::sink(), output.clone(), cancellation.clone()).await;
+    output.end_pipeline();
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shell::core::command_executor::execute_shell_command;
+    use crate::shell::core::output;
+
+    fn new_shell(home: &Path, project: &Path) -> ShellCore {
+        let mut shell = ShellCore::new();
+        shell.variables.insert("HOME".to_string(), home.to_string_lossy().into_owned());
+        shell.current_dir = project.to_path_buf();
+        shell
+    }
+
+    #[tokio::test]
+    async fn test_user_rc_opts_into_project_rc() {
+        let home = tempfile::tempdir().unwrap();
+        let project = tempfile::tempdir().unwrap();
+        std::fs::write(project.path().join(PROJECT_RC), "PROJECT=\"$GREETING from project\"\n").unwrap();
+
+        let mut shell = new_shell(home.path(), project.path());
+        let (sink, _receiver) = OutputSink::new();
+        assert!(run_startup_files(&mut shell, &sink, &CancellationToken::new()).await.is_empty());
+        assert_eq!(shell.get_var("PROJECT"), None);
+
+        let user_rc = home.path().join(USER_RC);
+        std::fs::create_dir_all(user_rc.parent().unwrap()).unwrap();
+        std::fs::write(&user_rc, "alias ll='echo long'\nexport GREETING=hi\nshopt -s projectrc\ncd /\n").unwrap();
+        let mut shell = new_shell(home.path(), project.path());
+        let files = run_startup_files(&mut shell, &sink, &CancellationToken::new()).await;
+        assert_eq!(files, vec![user_rc, project.path().join(PROJECT_RC)]);
+        assert_eq!(shell.get_var("PROJECT"), Some("hi from project".to_string()));
+        assert_eq!(execute_shell_command(&mut shell, "ll").await, "long\n");
+    }
+
+    #[tokio::test]
+    async fn test_errors_are_reported() {
+        let home = tempfile::tempdir().unwrap();
+        let user_rc = home.path().join(USER_RC);
+        std::fs::create_dir_all(user_rc.parent().unwrap()).unwrap();
+        std::fs::write(&user_rc, "no_such_command_12345\nLOADED=1\n").unwrap();
+
+        let mut shell = new_shell(home.path(), home.path());
+        let (sink, receiver) = OutputSink::new();
+        run_startup_files(&mut shell, &sink, &CancellationToken::new()).await;
+        drop(sink);
+        assert!(output::collect(receiver).contains("no_such_command_12345"));
+        assert_eq!(shell.get_var("LOADED"), Some("1".to_string()));
+    }
+}
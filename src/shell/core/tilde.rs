@@ -0,0 +1,185 @@
+//! Tilde expansion, which replaces `~` at the start of a word with a home
+//! directory.
+//!
+//! `~` is the value of `HOME`, `~user` the home directory of `user`, `~+`
+//! the current directory and `~-` the previous one (`OLDPWD`). The prefix
+//! ends at the first `/`, and must be entirely unquoted: `~"user"` and `\~`
+//! are left alone. In the value of an assignment, a tilde after a `:` is
+//! expanded too, so `PATH=~/bin:~/.cargo/bin` works as expected.
+//!
+//! The directory replaces the prefix as quoted text, so it is never split
+//! into fields or matched against files.
+
+use crate::shell::core::parser::ast::{Word, WordPart};
+use crate::shell::core::ShellCore;
+
+/// Expands the tilde prefixes of a word.
+///
+/// # Arguments
+///
+/// * `shell_core` - The shell state providing `HOME`, `OLDPWD` and the
+///   current directory.
+/// * `word` - The word to expand.
+/// * `assignment` - Whether the word is the value of an assignment, in
+///   which a tilde after each `:` is expanded as well.
+///
+/// # Returns
+///
+/// The word with every prefix that names a known directory replaced.
+pub fn expand_tilde(shell_core: &ShellCore, word: &Word, assignment: bool) -> Word {
+    let mut parts = Vec::new();
+    // Whether the next character may start a tilde prefix.
+    let mut at_start = true;
+    for (index, part) in word.parts.iter().enumerate() {
+        let WordPart::Literal(text) = part else {
+            parts.push(part.clone());
+            at_start = false;
+            continue;
+        };
+        let last_part = index + 1 == word.parts.len();
+        let mut literal = String::new();
+        let mut rest = text.as_str();
+        loop {
+            if at_start {
+                if let Some((dir, consumed)) = tilde_prefix(shell_core, rest, assignment, last_part) {
+                    if !literal.is_empty() {
+                        parts.push(WordPart::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(WordPart::Quoted(dir));
+                    rest = &rest[consumed..];
+                }
+            }
+            match rest.find(':').filter(|_| assignment) {
+                Some(colon) => {
+                    literal.push_str(&rest[..=colon]);
+                    rest = &rest[colon + 1..];
+                    at_start = true;
+                }
+                None => {
+                    at_start &= rest.is_empty();
+                    literal.push_str(rest);
+                    break;
+                }
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(WordPart::Literal(literal));
+        }
+    }
+    Word { parts }
+}
+
+/// Resolves the tilde prefix at the start of `text`, if there is one.
+///
+/// # Returns
+///
+/// The directory and the length of the prefix it replaces, or `None` if
+/// `text` does not start with a prefix naming a known directory.
+fn tilde_prefix(shell_core: &ShellCore, text: &str, assignment: bool, last_part: bool) -> Option<(String, usize)> {
+    let after = text.strip_prefix('~')?;
+    let end = after.find(|c| c == '/' || (assignment && c == ':'));
+    // A prefix running on into a quoted or expanded part is not expanded.
+    if end.is_none() && !last_part {
+        return None;
+    }
+    let name = &after[..end.unwrap_or(after.len())];
+    let dir = match name {
+        "" => shell_core
+            .get_var("HOME")
+            .or_else(|| std::env::home_dir().map(|home| home.to_string_lossy().into_owned()))?,
+        "+" => shell_core.current_dir.to_string_lossy().into_owned(),
+        "-" => shell_core.get_var("OLDPWD")?,
+        _ => user_home(name)?,
+    };
+    Some((dir, name.len() + 1))
+}
+
+/// Looks up the home directory of a user in the password database.
+#[cfg(unix)]
+fn user_home(name: &str) -> Option<String> {
+    use std::ffi::{CStr, CString};
+
+    let name = CString::new(name).ok()?;
+    let mut buffer = vec![0 as libc::c_char; 16 * 1024];
+    // SAFETY: `passwd` is plain data that `getpwnam_r` fills in, pointing
+    // its strings into `buffer`, which outlives every use of them below.
+    unsafe {
+        let mut passwd: libc::passwd = std::mem::zeroed();
+        let mut result = std::ptr::null_mut();
+        let status = libc::getpwnam_r(name.as_ptr(), &mut passwd, buffer.as_mut_ptr(), buffer.len(), &mut result);
+        if status != 0 || result.is_null() || passwd.pw_dir.is_null() {
+            return None;
+        }
+        Some(CStr::from_ptr(passwd.pw_dir).to_string_lossy().into_owned())
+    }
+}
+
+/// Other users' home directories are only known on Unix.
+#[cfg(not(unix))]
+fn user_home(_name: &str) -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shell() -> ShellCore {
+        let mut shell_core = ShellCore::new();
+        shell_core.variables.insert("HOME".to_string(), "/home/me".to_string());
+        shell_core.current_dir = "/work".into();
+        shell_core
+    }
+
+    fn expand(shell_core: &ShellCore, word: &Word, assignment: bool) -> String {
+        expand_tilde(shell_core, word, assignment).unquoted()
+    }
+
+    #[test]
+    fn test_home_and_directory_prefixes() {
+        let shell_core = shell();
+        assert_eq!(expand(&shell_core, &Word::literal("~"), false), "/home/me");
+        assert_eq!(expand(&shell_core, &Word::literal("~/src/~"), false), "/home/me/src/~");
+        assert_eq!(expand(&shell_core, &Word::literal("~+/x"), false), "/work/x");
+        assert_eq!(expand(&shell_core, &Word::literal("a~"), false), "a~");
+    }
+
+    #[test]
+    fn test_unknown_prefixes_are_kept() {
+        let mut shell_core = shell();
+        shell_core.variables.remove("OLDPWD");
+        std::env::remove_var("OLDPWD");
+        assert_eq!(expand(&shell_core, &Word::literal("~-"), false), "~-");
+        assert_eq!(expand(&shell_core, &Word::literal("~no_such_user_123/x"), false), "~no_such_user_123/x");
+
+        shell_core.variables.insert("OLDPWD".to_string(), "/previous".to_string());
+        assert_eq!(expand(&shell_core, &Word::literal("~-"), false), "/previous");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_user_prefix() {
+        let shell_core = shell();
+        let home = user_home("root").unwrap();
+        assert_eq!(expand(&shell_core, &Word::literal("~root/x"), false), format!("{}/x", home));
+    }
+
+    #[test]
+    fn test_quoted_prefixes_are_not_expanded() {
+        let shell_core = shell();
+        let word = Word { parts: vec![WordPart::Literal("~".to_string()), WordPart::Quoted("root".to_string())] };
+        assert_eq!(expand_tilde(&shell_core, &word, false), word);
+        let word = Word { parts: vec![WordPart::Quoted("~".to_string())] };
+        assert_eq!(expand_tilde(&shell_core, &word, false), word);
+        // The expansion itself counts as quoted.
+        let expanded = expand_tilde(&shell_core, &Word::literal("~/x"), false);
+        assert_eq!(expanded.parts[0], WordPart::Quoted("/home/me".to_string()));
+    }
+
+    #[test]
+    fn test_assignment_values() {
+        let shell_core = shell();
+        assert_eq!(expand(&shell_core, &Word::literal("~/bin:~:/usr/bin"), true), "/home/me/bin:/home/me:/usr/bin");
+        assert_eq!(expand(&shell_core, &Word::literal("~/bin:~"), false), "/home/me/bin:~");
+    }
+}
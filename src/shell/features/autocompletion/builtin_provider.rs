@@ -1,15 +1,18 @@
 //! Suggests built-in commands.
 
+use crate::shell::core::builtins;
+
+/// Suggests the names of builtins in the shell's builtin registry that
+/// start with the command being typed.
 pub(super) async fn get_builtin_suggestions(input: &str) -> Vec<String> {
     let mut builtin_suggestions = Vec::new();
-    let built_in_commands = vec!["ls", "cd", "ping", "clear", "open", "mkdir", "rm", "cp", "mv"];
     let parts = shlex::split(input).unwrap_or_default();
 
     if parts.len() <= 1 && !input.ends_with(' ') {
         let cmd_part = if parts.is_empty() { "" } else { &parts[0] };
-        for cmd in &built_in_commands {
-            if cmd.starts_with(cmd_part) {
-                builtin_suggestions.push(cmd.to_string());
+        for builtin in builtins::registry().iter() {
+            if builtin.name().starts_with(cmd_part) {
+                builtin_suggestions.push(builtin.name().to_string());
             }
         }
     }
@@ -18,13 +21,13 @@ pub(super) async fn get_builtin_suggestions(input: &str) -> Vec<String> {
 
 #[cfg(test)]
 mod tests {
-    use crate::shell::history::CommandHistory;
+    use crate::shell::history::HistoryStore;
     use crate::shell::features::autocompletion::Autocompleter;
     use std::path::PathBuf;
 
     #[tokio::test]
     async fn test_builtin_command_suggestions() {
-        let history = CommandHistory::new();
+        let history = HistoryStore::new();
         let autocompleter = Autocompleter::new(history);
         let current_dir = PathBuf::from(".");
 
@@ -34,6 +37,10 @@ mod tests {
 
         let suggestions = autocompleter.get_suggestions("o", &current_dir).await;
         assert!(suggestions.contains(&"open".to_string()));
+
+        // Every registered builtin is suggested, including `help` and `type`.
+        let suggestions = autocompleter.get_suggestions("ty", &current_dir).await;
+        assert!(suggestions.contains(&"type".to_string()));
         
         // Should not suggest anything if there is a space
         let suggestions_with_space = autocompleter.get_suggestions("ls ", &current_dir).await;
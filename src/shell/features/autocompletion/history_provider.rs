@@ -1,38 +1,41 @@
 //! Suggests commands from history.
 
-use crate::shell::history::CommandHistory;
+use crate::shell::history::HistoryStore;
 
 pub(super) async fn get_history_suggestions(
-    command_history: &CommandHistory,
+    command_history: &HistoryStore,
     input: &str,
 ) -> Vec<String> {
-    let mut history_suggestions = Vec::new();
-    if input.is_empty() {
-        for cmd in command_history.history.iter().rev().take(5) {
-            history_suggestions.push(cmd.clone());
-        }
-    } else {
-        for cmd in command_history.history.iter().rev() {
-            if cmd.starts_with(input) && cmd != input {
+    command_history.read(|entries| {
+        let mut history_suggestions = Vec::new();
+        let commands = entries.iter().map(|entry| &entry.command);
+        if input.is_empty() {
+            for cmd in commands.rev().take(5) {
                 history_suggestions.push(cmd.clone());
             }
+        } else {
+            for cmd in commands.rev() {
+                if cmd.starts_with(input) && cmd != input {
+                    history_suggestions.push(cmd.clone());
+                }
+            }
         }
-    }
-    history_suggestions
+        history_suggestions
+    })
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::shell::history::CommandHistory;
+    use crate::shell::history::HistoryStore;
     use crate::shell::features::autocompletion::Autocompleter;
     use std::path::PathBuf;
 
     #[tokio::test]
     async fn test_history_suggestions() {
-        let mut history = CommandHistory::new();
-        history.add("cmd1".to_string());
-        history.add("cmd2".to_string());
-        let autocompleter = Autocompleter::new(history);
+        let history = HistoryStore::new();
+        history.add("cmd1".to_string().into());
+        history.add("cmd2".to_string().into());
+        let autocompleter = Autocompleter::new(history.clone());
         let current_dir = PathBuf::from(".");
 
         let suggestions = autocompleter.get_suggestions("", &current_dir).await;
@@ -46,5 +49,10 @@ mod tests {
         // Should not suggest the exact match
         let suggestions_exact = autocompleter.get_suggestions("cmd1", &current_dir).await;
         assert!(!suggestions_exact.contains(&"cmd1".to_string()));
+
+        // Commands added later, e.g. in another tab, are suggested too.
+        history.add("cmd3".to_string().into());
+        let suggestions = autocompleter.get_suggestions("cmd", &current_dir).await;
+        assert!(suggestions.contains(&"cmd3".to_string()));
     }
 }
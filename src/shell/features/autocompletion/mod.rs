@@ -4,23 +4,24 @@ mod builtin_provider;
 mod history_provider;
 mod path_provider;
 
-use crate::shell::history::CommandHistory;
-use std::path::PathBuf;
+use crate::shell::history::HistoryStore;
+use std::path::Path;
 
 /// Generates command suggestions based on the current input and context.
 #[derive(Clone)]
 pub struct Autocompleter {
-    command_history: CommandHistory,
+    /// The live history, which includes the commands entered in every tab.
+    command_history: HistoryStore,
 }
 
 impl Autocompleter {
-    pub fn new(command_history: CommandHistory) -> Self {
+    pub fn new(command_history: HistoryStore) -> Self {
         Self { command_history }
     }
 
     /// Provides suggestions based on the current input.
     /// This will include built-in commands, history, and file paths.
-    pub async fn get_suggestions(&self, input: &str, current_dir: &PathBuf) -> Vec<String> {
+    pub async fn get_suggestions(&self, input: &str, current_dir: &Path) -> Vec<String> {
         let mut suggestions = Vec::new();
 
         // Get suggestions from all providers concurrently.
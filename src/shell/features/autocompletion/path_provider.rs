@@ -10,9 +10,7 @@ pub(super) async fn get_filesystem_suggestions(
     let mut fs_suggestions = Vec::new();
     let mut parts = shlex::split(input).unwrap_or_default();
 
-    if input.is_empty() {
-        parts.push("".to_string());
-    } else if input.ends_with(' ') {
+    if input.is_empty() || input.ends_with(' ') {
         parts.push("".to_string());
     }
 
@@ -38,7 +36,7 @@ pub(super) async fn get_filesystem_suggestions(
             if let Some(file_name_os) = entry.path().file_name() {
                 let file_name = file_name_os.to_string_lossy();
                 if file_name.starts_with(&prefix) {
-                    let is_dir = entry.file_type().await.map_or(false, |ft| ft.is_dir());
+                    let is_dir = entry.file_type().await.is_ok_and(|ft| ft.is_dir());
                     
                     let new_last_part = if last_part.ends_with('/') || last_part.ends_with('\\') {
                         format!("{}{}", last_part, file_name)
@@ -70,7 +68,7 @@ pub(super) async fn get_filesystem_suggestions(
 
 #[cfg(test)]
 mod tests {
-    use crate::shell::history::CommandHistory;
+    use crate::shell::history::HistoryStore;
     use crate::shell::features::autocompletion::Autocompleter;
     use std::env;
     use tokio::fs;
@@ -85,7 +83,7 @@ mod tests {
     
     #[tokio::test]
     async fn test_file_system_suggestions() {
-        let history = CommandHistory::new();
+        let history = HistoryStore::new();
         let autocompleter = Autocompleter::new(history);
         let temp_dir = setup_test_dir("test_fs_suggestions").await;
 
@@ -101,7 +99,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_path_with_slash_suggestions() {
-        let history = CommandHistory::new();
+        let history = HistoryStore::new();
         let autocompleter = Autocompleter::new(history);
         let temp_dir = setup_test_dir("test_path_with_slash_suggestions").await;
 
@@ -117,7 +115,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_autocompletion_with_quotes() {
-        let history = CommandHistory::new();
+        let history = HistoryStore::new();
         let autocompleter = Autocompleter::new(history);
         let temp_dir = setup_test_dir("test_autocompletion_with_quotes").await;
 
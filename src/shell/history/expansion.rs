@@ -0,0 +1,146 @@
+//! History expansion: `!!`, `!n`, `!-n` and `!prefix` in a command line
+//! typed by the user.
+//!
+//! Only lines entered interactively are expanded, before they are added to
+//! the history; scripts and startup files are run as written.
+
+use super::HistoryEntry;
+
+/// The characters that end the prefix of a `!prefix` reference.
+const PREFIX_TERMINATORS: &[char] = &[';', '&', '|', '<', '>', '(', ')', '"', '\'', '`'];
+
+/// Replaces the history references of a command line with the commands they
+/// refer to.
+///
+/// * `!!` is the last command.
+/// * `!n` is command number `n`, as numbered by `history`.
+/// * `!-n` is the command `n` commands back, so `!-1` is `!!`.
+/// * `!prefix` is the last command starting with `prefix`.
+///
+/// A `!` is left as it is inside single quotes, after a backslash, before a
+/// space, `=` or a character that ends a word such as `(` or `;`, at the end
+/// of the line, and where the shell gives it a meaning of its own: `$!`,
+/// `${!name}` and `[!chars]`.
+///
+/// # Arguments
+///
+/// * `line` - The command line as typed.
+/// * `entries` - The history, oldest first.
+///
+/// # Returns
+///
+/// The expanded line, or an error naming the first reference that matches
+/// no command.
+pub fn expand_history(line: &str, entries: &[HistoryEntry]) -> Result<String, String> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut expanded = String::with_capacity(line.len());
+    let mut in_single_quotes = false;
+    let mut in_double_quotes = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\\' if !in_single_quotes => {
+                expanded.push(c);
+                if let Some(&next) = chars.get(i + 1) {
+                    expanded.push(next);
+                    i += 1;
+                }
+            }
+            '\'' if !in_double_quotes => {
+                in_single_quotes = !in_single_quotes;
+                expanded.push(c);
+            }
+            '"' if !in_single_quotes => {
+                in_double_quotes = !in_double_quotes;
+                expanded.push(c);
+            }
+            '!' if !in_single_quotes && is_reference(&chars, i) => {
+                let (spec, length) = reference_at(&chars[i + 1..]);
+                let command = resolve(&spec, entries).ok_or_else(|| format!("!{}: event not found", spec))?;
+                expanded.push_str(command);
+                i += length;
+            }
+            _ => expanded.push(c),
+        }
+        i += 1;
+    }
+    Ok(expanded)
+}
+
+/// Returns whether the `!` at `index` starts a history reference.
+fn is_reference(chars: &[char], index: usize) -> bool {
+    let literal_before = index > 0 && matches!(chars[index - 1], '$' | '{' | '[');
+    let literal_after = match chars.get(index + 1) {
+        None => true,
+        Some(next) => next.is_whitespace() || *next == '=' || PREFIX_TERMINATORS.contains(next),
+    };
+    !literal_before && !literal_after
+}
+
+/// Reads the reference that follows a `!`.
+///
+/// # Returns
+///
+/// The reference without its `!`, e.g. `!`, `-2` or `git`, and its length
+/// in characters.
+fn reference_at(chars: &[char]) -> (String, usize) {
+    let length = match chars {
+        ['!', ..] => 1,
+        ['-', rest @ ..] => 1 + rest.iter().take_while(|c| c.is_ascii_digit()).count(),
+        [c, ..] if c.is_ascii_digit() => chars.iter().take_while(|c| c.is_ascii_digit()).count(),
+        _ => chars.iter().take_while(|c| !c.is_whitespace() && !PREFIX_TERMINATORS.contains(c)).count(),
+    };
+    (chars[..length].iter().collect(), length)
+}
+
+/// Finds the command a reference refers to.
+fn resolve<'a>(spec: &str, entries: &'a [HistoryEntry]) -> Option<&'a str> {
+    let entry = if spec == "!" {
+        entries.last()
+    } else if let Some(back) = spec.strip_prefix('-') {
+        let back: usize = back.parse().ok()?;
+        entries.len().checked_sub(back).and_then(|index| entries.get(index))
+    } else if let Ok(number) = spec.parse::<usize>() {
+        number.checked_sub(1).and_then(|index| entries.get(index))
+    } else {
+        entries.iter().rev().find(|entry| entry.command.starts_with(spec))
+    };
+    entry.map(|entry| entry.command.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> Vec<HistoryEntry> {
+        ["git status", "cargo build", "git commit -m 'fix'", "ls -l"].iter().map(|command| command.to_string().into()).collect()
+    }
+
+    #[test]
+    fn test_references_are_expanded() {
+        let entries = history();
+        assert_eq!(expand_history("sudo !!", &entries).unwrap(), "sudo ls -l");
+        assert_eq!(expand_history("!2 --release", &entries).unwrap(), "cargo build --release");
+        assert_eq!(expand_history("!-2", &entries).unwrap(), "git commit -m 'fix'");
+        assert_eq!(expand_history("!git; !car|wc", &entries).unwrap(), "git commit -m 'fix'; cargo build|wc");
+        assert_eq!(expand_history("echo \"!!\"", &entries).unwrap(), "echo \"ls -l\"");
+    }
+
+    #[test]
+    fn test_literal_exclamation_marks() {
+        let entries = history();
+        for line in ["echo 'hi!!'", "echo hi\\!", "! false", "[ a != b ]", "echo $!", "echo ${!name}", "ls [!a]*", "echo hi!"] {
+            assert_eq!(expand_history(line, &entries).unwrap(), line);
+        }
+    }
+
+    #[test]
+    fn test_unknown_references() {
+        let entries = history();
+        assert_eq!(expand_history("!9", &entries).unwrap_err(), "!9: event not found");
+        assert_eq!(expand_history("!-5", &entries).unwrap_err(), "!-5: event not found");
+        assert_eq!(expand_history("!make && ls", &entries).unwrap_err(), "!make: event not found");
+        assert_eq!(expand_history("!!", &[]).unwrap_err(), "!!: event not found");
+    }
+}
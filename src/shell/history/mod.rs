@@ -1,48 +1,71 @@
 //! This module provides functionality for managing command history in a shell-like application.
 //! It allows adding commands, navigating through the history (up and down), and resetting the history index.
+//! The commands themselves are kept in a `HistoryStore`, shared by every tab and saved to disk,
+//! `expand_history` replaces `!!`, `!n` and `!prefix` in a command line with earlier commands,
+//! and `search` finds the commands that fuzzily match a query.
 
-/// `CommandHistory` stores a list of commands entered by the user
+mod expansion;
+mod search;
+mod store;
+
+pub use expansion::expand_history;
+pub use search::{fuzzy_match, search, FuzzyMatch, SearchResult};
+pub use store::{format_duration, EntryId, HistoryEntry, HistoryStore};
+
+/// `CommandHistory` gives access to the commands entered by the user
 /// and keeps track of the current position when navigating through the history.
-#[derive(Clone)]
+///
+/// Each tab has its own position in the same `HistoryStore`.
+#[derive(Clone, Default)]
 pub struct CommandHistory {
-    /// A vector storing the history of commands as strings.
-    pub history: Vec<String>,
+    /// The commands, shared with the other tabs.
+    store: HistoryStore,
     /// The current index in the history when navigating. `None` if not navigating.
     current_index: Option<usize>,
 }
 
 impl CommandHistory {
-    /// Creates a new, empty `CommandHistory` instance.
+    /// Creates a new, empty `CommandHistory` instance kept in memory only.
     pub fn new() -> Self {
-        Self {
-            history: Vec::new(),
-            current_index: None,
-        }
+        Self::default()
+    }
+
+    /// Creates a `CommandHistory` that navigates the commands of `store`.
+    pub fn with_store(store: HistoryStore) -> Self {
+        Self { store, current_index: None }
+    }
+
+    /// Returns the commands this history navigates.
+    pub fn store(&self) -> &HistoryStore {
+        &self.store
     }
 
     /// Adds a new command to the history.
     ///
-    /// The command is only added if it's not empty and not a duplicate of the last command.
+    /// The command is only added if it's not empty, and replaces the last entry if it repeats its command.
     /// After adding, the `current_index` is reset to `None`.
     ///
     /// # Arguments
     ///
-    /// * `command` - The command string to add to the history.
-    pub fn add(&mut self, command: String) {
-        if !command.is_empty() && self.history.last() != Some(&command) {
-            self.history.push(command);
-        }
+    /// * `entry` - The command to add, or its entry with where it runs.
+    ///
+    /// # Returns
+    ///
+    /// The id of the entry, which `HistoryStore::finish` saves once the command has run.
+    pub fn add(&mut self, entry: impl Into<HistoryEntry>) -> Option<EntryId> {
         self.current_index = None;
+        self.store.add(entry.into())
     }
 
     /// Navigates up through the command history.
     ///
     /// # Returns
     ///
-    /// An `Option<&str>` containing the command string if navigation is successful,
+    /// An `Option<String>` containing the command string if navigation is successful,
     /// or `None` if at the beginning of the history or history is empty.
-    pub fn navigate_up(&mut self) -> Option<&str> {
-        if self.history.is_empty() {
+    pub fn navigate_up(&mut self) -> Option<String> {
+        let len = self.store.len();
+        if len == 0 {
             return None;
         }
 
@@ -54,26 +77,27 @@ impl CommandHistory {
                     Some(0)
                 }
             }
-            None => Some(self.history.len() - 1),
+            None => Some(len - 1),
         };
         self.current_index = new_index;
-        new_index.map(|i| self.history[i].as_str())
+        new_index.and_then(|i| self.store.get(i)).map(|entry| entry.command)
     }
 
     /// Navigates down through the command history.
     ///
     /// # Returns
     ///
-    /// An `Option<&str>` containing the command string if navigation is successful,
+    /// An `Option<String>` containing the command string if navigation is successful,
     /// or `None` if at the end of the history.
-    pub fn navigate_down(&mut self) -> Option<&str> {
-        if self.history.is_empty() {
+    pub fn navigate_down(&mut self) -> Option<String> {
+        let len = self.store.len();
+        if len == 0 {
             return None;
         }
 
         let new_index = match self.current_index {
             Some(index) => {
-                if index < self.history.len() - 1 {
+                if index < len - 1 {
                     Some(index + 1)
                 } else {
                     None // Reached the end of history, clear input
@@ -82,7 +106,7 @@ impl CommandHistory {
             None => None, // No history to navigate down from
         };
         self.current_index = new_index;
-        new_index.map(|i| self.history[i].as_str())
+        new_index.and_then(|i| self.store.get(i)).map(|entry| entry.command)
     }
 }
 
@@ -90,10 +114,14 @@ impl CommandHistory {
 mod tests {
     use super::CommandHistory;
 
+    fn commands(history: &CommandHistory) -> Vec<String> {
+        history.store().read(|entries| entries.iter().map(|entry| entry.command.clone()).collect())
+    }
+
     #[test]
     fn test_new_command_history() {
         let history = CommandHistory::new();
-        assert!(history.history.is_empty());
+        assert!(commands(&history).is_empty());
         assert!(history.current_index.is_none());
     }
 
@@ -101,19 +129,19 @@ mod tests {
     fn test_add_command() {
         let mut history = CommandHistory::new();
         history.add("cmd1".to_string());
-        assert_eq!(history.history, vec!["cmd1"]);
+        assert_eq!(commands(&history), vec!["cmd1"]);
         assert!(history.current_index.is_none());
 
         history.add("cmd2".to_string());
-        assert_eq!(history.history, vec!["cmd1", "cmd2"]);
+        assert_eq!(commands(&history), vec!["cmd1", "cmd2"]);
 
         // Test adding duplicate command
         history.add("cmd2".to_string());
-        assert_eq!(history.history, vec!["cmd1", "cmd2"]);
+        assert_eq!(commands(&history), vec!["cmd1", "cmd2"]);
 
         // Test adding empty command
         history.add("".to_string());
-        assert_eq!(history.history, vec!["cmd1", "cmd2"]);
+        assert_eq!(commands(&history), vec!["cmd1", "cmd2"]);
     }
 
     #[test]
@@ -125,16 +153,16 @@ mod tests {
         history.add("cmd2".to_string());
         history.add("cmd3".to_string());
 
-        assert_eq!(history.navigate_up(), Some("cmd3"));
+        assert_eq!(history.navigate_up(), Some("cmd3".to_string()));
         assert_eq!(history.current_index, Some(2));
 
-        assert_eq!(history.navigate_up(), Some("cmd2"));
+        assert_eq!(history.navigate_up(), Some("cmd2".to_string()));
         assert_eq!(history.current_index, Some(1));
 
-        assert_eq!(history.navigate_up(), Some("cmd1"));
+        assert_eq!(history.navigate_up(), Some("cmd1".to_string()));
         assert_eq!(history.current_index, Some(0));
 
-        assert_eq!(history.navigate_up(), Some("cmd1")); // At the beginning
+        assert_eq!(history.navigate_up(), Some("cmd1".to_string())); // At the beginning
         assert_eq!(history.current_index, Some(0));
     }
 
@@ -150,10 +178,10 @@ mod tests {
         history.navigate_up(); // cmd2
         history.navigate_up(); // cmd1
 
-        assert_eq!(history.navigate_down(), Some("cmd2"));
+        assert_eq!(history.navigate_down(), Some("cmd2".to_string()));
         assert_eq!(history.current_index, Some(1));
 
-        assert_eq!(history.navigate_down(), Some("cmd3"));
+        assert_eq!(history.navigate_down(), Some("cmd3".to_string()));
         assert_eq!(history.current_index, Some(2));
 
         assert!(history.navigate_down().is_none()); // At the end
@@ -171,7 +199,7 @@ mod tests {
         history.add("cmd2".to_string());
         history.navigate_up(); // cmd2
         history.add("cmd3".to_string());
-        assert_eq!(history.history, vec!["cmd1", "cmd2", "cmd3"]);
+        assert_eq!(commands(&history), vec!["cmd1", "cmd2", "cmd3"]);
         assert!(history.current_index.is_none());
     }
 }
\ No newline at end of file
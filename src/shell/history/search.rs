@@ -0,0 +1,196 @@
+//! Fuzzy search over the command history, as used by the reverse search of
+//! the GUI (Ctrl+R).
+//!
+//! A command matches a query if it contains the query's characters in
+//! order, ignoring case. Commands are ranked by how well they match, how
+//! recently and how often they were run, and whether they were run in the
+//! current directory.
+
+use std::collections::HashMap;
+use super::HistoryEntry;
+
+/// The score of each matched character.
+const MATCH_SCORE: f64 = 16.0;
+/// Added for a character matched right after the previous one.
+const CONSECUTIVE_BONUS: f64 = 8.0;
+/// Added for a character matched at the start of a word.
+const WORD_START_BONUS: f64 = 8.0;
+/// The score of the most recent command, halved after every
+/// `RECENCY_HALF_LIFE` newer commands.
+const RECENCY_SCORE: f64 = 40.0;
+const RECENCY_HALF_LIFE: f64 = 20.0;
+/// Multiplies the logarithm of the number of times a command was run.
+const FREQUENCY_SCORE: f64 = 10.0;
+/// Added for a command run in the current directory.
+const CWD_SCORE: f64 = 20.0;
+
+/// Where a query matches a text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuzzyMatch {
+    /// Higher for matches in fewer, longer runs that start words.
+    pub score: f64,
+    /// The byte offsets of the matched characters in the text.
+    pub positions: Vec<usize>,
+}
+
+/// Matches the characters of `query`, in order and ignoring case, in
+/// `text`.
+///
+/// Of the places the query matches, the one that ends first is chosen and
+/// made as short as it can be, so `cm` matches the `cm` of `cargo cmd`
+/// rather than its first `c` and `m`.
+///
+/// # Returns
+///
+/// The match, or `None` if some character of the query is missing. An
+/// empty query matches any text.
+pub fn fuzzy_match(query: &str, text: &str) -> Option<FuzzyMatch> {
+    let query: Vec<char> = query.chars().map(fold_case).collect();
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    if query.is_empty() {
+        return Some(FuzzyMatch { score: 0.0, positions: Vec::new() });
+    }
+
+    // The first place the whole query has been seen ends the match...
+    let mut matched = 0;
+    let end = chars.iter().position(|&(_, c)| {
+        if fold_case(c) == query[matched] {
+            matched += 1;
+        }
+        matched == query.len()
+    })?;
+    // ...which starts at the last place the query can be read backwards
+    // from there.
+    let mut indices = Vec::with_capacity(query.len());
+    for index in (0..=end).rev() {
+        if fold_case(chars[index].1) == query[query.len() - indices.len() - 1] {
+            indices.push(index);
+            if indices.len() == query.len() {
+                break;
+            }
+        }
+    }
+    indices.reverse();
+
+    let mut score = 0.0;
+    for (n, &index) in indices.iter().enumerate() {
+        score += MATCH_SCORE;
+        if n > 0 && indices[n - 1] + 1 == index {
+            score += CONSECUTIVE_BONUS;
+        }
+        if index == 0 || !chars[index - 1].1.is_alphanumeric() {
+            score += WORD_START_BONUS;
+        }
+    }
+    // Every unmatched character inside the match costs a point.
+    score -= (indices[indices.len() - 1] - indices[0] + 1 - indices.len()) as f64;
+    Some(FuzzyMatch { score, positions: indices.iter().map(|&index| chars[index].0).collect() })
+}
+
+/// A command of the history that matches a search.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    /// The index of the command's most recent entry.
+    pub index: usize,
+    /// How many entries have the same command.
+    pub runs: usize,
+    /// The byte offsets of the matched characters in the command.
+    pub positions: Vec<usize>,
+    pub score: f64,
+}
+
+/// Finds the commands of the history that match `query`, best first.
+///
+/// Each command is listed once, however many times it was run.
+///
+/// # Arguments
+///
+/// * `entries` - The history, oldest first.
+/// * `query` - The characters to look for; empty to list every command.
+/// * `cwd` - The current directory, whose commands rank higher.
+pub fn search(entries: &[HistoryEntry], query: &str, cwd: &str) -> Vec<SearchResult> {
+    // The newest entry of each command, how often it was run and whether
+    // it was ever run in `cwd`, newest command first.
+    let mut commands: Vec<(usize, usize, bool)> = Vec::new();
+    let mut positions: HashMap<&str, usize> = HashMap::new();
+    for (index, entry) in entries.iter().enumerate().rev() {
+        let in_cwd = entry.cwd.as_deref() == Some(cwd);
+        match positions.get(entry.command.as_str()) {
+            Some(&position) => {
+                commands[position].1 += 1;
+                commands[position].2 |= in_cwd;
+            }
+            None => {
+                positions.insert(&entry.command, commands.len());
+                commands.push((index, 1, in_cwd));
+            }
+        }
+    }
+
+    let mut results: Vec<SearchResult> = commands
+        .into_iter()
+        .enumerate()
+        .filter_map(|(age, (index, runs, in_cwd))| {
+            let found = fuzzy_match(query, &entries[index].command)?;
+            let recency = RECENCY_SCORE * 0.5f64.powf(age as f64 / RECENCY_HALF_LIFE);
+            let frequency = FREQUENCY_SCORE * (runs as f64).ln();
+            let cwd = if in_cwd { CWD_SCORE } else { 0.0 };
+            let score = found.score + recency + frequency + cwd;
+            Some(SearchResult { index, runs, positions: found.positions, score })
+        })
+        .collect();
+    // The sort is stable, so equal scores stay newest first.
+    results.sort_by(|a, b| b.score.total_cmp(&a.score));
+    results
+}
+
+/// Lowercases a character for a case-insensitive comparison.
+fn fold_case(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(command: &str, cwd: &str) -> HistoryEntry {
+        HistoryEntry { cwd: Some(cwd.to_string()), ..HistoryEntry::new(command.to_string()) }
+    }
+
+    fn commands<'a>(entries: &'a [HistoryEntry], results: &[SearchResult]) -> Vec<&'a str> {
+        results.iter().map(|result| entries[result.index].command.as_str()).collect()
+    }
+
+    #[test]
+    fn test_fuzzy_match() {
+        assert_eq!(fuzzy_match("gCO", "git commit").unwrap().positions, vec![0, 4, 5]);
+        assert_eq!(fuzzy_match("cm", "cargo cmd").unwrap().positions, vec![6, 7]);
+        assert_eq!(fuzzy_match("éa", "Écran a").unwrap().positions, vec![0, 4]);
+        assert!(fuzzy_match("gx", "git checkout").is_none());
+
+        // Runs of characters at word starts score best.
+        let word = fuzzy_match("test", "cargo test").unwrap().score;
+        let spread = fuzzy_match("test", "the best").unwrap().score;
+        assert!(word > spread);
+    }
+
+    #[test]
+    fn test_search_ranks_by_match_recency_frequency_and_cwd() {
+        let entries = vec![
+            entry("cargo test", "/src"),
+            entry("cargo build", "/src"),
+            entry("cargo test", "/src"),
+            entry("make test", "/other"),
+            entry("cat todo.txt", "/src"),
+        ];
+        let results = search(&entries, "test", "/src");
+        // Run twice and in `/src`, `cargo test` beats the newer `make test`.
+        assert_eq!(commands(&entries, &results), vec!["cargo test", "make test"]);
+        assert_eq!((results[0].index, results[0].runs), (2, 2));
+        assert_eq!(commands(&entries, &search(&entries, "test", "/other"))[0], "make test");
+
+        // Without a query, every command is listed once.
+        assert_eq!(search(&entries, "", "/").len(), 4);
+        assert!(search(&entries, "xyz", "/src").is_empty());
+    }
+}
@@ -0,0 +1,465 @@
+//! The command history shared by every tab and kept on disk.
+//!
+//! Each command is recorded with when, where and how it ran, and appended
+//! to the history file as a JSON line once it finishes. The file is written
+//! under an exclusive lock, so several windows of the application writing
+//! at the same time cannot mix up their lines, and a line left incomplete
+//! by a crash is skipped when the file is read. It keeps the last
+//! `HISTSIZE` commands, and is rewritten without the oldest ones once it
+//! holds more.
+//!
+//! The file is read once, when the application starts, so commands run in
+//! other windows meanwhile only appear in the history after a restart.
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+/// The directory of the application's data, inside the platform's data
+/// directory.
+const DATA_DIR: &str = "my_cli_tool";
+
+/// The name of the history file in the data directory.
+const HISTORY_FILE: &str = "history";
+
+/// The number of commands kept when `HISTSIZE` is not set.
+pub const DEFAULT_HISTORY_SIZE: usize = 10_000;
+
+/// A command of the history, with where and how it ran.
+///
+/// Only `command` is known for every entry; the other fields are recorded
+/// by the GUI and missing from entries saved before they existed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<DateTime<Local>>,
+    /// The working directory the command started in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    /// The git branch checked out in that directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_branch: Option<String>,
+    /// The title of the tab the command was entered in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tab: Option<String>,
+    /// `None` while the command runs, or if it never finished.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_status: Option<i32>,
+    /// How long the command ran, in milliseconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+}
+
+impl HistoryEntry {
+    /// Creates the entry of a command that starts now.
+    pub fn new(command: String) -> Self {
+        Self {
+            command,
+            started_at: Some(Local::now()),
+            cwd: None,
+            git_branch: None,
+            tab: None,
+            exit_status: None,
+            duration_ms: None,
+        }
+    }
+
+    /// Returns whether the command finished with a non-zero status.
+    pub fn failed(&self) -> bool {
+        self.exit_status.is_some_and(|status| status != 0)
+    }
+}
+
+impl From<String> for HistoryEntry {
+    fn from(command: String) -> Self {
+        Self::new(command)
+    }
+}
+
+/// Formats how long a command ran, e.g. `120ms`, `4.2s` or `3m 05s`, as
+/// block headers and `history` show it.
+pub fn format_duration(duration: chrono::Duration) -> String {
+    let millis = duration.num_milliseconds().max(0);
+    if millis < 1000 {
+        format!("{}ms", millis)
+    } else if millis < 60_000 {
+        format!("{:.1}s", millis as f64 / 1000.0)
+    } else {
+        let seconds = millis / 1000;
+        format!("{}m {:02}s", seconds / 60, seconds % 60)
+    }
+}
+
+/// Identifies the entry of a command that is running, from `add` until
+/// `finish`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EntryId(u64);
+
+/// The commands entered so far, oldest first.
+///
+/// Clones share the same history, so a command added in one tab is seen by
+/// the others and by their autocompletion straight away.
+#[derive(Clone, Default)]
+pub struct HistoryStore {
+    inner: Arc<RwLock<Inner>>,
+}
+
+struct Inner {
+    entries: Vec<HistoryEntry>,
+    /// The id of each entry whose command is running, by position in
+    /// `entries`.
+    ids: Vec<Option<EntryId>>,
+    /// The entries of the commands that are running, which stay here even
+    /// if a repeat of the command replaces them in `entries`.
+    running: HashMap<EntryId, HistoryEntry>,
+    next_id: u64,
+    /// The file finished commands are appended to, if the history is kept
+    /// on disk.
+    file: Option<PathBuf>,
+    /// The most entries kept, in memory and in the file.
+    limit: usize,
+}
+
+impl Default for Inner {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+            ids: Vec::new(),
+            running: HashMap::new(),
+            next_id: 0,
+            file: None,
+            limit: DEFAULT_HISTORY_SIZE,
+        }
+    }
+}
+
+impl HistoryStore {
+    /// Creates an empty history that is kept in memory only.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Opens the history kept in a file, keeping as many commands as the
+    /// `HISTSIZE` environment variable says, or `DEFAULT_HISTORY_SIZE`.
+    ///
+    /// The file and its directory are created when the first command
+    /// finishes.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The history file, usually `default_path()`.
+    ///
+    /// # Returns
+    ///
+    /// The history with the commands already in the file, or the error that
+    /// prevented reading it.
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let limit = std::env::var("HISTSIZE").ok().and_then(|size| size.parse().ok()).unwrap_or(DEFAULT_HISTORY_SIZE);
+        Self::open_with_limit(path, limit)
+    }
+
+    /// Opens the history kept in a file, keeping at most `limit` commands.
+    pub fn open_with_limit(path: impl Into<PathBuf>, limit: usize) -> io::Result<Self> {
+        let path = path.into();
+        let mut entries = match File::open(&path) {
+            Ok(file) => {
+                // Waits for a write in progress, so that its line is complete.
+                file.lock_shared()?;
+                read_entries(BufReader::new(&file))?
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        entries.drain(..entries.len().saturating_sub(limit));
+        let inner = Inner { ids: vec![None; entries.len()], entries, file: Some(path), limit, ..Inner::default() };
+        Ok(Self { inner: Arc::new(RwLock::new(inner)) })
+    }
+
+    /// Returns where the history is kept: `my_cli_tool/history` in the
+    /// platform's data directory, e.g. `~/.local/share` on Linux.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join(DATA_DIR).join(HISTORY_FILE))
+    }
+
+    /// Adds the entry of a command that is starting.
+    ///
+    /// Empty commands are not added, and a repeat of the last command
+    /// replaces its entry. The entry is saved to the history file by
+    /// `finish`.
+    ///
+    /// # Returns
+    ///
+    /// The id of the entry, to pass to `finish`.
+    pub fn add(&self, entry: HistoryEntry) -> Option<EntryId> {
+        if entry.command.is_empty() {
+            return None;
+        }
+        let mut inner = self.inner.write().unwrap();
+        let id = EntryId(inner.next_id);
+        inner.next_id += 1;
+        inner.running.insert(id, entry.clone());
+        if push_entry(&mut inner.entries, entry) {
+            inner.ids.pop();
+        }
+        inner.ids.push(Some(id));
+        let excess = inner.entries.len().saturating_sub(inner.limit);
+        inner.entries.drain(..excess);
+        inner.ids.drain(..excess);
+        Some(id)
+    }
+
+    /// Records how the command of an entry ended and appends the entry to
+    /// the history file.
+    ///
+    /// If a repeat of the command, e.g. in another tab, has replaced the
+    /// entry in the meantime, only the file is written to.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The id returned by `add`.
+    /// * `exit_status` - The status the command finished with.
+    ///
+    /// # Returns
+    ///
+    /// An error if the entry could not be written to the file; it is in the
+    /// history of this session all the same.
+    pub fn finish(&self, id: EntryId, exit_status: i32) -> io::Result<()> {
+        let mut inner = self.inner.write().unwrap();
+        let Some(mut entry) = inner.running.remove(&id) else {
+            return Ok(());
+        };
+        entry.exit_status = Some(exit_status);
+        entry.duration_ms = entry.started_at.map(|started_at| (Local::now() - started_at).num_milliseconds().max(0) as u64);
+        if let Some(index) = inner.ids.iter().rposition(|&entry_id| entry_id == Some(id)) {
+            inner.ids[index] = None;
+            inner.entries[index] = entry.clone();
+        }
+        match &inner.file {
+            Some(path) => append_entry(path, &entry, inner.limit),
+            None => Ok(()),
+        }
+    }
+
+    /// Reads the entries, oldest first.
+    ///
+    /// The history is locked only while `read` runs, which must not block.
+    pub fn read<R>(&self, read: impl FnOnce(&[HistoryEntry]) -> R) -> R {
+        read(&self.inner.read().unwrap().entries)
+    }
+
+    /// Returns a number that changes whenever an entry is added, even once
+    /// the oldest entries are dropped to stay within the limit.
+    pub fn revision(&self) -> u64 {
+        self.inner.read().unwrap().next_id
+    }
+
+    /// Returns the number of entries in the history.
+    pub fn len(&self) -> usize {
+        self.read(<[HistoryEntry]>::len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the entry at `index`, counting from the oldest one.
+    pub fn get(&self, index: usize) -> Option<HistoryEntry> {
+        self.read(|entries| entries.get(index).cloned())
+    }
+}
+
+/// Adds an entry, replacing the last one if it has the same command.
+///
+/// # Returns
+///
+/// Whether the last entry was replaced.
+fn push_entry(entries: &mut Vec<HistoryEntry>, entry: HistoryEntry) -> bool {
+    match entries.last_mut() {
+        Some(last) if last.command == entry.command => {
+            *last = entry;
+            true
+        }
+        _ => {
+            entries.push(entry);
+            false
+        }
+    }
+}
+
+/// Reads the entries of a history file, skipping lines that are not valid
+/// entries.
+fn read_entries(reader: impl BufRead) -> io::Result<Vec<HistoryEntry>> {
+    let mut entries = Vec::new();
+    for line in reader.lines() {
+        if let Ok(entry) = serde_json::from_str(&line?) {
+            push_entry(&mut entries, entry);
+        }
+    }
+    Ok(entries)
+}
+
+/// Appends an entry to a history file as a single write, while holding an
+/// exclusive lock on the file, and then drops the oldest lines if the file
+/// holds more than `limit`.
+fn append_entry(path: &Path, entry: &HistoryEntry, limit: usize) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new().create(true).read(true).append(true).open(path)?;
+    file.lock()?;
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+    // A line cut short by a crash is ended first, so that it does not
+    // swallow this one.
+    if !ends_with_newline(&mut file)? {
+        line.insert(0, '\n');
+    }
+    file.write_all(line.as_bytes())?;
+    // The lock is released when the file is closed.
+    trim_file(&mut file, limit)
+}
+
+/// Rewrites a locked history file with only its last `limit` lines, if it
+/// has more.
+fn trim_file(file: &mut File, limit: usize) -> io::Result<()> {
+    let mut contents = String::new();
+    file.seek(SeekFrom::Start(0))?;
+    file.read_to_string(&mut contents)?;
+    let lines: Vec<&str> = contents.lines().collect();
+    if lines.len() <= limit {
+        return Ok(());
+    }
+    let mut kept = lines[lines.len() - limit..].join("\n");
+    if !kept.is_empty() {
+        kept.push('\n');
+    }
+    // The file is opened for appending, so the write starts at its new end.
+    file.set_len(0)?;
+    file.write_all(kept.as_bytes())
+}
+
+/// Returns whether a file is empty or its last byte is a newline.
+fn ends_with_newline(file: &mut File) -> io::Result<bool> {
+    let length = file.metadata()?.len();
+    if length == 0 {
+        return Ok(true);
+    }
+    let mut last = [0];
+    file.seek(SeekFrom::Start(length - 1))?;
+    file.read_exact(&mut last)?;
+    Ok(last[0] == b'\n')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commands(history: &HistoryStore) -> Vec<String> {
+        history.read(|entries| entries.iter().map(|entry| entry.command.clone()).collect())
+    }
+
+    #[test]
+    fn test_history_is_kept_in_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data/history");
+        let history = HistoryStore::open(&path).unwrap();
+        assert!(history.is_empty());
+        let ls = history.add(HistoryEntry { cwd: Some("/src".to_string()), ..HistoryEntry::new("ls -l".to_string()) }).unwrap();
+        history.finish(ls, 0).unwrap();
+        let loop_command = "for f in *; do\n  echo \"$f\"\ndone".to_string();
+        let running = history.add(loop_command.clone().into()).unwrap();
+
+        // Another window appends to the same file.
+        let other = HistoryStore::open(&path).unwrap();
+        let make = other.add("make".to_string().into()).unwrap();
+        other.finish(make, 2).unwrap();
+        // A write that was cut short is skipped, without losing the next one.
+        OpenOptions::new().append(true).open(&path).unwrap().write_all(b"{\"comm").unwrap();
+        history.finish(running, 0).unwrap();
+
+        let reopened = HistoryStore::open(&path).unwrap();
+        assert_eq!(commands(&reopened), vec!["ls -l".to_string(), "make".to_string(), loop_command]);
+        let ls = reopened.get(0).unwrap();
+        assert_eq!((ls.cwd.as_deref(), ls.exit_status), (Some("/src"), Some(0)));
+        assert!(ls.duration_ms.is_some() && ls.started_at.is_some());
+        assert!(reopened.get(1).unwrap().failed());
+    }
+
+    #[test]
+    fn test_clones_share_the_history() {
+        let history = HistoryStore::new();
+        let other_tab = history.clone();
+        let first = other_tab.add("cargo test".to_string().into()).unwrap();
+        assert_eq!(history.add(String::new().into()), None);
+        assert_eq!(commands(&history), vec!["cargo test"]);
+
+        // Running the same command again replaces its entry.
+        history.finish(first, 1).unwrap();
+        assert_ne!(history.add("cargo test".to_string().into()), Some(first));
+        assert_eq!(history.len(), 1);
+        assert_eq!(history.get(0).unwrap().exit_status, None);
+    }
+
+    #[test]
+    fn test_repeat_in_another_tab_keeps_its_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history");
+        let tab_a = HistoryStore::open(&path).unwrap();
+        let tab_b = tab_a.clone();
+        let make_a = tab_a.add(HistoryEntry { tab: Some("A".to_string()), ..HistoryEntry::new("make".to_string()) }).unwrap();
+        let make_b = tab_b.add(HistoryEntry { tab: Some("B".to_string()), ..HistoryEntry::new("make".to_string()) }).unwrap();
+
+        // Tab A's make finishing does not overwrite tab B's, still running.
+        tab_a.finish(make_a, 2).unwrap();
+        let entry = tab_b.get(0).unwrap();
+        assert_eq!((entry.tab.as_deref(), entry.exit_status), (Some("B"), None));
+        tab_b.finish(make_b, 0).unwrap();
+        let entry = tab_b.get(0).unwrap();
+        assert_eq!((entry.tab.as_deref(), entry.exit_status), (Some("B"), Some(0)));
+
+        // Both runs are in the file, where the last one counts.
+        let lines = fs::read_to_string(&path).unwrap();
+        assert_eq!(lines.lines().count(), 2);
+        let reopened = HistoryStore::open(&path).unwrap();
+        assert_eq!(reopened.get(0).unwrap().tab.as_deref(), Some("B"));
+        // Finishing twice writes nothing more.
+        tab_b.finish(make_b, 0).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), lines);
+    }
+
+    #[test]
+    fn test_history_is_limited() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history");
+        let history = HistoryStore::open_with_limit(&path, 3).unwrap();
+        for command in ["a", "b", "c", "d", "e"] {
+            let id = history.add(command.to_string().into()).unwrap();
+            history.finish(id, 0).unwrap();
+        }
+        assert_eq!(commands(&history), vec!["c", "d", "e"]);
+        assert_eq!(history.revision(), 5);
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 3);
+        assert_eq!(commands(&HistoryStore::open_with_limit(&path, 3).unwrap()), vec!["c", "d", "e"]);
+        assert_eq!(commands(&HistoryStore::open_with_limit(&path, 2).unwrap()), vec!["d", "e"]);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(chrono::Duration::milliseconds(120)), "120ms");
+        assert_eq!(format_duration(chrono::Duration::milliseconds(4210)), "4.2s");
+        assert_eq!(format_duration(chrono::Duration::seconds(185)), "3m 05s");
+        assert_eq!(format_duration(chrono::Duration::milliseconds(-5)), "0ms");
+    }
+
+    #[test]
+    fn test_entries_without_details_are_read() {
+        let entries = read_entries("{\"command\":\"ls\"}\n{\"command\":\"ls\",\"exit_status\":1}\n".as_bytes()).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!((entries[0].started_at, entries[0].exit_status), (None, Some(1)));
+    }
+}